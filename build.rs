@@ -0,0 +1,50 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Provides `BUILD_TIMESTAMP` and, inside a git repository, `GIT_COMMIT_HASH`
+// to be read with `option_env!` at compile time.
+fn main() {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    println!("cargo:rustc-env=BUILD_TIMESTAMP={}", timestamp);
+
+    let hash = Command::new("git")
+        .args(&["rev-parse", "HEAD"])
+        .output()
+        .ok()
+        .and_then(|o| {
+            if o.status.success() {
+                String::from_utf8(o.stdout).ok()
+            } else {
+                None
+            }
+        });
+    if let Some(hash) = hash {
+        println!("cargo:rustc-env=GIT_COMMIT_HASH={}", hash.trim());
+        rerun_on_new_commits();
+    }
+}
+
+// A new commit moves the branch that `.git/HEAD` refers to
+// (or `HEAD` itself if it is detached).
+fn rerun_on_new_commits() {
+    let head = Path::new(".git/HEAD");
+    if !head.exists() {
+        return;
+    }
+    println!("cargo:rerun-if-changed={}", head.display());
+    if let Ok(content) = fs::read_to_string(head) {
+        if content.starts_with("ref: ") {
+            let reference = Path::new(".git").join(content[5..].trim());
+            println!("cargo:rerun-if-changed={}", reference.display());
+        }
+    }
+    let packed_refs = Path::new(".git/packed-refs");
+    if packed_refs.exists() {
+        println!("cargo:rerun-if-changed={}", packed_refs.display());
+    }
+}
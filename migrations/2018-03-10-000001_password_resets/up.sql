@@ -0,0 +1,6 @@
+CREATE TABLE password_resets (
+    token    TEXT PRIMARY KEY NOT NULL,
+    username TEXT    NOT NULL,
+    created  INTEGER NOT NULL,
+    FOREIGN KEY (username) REFERENCES users(username)
+);
@@ -0,0 +1,14 @@
+-- SQLite can't drop columns, so we have to rebuild the table
+CREATE TABLE ratings_backup AS SELECT id, created, title, value, context, source, entry_id FROM ratings;
+DROP TABLE ratings;
+CREATE TABLE ratings (
+    id      TEXT PRIMARY KEY NOT NULL,
+    created INTEGER NOT NULL,
+    title   TEXT NOT NULL,
+    value   INTEGER NOT NULL,
+    context TEXT NOT NULL,
+    source  TEXT,
+    entry_id TEXT NOT NULL
+);
+INSERT INTO ratings SELECT * FROM ratings_backup;
+DROP TABLE ratings_backup;
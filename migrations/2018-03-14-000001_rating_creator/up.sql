@@ -0,0 +1 @@
+ALTER TABLE ratings ADD COLUMN created_by TEXT;
@@ -0,0 +1,29 @@
+-- SQLite can't drop columns, so we have to rebuild the table
+CREATE TABLE entries_backup AS SELECT id, osm_node, created, version, current, title, description, lat, lng, street, zip, city, country, email, telephone, homepage, license, created_by, updated, archived, opening_hours FROM entries;
+DROP TABLE entries;
+CREATE TABLE entries (
+    id          TEXT NOT NULL,
+    osm_node    INTEGER,
+    created     INTEGER NOT NULL,
+    version     INTEGER NOT NULL,
+    current     BOOLEAN NOT NULL,
+    title       TEXT NOT NULL,
+    description TEXT NOT NULL,
+    lat         FLOAT NOT NULL,
+    lng         FLOAT NOT NULL,
+    street      TEXT,
+    zip         TEXT,
+    city        TEXT,
+    country     TEXT,
+    email       TEXT,
+    telephone   TEXT,
+    homepage    TEXT,
+    license     TEXT,
+    created_by  TEXT,
+    updated     INTEGER,
+    archived    INTEGER,
+    opening_hours TEXT,
+    PRIMARY KEY (id, version)
+);
+INSERT INTO entries SELECT * FROM entries_backup;
+DROP TABLE entries_backup;
@@ -0,0 +1 @@
+ALTER TABLE entries ADD COLUMN image_urls TEXT;
@@ -0,0 +1,78 @@
+use entities as e;
+use super::xml::escape;
+use chrono::prelude::*;
+
+/// Creates an RSS 2.0 feed of the most recently created entries
+pub fn rss(mut entries: Vec<e::Entry>, base_url: &str, max_items: usize) -> String {
+    entries.sort_by(|a, b| b.created.cmp(&a.created));
+    let mut feed = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <rss version=\"2.0\">\n\
+         <channel>\n\
+         <title>OpenFairDB - new entries</title>\n\
+         <link>{}</link>\n\
+         <description>The most recently created entries</description>\n",
+        escape(base_url)
+    );
+    for e in entries.iter().take(max_items) {
+        let link = escape(&format!("{}/#/?entry={}", base_url, e.id));
+        let date = Utc.timestamp(e.created as i64, 0).to_rfc2822();
+        feed.push_str(&format!(
+            "<item>\
+             <title>{title}</title>\
+             <link>{link}</link>\
+             <guid>{link}</guid>\
+             <description>{description}</description>\
+             <pubDate>{date}</pubDate>\
+             </item>\n",
+            title = escape(&e.title),
+            link = link,
+            description = escape(&e.description),
+            date = date
+        ));
+    }
+    feed.push_str("</channel>\n</rss>\n");
+    feed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use business::builder::EntryBuilder;
+
+    fn entry(id: &str, created: u64) -> e::Entry {
+        let mut e = e::Entry::build().id(id).title(id).finish();
+        e.created = created;
+        e
+    }
+
+    #[test]
+    fn newest_entries_first() {
+        let entries = vec![entry("b", 200), entry("a", 100), entry("c", 300)];
+        let feed = rss(entries, "https://example.org", 10);
+        let a = feed.find("<title>a</title>").unwrap();
+        let b = feed.find("<title>b</title>").unwrap();
+        let c = feed.find("<title>c</title>").unwrap();
+        assert!(c < b && b < a);
+        assert!(feed.contains("<link>https://example.org/#/?entry=c</link>"));
+        assert!(feed.contains("<pubDate>Thu, 01 Jan 1970 00:05:00 +0000</pubDate>"));
+    }
+
+    #[test]
+    fn limit_the_number_of_items() {
+        let entries = vec![entry("b", 200), entry("a", 100), entry("c", 300)];
+        let feed = rss(entries, "https://example.org", 2);
+        assert_eq!(feed.matches("<item>").count(), 2);
+        assert!(!feed.contains("<title>a</title>"));
+    }
+
+    #[test]
+    fn escape_entry_content() {
+        let mut e = entry("x", 0);
+        e.title = "Fish & Chips".into();
+        e.description = "<b>tasty</b>".into();
+        let feed = rss(vec![e], "https://example.org", 10);
+        assert!(feed.contains("<title>Fish &amp; Chips</title>"));
+        assert!(feed.contains("<description>&lt;b&gt;tasty&lt;/b&gt;</description>"));
+    }
+}
@@ -0,0 +1,161 @@
+use business::geocode::{Address, Geocoder};
+use entities::Coordinate;
+use reqwest::{self, header::UserAgent};
+use serde_json;
+use url::Url;
+
+const USER_AGENT: &str = "openfairdb";
+
+/// A geocoder that asks a Nominatim compatible service
+pub struct Nominatim {
+    url: String,
+}
+
+impl Nominatim {
+    pub fn new(url: &str) -> Nominatim {
+        Nominatim {
+            url: url.trim_right_matches('/').into(),
+        }
+    }
+
+    fn get(&self, path: &str, params: &[(&str, String)]) -> Option<String> {
+        let url = Url::parse_with_params(&format!("{}/{}", self.url, path), params)
+            .map_err(|err| warn!("Invalid geocoding URL {}: {}", self.url, err))
+            .ok()?;
+        let mut res = reqwest::Client::new()
+            .get(url)
+            .header(UserAgent::new(USER_AGENT))
+            .send()
+            .map_err(|err| warn!("Geocoding request failed: {}", err))
+            .ok()?;
+        if !res.status().is_success() {
+            warn!("Geocoding request failed: {}", res.status());
+            return None;
+        }
+        res.text()
+            .map_err(|err| warn!("Could not read geocoding response: {}", err))
+            .ok()
+    }
+}
+
+impl Geocoder for Nominatim {
+    fn reverse(&self, lat: f64, lng: f64) -> Option<Address> {
+        let params = [
+            ("format", "jsonv2".to_string()),
+            ("lat", lat.to_string()),
+            ("lon", lng.to_string()),
+        ];
+        let res = self.get("reverse", &params)?;
+        address_from_reverse_result(&res)
+    }
+
+    fn forward(&self, address: &Address) -> Option<Coordinate> {
+        let fields = [
+            ("street", &address.street),
+            ("postalcode", &address.zip),
+            ("city", &address.city),
+            ("country", &address.country),
+        ];
+        let mut params = vec![("format", "jsonv2".to_string()), ("limit", "1".to_string())];
+        for &(key, value) in &fields {
+            if let Some(ref v) = *value {
+                params.push((key, v.clone()));
+            }
+        }
+        let res = self.get("search", &params)?;
+        coordinate_from_search_result(&res)
+    }
+}
+
+#[derive(Deserialize)]
+struct ReverseResult {
+    address: Option<NominatimAddress>,
+}
+
+#[derive(Deserialize)]
+struct NominatimAddress {
+    road: Option<String>,
+    house_number: Option<String>,
+    postcode: Option<String>,
+    city: Option<String>,
+    town: Option<String>,
+    village: Option<String>,
+    country: Option<String>,
+}
+
+impl From<NominatimAddress> for Address {
+    fn from(a: NominatimAddress) -> Address {
+        let street = match (a.road, a.house_number) {
+            (Some(road), Some(nr)) => Some(format!("{} {}", road, nr)),
+            (road, _) => road,
+        };
+        Address {
+            street,
+            zip: a.postcode,
+            city: a.city.or(a.town).or(a.village),
+            country: a.country,
+        }
+    }
+}
+
+fn address_from_reverse_result(json: &str) -> Option<Address> {
+    let res: ReverseResult = serde_json::from_str(json)
+        .map_err(|err| warn!("Invalid geocoding response: {}", err))
+        .ok()?;
+    res.address.map(Address::from)
+}
+
+#[derive(Deserialize)]
+struct SearchResult {
+    lat: String,
+    lon: String,
+}
+
+fn coordinate_from_search_result(json: &str) -> Option<Coordinate> {
+    let res: Vec<SearchResult> = serde_json::from_str(json)
+        .map_err(|err| warn!("Invalid geocoding response: {}", err))
+        .ok()?;
+    let first = res.into_iter().next()?;
+    Some(Coordinate {
+        lat: first.lat.parse().ok()?,
+        lng: first.lon.parse().ok()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reverse_result() {
+        let json = r#"{"place_id":"1","lat":"48.77","lon":"9.18","address":{"house_number":"5","road":"Marktplatz","town":"Stuttgart","postcode":"70173","country":"Deutschland","country_code":"de"}}"#;
+        assert_eq!(
+            address_from_reverse_result(json),
+            Some(Address {
+                street: Some("Marktplatz 5".into()),
+                zip: Some("70173".into()),
+                city: Some("Stuttgart".into()),
+                country: Some("Deutschland".into()),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_reverse_result_without_address() {
+        assert_eq!(
+            address_from_reverse_result(r#"{"error":"Unable to geocode"}"#),
+            None
+        );
+        assert_eq!(address_from_reverse_result("no json"), None);
+    }
+
+    #[test]
+    fn parse_search_result() {
+        let json = r#"[{"place_id":"1","lat":"48.7758","lon":"9.1829","display_name":"Stuttgart"}]"#;
+        let c = coordinate_from_search_result(json).unwrap();
+        assert_eq!(c.lat, 48.7758);
+        assert_eq!(c.lng, 9.1829);
+        assert!(coordinate_from_search_result("[]").is_none());
+        assert!(coordinate_from_search_result(r#"[{"lat":"x","lon":"9"}]"#).is_none());
+    }
+}
@@ -0,0 +1,110 @@
+use entities as e;
+
+#[cfg_attr(rustfmt, rustfmt_skip)]
+#[derive(Serialize)]
+pub struct FeatureCollection {
+    #[serde(rename = "type")]
+    pub kind     : &'static str,
+    pub features : Vec<Feature>,
+}
+
+#[cfg_attr(rustfmt, rustfmt_skip)]
+#[derive(Serialize)]
+pub struct Feature {
+    #[serde(rename = "type")]
+    pub kind       : &'static str,
+    pub id         : String,
+    pub geometry   : Point,
+    pub properties : Properties,
+}
+
+#[cfg_attr(rustfmt, rustfmt_skip)]
+#[derive(Serialize)]
+pub struct Point {
+    #[serde(rename = "type")]
+    pub kind        : &'static str,
+    // GeoJSON expects [longitude, latitude]
+    pub coordinates : [f64; 2],
+}
+
+#[cfg_attr(rustfmt, rustfmt_skip)]
+#[derive(Serialize)]
+pub struct Properties {
+    pub title       : String,
+    pub description : String,
+    pub categories  : Vec<String>,
+    pub tags        : Vec<String>,
+}
+
+// Entity -> GeoJSON
+
+impl From<Vec<e::Entry>> for FeatureCollection {
+    fn from(entries: Vec<e::Entry>) -> FeatureCollection {
+        let features = entries
+            .into_iter()
+            .filter(|e| e.lat.is_finite() && e.lng.is_finite())
+            .map(Feature::from)
+            .collect();
+        FeatureCollection {
+            kind: "FeatureCollection",
+            features,
+        }
+    }
+}
+
+impl From<e::Entry> for Feature {
+    fn from(e: e::Entry) -> Feature {
+        Feature {
+            kind: "Feature",
+            id: e.id,
+            geometry: Point {
+                kind: "Point",
+                coordinates: [e.lng, e.lat],
+            },
+            properties: Properties {
+                title: e.title,
+                description: e.description,
+                categories: e.categories,
+                tags: e.tags,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use business::builder::EntryBuilder;
+    use serde_json;
+    use std::f64::{INFINITY, NAN};
+
+    #[test]
+    fn entry_to_feature() {
+        let e = e::Entry::build()
+            .id("foo")
+            .title("a title")
+            .description("a description")
+            .lat(48.0)
+            .lng(9.0)
+            .categories(vec!["x"])
+            .tags(vec!["bio", "fair"])
+            .finish();
+        let json = serde_json::to_string(&FeatureCollection::from(vec![e])).unwrap();
+        assert_eq!(
+            json,
+            r#"{"type":"FeatureCollection","features":[{"type":"Feature","id":"foo","geometry":{"type":"Point","coordinates":[9.0,48.0]},"properties":{"title":"a title","description":"a description","categories":["x"],"tags":["bio","fair"]}}]}"#
+        );
+    }
+
+    #[test]
+    fn skip_entries_with_invalid_coordinates() {
+        let entries = vec![
+            e::Entry::build().id("a").lat(NAN).lng(1.0).finish(),
+            e::Entry::build().id("b").lat(1.0).lng(INFINITY).finish(),
+            e::Entry::build().id("c").lat(1.0).lng(1.0).finish(),
+        ];
+        let collection = FeatureCollection::from(entries);
+        assert_eq!(collection.features.len(), 1);
+        assert_eq!(collection.features[0].id, "c");
+    }
+}
@@ -0,0 +1,59 @@
+use entities as e;
+use super::xml::escape;
+
+/// Creates a GPX document with one waypoint per entry
+pub fn from_entries(entries: &[e::Entry]) -> String {
+    let mut gpx = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <gpx version=\"1.1\" creator=\"OpenFairDB\" xmlns=\"http://www.topografix.com/GPX/1/1\">\n",
+    );
+    for e in entries
+        .iter()
+        .filter(|e| e.lat.is_finite() && e.lng.is_finite())
+    {
+        gpx.push_str(&format!(
+            "<wpt lat=\"{}\" lon=\"{}\"><name>{}</name><desc>{}</desc></wpt>\n",
+            e.lat,
+            e.lng,
+            escape(&e.title),
+            escape(&e.description)
+        ));
+    }
+    gpx.push_str("</gpx>\n");
+    gpx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use business::builder::EntryBuilder;
+    use std::f64::NAN;
+
+    #[test]
+    fn entries_to_gpx() {
+        let entries = vec![
+            e::Entry::build()
+                .title("Fish & Chips <Shop>")
+                .description("a description")
+                .lat(48.5)
+                .lng(9.0)
+                .finish(),
+            e::Entry::build().title("invalid").lat(NAN).lng(1.0).finish(),
+        ];
+        assert_eq!(
+            from_entries(&entries),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <gpx version=\"1.1\" creator=\"OpenFairDB\" xmlns=\"http://www.topografix.com/GPX/1/1\">\n\
+             <wpt lat=\"48.5\" lon=\"9\"><name>Fish &amp; Chips &lt;Shop&gt;</name><desc>a description</desc></wpt>\n\
+             </gpx>\n"
+        );
+    }
+
+    #[test]
+    fn empty_gpx() {
+        let gpx = from_entries(&[]);
+        assert!(gpx.contains("<gpx "));
+        assert!(gpx.ends_with("</gpx>\n"));
+        assert!(!gpx.contains("<wpt"));
+    }
+}
@@ -1,26 +1,34 @@
 use entities as e;
+use business::cluster;
+use business::error::ParameterError;
+use std::str::FromStr;
 
 #[cfg_attr(rustfmt, rustfmt_skip)]
 #[derive(Serialize)]
 pub struct Entry {
-    pub id          : String,
-    pub created     : u64,
-    pub version     : u64,
-    pub title       : String,
-    pub description : String,
-    pub lat         : f64,
-    pub lng         : f64,
-    pub street      : Option<String>,
-    pub zip         : Option<String>,
-    pub city        : Option<String>,
-    pub country     : Option<String>,
-    pub email       : Option<String>,
-    pub telephone   : Option<String>,
-    pub homepage    : Option<String>,
-    pub categories  : Vec<String>,
-    pub tags        : Vec<String>,
-    pub ratings     : Vec<String>,
-    pub license     : Option<String>,
+    pub id            : String,
+    pub created       : u64,
+    pub updated       : Option<u64>,
+    pub version       : u64,
+    pub title         : String,
+    pub description   : String,
+    pub lat           : f64,
+    pub lng           : f64,
+    pub street        : Option<String>,
+    pub zip           : Option<String>,
+    pub city          : Option<String>,
+    pub country       : Option<String>,
+    pub email         : Option<String>,
+    pub telephone     : Option<String>,
+    pub homepage      : Option<String>,
+    pub opening_hours : Option<String>,
+    pub image_urls    : Vec<String>,
+    pub categories    : Vec<String>,
+    pub tags          : Vec<String>,
+    pub ratings       : Vec<String>,
+    pub rating_count  : usize,
+    pub comment_count : usize,
+    pub license       : Option<String>,
 }
 
 #[cfg_attr(rustfmt, rustfmt_skip)]
@@ -32,7 +40,9 @@ pub struct Rating {
     pub value       : i8,
     pub context     : e::RatingContext,
     pub comments    : Vec<Comment>,
-    pub source      : String
+    pub source      : String,
+    /// The name of the user who rated
+    pub user        : Option<String>,
 }
 
 #[cfg_attr(rustfmt, rustfmt_skip)]
@@ -49,15 +59,91 @@ pub struct EntryIdWithCoordinates {
     pub id : String,
     pub lat: f64,
     pub lng: f64,
+    /// Distance to the center of the search bbox in the requested unit,
+    /// at most one of them is set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distance_m: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distance_km: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distance_mi: Option<f64>,
+}
+
+/// The unit of distances in responses.
+/// The business logic always uses meters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DistanceUnit {
+    Meters,
+    Kilometers,
+    Miles,
+}
+
+const METERS_PER_MILE: f64 = 1_609.344;
+
+impl Default for DistanceUnit {
+    fn default() -> DistanceUnit {
+        DistanceUnit::Meters
+    }
+}
+
+impl FromStr for DistanceUnit {
+    type Err = ParameterError;
+    fn from_str(s: &str) -> Result<DistanceUnit, ParameterError> {
+        Ok(match s {
+            "m" => DistanceUnit::Meters,
+            "km" => DistanceUnit::Kilometers,
+            "mi" => DistanceUnit::Miles,
+            _ => {
+                return Err(ParameterError::DistanceUnit);
+            }
+        })
+    }
+}
+
+impl DistanceUnit {
+    /// Converts a distance in meters into this unit
+    pub fn convert(self, meters: f64) -> f64 {
+        match self {
+            DistanceUnit::Meters => meters,
+            DistanceUnit::Kilometers => meters / 1_000.0,
+            DistanceUnit::Miles => meters / METERS_PER_MILE,
+        }
+    }
+}
+
+/// A visible search result is either just the id or,
+/// if requested, the whole entry.
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum SearchResult {
+    Id(EntryIdWithCoordinates),
+    Entry(Entry),
 }
 
 #[cfg_attr(rustfmt, rustfmt_skip)]
 #[derive(Serialize)]
 pub struct SearchResponse {
-    pub visible   : Vec<EntryIdWithCoordinates>,
+    pub visible   : Vec<SearchResult>,
     pub invisible : Vec<EntryIdWithCoordinates>
 }
 
+#[derive(Serialize)]
+pub struct Cluster {
+    pub lat: f64,
+    pub lng: f64,
+    pub count: usize,
+}
+
+impl From<cluster::Cluster> for Cluster {
+    fn from(c: cluster::Cluster) -> Cluster {
+        Cluster {
+            lat: c.lat,
+            lng: c.lng,
+            count: c.count,
+        }
+    }
+}
+
 #[derive(Serialize)]
 pub struct User {
     pub username: String,
@@ -74,30 +160,212 @@ pub struct BboxSubscription{
     pub north_east_lng  : f64,
 }
 
+#[cfg_attr(rustfmt, rustfmt_skip)]
+#[derive(Serialize, Deserialize)]
+pub struct Subscription {
+    pub id          : String,
+    pub south_west  : e::Coordinate,
+    pub north_east  : e::Coordinate,
+}
+
+#[cfg_attr(rustfmt, rustfmt_skip)]
+#[derive(Serialize)]
+pub struct GeoCenter {
+    pub south_west    : e::Coordinate,
+    pub north_east    : e::Coordinate,
+    pub center        : e::Coordinate,
+    pub extended_bbox : e::Bbox,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RatingConfig {
+    pub min: i8,
+    pub max: i8,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
+}
+
+/// The body of all error responses
+#[derive(Serialize, Deserialize)]
+pub struct Error {
+    /// A stable name of the kind of error, e.g. `not_found`
+    pub error: String,
+    pub message: String,
+}
+
+/// The body of a `422 Unprocessable Entity` response
+#[derive(Serialize, Deserialize)]
+pub struct ValidationErrors {
+    pub error: String,
+    pub message: String,
+    pub errors: Vec<FieldError>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+/// The body of a `409 Conflict` response
+#[derive(Serialize, Deserialize)]
+pub struct VersionConflict {
+    pub error: String,
+    pub message: String,
+    pub current_version: u64,
+}
+
+#[derive(Serialize)]
+pub struct ServerInfo {
+    pub version: &'static str,
+    pub build_timestamp: Option<u64>,
+    pub git_commit_hash: Option<&'static str>,
+    pub base_url: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum BatchResult {
+    #[serde(rename = "ok")]
+    Ok(String),
+    #[serde(rename = "error")]
+    Err(String),
+}
+
 // Entity -> JSON
 
+impl Rating {
+    pub fn from_rating_with_comments(r: e::Rating, comments: Vec<e::Comment>) -> Rating {
+        Rating {
+            id: r.id,
+            created: r.created,
+            title: r.title,
+            value: r.value,
+            context: r.context,
+            source: r.source.unwrap_or_else(|| "".into()),
+            user: r.created_by,
+            comments: comments
+                .into_iter()
+                .map(|c| Comment {
+                    id: c.id,
+                    created: c.created,
+                    text: c.text,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl EntryIdWithCoordinates {
+    pub fn from_entry(e: e::Entry, distance_in_meters: Option<f64>, unit: DistanceUnit) -> Self {
+        let distance = |u: DistanceUnit| {
+            if u == unit {
+                distance_in_meters.map(|d| unit.convert(d))
+            } else {
+                None
+            }
+        };
+        EntryIdWithCoordinates {
+            distance_m: distance(DistanceUnit::Meters),
+            distance_km: distance(DistanceUnit::Kilometers),
+            distance_mi: distance(DistanceUnit::Miles),
+            id: e.id,
+            lat: e.lat,
+            lng: e.lng,
+        }
+    }
+}
+
 #[cfg_attr(rustfmt, rustfmt_skip)]
 impl Entry {
-    pub fn from_entry_with_ratings(e: e::Entry, ratings: Vec<e::Rating>) -> Entry {
+    pub fn from_entry_with_ratings(
+        e: e::Entry,
+        ratings: Vec<e::Rating>,
+        comment_count: usize,
+    ) -> Entry {
         Entry{
-            id          : e.id,
-            created     : e.created,
-            version     : e.version,
-            title       : e.title,
-            description : e.description,
-            lat         : e.lat,
-            lng         : e.lng,
-            street      : e.street,
-            zip         : e.zip,
-            city        : e.city,
-            country     : e.country,
-            email       : e.email,
-            telephone   : e.telephone,
-            homepage    : e.homepage,
-            categories  : e.categories,
-            tags        : e.tags,
-            ratings     : ratings.into_iter().map(|r|r.id).collect(),
-            license     : e.license,
+            id            : e.id,
+            created       : e.created,
+            updated       : e.updated,
+            version       : e.version,
+            title         : e.title,
+            description   : e.description,
+            lat           : e.lat,
+            lng           : e.lng,
+            street        : e.street,
+            zip           : e.zip,
+            city          : e.city,
+            country       : e.country,
+            email         : e.email,
+            telephone     : e.telephone,
+            homepage      : e.homepage,
+            opening_hours : e.opening_hours,
+            image_urls    : e.image_urls,
+            categories    : e.categories,
+            tags          : e.tags,
+            rating_count  : ratings.len(),
+            comment_count,
+            ratings       : ratings.into_iter().map(|r|r.id).collect(),
+            license       : e.license,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use business::builder::EntryBuilder;
+
+    #[test]
+    fn convert_distances() {
+        let mi = DistanceUnit::Miles.convert(1_609.34);
+        assert!((mi - 1.0).abs() < 1e-5);
+        assert_eq!(DistanceUnit::Kilometers.convert(1_500.0), 1.5);
+        assert_eq!(DistanceUnit::Meters.convert(1_500.0), 1_500.0);
+    }
+
+    #[test]
+    fn parse_distance_unit() {
+        assert_eq!("m".parse::<DistanceUnit>().unwrap(), DistanceUnit::Meters);
+        assert_eq!("km".parse::<DistanceUnit>().unwrap(), DistanceUnit::Kilometers);
+        assert_eq!("mi".parse::<DistanceUnit>().unwrap(), DistanceUnit::Miles);
+        assert!("ft".parse::<DistanceUnit>().is_err());
+    }
+
+    #[test]
+    fn set_only_the_distance_in_the_requested_unit() {
+        let e = e::Entry::build().id("a").finish();
+        let x = EntryIdWithCoordinates::from_entry(e.clone(), Some(1_609.34), DistanceUnit::Miles);
+        assert!((x.distance_mi.unwrap() - 1.0).abs() < 1e-5);
+        assert!(x.distance_m.is_none());
+        assert!(x.distance_km.is_none());
+        let x = EntryIdWithCoordinates::from_entry(e, None, DistanceUnit::Miles);
+        assert!(x.distance_mi.is_none());
+    }
+
+    #[test]
+    fn serialize_rating_and_comment_counts() {
+        use serde_json;
+        let e = e::Entry::build().id("a").finish();
+        let json = serde_json::to_string(&Entry::from_entry_with_ratings(e.clone(), vec![], 0))
+            .unwrap();
+        assert!(json.contains(r#""rating_count":0,"comment_count":0"#));
+
+        let rating = e::Rating {
+            id: "r".into(),
+            entry_id: "a".into(),
+            created: 0,
+            title: "title".into(),
+            value: 2,
+            context: e::RatingContext::Fairness,
+            source: None,
+            created_by: None,
+        };
+        let json = serde_json::to_string(&Entry::from_entry_with_ratings(e, vec![rating], 3))
+            .unwrap();
+        assert!(json.contains(r#""ratings":["r"],"rating_count":1,"comment_count":3"#));
+    }
+}
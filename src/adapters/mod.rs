@@ -1,2 +1,8 @@
 pub mod json;
+pub mod feed;
+pub mod geocode;
+pub mod geojson;
+pub mod gpx;
+pub mod osm;
 pub mod user_communication;
+pub mod xml;
@@ -0,0 +1,293 @@
+use entities::*;
+use std::collections::HashMap;
+use std::result;
+use serde_json;
+use chrono::prelude::*;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct OsmQueryResult {
+    elements: Vec<OsmNode>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OsmNode {
+    pub id: u64,
+    pub lat: f64,
+    pub lon: f64,
+    pub tags: HashMap<String, String>,
+}
+
+/// Parses the JSON result of an Overpass API query
+pub fn parse_query_result(data: &str) -> result::Result<Vec<OsmNode>, serde_json::Error> {
+    let r: OsmQueryResult = serde_json::from_str(data)?;
+    Ok(r.elements)
+}
+
+fn map_osm_tags(osm_tags: &HashMap<String, String>) -> Vec<Tag> {
+    let mut tags = vec![];
+    let mut tag_map = HashMap::new();
+
+    // TODO: use config file
+    tag_map.insert("diet:vegan", "vegan");
+    tag_map.insert("diet:vegetarian", "vegetarisch");
+    tag_map.insert("diet:egg_free", "eifrei");
+    tag_map.insert("diet:lactose_free", "laktosefrei");
+    tag_map.insert("diet:soy_free", "soyafrei");
+    tag_map.insert("diet:dairy_free", "milchfrei");
+    tag_map.insert("diet:gluten_free", "glutenfrei");
+    tag_map.insert("organic", "bio");
+
+    for (k, v) in tag_map {
+        if let Some(_val) = osm_tags.get(k) {
+            tags.push(Tag { id: v.into() });
+        }
+    }
+    tags
+}
+
+/// Maps an OSM node to a new entry.
+/// Nodes without a name are skipped.
+pub fn entry_from_node(osm: &OsmNode) -> Option<Entry> {
+    let title = osm.tags.get("name")?.clone();
+
+    let description = title.clone();
+
+    let id = Uuid::new_v4().simple().to_string();
+
+    let osm_node = Some(osm.id);
+
+    let lat = osm.lat;
+    let lng = osm.lon;
+
+    let version = 0;
+    let created = Utc::now().timestamp() as u64;
+    let house_nr = osm.tags.get("addr:housenumber").cloned();
+    let street = osm.tags.get("addr:street").cloned();
+    let zip = osm.tags.get("addr:postcode").cloned();
+    let city = osm.tags.get("addr:city").cloned();
+    let country = osm.tags.get("addr:country").cloned();
+    let email = None;
+    let telephone = osm.tags.get("phone").cloned();
+    let homepage = osm.tags.get("website").cloned();
+    let opening_hours = osm.tags.get("opening_hours").cloned();
+    let categories = vec![];
+    let license = Some("ODbL-1.0".into());
+
+    let street = street.map(|s| {
+        if let Some(nr) = house_nr {
+            format!("{} {}", s, nr)
+        } else {
+            s
+        }
+    });
+
+    let tags = map_osm_tags(&osm.tags).into_iter().map(|t| t.id).collect();
+
+    Some(Entry {
+        id,
+        osm_node,
+        created,
+        version,
+        title,
+        description,
+        lat,
+        lng,
+        street,
+        zip,
+        city,
+        country,
+        email,
+        telephone,
+        homepage,
+        opening_hours,
+        image_urls: vec![],
+        categories,
+        tags,
+        license,
+        created_by: None,
+        updated: None,
+        archived: None,
+    })
+}
+
+/// Applies a re-imported node to the entry that was created from it before.
+/// Everything that doesn't come from OSM (description, categories,
+/// additional tags, ...) is kept.
+/// Returns `None` if nothing changed.
+pub fn update_entry(old: &Entry, imported: Entry) -> Option<Entry> {
+    let mut tags = old.tags.clone();
+    for t in imported.tags {
+        if !tags.contains(&t) {
+            tags.push(t);
+        }
+    }
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    let e = Entry {
+        id          : old.id.clone(),
+        created     : old.created,
+        version     : old.version,
+        updated     : old.updated,
+        archived    : old.archived,
+        description : old.description.clone(),
+        email       : old.email.clone(),
+        categories  : old.categories.clone(),
+        created_by  : old.created_by.clone(),
+        image_urls  : old.image_urls.clone(),
+        tags,
+        ..imported
+    };
+    if e == *old {
+        return None;
+    }
+    Some(Entry {
+        version: old.version + 1,
+        updated: Some(Utc::now().timestamp() as u64),
+        ..e
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_query_result() {
+        let result = r#"{
+          "version": 0.6,
+          "generator": "Overpass API 0.7.54.12 054bb0bb",
+          "osm3s": {
+            "timestamp_osm_base": "2017-11-22T22:20:03Z",
+            "copyright": "The data included in this document is from www.openstreetmap.org. The data is made available under ODbL."
+          },
+          "elements": [
+
+        {
+          "type": "node",
+          "id": 20962297,
+          "lat": 47.0598329,
+          "lon": 15.4701174,
+          "tags": {
+            "addr:city": "Graz",
+            "addr:country": "AT",
+            "addr:housenumber": "107a",
+            "addr:postcode": "8042",
+            "addr:street": "Plüddemanngasse",
+            "diet:dairy_free": "yes",
+            "diet:egg_free": "yes",
+            "diet:gluten_free": "yes",
+            "diet:lactose_free": "yes",
+            "diet:soy_free": "yes",
+            "diet:vegan": "yes",
+            "diet:vegetarian": "yes",
+            "name": "denn's Biomarkt",
+            "opening_hours": "Mo-Fr 08:00-19:00; Sa 08:00-18:00",
+            "organic": "only",
+            "phone": "+43 316-422677",
+            "shop": "supermarket",
+            "start_date": "2016-04-21",
+            "website": "http://www.denns-biomarkt.at/",
+            "wheelchair": "limited"
+          }
+        }]
+        }"#;
+        let x = parse_query_result(result).unwrap();
+        assert_eq!(x.len(), 1);
+        assert_eq!(x[0].id, 20962297);
+        assert_eq!(x[0].tags.get("addr:city").unwrap(), "Graz");
+    }
+
+    #[test]
+    fn test_from_osm_for_entry() {
+        let mut tags = HashMap::new();
+
+        tags.insert("addr:street".into(), "Plüddemanngasse".into());
+        tags.insert("addr:housenumber".into(), "107a".into());
+        tags.insert("addr:postcode".into(), "8042".into());
+        tags.insert("addr:city".into(), "Graz".into());
+        tags.insert("addr:country".into(), "AT".into());
+        tags.insert("name".into(), "denn's Biomarkt".into());
+        tags.insert("phone".into(), "+43 316-422677".into());
+        tags.insert("website".into(), "http://www.denns-biomarkt.at/".into());
+        tags.insert("opening_hours".into(), "Mo-Fr 08:00-19:00".into());
+
+        tags.insert("diet:dairy_free".into(), "yes".into());
+        tags.insert("diet:egg_free".into(), "yes".into());
+        tags.insert("diet:gluten_free".into(), "yes".into());
+        tags.insert("diet:lactose_free".into(), "yes".into());
+        tags.insert("diet:soy_free".into(), "yes".into());
+        tags.insert("diet:vegan".into(), "yes".into());
+        tags.insert("diet:vegetarian".into(), "yes".into());
+        tags.insert("organic".into(), "only".into());
+
+        let osm = OsmNode {
+            id: 12123,
+            lat: 48.0,
+            lon: 10.0,
+            tags,
+        };
+
+        let e = entry_from_node(&osm).unwrap();
+
+        assert_eq!(e.lat, 48.0);
+        assert_eq!(e.lng, 10.0);
+        assert_eq!(e.version, 0);
+        assert_eq!(e.osm_node, Some(12123));
+        assert_eq!(e.title, "denn's Biomarkt");
+        assert_eq!(e.description, "denn's Biomarkt");
+        assert_eq!(e.city, Some("Graz".into()));
+        assert_eq!(e.zip, Some("8042".into()));
+        assert_eq!(e.country, Some("AT".into()));
+        assert_eq!(e.street, Some("Plüddemanngasse 107a".into()));
+        assert_eq!(e.homepage, Some("http://www.denns-biomarkt.at/".into()));
+        assert_eq!(e.telephone, Some("+43 316-422677".into()));
+        assert_eq!(e.opening_hours, Some("Mo-Fr 08:00-19:00".into()));
+        assert_eq!(e.license, Some("ODbL-1.0".into()));
+
+        assert!(e.tags.iter().any(|id| id == "vegan"));
+        assert!(e.tags.iter().any(|id| id == "vegetarisch"));
+        assert!(e.tags.iter().any(|id| id == "bio"));
+        assert!(e.tags.iter().any(|id| id == "eifrei"));
+        assert!(e.tags.iter().any(|id| id == "laktosefrei"));
+        assert!(e.tags.iter().any(|id| id == "soyafrei"));
+        assert!(e.tags.iter().any(|id| id == "milchfrei"));
+        assert!(e.tags.iter().any(|id| id == "glutenfrei"));
+    }
+
+    #[test]
+    fn update_entry_from_node() {
+        let mut tags = HashMap::new();
+        tags.insert("name".into(), "Weltladen".into());
+        tags.insert("phone".into(), "0123".into());
+        tags.insert("organic".into(), "yes".into());
+        let osm = OsmNode {
+            id: 42,
+            lat: 48.0,
+            lon: 10.0,
+            tags,
+        };
+        let mut old = entry_from_node(&osm).unwrap();
+        assert!(update_entry(&old, entry_from_node(&osm).unwrap()).is_none());
+
+        old.description = "a fair trade shop".into();
+        old.categories = vec!["x".into()];
+        old.tags.push("fair".into());
+        assert!(update_entry(&old, entry_from_node(&osm).unwrap()).is_none());
+
+        let mut moved = osm.clone();
+        moved.lat = 48.1;
+        moved.tags.insert("diet:vegan".into(), "yes".into());
+        let e = update_entry(&old, entry_from_node(&moved).unwrap()).unwrap();
+        assert_eq!(e.id, old.id);
+        assert_eq!(e.version, old.version + 1);
+        assert!(e.updated.is_some());
+        assert_eq!(e.lat, 48.1);
+        assert_eq!(e.description, "a fair trade shop");
+        assert_eq!(e.categories, vec!["x"]);
+        assert_eq!(e.tags, vec!["bio", "fair", "vegan"]);
+
+        let mut no_name = osm.clone();
+        no_name.tags.remove("name");
+        assert!(entry_from_node(&no_name).is_none());
+    }
+}
@@ -1,15 +1,154 @@
 use business::usecase::{NewEntry, UpdateEntry};
 use entities::Entry;
+use super::xml;
+use std::str::FromStr;
 
-pub fn email_confirmation_email(u_id: &str) -> String {
-    format!(
-        "Na du Weltverbesserer*,\nwir freuen uns dass du bei der Karte von Morgen mit dabei bist!\n\nBitte bestätige deine Email-Adresse hier:\nhttps://kartevonmorgen.org/#/?confirm_email={}.\n\neuphorische Grüße\ndas Karte von Morgen-Team",
-        u_id
+/// The language of the emails
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Language {
+    German,
+    English,
+}
+
+impl Default for Language {
+    fn default() -> Language {
+        Language::German
+    }
+}
+
+impl FromStr for Language {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Language, ()> {
+        match s {
+            "de" => Ok(Language::German),
+            "en" => Ok(Language::English),
+            _ => Err(()),
+        }
+    }
+}
+
+/// An email with a plain text and an HTML version of the same content
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+}
+
+// A value that is filled into a template
+enum Value {
+    Text(String),
+    Link(String),
+}
+
+fn render_value(v: &Value, html: bool) -> String {
+    match *v {
+        Value::Text(ref s) | Value::Link(ref s) if !html => s.clone(),
+        Value::Link(ref url) if url.starts_with("http://") || url.starts_with("https://") => {
+            let url = xml::escape(url);
+            format!("<a href=\"{}\">{}</a>", url, url)
+        }
+        Value::Text(ref s) | Value::Link(ref s) => xml::escape(s),
+    }
+}
+
+// Replaces the `{name}` placeholders of the template in a single pass,
+// so placeholders within the values are left alone.
+fn render(template: &str, values: &[(&str, Value)], html: bool) -> String {
+    let literal = |s: &str| if html { xml::escape(s) } else { s.to_string() };
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&literal(&rest[..start]));
+        let after = &rest[start..];
+        match after.find('}') {
+            Some(end) => {
+                let name = &after[1..end];
+                match values.iter().find(|&&(n, _)| n == name) {
+                    Some(&(_, ref v)) => out.push_str(&render_value(v, html)),
+                    None => out.push_str(&literal(&after[..end + 1])),
+                }
+                rest = &after[end + 1..];
+            }
+            None => {
+                out.push_str(&literal(after));
+                rest = "";
+            }
+        }
+    }
+    out.push_str(&literal(rest));
+    if html {
+        format!(
+            "<html><body>{}</body></html>",
+            out.replace("\n", "<br>\n")
+        )
+    } else {
+        out
+    }
+}
+
+fn email(subject: String, template: &str, values: &[(&str, Value)]) -> Email {
+    Email {
+        subject,
+        text: render(template, values, false),
+        html: render(template, values, true),
+    }
+}
+
+pub fn email_confirmation_email(lang: Language, base_url: &str, u_id: &str) -> Email {
+    let (subject, template) = match lang {
+        Language::German => (
+            "Karte von Morgen: bitte bestätige deine Email-Adresse",
+            "Na du Weltverbesserer*,\nwir freuen uns dass du bei der Karte von Morgen mit dabei bist!\n\nBitte bestätige deine Email-Adresse hier:\n{url}.\n\neuphorische Grüße\ndas Karte von Morgen-Team",
+        ),
+        Language::English => (
+            "Karte von Morgen: please confirm your email address",
+            "Hi world changer,\nwe are happy that you joined the Karte von Morgen!\n\nPlease confirm your email address here:\n{url}.\n\nEuphoric regards\nthe Karte von Morgen team",
+        ),
+    };
+    let url = format!("{}/#/?confirm_email={}", base_url, u_id);
+    email(subject.into(), template, &[("url", Value::Link(url))])
+}
+
+pub fn password_reset_email(lang: Language, base_url: &str, username: &str, token: &str) -> Email {
+    let (subject, template) = match lang {
+        Language::German => (
+            "Karte von Morgen: Passwort zurücksetzen",
+            "Hallo {username},\ndu hast angefragt dein Passwort bei der Karte von Morgen zurückzusetzen.\n\nHier kannst du ein neues Passwort vergeben:\n{url}\n\nDer Link ist 24 Stunden gültig. Falls du das nicht angefragt hast, kannst du diese Email einfach ignorieren.\n\neuphorische Grüße\ndas Karte von Morgen-Team",
+        ),
+        Language::English => (
+            "Karte von Morgen: reset your password",
+            "Hello {username},\nyou asked to reset your password for the Karte von Morgen.\n\nYou can choose a new password here:\n{url}\n\nThe link is valid for 24 hours. If you did not ask for it, just ignore this email.\n\nEuphoric regards\nthe Karte von Morgen team",
+        ),
+    };
+    let url = format!("{}/#/?reset_password={}", base_url, token);
+    email(
+        subject.into(),
+        template,
+        &[
+            ("username", Value::Text(username.into())),
+            ("url", Value::Link(url)),
+        ],
     )
 }
 
-pub fn new_entry_email(e: &NewEntry, id: &str, categories: &[String]) -> String {
-    let intro_sentence = "ein neuer Eintrag auf der Karte von Morgen wurde erstellt";
+pub fn new_entry_email(
+    lang: Language,
+    base_url: &str,
+    e: &NewEntry,
+    id: &str,
+    categories: &[String],
+) -> Email {
+    let (subject, intro_sentence) = match lang {
+        Language::German => (
+            "Karte von Morgen - neuer Eintrag: ",
+            "ein neuer Eintrag auf der Karte von Morgen wurde erstellt",
+        ),
+        Language::English => (
+            "Karte von Morgen - new entry: ",
+            "a new entry was created on the Karte von Morgen",
+        ),
+    };
     let entry = Entry {
         id: id.into(),
         osm_node: None,
@@ -22,19 +161,39 @@ pub fn new_entry_email(e: &NewEntry, id: &str, categories: &[String]) -> String
         email: e.email.clone(),
         telephone: e.telephone.clone(),
         homepage: e.homepage.clone(),
+        opening_hours: e.opening_hours.clone(),
+        image_urls: e.image_urls.clone(),
         tags: e.tags.clone(),
         categories: e.categories.clone(),
         lat: 0.0,
         lng: 0.0,
         created: 0,
+        updated: None,
+        archived: None,
         version: 0,
         license: None,
+        created_by: None,
     };
-    entry_email(&entry, categories, &e.tags, intro_sentence)
+    let subject = String::from(subject) + &e.title;
+    entry_email(lang, base_url, subject, &entry, categories, intro_sentence)
 }
 
-pub fn changed_entry_email(e: &UpdateEntry, categories: &[String]) -> String {
-    let intro_sentence = "folgender Eintrag der Karte von Morgen wurde verändert";
+pub fn changed_entry_email(
+    lang: Language,
+    base_url: &str,
+    e: &UpdateEntry,
+    categories: &[String],
+) -> Email {
+    let (subject, intro_sentence) = match lang {
+        Language::German => (
+            "Karte von Morgen - Eintrag verändert: ",
+            "folgender Eintrag der Karte von Morgen wurde verändert",
+        ),
+        Language::English => (
+            "Karte von Morgen - entry changed: ",
+            "the following entry of the Karte von Morgen was changed",
+        ),
+    };
     let entry = Entry {
         id: e.id.clone(),
         osm_node: e.osm_node,
@@ -47,23 +206,31 @@ pub fn changed_entry_email(e: &UpdateEntry, categories: &[String]) -> String {
         email: e.email.clone(),
         telephone: e.telephone.clone(),
         homepage: e.homepage.clone(),
+        opening_hours: e.opening_hours.clone(),
+        image_urls: e.image_urls.clone(),
         tags: e.tags.clone(),
         categories: e.categories.clone(),
         lat: 0.0,
         lng: 0.0,
         created: 0,
+        updated: None,
+        archived: None,
         version: 0,
         license: None,
+        created_by: None,
     };
-    entry_email(&entry, categories, &e.tags, intro_sentence)
+    let subject = String::from(subject) + &e.title;
+    entry_email(lang, base_url, subject, &entry, categories, intro_sentence)
 }
 
 pub fn entry_email(
+    lang: Language,
+    base_url: &str,
+    subject: String,
     e: &Entry,
     categories: &[String],
-    tags: &[String],
     intro_sentence: &str,
-) -> String {
+) -> Email {
     let category = if !categories.is_empty() {
         categories[0].clone()
     } else {
@@ -78,8 +245,8 @@ pub fn entry_email(
         e.country.clone().unwrap_or_else(|| "".into()),
     ].join(", ");
 
-    format!(
-        "Hallo,
+    let template = match lang {
+        Language::German => "Hallo,
 {introSentence}:\n
 {title} ({category})
 {description}\n
@@ -89,19 +256,141 @@ pub fn entry_email(
     Email-Adresse: {email}
     Telefon: {telephone}\n
 Eintrag anschauen oder bearbeiten:
-https://kartevonmorgen.org/#/?entry={id}\n
-Du kannst dein Abonnement des Kartenbereichs abbestellen indem du dich auf https://kartevonmorgen.org einloggst.\n
+{entryUrl}\n
+Du kannst dein Abonnement des Kartenbereichs abbestellen indem du dich auf {baseUrl} einloggst.\n
 euphorische Grüße
 das Karte von Morgen-Team",
-        introSentence = intro_sentence,
-        title = &e.title,
-        id = &e.id,
-        description = &e.description,
-        address = address,
-        email = e.email.clone().unwrap_or_else(||"".into()),
-        telephone = e.telephone.clone().unwrap_or_else(||"".into()),
-        homepage = e.homepage.clone().unwrap_or_else(||"".into()),
-        category = category,
-        tags = tags.join(", ")
-    )
+        Language::English => "Hello,
+{introSentence}:\n
+{title} ({category})
+{description}\n
+    Tags: {tags}
+    Address: {address}
+    Homepage: {homepage}
+    Email address: {email}
+    Phone: {telephone}\n
+View or edit the entry:
+{entryUrl}\n
+You can unsubscribe from the map area by logging in at {baseUrl}.\n
+Euphoric regards
+the Karte von Morgen team",
+    };
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    let values = [
+        ("introSentence", Value::Text(intro_sentence.into())),
+        ("title",         Value::Text(e.title.clone())),
+        ("category",      Value::Text(category)),
+        ("description",   Value::Text(e.description.clone())),
+        ("tags",          Value::Text(e.tags.join(", "))),
+        ("address",       Value::Text(address)),
+        ("homepage",      Value::Link(e.homepage.clone().unwrap_or_else(|| "".into()))),
+        ("email",         Value::Text(e.email.clone().unwrap_or_else(|| "".into()))),
+        ("telephone",     Value::Text(e.telephone.clone().unwrap_or_else(|| "".into()))),
+        ("entryUrl",      Value::Link(format!("{}/#/?entry={}", base_url, e.id))),
+        ("baseUrl",       Value::Link(base_url.into())),
+    ];
+    email(subject, template, &values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use business::builder::EntryBuilder;
+
+    const BASE_URL: &str = "https://example.org";
+
+    const GERMAN_PHRASES: [&str; 8] = [
+        "Hallo",
+        "Grüße",
+        "bestätige",
+        "Eintrag",
+        "Adresse",
+        "Webseite",
+        "Passwort",
+        "verändert",
+    ];
+
+    fn all_emails(lang: Language) -> Vec<Email> {
+        let e = Entry::build()
+            .id("foo")
+            .title("A title")
+            .description("A description")
+            .tags(vec!["bio"])
+            .finish();
+        vec![
+            email_confirmation_email(lang, BASE_URL, "123"),
+            password_reset_email(lang, BASE_URL, "bob", "abc"),
+            entry_email(lang, BASE_URL, "subject".into(), &e, &["x".to_string()], "intro"),
+        ]
+    }
+
+    #[test]
+    fn parse_language() {
+        assert_eq!("de".parse::<Language>(), Ok(Language::German));
+        assert_eq!("en".parse::<Language>(), Ok(Language::English));
+        assert!("fr".parse::<Language>().is_err());
+        assert_eq!(Language::default(), Language::German);
+    }
+
+    #[test]
+    fn english_emails_contain_no_german_phrases() {
+        for mail in all_emails(Language::English) {
+            for phrase in GERMAN_PHRASES.iter() {
+                assert!(!mail.subject.contains(phrase), "{:?}", mail);
+                assert!(!mail.text.contains(phrase), "{:?}", mail);
+                assert!(!mail.html.contains(phrase), "{:?}", mail);
+            }
+        }
+        assert!(all_emails(Language::German)
+            .iter()
+            .any(|mail| mail.text.contains("Grüße")));
+        assert!(all_emails(Language::English)[2].text.contains("Tags: bio\n"));
+    }
+
+    #[test]
+    fn links_use_the_base_url() {
+        for lang in &[Language::German, Language::English] {
+            let mail = email_confirmation_email(*lang, BASE_URL, "123");
+            assert!(mail.text.contains("https://example.org/#/?confirm_email=123"));
+            assert!(!mail.text.contains("kartevonmorgen.org"));
+            let e = Entry::build().id("foo").finish();
+            let mail = entry_email(*lang, BASE_URL, "".into(), &e, &[], "intro");
+            assert!(mail.text.contains("https://example.org/#/?entry=foo"));
+            assert!(!mail.text.contains("kartevonmorgen.org"));
+        }
+    }
+
+    #[test]
+    fn html_contains_links() {
+        let e = Entry::build().id("foo").finish();
+        let mut e = e;
+        e.homepage = Some("https://foo.tld/?a=1&b=2".into());
+        let mail = entry_email(Language::English, BASE_URL, "".into(), &e, &[], "intro");
+        assert!(mail.html.starts_with("<html><body>Hello,<br>\n"));
+        assert!(mail.html.contains(
+            r#"<a href="https://example.org/#/?entry=foo">https://example.org/#/?entry=foo</a>"#
+        ));
+        assert!(mail.html.contains(
+            r#"<a href="https://foo.tld/?a=1&amp;b=2">https://foo.tld/?a=1&amp;b=2</a>"#
+        ));
+        assert!(mail.text.contains("Homepage: https://foo.tld/?a=1&b=2\n"));
+    }
+
+    #[test]
+    fn escape_user_content_in_html() {
+        let mut e = Entry::build()
+            .id("foo")
+            .title("<script>alert('x')</script>")
+            .description("Fish & Chips {entryUrl}")
+            .finish();
+        e.homepage = Some("javascript:alert(1)".into());
+        let mail = entry_email(Language::German, BASE_URL, "".into(), &e, &[], "intro");
+        assert!(!mail.html.contains("<script>"));
+        assert!(mail.html.contains("&lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt;"));
+        assert!(mail.html.contains("Fish &amp; Chips {entryUrl}"));
+        assert!(!mail.html.contains("href=\"javascript"));
+        assert!(mail.text.contains("<script>alert('x')</script>"));
+        assert!(mail.text.contains("Fish & Chips {entryUrl}"));
+    }
 }
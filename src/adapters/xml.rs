@@ -0,0 +1,23 @@
+/// Escapes the characters that have a special meaning in XML and HTML
+pub fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[test]
+fn escape_special_characters() {
+    assert_eq!(escape("Fish & Chips"), "Fish &amp; Chips");
+    assert_eq!(escape("<b>\"x\"</b>"), "&lt;b&gt;&quot;x&quot;&lt;/b&gt;");
+    assert_eq!(escape("it's"), "it&#39;s");
+    assert_eq!(escape("Grüße"), "Grüße");
+}
@@ -1,4 +1,6 @@
 use entities::*;
+use business::error::ParameterError;
+use business::validate::{self, Validate};
 use uuid::Uuid;
 
 pub trait EntryBuilder {
@@ -14,6 +16,10 @@ impl EntryBuild {
         self.entry.id = id.into();
         self
     }
+    pub fn created(mut self, created: u64) -> Self {
+        self.entry.created = created;
+        self
+    }
     pub fn version(mut self, v: u64) -> Self {
         self.entry.version = v;
         self
@@ -42,9 +48,63 @@ impl EntryBuild {
         self.entry.tags = tags.into_iter().map(|x| x.into()).collect();
         self
     }
+    pub fn street(mut self, street: &str) -> Self {
+        self.entry.street = Some(street.into());
+        self
+    }
+    pub fn zip(mut self, zip: &str) -> Self {
+        self.entry.zip = Some(zip.into());
+        self
+    }
+    pub fn city(mut self, city: &str) -> Self {
+        self.entry.city = Some(city.into());
+        self
+    }
+    pub fn country(mut self, country: &str) -> Self {
+        self.entry.country = Some(country.into());
+        self
+    }
+    pub fn email(mut self, email: &str) -> Self {
+        self.entry.email = Some(email.into());
+        self
+    }
+    pub fn telephone(mut self, tel: &str) -> Self {
+        self.entry.telephone = Some(tel.into());
+        self
+    }
+    pub fn homepage(mut self, url: &str) -> Self {
+        self.entry.homepage = Some(url.into());
+        self
+    }
+    pub fn opening_hours(mut self, hours: &str) -> Self {
+        self.entry.opening_hours = Some(hours.into());
+        self
+    }
+    pub fn image_urls(mut self, urls: Vec<&str>) -> Self {
+        self.entry.image_urls = urls.into_iter().map(|u| u.into()).collect();
+        self
+    }
+    pub fn license(mut self, license: &str) -> Self {
+        self.entry.license = Some(license.into());
+        self
+    }
+    pub fn osm_node(mut self, node: u64) -> Self {
+        self.entry.osm_node = Some(node);
+        self
+    }
+    /// Returns the entry without any checks
     pub fn finish(self) -> Entry {
         self.entry
     }
+    /// Returns the entry only if it passes the same validation
+    /// as new entries do.
+    pub fn build_valid(self) -> Result<Entry, ParameterError> {
+        self.entry.validate()?;
+        if let Some(ref l) = self.entry.license {
+            validate::license(l)?;
+        }
+        Ok(self.entry)
+    }
 }
 
 impl EntryBuilder for Entry {
@@ -59,24 +119,109 @@ impl Default for Entry {
     fn default() -> Entry {
         #[cfg_attr(rustfmt, rustfmt_skip)]
         Entry{
-            id          : Uuid::new_v4().simple().to_string(),
-            osm_node    : None,
-            created     : 0,
-            version     : 0,
-            title       : "".into(),
-            description : "".into(),
-            lat         : 0.0,
-            lng         : 0.0,
-            street      : None,
-            zip         : None,
-            city        : None,
-            country     : None,
-            email       : None,
-            telephone   : None,
-            homepage    : None,
-            categories  : vec![],
-            tags        : vec![],
-            license     : None,
+            id            : Uuid::new_v4().simple().to_string(),
+            osm_node      : None,
+            created       : 0,
+            updated       : None,
+            archived      : None,
+            version       : 0,
+            title         : "".into(),
+            description   : "".into(),
+            lat           : 0.0,
+            lng           : 0.0,
+            street        : None,
+            zip           : None,
+            city          : None,
+            country       : None,
+            email         : None,
+            telephone     : None,
+            homepage      : None,
+            opening_hours : None,
+            image_urls    : vec![],
+            categories    : vec![],
+            tags          : vec![],
+            license       : None,
+            created_by    : None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_entry_with_all_fields() {
+        let e = Entry::build()
+            .id("foo")
+            .version(3)
+            .title("Weltladen")
+            .description("fair trade shop")
+            .lat(48.7755)
+            .lng(9.1827)
+            .street("Hauptstraße 1")
+            .zip("70173")
+            .city("Stuttgart")
+            .country("Germany")
+            .email("info@weltladen.tld")
+            .telephone("+49 711 123")
+            .homepage("https://weltladen.tld")
+            .categories(vec!["x"])
+            .tags(vec!["fair", "bio"])
+            .license("CC0-1.0")
+            .osm_node(42)
+            .finish();
+        assert_eq!(e.id, "foo");
+        assert_eq!(e.version, 3);
+        assert_eq!(e.title, "Weltladen");
+        assert_eq!(e.description, "fair trade shop");
+        assert_eq!(e.lat, 48.7755);
+        assert_eq!(e.lng, 9.1827);
+        assert_eq!(e.street, Some("Hauptstraße 1".into()));
+        assert_eq!(e.zip, Some("70173".into()));
+        assert_eq!(e.city, Some("Stuttgart".into()));
+        assert_eq!(e.country, Some("Germany".into()));
+        assert_eq!(e.email, Some("info@weltladen.tld".into()));
+        assert_eq!(e.telephone, Some("+49 711 123".into()));
+        assert_eq!(e.homepage, Some("https://weltladen.tld".into()));
+        assert_eq!(e.categories, vec!["x"]);
+        assert_eq!(e.tags, vec!["fair", "bio"]);
+        assert_eq!(e.license, Some("CC0-1.0".into()));
+        assert_eq!(e.osm_node, Some(42));
+        assert_eq!(e.created, 0);
+        assert_eq!(e.created_by, None);
+    }
+
+    #[test]
+    fn build_valid_entry() {
+        let e = Entry::build()
+            .title("Weltladen")
+            .description("fair trade shop")
+            .lat(48.7755)
+            .lng(9.1827)
+            .license("CC0-1.0")
+            .build_valid()
+            .unwrap();
+        assert_eq!(e.title, "Weltladen");
+    }
+
+    #[test]
+    fn build_invalid_entries() {
+        let valid = || Entry::build().title("foo").description("bar");
+        assert!(valid().build_valid().is_ok());
+        match valid().lat(91.0).build_valid().err().unwrap() {
+            ParameterError::Coordinate => {}
+            _ => panic!("invalid error type"),
+        }
+        // finish doesn't check anything
+        assert_eq!(valid().lat(91.0).finish().lat, 91.0);
+        match valid().title(" ").build_valid().err().unwrap() {
+            ParameterError::EmptyTitle => {}
+            _ => panic!("invalid error type"),
+        }
+        match valid().license("proprietary").build_valid().err().unwrap() {
+            ParameterError::License => {}
+            _ => panic!("invalid error type"),
         }
     }
 }
@@ -0,0 +1,108 @@
+use business::error::ParameterError;
+use entities::Entry;
+use std::collections::BTreeMap;
+
+/// The entries within one cell of the grid
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cluster {
+    /// Centroid of the entries
+    pub lat: f64,
+    pub lng: f64,
+    pub count: usize,
+}
+
+/// A cell size of up to 180 degrees
+pub fn grid_size(grid: f64) -> Result<f64, ParameterError> {
+    if grid.is_finite() && grid > 0.0 && grid <= 180.0 {
+        Ok(grid)
+    } else {
+        Err(ParameterError::GridSize)
+    }
+}
+
+/// Buckets the entries into a lat/lng grid with
+/// the given cell size (in degrees) and returns
+/// the non-empty cells from south west to north east.
+pub fn clusters(entries: &[Entry], grid: f64) -> Vec<Cluster> {
+    // (row, column) -> (sum of lat, sum of lng, count)
+    let mut cells: BTreeMap<(i64, i64), (f64, f64, usize)> = BTreeMap::new();
+    for e in entries {
+        let cell = (
+            (e.lat / grid).floor() as i64,
+            (e.lng / grid).floor() as i64,
+        );
+        let sums = cells.entry(cell).or_insert((0.0, 0.0, 0));
+        sums.0 += e.lat;
+        sums.1 += e.lng;
+        sums.2 += 1;
+    }
+    cells
+        .into_iter()
+        .map(|(_, (lat, lng, count))| Cluster {
+            lat: lat / count as f64,
+            lng: lng / count as f64,
+            count,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use business::builder::EntryBuilder;
+
+    fn entry(id: &str, lat: f64, lng: f64) -> Entry {
+        Entry::build().id(id).lat(lat).lng(lng).finish()
+    }
+
+    #[test]
+    fn cluster_entries_of_two_cells() {
+        let entries = vec![
+            entry("a", 1.0, 1.0),
+            entry("b", 3.0, 2.0),
+            entry("c", 2.0, 3.0),
+            entry("d", 11.0, 12.0),
+            entry("e", 13.0, 14.0),
+        ];
+        let clusters = clusters(&entries, 10.0);
+        assert_eq!(
+            clusters,
+            vec![
+                Cluster {
+                    lat: 2.0,
+                    lng: 2.0,
+                    count: 3,
+                },
+                Cluster {
+                    lat: 12.0,
+                    lng: 13.0,
+                    count: 2,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn cluster_negative_coordinates_into_their_own_cells() {
+        let entries = vec![entry("a", -1.0, -1.0), entry("b", 1.0, 1.0)];
+        let clusters = clusters(&entries, 10.0);
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[0].lat, -1.0);
+        assert_eq!(clusters[1].lat, 1.0);
+        assert!(clusters.iter().all(|c| c.count == 1));
+    }
+
+    #[test]
+    fn no_clusters_without_entries() {
+        assert!(clusters(&[], 1.0).is_empty());
+    }
+
+    #[test]
+    fn check_the_grid_size() {
+        assert_eq!(grid_size(0.5).unwrap(), 0.5);
+        assert_eq!(grid_size(180.0).unwrap(), 180.0);
+        assert!(grid_size(0.0).is_err());
+        assert!(grid_size(-1.0).is_err());
+        assert!(grid_size(181.0).is_err());
+    }
+}
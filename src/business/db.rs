@@ -1,9 +1,35 @@
 use super::error::RepoError;
 use std::result;
 use entities::*;
+use super::filter::Combination;
 
 type Result<T> = result::Result<T, RepoError>;
 
+/// The filters of an entry search
+#[derive(Debug, Clone)]
+pub struct SearchParams {
+    /// Only entries within this box (all entries if `None`)
+    pub bbox: Option<Bbox>,
+    /// Entries with at least one or all of these categories
+    pub categories: Option<Vec<String>>,
+    pub categories_mode: Combination,
+    /// Comma separated words that the title or description contains
+    pub text: String,
+    /// Entries with all of these tags
+    pub tags: Vec<String>,
+    /// Entries with none of these tags
+    pub exclude_tags: Vec<String>,
+    /// Entries in this city (ignoring case)
+    pub city: Option<String>,
+    /// Entries in this country (ignoring case)
+    pub country: Option<String>,
+    /// Entries created at or after this unix timestamp
+    pub created_after: Option<u64>,
+    /// Entries created at or before this unix timestamp
+    pub created_before: Option<u64>,
+    pub include_archived: bool,
+}
+
 pub trait Repo<T> {
     fn get(&self, &str) -> Result<T>;
     fn all(&self) -> Result<Vec<T>>;
@@ -15,15 +41,32 @@ pub trait Db {
     fn create_entry(&mut self, &Entry) -> Result<()>;
     fn create_tag_if_it_does_not_exist(&mut self, &Tag) -> Result<()>;
     fn create_category_if_it_does_not_exist(&mut self, &Category) -> Result<()>;
+    fn create_category(&mut self, &Category) -> Result<()>;
     fn create_user(&mut self, &User) -> Result<()>;
     fn create_comment(&mut self, &Comment) -> Result<()>;
     fn create_rating(&mut self, &Rating) -> Result<()>;
     fn create_bbox_subscription(&mut self, &BboxSubscription) -> Result<()>;
+    fn create_password_reset(&mut self, &PasswordReset) -> Result<()>;
 
     fn get_entry(&self, &str) -> Result<Entry>;
+    /// The current versions of the entries with one of the ids in any order;
+    /// unknown ids are ignored
+    fn get_entries(&self, &[String]) -> Result<Vec<Entry>>;
+    /// All versions of an entry, the oldest first
+    fn get_entry_history(&self, &str) -> Result<Vec<Entry>>;
     fn get_user(&self, &str) -> Result<User>;
+    fn get_password_reset(&self, &str) -> Result<PasswordReset>;
+    fn get_rating(&self, &str) -> Result<Rating>;
+    /// The ratings with one of the ids in any order; unknown ids are ignored
+    fn get_ratings(&self, &[String]) -> Result<Vec<Rating>>;
+    /// The ratings of the entries with one of the ids in any order
+    fn get_ratings_by_entry_ids(&self, &[String]) -> Result<Vec<Rating>>;
+    fn get_comment(&self, &str) -> Result<Comment>;
+    /// The comments on the ratings with one of the ids in any order
+    fn get_comments_by_rating_ids(&self, &[String]) -> Result<Vec<Comment>>;
 
     fn get_entries_by_bbox(&self, &Bbox) -> Result<Vec<Entry>>;
+    fn search_entries(&self, &SearchParams) -> Result<Vec<Entry>>;
 
     fn all_entries(&self) -> Result<Vec<Entry>>;
     fn all_categories(&self) -> Result<Vec<Category>>;
@@ -33,11 +76,30 @@ pub trait Db {
     fn all_users(&self) -> Result<Vec<User>>;
     fn all_bbox_subscriptions(&self) -> Result<Vec<BboxSubscription>>;
 
+    /// Counts the current entries that are not archived
+    fn count_entries(&self) -> Result<usize>;
+    fn count_tags(&self) -> Result<usize>;
+
     fn update_entry(&mut self, &Entry) -> Result<()>;
-    fn confirm_email_address(&mut self, &str) -> Result<User>; // TODO: move into business layer
+    fn update_user(&mut self, &User) -> Result<()>;
+    fn update_comment(&mut self, &Comment) -> Result<()>;
+    fn update_rating(&mut self, &Rating) -> Result<()>;
+    fn confirm_email_address(&mut self, &str) -> Result<User>;
 
+    fn delete_entry(&mut self, &str) -> Result<()>;
     fn delete_bbox_subscription(&mut self, &str) -> Result<()>;
+    fn delete_tag(&mut self, &str) -> Result<()>;
+    fn delete_rating(&mut self, &str) -> Result<()>;
+    fn delete_comment(&mut self, &str) -> Result<()>;
     fn delete_user(&mut self, &str) -> Result<()>;
+    fn delete_password_reset(&mut self, &str) -> Result<()>;
 
     fn import_multiple_entries(&mut self, &[Entry]) -> Result<()>;
+
+    /// Runs `f` as a unit: if it fails, none of its writes are kept
+    fn transaction<T, E, F>(&mut self, f: F) -> result::Result<T, E>
+    where
+        Self: Sized,
+        E: From<RepoError>,
+        F: FnOnce(&mut Self) -> result::Result<T, E>;
 }
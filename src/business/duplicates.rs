@@ -9,13 +9,39 @@ pub enum DuplicateType {
     SimilarWords,
 }
 
+/// Thresholds of the duplicate detection
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateConfig {
+    /// Entries further apart are never duplicates
+    pub max_distance_m: f64,
+    /// Titles with a smaller share of equal characters
+    /// are no `SimilarChars` duplicates (0.0 to 1.0)
+    pub min_title_similarity: f32,
+    /// Titles that differ in more words
+    /// are no `SimilarWords` duplicates
+    pub max_words_different: u32,
+}
+
+impl Default for DuplicateConfig {
+    fn default() -> DuplicateConfig {
+        DuplicateConfig {
+            max_distance_m: 100.0,
+            min_title_similarity: 0.7,
+            max_words_different: 2,
+        }
+    }
+}
+
 // return vector of entries like: (entry1ID, entry2ID, reason)
 // where entry1 and entry2 are similar entries
-pub fn find_duplicates(entries: &[Entry]) -> Vec<(String, String, DuplicateType)> {
+pub fn find_duplicates(
+    entries: &[Entry],
+    cfg: &DuplicateConfig,
+) -> Vec<(String, String, DuplicateType)> {
     let mut duplicates = Vec::new();
     for i in 0..entries.len() {
         for j in (i + 1)..entries.len() {
-            if let Some(t) = is_duplicate(&entries[i], &entries[j]) {
+            if let Some(t) = is_duplicate(&entries[i], &entries[j], cfg) {
                 duplicates.push((entries[i].id.clone(), entries[j].id.clone(), t));
             }
         }
@@ -24,10 +50,13 @@ pub fn find_duplicates(entries: &[Entry]) -> Vec<(String, String, DuplicateType)
 }
 
 // returns a DuplicateType if the two entries have a similar title, returns None otherwise
-fn is_duplicate(e1: &Entry, e2: &Entry) -> Option<DuplicateType> {
-    if similar_title(e1, e2, 0.3, 0) && in_close_proximity(e1, e2, 100.0) {
+fn is_duplicate(e1: &Entry, e2: &Entry, cfg: &DuplicateConfig) -> Option<DuplicateType> {
+    if !in_close_proximity(e1, e2, cfg.max_distance_m) {
+        return None;
+    }
+    if similar_title(e1, e2, 1.0 - cfg.min_title_similarity, 0) {
         Some(DuplicateType::SimilarChars)
-    } else if similar_title(e1, e2, 0.0, 2) && in_close_proximity(e1, e2, 100.0) {
+    } else if similar_title(e1, e2, 0.0, cfg.max_words_different) {
         Some(DuplicateType::SimilarWords)
     } else {
         None
@@ -47,7 +76,7 @@ fn entry_distance_in_meters(e1: &Entry, e2: &Entry) -> f64 {
         lat: e2.lat,
         lng: e2.lng,
     };
-    geo::distance(&coord1, &coord2) * 1000.0
+    geo::distance(&coord1, &coord2)
 }
 
 fn similar_title(
@@ -257,16 +286,52 @@ mod tests {
             5.003816366195670,
         );
 
+        let cfg = DuplicateConfig::default();
+
         // titles have a word that is equal
-        assert_eq!(Some(DuplicateType::SimilarWords), is_duplicate(&e1, &e2));
+        assert_eq!(Some(DuplicateType::SimilarWords), is_duplicate(&e1, &e2, &cfg));
         // titles similar: small levenshtein distance
-        assert_eq!(Some(DuplicateType::SimilarChars), is_duplicate(&e1, &e4));
+        assert_eq!(Some(DuplicateType::SimilarChars), is_duplicate(&e1, &e4, &cfg));
         // titles similar: small hamming distance
-        assert_eq!(Some(DuplicateType::SimilarChars), is_duplicate(&e1, &e3));
+        assert_eq!(Some(DuplicateType::SimilarChars), is_duplicate(&e1, &e3, &cfg));
         // titles not similar
-        assert_eq!(None, is_duplicate(&e2, &e4));
+        assert_eq!(None, is_duplicate(&e2, &e4, &cfg));
         // entries not located close together
-        assert_eq!(None, is_duplicate(&e4, &e5));
+        assert_eq!(None, is_duplicate(&e4, &e5, &cfg));
+    }
+
+    #[test]
+    fn test_max_distance_edge() {
+        let e1 = new_entry("Weltladen".into(), "".into(), 48.0, 9.0);
+        let e2 = new_entry("Weltladen".into(), "".into(), 48.0005, 9.0);
+        let dist = entry_distance_in_meters(&e1, &e2);
+        let mut cfg = DuplicateConfig::default();
+        cfg.max_distance_m = dist;
+        assert_eq!(Some(DuplicateType::SimilarChars), is_duplicate(&e1, &e2, &cfg));
+        cfg.max_distance_m = dist - 0.01;
+        assert_eq!(None, is_duplicate(&e1, &e2, &cfg));
+    }
+
+    #[test]
+    fn test_min_title_similarity_edge() {
+        // two characters removed from a title of ten
+        let e1 = new_entry("0123456789".into(), "".into(), 48.0, 9.0);
+        let e2 = new_entry("01234567".into(), "".into(), 48.0, 9.0);
+        let mut cfg = DuplicateConfig::default();
+        cfg.min_title_similarity = 0.8;
+        assert_eq!(Some(DuplicateType::SimilarChars), is_duplicate(&e1, &e2, &cfg));
+        cfg.min_title_similarity = 0.9;
+        assert_eq!(None, is_duplicate(&e1, &e2, &cfg));
+    }
+
+    #[test]
+    fn test_max_words_different_edge() {
+        let e1 = new_entry("eins zwei drei".into(), "".into(), 48.0, 9.0);
+        let e2 = new_entry("eins zwei fünf sechs".into(), "".into(), 48.0, 9.0);
+        let mut cfg = DuplicateConfig::default();
+        assert_eq!(Some(DuplicateType::SimilarWords), is_duplicate(&e1, &e2, &cfg));
+        cfg.max_words_different = 1;
+        assert_eq!(None, is_duplicate(&e1, &e2, &cfg));
     }
 
     #[test]
@@ -8,6 +8,12 @@ quick_error!{
         Bbox{
             description("Bounding box is invalid")
         }
+        Coordinate{
+            description("Coordinate is out of range")
+        }
+        UnknownAddress{
+            description("The coordinate of the address could not be found")
+        }
         License{
             description("Unsupported license")
         }
@@ -17,9 +23,43 @@ quick_error!{
         Url{
             description("Invalid URL")
         }
+        ImageUrl{
+            description("Invalid image URL")
+        }
+        Telephone{
+            description("Invalid telephone number")
+        }
+        OpeningHours{
+            description("Invalid opening hours")
+        }
+        /// Doesn't match the format of the country (see `validate::zip`)
+        Zip{
+            description("Invalid zip code for this country")
+        }
+        /// Nothing is left after `validate::tag` normalized it
+        Tag{
+            description("Invalid tag")
+        }
+        /// More than `validate::MAX_IMAGE_URLS`
+        TooManyImageUrls{
+            description("Too many image URLs")
+        }
         UserName{
             description("Invalid username")
         }
+        /// Doesn't match `validate::entry_id`
+        EntryId{
+            description("Invalid entry id")
+        }
+        EntryExists{
+            description("An entry with this id already exists")
+        }
+        EmptyCategoryName{
+            description("The name of the category must not be empty")
+        }
+        CategoryExists{
+            description("A category with this name already exists")
+        }
         UserExists{
             description("The user already exits")
         }
@@ -29,9 +69,24 @@ quick_error!{
         EmptyComment{
             description("Empty comment")
         }
+        EmptyTitle{
+            description("The title must not be empty")
+        }
+        EmptyDescription{
+            description("The description must not be empty")
+        }
+        /// The value is outside of the range
+        /// `validate::RATING_VALUE_MIN..=validate::RATING_VALUE_MAX` (-1 to 2)
         RatingValue{
             description("Rating value out of range")
         }
+        AlreadyRated{
+            description("The user already rated this entry in this context")
+        }
+        /// See `validate::SELF_RATING_POLICY`
+        SelfRating{
+            description("The creator of an entry can't rate it")
+        }
         Credentials {
             description("Invalid credentials")
         }
@@ -41,6 +96,116 @@ quick_error!{
         Forbidden{
             description("This is not allowed")
         }
+        PasswordResetToken{
+            description("Invalid or expired password reset token")
+        }
+        /// More than the configured maximum of ids at once
+        TooManyIds{
+            description("Too many ids requested at once")
+        }
+        /// More than the maximum of items in a batch
+        TooManyItems{
+            description("Too many items in the batch")
+        }
+        /// The items of a batch exceed the rate limit
+        TooManyRequests{
+            description("Too many requests")
+        }
+        /// Another request with the same `Idempotency-Key` is still running
+        IdempotencyKeyInUse{
+            description("A request with this idempotency key is still in progress")
+        }
+        SortOrder{
+            description("Invalid sort order")
+        }
+        Combination{
+            description("Invalid combination (any or all)")
+        }
+        /// Neither a unix timestamp nor an ISO date (see `filter::timestamp`)
+        Timestamp{
+            description("Invalid date (unix timestamp or YYYY-MM-DD)")
+        }
+        DistanceUnit{
+            description("Invalid distance unit (m, km or mi)")
+        }
+        /// The cell size of `cluster::clusters` in degrees
+        GridSize{
+            description("Invalid grid size (more than 0 and up to 180 degrees)")
+        }
+    }
+}
+
+impl ParameterError {
+    /// The field of the rejected input that is invalid.
+    /// Errors that don't belong to a single field return `None`.
+    pub fn field(&self) -> Option<&'static str> {
+        use self::ParameterError::*;
+        match *self {
+            Coordinate => Some("coordinate"),
+            UnknownAddress => Some("address"),
+            License => Some("license"),
+            Email => Some("email"),
+            Url => Some("homepage"),
+            ImageUrl | TooManyImageUrls => Some("image_urls"),
+            Telephone => Some("telephone"),
+            OpeningHours => Some("opening_hours"),
+            Zip => Some("zip"),
+            Tag => Some("tags"),
+            UserName => Some("username"),
+            EntryId => Some("id"),
+            EmptyCategoryName => Some("name"),
+            Password => Some("password"),
+            EmptyComment => Some("text"),
+            EmptyTitle => Some("title"),
+            EmptyDescription => Some("description"),
+            RatingValue => Some("value"),
+            _ => None,
+        }
+    }
+
+    /// A stable name of the error for clients
+    pub fn kind(&self) -> &'static str {
+        use self::ParameterError::*;
+        match *self {
+            Bbox => "bbox",
+            Coordinate => "coordinate",
+            UnknownAddress => "unknown_address",
+            License => "license",
+            Email => "email",
+            Url => "url",
+            ImageUrl => "image_url",
+            Telephone => "telephone",
+            OpeningHours => "opening_hours",
+            Zip => "zip",
+            Tag => "tag",
+            TooManyImageUrls => "too_many_image_urls",
+            UserName => "user_name",
+            EntryId => "entry_id",
+            EntryExists => "entry_exists",
+            EmptyCategoryName => "empty_category_name",
+            CategoryExists => "category_exists",
+            UserExists => "user_exists",
+            Password => "password",
+            EmptyComment => "empty_comment",
+            EmptyTitle => "empty_title",
+            EmptyDescription => "empty_description",
+            RatingValue => "rating_value",
+            AlreadyRated => "already_rated",
+            SelfRating => "self_rating",
+            Credentials => "credentials",
+            EmailNotConfirmed => "email_not_confirmed",
+            Forbidden => "forbidden",
+            PasswordResetToken => "password_reset_token",
+            TooManyIds => "too_many_ids",
+            TooManyItems => "too_many_items",
+            TooManyRequests => "too_many_requests",
+            IdempotencyKeyInUse => "idempotency_key_in_use",
+            SortOrder => "sort_order",
+            Combination => "combination",
+            Timestamp => "timestamp",
+            DistanceUnit => "distance_unit",
+            GridSize => "grid_size",
+        }
     }
 }
 
@@ -54,8 +219,10 @@ quick_error!{
         AlreadyExists{
             description("The object already exists")
         }
-        InvalidVersion{
+        /// Carries the current version of the stored object
+        InvalidVersion(current: u64){
             description("The version of the object is invalid")
+            display("The version of the object is invalid (current version: {})", current)
         }
         Io(err: io::Error) {
             from()
@@ -68,6 +235,19 @@ quick_error!{
     }
 }
 
+impl RepoError {
+    /// A stable name of the error for clients
+    pub fn kind(&self) -> &'static str {
+        match *self {
+            RepoError::NotFound => "not_found",
+            #[cfg(test)]
+            RepoError::AlreadyExists => "already_exists",
+            RepoError::InvalidVersion(_) => "invalid_version",
+            RepoError::Io(_) | RepoError::Other(_) => "internal",
+        }
+    }
+}
+
 quick_error!{
     #[derive(Debug)]
     pub enum Error {
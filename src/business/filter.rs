@@ -1,5 +1,38 @@
+use chrono::{DateTime, NaiveDate};
 use entities::*;
+use business::db::SearchParams;
+use business::error::ParameterError;
 use business::geo::is_in_bbox;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// How multiple values of a filter are combined
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Combination {
+    /// At least one of the values
+    Any,
+    /// All of the values
+    All,
+}
+
+impl Default for Combination {
+    fn default() -> Combination {
+        Combination::Any
+    }
+}
+
+impl FromStr for Combination {
+    type Err = ParameterError;
+    fn from_str(s: &str) -> Result<Combination, ParameterError> {
+        Ok(match s {
+            "any" => Combination::Any,
+            "all" => Combination::All,
+            _ => {
+                return Err(ParameterError::Combination);
+            }
+        })
+    }
+}
 
 pub trait InBBox {
     fn in_bbox(&self, bb: &Bbox) -> bool;
@@ -11,38 +44,179 @@ impl InBBox for Entry {
     }
 }
 
-pub fn entries_by_category_ids<'a>(ids: &'a [String]) -> Box<Fn(&Entry) -> bool + 'a> {
-    Box::new(move |e| ids.iter().any(|c| e.categories.iter().any(|x| x == c)))
+pub fn entries_by_category_ids<'a>(
+    ids: &'a [String],
+    combination: Combination,
+) -> Box<Fn(&Entry) -> bool + 'a> {
+    let has_category = move |e: &Entry, c: &String| e.categories.iter().any(|x| x == c);
+    match combination {
+        Combination::Any => Box::new(move |e: &Entry| ids.iter().any(|c| has_category(e, c))),
+        Combination::All => Box::new(move |e: &Entry| ids.iter().all(|c| has_category(e, c))),
+    }
 }
 
+/// The search text matches the title, the description,
+/// the tags and the names of the `categories` of an entry.
 pub fn entries_by_tags_or_search_text<'a>(
     text: &'a str,
     tags: &'a [String],
+    categories: &'a [Category],
 ) -> Box<Fn(&Entry) -> bool + 'a> {
     let words = to_words(text);
+    let category_names: HashMap<_, _> = if text.is_empty() {
+        HashMap::new()
+    } else {
+        categories
+            .iter()
+            .map(|c| (c.id.as_str(), normalize(&c.name)))
+            .collect()
+    };
+    let by_text = move |entry: &Entry| {
+        let title = normalize(&entry.title);
+        let description = normalize(&entry.description);
+        let tags: Vec<_> = entry.tags.iter().map(|t| normalize(t)).collect();
+        let categories: Vec<_> = entry
+            .categories
+            .iter()
+            .filter_map(|id| category_names.get(id.as_str()))
+            .collect();
+        words.iter().any(|word| {
+            let word = word.as_str();
+            title.contains(word)
+                || description.contains(word)
+                || tags.iter().any(|t| t.contains(word))
+                || categories.iter().any(|c| c.contains(word))
+        })
+    };
 
     if !tags.is_empty() {
         Box::new(move |entry| {
             tags.iter()
                 .map(|t| t.to_lowercase())
                 .all(|tag| entry.tags.iter().any(|t| *t == tag))
-                || ((!text.is_empty() && words.iter().any(|word| {
-                    entry.title.to_lowercase().contains(word)
-                        || entry.description.to_lowercase().contains(word)
-                })) || (text.is_empty() && tags[0] == ""))
+                || ((!text.is_empty() && by_text(entry)) || (text.is_empty() && tags[0] == ""))
         })
     } else {
-        Box::new(move |entry| {
-            ((!text.is_empty() && words.iter().any(|word| {
-                entry.title.to_lowercase().contains(word)
-                    || entry.description.to_lowercase().contains(word)
-            })) || text.is_empty())
-        })
+        Box::new(move |entry| (!text.is_empty() && by_text(entry)) || text.is_empty())
+    }
+}
+
+pub fn entries_without_tags<'a>(tags: &'a [String]) -> Box<Fn(&Entry) -> bool + 'a> {
+    let tags: Vec<_> = tags.iter().map(|t| t.to_lowercase()).collect();
+    Box::new(move |e| !e.tags.iter().any(|t| tags.contains(t)))
+}
+
+/// Empty values match every entry.
+pub fn entries_by_location<'a>(
+    city: Option<&'a str>,
+    country: Option<&'a str>,
+) -> Box<Fn(&Entry) -> bool + 'a> {
+    let matches = |expected: Option<&str>, actual: &Option<String>| match expected {
+        Some(x) if !x.trim().is_empty() => actual
+            .as_ref()
+            .map(|a| a.trim().to_lowercase() == x.trim().to_lowercase())
+            .unwrap_or(false),
+        _ => true,
+    };
+    Box::new(move |e| matches(city, &e.city) && matches(country, &e.country))
+}
+
+/// Both bounds are inclusive, without a bound the range is open-ended.
+pub fn entries_by_created_range(
+    after: Option<u64>,
+    before: Option<u64>,
+) -> Box<Fn(&Entry) -> bool> {
+    Box::new(move |e| {
+        after.map(|a| e.created >= a).unwrap_or(true)
+            && before.map(|b| e.created <= b).unwrap_or(true)
+    })
+}
+
+/// Parses a unix timestamp (in seconds), an RFC 3339 date and time
+/// or an ISO date (`YYYY-MM-DD`), which stands for its first
+/// or (with `end_of_day`) its last second in UTC.
+pub fn timestamp(s: &str, end_of_day: bool) -> Result<u64, ParameterError> {
+    let s = s.trim();
+    if let Ok(t) = s.parse::<u64>() {
+        return Ok(t);
     }
+    let t = match DateTime::parse_from_rfc3339(s) {
+        Ok(dt) => dt.timestamp(),
+        Err(_) => {
+            let date = NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .map_err(|_| ParameterError::Timestamp)?;
+            if end_of_day {
+                date.and_hms(23, 59, 59).timestamp()
+            } else {
+                date.and_hms(0, 0, 0).timestamp()
+            }
+        }
+    };
+    if t < 0 {
+        return Err(ParameterError::Timestamp);
+    }
+    Ok(t as u64)
+}
+
+/// The `categories` are needed to match their names with the search text.
+pub fn entries_by_search_params<'a>(
+    params: &'a SearchParams,
+    categories: &'a [Category],
+) -> Box<Fn(&Entry) -> bool + 'a> {
+    let by_text_or_tags = entries_by_tags_or_search_text(&params.text, &params.tags, categories);
+    let without_excluded_tags = entries_without_tags(&params.exclude_tags);
+    let by_location = entries_by_location(
+        params.city.as_ref().map(|x| x.as_str()),
+        params.country.as_ref().map(|x| x.as_str()),
+    );
+    let by_created = entries_by_created_range(params.created_after, params.created_before);
+    let by_categories = params
+        .categories
+        .as_ref()
+        .map(|ids| entries_by_category_ids(ids, params.categories_mode));
+    Box::new(move |e| {
+        params.bbox.as_ref().map(|bb| e.in_bbox(bb)).unwrap_or(true)
+            && (params.include_archived || e.archived.is_none())
+            && by_categories.as_ref().map(|f| f(e)).unwrap_or(true)
+            && by_text_or_tags(e)
+            && without_excluded_tags(e)
+            && by_location(e)
+            && by_created(e)
+    })
+}
+
+/// Lower case without diacritics,
+/// so that "Café" matches "cafe" and "Straße" matches "strasse".
+pub fn normalize(txt: &str) -> String {
+    let mut res = String::with_capacity(txt.len());
+    for c in txt.chars().flat_map(|c| c.to_lowercase()) {
+        match c {
+            'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => res.push('a'),
+            'æ' => res.push_str("ae"),
+            'ç' | 'ć' | 'ĉ' | 'č' => res.push('c'),
+            'ď' | 'đ' => res.push('d'),
+            'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ė' | 'ę' | 'ě' => res.push('e'),
+            'ğ' => res.push('g'),
+            'ì' | 'í' | 'î' | 'ï' | 'ī' | 'į' | 'ı' => res.push('i'),
+            'ĺ' | 'ľ' | 'ł' => res.push('l'),
+            'ñ' | 'ń' | 'ň' => res.push('n'),
+            'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ő' => res.push('o'),
+            'œ' => res.push_str("oe"),
+            'ŕ' | 'ř' => res.push('r'),
+            'ś' | 'ş' | 'š' => res.push('s'),
+            'ß' => res.push_str("ss"),
+            'ţ' | 'ť' => res.push('t'),
+            'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' | 'ű' | 'ų' => res.push('u'),
+            'ý' | 'ÿ' => res.push('y'),
+            'ź' | 'ż' | 'ž' => res.push('z'),
+            c => res.push(c),
+        }
+    }
+    res
 }
 
 fn to_words(txt: &str) -> Vec<String> {
-    txt.to_lowercase()
+    normalize(txt)
         .split(',')
         .map(|x| x.to_string())
         .collect()
@@ -82,6 +256,26 @@ mod tests {
         assert_eq!(e.in_bbox(&bb), false);
     }
 
+    #[test]
+    fn is_in_bounding_box_across_antimeridian() {
+        let bb = Bbox {
+            south_west: Coordinate {
+                lat: -10.0,
+                lng: 170.0,
+            },
+            north_east: Coordinate {
+                lat: 10.0,
+                lng: -170.0,
+            },
+        };
+        let e = Entry::build().lat(5.0).lng(179.0).finish();
+        assert_eq!(e.in_bbox(&bb), true);
+        let e = Entry::build().lat(5.0).lng(-175.0).finish();
+        assert_eq!(e.in_bbox(&bb), true);
+        let e = Entry::build().lat(5.0).lng(0.0).finish();
+        assert_eq!(e.in_bbox(&bb), false);
+    }
+
     #[test]
     fn filter_by_bounding_box() {
         let bb = Bbox {
@@ -120,25 +314,56 @@ mod tests {
         let x: Vec<_> = entries
             .iter()
             .cloned()
-            .filter(&*entries_by_category_ids(&ab))
+            .filter(&*entries_by_category_ids(&ab, Combination::Any))
             .collect();
         assert_eq!(x.len(), 2);
         let b = vec!["b".into()];
         let x: Vec<_> = entries
             .iter()
             .cloned()
-            .filter(&*entries_by_category_ids(&b))
+            .filter(&*entries_by_category_ids(&b, Combination::Any))
             .collect();
         assert_eq!(x.len(), 1);
         let c = vec!["c".into()];
         let x: Vec<_> = entries
             .iter()
             .cloned()
-            .filter(&*entries_by_category_ids(&c))
+            .filter(&*entries_by_category_ids(&c, Combination::Any))
             .collect();
         assert_eq!(x.len(), 1);
     }
 
+    #[test]
+    fn filter_by_any_or_all_categories() {
+        let entries = vec![
+            Entry::build().id("a").categories(vec!["cafe"]).finish(),
+            Entry::build()
+                .id("b")
+                .categories(vec!["restaurant", "cafe"])
+                .finish(),
+            Entry::build().id("c").categories(vec!["shop"]).finish(),
+        ];
+        let ids = vec!["cafe".into(), "restaurant".into()];
+        let matching = |combination| {
+            let matches = entries_by_category_ids(&ids, combination);
+            entries
+                .iter()
+                .filter(|e| matches(e))
+                .map(|e| e.id.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(matching(Combination::Any), vec!["a", "b"]);
+        assert_eq!(matching(Combination::All), vec!["b"]);
+    }
+
+    #[test]
+    fn parse_combination() {
+        assert_eq!("any".parse::<Combination>().unwrap(), Combination::Any);
+        assert_eq!("all".parse::<Combination>().unwrap(), Combination::All);
+        assert!("or".parse::<Combination>().is_err());
+        assert_eq!(Combination::default(), Combination::Any);
+    }
+
     #[test]
     fn filter_by_tags_or_text() {
         let entries = vec![
@@ -177,28 +402,28 @@ mod tests {
         let x: Vec<_> = entries
             .iter()
             .cloned()
-            .filter(&*entries_by_tags_or_search_text(&no_string, &no_tags))
+            .filter(&*entries_by_tags_or_search_text(&no_string, &no_tags, &[]))
             .collect();
         assert_eq!(x.len(), 5);
 
         let x: Vec<_> = entries_without_tags
             .iter()
             .cloned()
-            .filter(&*entries_by_tags_or_search_text(&other, &tags1))
+            .filter(&*entries_by_tags_or_search_text(&other, &tags1, &[]))
             .collect();
         assert_eq!(x.len(), 0);
 
         let x: Vec<_> = entries_without_tags
             .iter()
             .cloned()
-            .filter(&*entries_by_tags_or_search_text(&other, &tags2))
+            .filter(&*entries_by_tags_or_search_text(&other, &tags2, &[]))
             .collect();
         assert_eq!(x.len(), 0);
 
         let x: Vec<_> = entries
             .iter()
             .cloned()
-            .filter(&*entries_by_tags_or_search_text(&other, &tags1))
+            .filter(&*entries_by_tags_or_search_text(&other, &tags1, &[]))
             .collect();
         assert_eq!(x.len(), 2);
         assert_eq!(x[0].id, "b");
@@ -207,7 +432,7 @@ mod tests {
         let x: Vec<_> = entries
             .iter()
             .cloned()
-            .filter(&*entries_by_tags_or_search_text(&other, &tags2))
+            .filter(&*entries_by_tags_or_search_text(&other, &tags2, &[]))
             .collect();
         assert_eq!(x.len(), 1);
         assert_eq!(x[0].id, "d");
@@ -215,7 +440,7 @@ mod tests {
         let x: Vec<_> = entries
             .iter()
             .cloned()
-            .filter(&*entries_by_tags_or_search_text(&other, &tags3))
+            .filter(&*entries_by_tags_or_search_text(&other, &tags3, &[]))
             .collect();
         assert_eq!(x.len(), 2);
         assert_eq!(x[0].id, "c");
@@ -224,7 +449,7 @@ mod tests {
         let x: Vec<_> = entries
             .iter()
             .cloned()
-            .filter(&*entries_by_tags_or_search_text(&no_string, &tags1))
+            .filter(&*entries_by_tags_or_search_text(&no_string, &tags1, &[]))
             .collect();
         assert_eq!(x.len(), 2);
         assert_eq!(x[0].id, "b");
@@ -233,7 +458,7 @@ mod tests {
         let x: Vec<_> = entries
             .iter()
             .cloned()
-            .filter(&*entries_by_tags_or_search_text(&solawi, &no_tags))
+            .filter(&*entries_by_tags_or_search_text(&solawi, &no_tags, &[]))
             .collect();
         assert_eq!(x.len(), 1);
         assert_eq!(x[0].id, "a");
@@ -241,7 +466,7 @@ mod tests {
         let x: Vec<_> = entries
             .iter()
             .cloned()
-            .filter(&*entries_by_tags_or_search_text(&solawi, &tags2))
+            .filter(&*entries_by_tags_or_search_text(&solawi, &tags2, &[]))
             .collect();
         assert_eq!(x.len(), 2);
         assert_eq!(x[0].id, "a");
@@ -250,7 +475,7 @@ mod tests {
         let x: Vec<_> = entries
             .iter()
             .cloned()
-            .filter(&*entries_by_tags_or_search_text(&bliblubb, &tags3))
+            .filter(&*entries_by_tags_or_search_text(&bliblubb, &tags3, &[]))
             .collect();
         assert_eq!(x.len(), 3);
         assert_eq!(x[0].id, "b");
@@ -260,9 +485,210 @@ mod tests {
         let x: Vec<_> = entries
             .iter()
             .cloned()
-            .filter(&*entries_by_tags_or_search_text(&tag1, &no_tags))
+            .filter(&*entries_by_tags_or_search_text(&tag1, &no_tags, &[]))
+            .collect();
+        assert_eq!(x.len(), 3);
+        assert_eq!(x[0].id, "b");
+        assert_eq!(x[1].id, "d");
+        assert_eq!(x[2].id, "e");
+
+        let x: Vec<_> = entries_without_tags
+            .iter()
+            .cloned()
+            .filter(&*entries_by_tags_or_search_text(&tag1, &no_tags, &[]))
             .collect();
         assert_eq!(x.len(), 1);
         assert_eq!(x[0].id, "e");
     }
+
+    fn search_params(tags: Vec<&str>, exclude_tags: Vec<&str>) -> SearchParams {
+        SearchParams {
+            bbox: None,
+            categories: None,
+            categories_mode: Combination::Any,
+            text: "".into(),
+            tags: tags.into_iter().map(|t| t.into()).collect(),
+            exclude_tags: exclude_tags.into_iter().map(|t| t.into()).collect(),
+            city: None,
+            country: None,
+            created_after: None,
+            created_before: None,
+            include_archived: false,
+        }
+    }
+
+    fn ids_by_search_params(entries: &[Entry], params: &SearchParams) -> Vec<String> {
+        let matches = entries_by_search_params(params, &[]);
+        entries
+            .iter()
+            .filter(|e| matches(e))
+            .map(|e| e.id.clone())
+            .collect()
+    }
+
+    #[test]
+    fn normalize_text() {
+        assert_eq!(normalize("Café"), "cafe");
+        assert_eq!(normalize("Straße"), "strasse");
+        assert_eq!(normalize("BERLIN"), "berlin");
+        assert_eq!(normalize("Ærø Œuvre"), "aero oeuvre");
+        assert_eq!(normalize("ÀÉÎÕÜ"), "aeiou");
+    }
+
+    #[test]
+    fn filter_by_text_without_case_and_accents() {
+        let entries = vec![
+            Entry::build().id("a").title("Café Müller").finish(),
+            Entry::build().id("b").description("in der Hauptstraße").finish(),
+            Entry::build().id("c").title("berlin").finish(),
+            Entry::build().id("d").title("Laden").finish(),
+        ];
+        let ids = |text: &str| {
+            let matches = entries_by_tags_or_search_text(text, &[], &[]);
+            entries
+                .iter()
+                .filter(|e| matches(e))
+                .map(|e| e.id.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids("cafe"), vec!["a"]);
+        assert_eq!(ids("CAFÉ"), vec!["a"]);
+        assert_eq!(ids("muller"), vec!["a"]);
+        assert_eq!(ids("strasse"), vec!["b"]);
+        assert_eq!(ids("Straße"), vec!["b"]);
+        assert_eq!(ids("BERLIN"), vec!["c"]);
+        assert_eq!(ids("BeRlIn"), vec!["c"]);
+    }
+
+    #[test]
+    fn filter_by_text_in_tags_and_categories() {
+        let categories = vec![
+            Category {
+                id: "x".into(),
+                created: 0,
+                version: 0,
+                name: "Initiative".into(),
+            },
+        ];
+        let entries = vec![
+            Entry::build().id("a").title("Werkstatt").finish(),
+            Entry::build().id("b").tags(vec!["coworking"]).finish(),
+            Entry::build().id("c").categories(vec!["x"]).finish(),
+            Entry::build()
+                .id("d")
+                .tags(vec!["coworking"])
+                .categories(vec!["x"])
+                .finish(),
+        ];
+        let ids = |text: &str, tags: &[String]| {
+            let matches = entries_by_tags_or_search_text(text, tags, &categories);
+            entries
+                .iter()
+                .filter(|e| matches(e))
+                .map(|e| e.id.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids("coworking", &[]), vec!["b", "d"]);
+        assert_eq!(ids("Initiative", &[]), vec!["c", "d"]);
+        assert_eq!(ids("WERKSTATT", &[]), vec!["a"]);
+        // Entries that match both the text and the tag are not excluded
+        assert_eq!(ids("coworking", &["coworking".into()]), vec!["b", "d"]);
+        assert_eq!(ids("werkstatt", &["coworking".into()]), vec!["a", "b", "d"]);
+    }
+
+    #[test]
+    fn filter_by_excluded_tags() {
+        let entries = vec![
+            Entry::build().id("a").tags(vec!["cafe"]).finish(),
+            Entry::build().id("b").tags(vec!["cafe", "chain"]).finish(),
+            Entry::build().id("c").tags(vec!["chain"]).finish(),
+            Entry::build().id("d").finish(),
+        ];
+        // include only
+        let params = search_params(vec!["cafe"], vec![]);
+        assert_eq!(ids_by_search_params(&entries, &params), vec!["a", "b"]);
+        // exclude only
+        let params = search_params(vec![], vec!["chain"]);
+        assert_eq!(ids_by_search_params(&entries, &params), vec!["a", "d"]);
+        // mixed
+        let params = search_params(vec!["cafe"], vec!["chain"]);
+        assert_eq!(ids_by_search_params(&entries, &params), vec!["a"]);
+        let params = search_params(vec!["cafe"], vec!["Chain", "other"]);
+        assert_eq!(ids_by_search_params(&entries, &params), vec!["a"]);
+        let params = search_params(vec!["cafe"], vec!["cafe"]);
+        assert!(ids_by_search_params(&entries, &params).is_empty());
+    }
+
+    #[test]
+    fn filter_by_location() {
+        let mut graz = Entry::build().id("graz").finish();
+        graz.city = Some("Graz".into());
+        graz.country = Some("AT".into());
+        let mut stuttgart = Entry::build().id("stuttgart").finish();
+        stuttgart.city = Some("Stuttgart".into());
+        stuttgart.country = Some("DE".into());
+        let nowhere = Entry::build().id("nowhere").finish();
+        let entries = vec![graz, stuttgart, nowhere];
+        let ids = |city: Option<&str>, country: Option<&str>| {
+            let matches = entries_by_location(city, country);
+            entries
+                .iter()
+                .filter(|e| matches(e))
+                .map(|e| e.id.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(None, Some("DE")), vec!["stuttgart"]);
+        assert_eq!(ids(None, Some("de")), vec!["stuttgart"]);
+        assert_eq!(ids(Some("graz"), None), vec!["graz"]);
+        assert_eq!(ids(Some("Graz"), Some("DE")), Vec::<&str>::new());
+        assert_eq!(ids(None, None).len(), 3);
+        assert_eq!(ids(Some(""), Some(" ")).len(), 3);
+
+        let mut params = search_params(vec![], vec![]);
+        params.country = Some("DE".into());
+        assert_eq!(ids_by_search_params(&entries, &params), vec!["stuttgart"]);
+    }
+
+    #[test]
+    fn filter_by_created_range() {
+        let entries = vec![
+            Entry::build().id("a").created(100).finish(),
+            Entry::build().id("b").created(200).finish(),
+            Entry::build().id("c").created(300).finish(),
+        ];
+        let ids = |after: Option<u64>, before: Option<u64>| {
+            let matches = entries_by_created_range(after, before);
+            entries
+                .iter()
+                .filter(|e| matches(e))
+                .map(|e| e.id.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(None, None), vec!["a", "b", "c"]);
+        assert_eq!(ids(Some(200), None), vec!["b", "c"]);
+        assert_eq!(ids(None, Some(200)), vec!["a", "b"]);
+        assert_eq!(ids(Some(200), Some(200)), vec!["b"]);
+        assert_eq!(ids(Some(101), Some(299)), vec!["b"]);
+        assert_eq!(ids(Some(301), None), Vec::<&str>::new());
+
+        let mut params = search_params(vec![], vec![]);
+        params.created_after = Some(150);
+        params.created_before = Some(300);
+        assert_eq!(ids_by_search_params(&entries, &params), vec!["b", "c"]);
+    }
+
+    #[test]
+    fn parse_timestamps() {
+        assert_eq!(timestamp("1514764800", false).unwrap(), 1_514_764_800);
+        assert_eq!(timestamp("2018-01-01", false).unwrap(), 1_514_764_800);
+        assert_eq!(timestamp("2018-01-01", true).unwrap(), 1_514_851_199);
+        assert_eq!(
+            timestamp("2018-01-01T01:00:00+01:00", true).unwrap(),
+            1_514_764_800
+        );
+        assert!(timestamp("1969-12-31", false).is_err());
+        assert!(timestamp("-1", false).is_err());
+        assert!(timestamp("2018-13-01", false).is_err());
+        assert!(timestamp("yesterday", false).is_err());
+    }
 }
@@ -1,10 +1,14 @@
 use business::error::ParameterError;
 use entities::{Bbox, Coordinate};
+use std::f64::consts::PI;
 
-// The Earth's radius in kilometers.
-static EARTH_RADIUS: f64 = 6371.0;
+// The Earth's mean radius in meters.
+const EARTH_RADIUS: f64 = 6_371_000.0;
 
-// distance in km
+// Length of one degree of latitude in meters.
+const METERS_PER_DEGREE: f64 = EARTH_RADIUS * PI / 180.0;
+
+/// Great-circle distance in meters (Haversine formula)
 pub fn distance(a: &Coordinate, b: &Coordinate) -> f64 {
     let lat1 = a.lat.to_radians();
     let lat2 = b.lat.to_radians();
@@ -18,6 +22,17 @@ pub fn distance(a: &Coordinate, b: &Coordinate) -> f64 {
     EARTH_RADIUS * c
 }
 
+/// Converts a north-south distance in meters into degrees of latitude.
+pub fn lat_degrees(meters: f64) -> f64 {
+    meters / METERS_PER_DEGREE
+}
+
+/// Converts an east-west distance in meters into degrees of longitude
+/// at the given latitude.
+pub fn lng_degrees(meters: f64, lat: f64) -> f64 {
+    (meters / (METERS_PER_DEGREE * lat.to_radians().cos())).min(180.0)
+}
+
 pub fn extract_bbox(s: &str) -> Result<Bbox, ParameterError> {
     let c = s.split(',')
         .map(|x| x.parse::<f64>())
@@ -39,12 +54,49 @@ pub fn extract_bbox(s: &str) -> Result<Bbox, ParameterError> {
     }
 }
 
+/// A bbox whose south west longitude is greater than its
+/// north east longitude spans the 180° meridian.
+pub fn crosses_antimeridian(bbox: &Bbox) -> bool {
+    bbox.south_west.lng > bbox.north_east.lng
+}
+
+/// Width of the bbox in degrees of longitude
+pub fn lng_span(bbox: &Bbox) -> f64 {
+    if crosses_antimeridian(bbox) {
+        bbox.north_east.lng + 360.0 - bbox.south_west.lng
+    } else {
+        bbox.north_east.lng - bbox.south_west.lng
+    }
+}
+
+/// Wraps a longitude into the range [-180, 180]
+pub fn normalize_lng(lng: f64) -> f64 {
+    if lng > 180.0 {
+        lng - 360.0
+    } else if lng < -180.0 {
+        lng + 360.0
+    } else {
+        lng
+    }
+}
+
+pub fn center(bbox: &Bbox) -> Coordinate {
+    Coordinate {
+        lat: (bbox.south_west.lat + bbox.north_east.lat) / 2.0,
+        lng: normalize_lng(bbox.south_west.lng + lng_span(bbox) / 2.0),
+    }
+}
+
 #[cfg_attr(rustfmt, rustfmt_skip)]
 pub fn is_in_bbox(lat: &f64, lng: &f64, bbox: &Bbox) -> bool {
+    let in_lng_range = if crosses_antimeridian(bbox) {
+        *lng >= bbox.south_west.lng || *lng <= bbox.north_east.lng
+    } else {
+        *lng >= bbox.south_west.lng && *lng <= bbox.north_east.lng
+    };
     *lat >= bbox.south_west.lat &&
-    *lng >= bbox.south_west.lng &&
     *lat <= bbox.north_east.lat &&
-    *lng <= bbox.north_east.lng
+    in_lng_range
 }
 
 #[cfg(test)]
@@ -77,8 +129,41 @@ mod tests {
             lng: 8.4630,
         };
 
-        assert!(distance(&stuttgart, &mannheim) > 92.0);
-        assert!(distance(&stuttgart, &mannheim) < 96.0);
+        assert!(distance(&stuttgart, &mannheim) > 92_000.0);
+        assert!(distance(&stuttgart, &mannheim) < 96_000.0);
+    }
+
+    fn assert_distance_within_one_percent(a: &Coordinate, b: &Coordinate, expected: f64) {
+        let d = distance(a, b);
+        assert!(
+            (d - expected).abs() <= expected * 0.01,
+            "expected {} m but was {} m",
+            expected,
+            d
+        );
+    }
+
+    #[test]
+    fn distance_of_known_city_pairs() {
+        let berlin = Coordinate {
+            lat: 52.5200,
+            lng: 13.4050,
+        };
+        let hamburg = Coordinate {
+            lat: 53.5511,
+            lng: 9.9937,
+        };
+        let munich = Coordinate {
+            lat: 48.1351,
+            lng: 11.5820,
+        };
+        let paris = Coordinate {
+            lat: 48.8566,
+            lng: 2.3522,
+        };
+        assert_distance_within_one_percent(&berlin, &hamburg, 255_000.0);
+        assert_distance_within_one_percent(&berlin, &munich, 504_000.0);
+        assert_distance_within_one_percent(&paris, &berlin, 878_000.0);
     }
 
     #[test]
@@ -118,6 +203,14 @@ mod tests {
         assert!(distance(&a, &b).is_nan());
     }
 
+    #[test]
+    fn degrees_of_distance() {
+        assert!((lat_degrees(111_195.0) - 1.0).abs() < 1e-5);
+        assert!((lng_degrees(111_195.0, 0.0) - 1.0).abs() < 1e-5);
+        assert!((lng_degrees(111_195.0, 60.0) - 2.0).abs() < 1e-5);
+        assert_eq!(lng_degrees(1.0, 90.0), 180.0);
+    }
+
     #[test]
     fn extract_bbox_from_str() {
         assert!(extract_bbox("0,-10.0870,200,3.0").is_ok());
@@ -141,6 +234,61 @@ mod tests {
         assert!(extract_bbox("5;4;3,0").is_err());
     }
 
+    #[test]
+    fn center_of_bbox() {
+        let bbox = Bbox {
+            south_west: Coordinate {
+                lat: -10.0,
+                lng: 0.0,
+            },
+            north_east: Coordinate {
+                lat: 20.0,
+                lng: 10.0,
+            },
+        };
+        let c = center(&bbox);
+        assert_eq!(c.lat, 5.0);
+        assert_eq!(c.lng, 5.0);
+    }
+
+    #[test]
+    fn center_of_bbox_across_antimeridian() {
+        let bbox = Bbox {
+            south_west: Coordinate {
+                lat: -10.0,
+                lng: 170.0,
+            },
+            north_east: Coordinate {
+                lat: 10.0,
+                lng: -160.0,
+            },
+        };
+        assert_eq!(lng_span(&bbox), 30.0);
+        let c = center(&bbox);
+        assert_eq!(c.lat, 0.0);
+        assert_eq!(c.lng, -175.0);
+    }
+
+    #[test]
+    fn is_in_bbox_across_antimeridian() {
+        let bbox = Bbox {
+            south_west: Coordinate {
+                lat: -10.0,
+                lng: 170.0,
+            },
+            north_east: Coordinate {
+                lat: 10.0,
+                lng: -170.0,
+            },
+        };
+        assert!(is_in_bbox(&0.0, &179.0, &bbox));
+        assert!(is_in_bbox(&0.0, &-179.0, &bbox));
+        assert!(is_in_bbox(&0.0, &180.0, &bbox));
+        assert!(!is_in_bbox(&0.0, &0.0, &bbox));
+        assert!(!is_in_bbox(&0.0, &160.0, &bbox));
+        assert!(!is_in_bbox(&20.0, &179.0, &bbox));
+    }
+
     #[test]
     fn test_is_in_bbox() {
         let bbox1 = Bbox {
@@ -0,0 +1,17 @@
+use entities::Coordinate;
+
+/// The parts of an address a geocoder knows about
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Address {
+    pub street: Option<String>,
+    pub zip: Option<String>,
+    pub city: Option<String>,
+    pub country: Option<String>,
+}
+
+pub trait Geocoder {
+    /// Looks up the address at the given coordinate
+    fn reverse(&self, lat: f64, lng: f64) -> Option<Address>;
+    /// Looks up the coordinate of the given address
+    fn forward(&self, address: &Address) -> Option<Coordinate>;
+}
@@ -1,6 +1,10 @@
+pub mod cluster;
 pub mod error;
 pub mod filter;
 pub mod geo;
+pub mod geocode;
+pub mod sanitize;
+pub mod search;
 pub mod duplicates;
 pub mod sort;
 pub mod validate;
@@ -0,0 +1,106 @@
+use regex::Regex;
+
+/// What happens to HTML in user submitted texts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HtmlPolicy {
+    /// Remove tags (and scripts and styles with their content)
+    Strip,
+    /// Keep everything but escape the special characters
+    Escape,
+}
+
+pub const HTML_POLICY: HtmlPolicy = HtmlPolicy::Strip;
+
+lazy_static! {
+    static ref SCRIPT_REGEX: Regex =
+        Regex::new(r"(?is)<(script|style)\b[^>]*>.*?</(script|style)\s*>").unwrap();
+    static ref COMMENT_REGEX: Regex = Regex::new(r"(?s)<!--.*?-->").unwrap();
+    static ref LINE_BREAK_REGEX: Regex = Regex::new(r"(?i)<br\s*/?>").unwrap();
+    // Only real tags, so that e.g. "a < b" survives
+    static ref TAG_REGEX: Regex = Regex::new(r"</?[a-zA-Z!][^>]*>").unwrap();
+}
+
+fn strip(s: &str) -> String {
+    let mut s = s.to_string();
+    // Removing a tag might put together a new one, e.g. "<scr<b>ipt>"
+    loop {
+        let stripped = SCRIPT_REGEX.replace_all(&s, "").into_owned();
+        let stripped = COMMENT_REGEX.replace_all(&stripped, "").into_owned();
+        let stripped = LINE_BREAK_REGEX.replace_all(&stripped, "\n").into_owned();
+        let stripped = TAG_REGEX.replace_all(&stripped, "").into_owned();
+        if stripped == s {
+            return s;
+        }
+        s = stripped;
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#x27;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Makes a text safe to be rendered as HTML
+pub fn text_with(s: &str, policy: HtmlPolicy) -> String {
+    match policy {
+        HtmlPolicy::Strip => strip(s),
+        HtmlPolicy::Escape => escape(s),
+    }
+}
+
+/// Makes a text safe to be rendered as HTML by following the `HTML_POLICY`
+pub fn text(s: &str) -> String {
+    text_with(s, HTML_POLICY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_scripts() {
+        assert_eq!(text_with("<script>alert(1)</script>", HtmlPolicy::Strip), "");
+        let s = "foo<SCRIPT type=\"text/javascript\">alert(1)</SCRIPT >bar";
+        assert_eq!(text_with(s, HtmlPolicy::Strip), "foobar");
+        let s = "<<b>script>alert(1)<</b>/script>";
+        assert_eq!(text_with(s, HtmlPolicy::Strip), "");
+        let s = "<img src=x onerror=\"alert(1)\">";
+        assert_eq!(text_with(s, HtmlPolicy::Strip), "");
+    }
+
+    #[test]
+    fn strip_tags_but_keep_the_text() {
+        let s = "<p>Fresh <b>bread</b><br/>every day</p><!-- hidden -->";
+        assert_eq!(text_with(s, HtmlPolicy::Strip), "Fresh bread\nevery day");
+        assert_eq!(text_with("line 1\nline 2", HtmlPolicy::Strip), "line 1\nline 2");
+    }
+
+    #[test]
+    fn escape_scripts() {
+        assert_eq!(
+            text_with("<script>alert('1')</script>", HtmlPolicy::Escape),
+            "&lt;script&gt;alert(&#x27;1&#x27;)&lt;/script&gt;"
+        );
+        assert_eq!(text_with("a & b", HtmlPolicy::Escape), "a &amp; b");
+    }
+
+    #[test]
+    fn keep_ordinary_punctuation() {
+        let s = "Fair & square: 1 < 2, 3 > 2; \"quoted\" (yes!) - 100% #bio?\nNew line";
+        assert_eq!(text_with(s, HtmlPolicy::Strip), s);
+        assert_eq!(
+            text_with("Open: Mo-Fr 9-18 Uhr, Sa. 10-14.", HtmlPolicy::Escape),
+            "Open: Mo-Fr 9-18 Uhr, Sa. 10-14."
+        );
+    }
+}
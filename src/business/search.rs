@@ -0,0 +1,94 @@
+use entities::Entry;
+use business::filter::normalize;
+use std::cmp::Ordering;
+
+const TITLE_WEIGHT: f64 = 3.0;
+const DESCRIPTION_WEIGHT: f64 = 1.0;
+const TAG_WEIGHT: f64 = 1.0;
+
+/// Splits a (comma separated) search text into normalized terms
+pub fn terms(text: &str) -> Vec<String> {
+    normalize(text)
+        .split(',')
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+/// How well an entry matches the (normalized) terms:
+/// every occurrence of a term counts,
+/// but matches in the title weigh more than others.
+pub fn score(e: &Entry, terms: &[String]) -> f64 {
+    let title = normalize(&e.title);
+    let description = normalize(&e.description);
+    terms
+        .iter()
+        .map(|t| {
+            let tags = e.tags.iter().filter(|tag| tag.contains(t.as_str())).count();
+            TITLE_WEIGHT * title.matches(t.as_str()).count() as f64
+                + DESCRIPTION_WEIGHT * description.matches(t.as_str()).count() as f64
+                + TAG_WEIGHT * tags as f64
+        })
+        .sum()
+}
+
+/// Sorts the entries by their score, the best match first.
+/// Entries with the same score keep their order.
+pub fn sort_by_relevance(entries: &mut Vec<Entry>, text: &str) {
+    let terms = terms(text);
+    let mut scored: Vec<_> = entries
+        .drain(..)
+        .map(|e| (score(&e, &terms), e))
+        .collect();
+    scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+    entries.extend(scored.into_iter().map(|(_, e)| e));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use business::builder::EntryBuilder;
+
+    #[test]
+    fn split_terms() {
+        assert_eq!(terms("Bio, Fair,,"), vec!["bio", "fair"]);
+        assert!(terms("").is_empty());
+    }
+
+    #[test]
+    fn title_matches_weigh_more() {
+        let terms = terms("bio");
+        let in_title = Entry::build().title("Bioladen").finish();
+        let in_description = Entry::build().description("bio food").finish();
+        let in_tags = Entry::build().tags(vec!["bio"]).finish();
+        let nowhere = Entry::build().title("Weltladen").finish();
+        assert!(score(&in_title, &terms) > score(&in_description, &terms));
+        assert!(score(&in_title, &terms) > score(&in_tags, &terms));
+        assert_eq!(score(&nowhere, &terms), 0.0);
+    }
+
+    #[test]
+    fn count_term_frequency() {
+        let terms = terms("bio");
+        let once = Entry::build().description("bio").finish();
+        let twice = Entry::build().description("bio bio").finish();
+        assert!(score(&twice, &terms) > score(&once, &terms));
+    }
+
+    #[test]
+    fn sort_entries_by_relevance() {
+        let mut entries = vec![
+            Entry::build().id("a").title("Weltladen").finish(),
+            Entry::build()
+                .id("b")
+                .title("Laden")
+                .description("with bio food")
+                .finish(),
+            Entry::build().id("c").title("Bioladen").finish(),
+            Entry::build().id("d").title("Shop").finish(),
+        ];
+        sort_by_relevance(&mut entries, "bio");
+        let ids: Vec<_> = entries.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, vec!["c", "b", "a", "d"]);
+    }
+}
@@ -2,6 +2,45 @@ use entities::*;
 use std::cmp::Ordering;
 use super::geo;
 use std::collections::HashMap;
+use std::str::FromStr;
+use super::error::ParameterError;
+use super::validate;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortOrder {
+    Distance,
+    Rating,
+    /// Like `Rating` but older ratings count less
+    RatingRecent,
+    /// How well the entries match the search text
+    Relevance,
+}
+
+/// The age (in days) after which a rating only counts half
+pub const DEFAULT_RATING_HALF_LIFE_DAYS: f64 = 365.0;
+
+const SECONDS_PER_DAY: f64 = 86_400.0;
+
+impl Default for SortOrder {
+    fn default() -> SortOrder {
+        SortOrder::Rating
+    }
+}
+
+impl FromStr for SortOrder {
+    type Err = ParameterError;
+    fn from_str(s: &str) -> Result<SortOrder, ParameterError> {
+        Ok(match s {
+            "distance" => SortOrder::Distance,
+            "rating" => SortOrder::Rating,
+            "rating_recent" => SortOrder::RatingRecent,
+            "relevance" => SortOrder::Relevance,
+            _ => {
+                return Err(ParameterError::SortOrder);
+            }
+        })
+    }
+}
 
 trait DistanceTo {
     fn distance_to(&self, &Coordinate) -> f64;
@@ -28,78 +67,116 @@ impl SortByDistanceTo for Vec<Entry> {
         if !(c.lat.is_finite() && c.lng.is_finite()) {
             return;
         }
-        self.sort_by(|a, _| {
-            if a.lat.is_finite() && a.lng.is_finite() {
-                Ordering::Less
-            } else {
-                warn!("invalid coordinate: {}/{}", a.lat, a.lng);
-                Ordering::Greater
-            }
-        });
-        self.sort_by(|a, b| {
+        // Entries with invalid coordinates keep their order at the end
+        let (mut valid, invalid): (Vec<Entry>, Vec<Entry>) = self.drain(..)
+            .partition(|e| e.lat.is_finite() && e.lng.is_finite());
+        for e in &invalid {
+            warn!("invalid coordinate: {}/{}", e.lat, e.lng);
+        }
+        valid.sort_by(|a, b| {
             a.distance_to(c)
                 .partial_cmp(&b.distance_to(c))
                 .unwrap_or(Ordering::Equal)
-        })
+        });
+        *self = valid;
+        self.extend(invalid);
     }
 }
 
 pub trait Rated {
     fn avg_rating(&self, &[Rating]) -> f64;
+    /// The average rating where the value of each rating decays
+    /// towards neutral (0) with its age: after `half_life_days`
+    /// a rating only counts half.
+    fn weighted_avg_rating(&self, ratings: &[Rating], now: u64, half_life_days: f64) -> f64;
 }
 
 impl Rated for Entry {
     fn avg_rating(&self, ratings: &[Rating]) -> f64 {
-        use self::RatingContext::*;
-
-        let ratings_for_entry: Vec<&Rating> =
-            ratings.iter().filter(|r| r.entry_id == self.id).collect();
-
-        let avg_ratings = vec![
-            avg_rating_for_context(&ratings_for_entry, &Diversity),
-            avg_rating_for_context(&ratings_for_entry, &Renewable),
-            avg_rating_for_context(&ratings_for_entry, &Fairness),
-            avg_rating_for_context(&ratings_for_entry, &Humanity),
-            avg_rating_for_context(&ratings_for_entry, &Transparency),
-            avg_rating_for_context(&ratings_for_entry, &Solidarity),
-        ];
+        avg_rating_by(self, ratings, &|_| 1.0)
+    }
 
-        let sum = avg_ratings
-            .iter()
-            .fold(0.0, |acc, &r| acc + r.unwrap_or(0.0));
-        let num_rated_contexts = avg_ratings
-            .iter()
-            .fold(0, |acc, &r| acc + if r.is_some() { 1 } else { 0 });
+    fn weighted_avg_rating(&self, ratings: &[Rating], now: u64, half_life_days: f64) -> f64 {
+        avg_rating_by(self, ratings, &|r| decay(r, now, half_life_days))
+    }
+}
 
-        if num_rated_contexts > 0 {
-            sum / 6.0
-        } else {
-            0.0
-        }
+// A non-positive half-life turns off the decay.
+fn decay(rating: &Rating, now: u64, half_life_days: f64) -> f64 {
+    if !(half_life_days > 0.0) {
+        return 1.0;
     }
+    let age_days = now.saturating_sub(rating.created) as f64 / SECONDS_PER_DAY;
+    0.5_f64.powf(age_days / half_life_days)
 }
 
+fn avg_rating_by(e: &Entry, ratings: &[Rating], weight: &Fn(&Rating) -> f64) -> f64 {
+    use self::RatingContext::*;
+
+    let ratings_for_entry: Vec<&Rating> = ratings
+        .iter()
+        .filter(|r| r.entry_id == e.id && !validate::is_self_rating(e, r.created_by.as_ref()))
+        .collect();
+
+    let avg_ratings = vec![
+        weighted_avg_rating_for_context(&ratings_for_entry, &Diversity, weight),
+        weighted_avg_rating_for_context(&ratings_for_entry, &Renewable, weight),
+        weighted_avg_rating_for_context(&ratings_for_entry, &Fairness, weight),
+        weighted_avg_rating_for_context(&ratings_for_entry, &Humanity, weight),
+        weighted_avg_rating_for_context(&ratings_for_entry, &Transparency, weight),
+        weighted_avg_rating_for_context(&ratings_for_entry, &Solidarity, weight),
+    ];
+
+    let sum = avg_ratings
+        .iter()
+        .fold(0.0, |acc, &r| acc + r.unwrap_or(0.0));
+    let num_rated_contexts = avg_ratings
+        .iter()
+        .fold(0, |acc, &r| acc + if r.is_some() { 1 } else { 0 });
+
+    if num_rated_contexts > 0 {
+        sum / 6.0
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
 fn avg_rating_for_context(ratings: &[&Rating], context: &RatingContext) -> Option<f64> {
+    weighted_avg_rating_for_context(ratings, context, &|_| 1.0)
+}
+
+fn weighted_avg_rating_for_context(
+    ratings: &[&Rating],
+    context: &RatingContext,
+    weight: &Fn(&Rating) -> f64,
+) -> Option<f64> {
     let applicable_ratings: Vec<&&Rating> = ratings
         .iter()
         .filter(|rating| rating.context == *context)
         .collect();
 
-    let sum = applicable_ratings
-        .iter()
-        .fold(0_i64, |acc, rating| acc + i64::from(rating.value)) as f64;
+    // Only ratings that were actually found count for the average
     let n = applicable_ratings.len();
-
-    let avg = sum / n as f64;
-    if avg.is_nan() {
-        None
-    } else {
-        Some(avg as f64)
+    if n == 0 {
+        return None;
     }
+
+    let sum = applicable_ratings.iter().fold(0.0, |acc, rating| {
+        acc + f64::from(rating.value) * weight(**rating)
+    });
+
+    Some(sum / n as f64)
 }
 
 pub trait SortByAverageRating {
     fn calc_avg_ratings(&self, &[Rating]) -> HashMap<String, f64>;
+    fn calc_weighted_avg_ratings(
+        &self,
+        ratings: &[Rating],
+        now: u64,
+        half_life_days: f64,
+    ) -> HashMap<String, f64>;
     fn sort_by_avg_rating(&mut self, avg_ratings: &HashMap<String, f64>);
 }
 
@@ -110,6 +187,22 @@ impl SortByAverageRating for Vec<Entry> {
             .collect()
     }
 
+    fn calc_weighted_avg_ratings(
+        &self,
+        ratings: &[Rating],
+        now: u64,
+        half_life_days: f64,
+    ) -> HashMap<String, f64> {
+        self.iter()
+            .map(|e| {
+                (
+                    e.id.clone(),
+                    e.weighted_avg_rating(ratings, now, half_life_days),
+                )
+            })
+            .collect()
+    }
+
     fn sort_by_avg_rating(&mut self, avg_ratings: &HashMap<String, f64>) {
         self.sort_by(|a, b| {
             avg_ratings
@@ -141,9 +234,23 @@ pub mod tests {
             value: value.into(),
             context: context,
             source: Some("blabla".into()),
+            created_by: None,
         }
     }
 
+    #[test]
+    fn parse_sort_order() {
+        assert_eq!("distance".parse::<SortOrder>().unwrap(), SortOrder::Distance);
+        assert_eq!("rating".parse::<SortOrder>().unwrap(), SortOrder::Rating);
+        assert_eq!(
+            "rating_recent".parse::<SortOrder>().unwrap(),
+            SortOrder::RatingRecent
+        );
+        assert_eq!("relevance".parse::<SortOrder>().unwrap(), SortOrder::Relevance);
+        assert!("foo".parse::<SortOrder>().is_err());
+        assert_eq!(SortOrder::default(), SortOrder::Rating);
+    }
+
     #[test]
     fn test_average_rating() {
         let entry1 = new_entry("a", 0.0, 0.0);
@@ -164,6 +271,45 @@ pub mod tests {
         assert_eq!(entry3.avg_rating(&ratings), 0.0);
     }
 
+    #[test]
+    fn average_rating_leaves_out_self_ratings() {
+        let mut entry = new_entry("a", 0.0, 0.0);
+        entry.created_by = Some("alice".into());
+        let mut own = new_rating("1", "a", 2, RatingContext::Diversity);
+        own.created_by = Some("alice".into());
+        let mut other = new_rating("2", "a", -1, RatingContext::Diversity);
+        other.created_by = Some("bob".into());
+        let anonymous = new_rating("3", "a", -1, RatingContext::Diversity);
+        let ratings = vec![own, other, anonymous];
+        assert_eq!(entry.avg_rating(&ratings), -1.0 / 6.0);
+        entry.created_by = None;
+        assert_eq!(entry.avg_rating(&ratings), 0.0);
+    }
+
+    #[test]
+    fn test_average_rating_ignores_missing_ratings() {
+        let entry = new_entry("a", 0.0, 0.0);
+        let all_ratings = vec![
+            new_rating("1", "a", 2, RatingContext::Diversity),
+            new_rating("2", "a", 4, RatingContext::Diversity),
+            new_rating("3", "a", 6, RatingContext::Diversity),
+        ];
+        // rating "3" is missing, e.g. because it has been deleted
+        let found_ratings = &all_ratings[0..2];
+
+        let ratings_for_entry: Vec<&Rating> = found_ratings.iter().collect();
+        assert_eq!(
+            avg_rating_for_context(&ratings_for_entry, &RatingContext::Diversity),
+            Some(3.0)
+        );
+        assert_eq!(
+            avg_rating_for_context(&ratings_for_entry, &RatingContext::Fairness),
+            None
+        );
+        assert_eq!(entry.avg_rating(found_ratings), 0.5);
+        assert_eq!(entry.avg_rating(&[]), 0.0);
+    }
+
     #[test]
     fn test_average_rating_different_contexts() {
         let entry1 = new_entry("a", 0.0, 0.0);
@@ -213,6 +359,39 @@ pub mod tests {
         // - negative ratings
     }
 
+    #[test]
+    fn newer_ratings_outrank_older_equal_ones() {
+        let mut entries = vec![new_entry("old", 0.0, 0.0), new_entry("new", 0.0, 0.0)];
+        let now = 1_000 * 86_400;
+        let mut old = new_rating("1", "old", 2, RatingContext::Diversity);
+        old.created = now - 400 * 86_400;
+        let mut new = new_rating("2", "new", 2, RatingContext::Diversity);
+        new.created = now - 86_400;
+        let ratings = vec![old, new];
+
+        // without the decay both are equal
+        assert_eq!(entries[0].avg_rating(&ratings), entries[1].avg_rating(&ratings));
+
+        let weighted = entries.calc_weighted_avg_ratings(&ratings, now, 365.0);
+        assert!(weighted["new"] > weighted["old"]);
+        entries.sort_by_avg_rating(&weighted);
+        assert_eq!(entries[0].id, "new");
+        assert_eq!(entries[1].id, "old");
+    }
+
+    #[test]
+    fn rating_weight_halves_after_the_half_life() {
+        let entry = new_entry("a", 0.0, 0.0);
+        let mut rating = new_rating("1", "a", 2, RatingContext::Diversity);
+        rating.created = 0;
+        let ratings = vec![rating];
+        let plain = entry.avg_rating(&ratings);
+        assert_eq!(entry.weighted_avg_rating(&ratings, 0, 10.0), plain);
+        assert_eq!(entry.weighted_avg_rating(&ratings, 10 * 86_400, 10.0), plain / 2.0);
+        // no decay
+        assert_eq!(entry.weighted_avg_rating(&ratings, 10 * 86_400, 0.0), plain);
+    }
+
     #[test]
     fn test_sort_by_avg_rating_with_no_ratings() {
         let mut entries = vec![
@@ -280,6 +459,23 @@ pub mod tests {
         assert_eq!(entries[2].id, "c");
     }
 
+    #[test]
+    fn sort_with_several_invalid_coordinates() {
+        let mut entries = vec![
+            new_entry("a", NAN, 0.0),
+            new_entry("b", 3.0, 0.0),
+            new_entry("c", 1.0, INFINITY),
+            new_entry("d", NAN, NAN),
+            new_entry("e", 1.0, 0.0),
+            new_entry("f", -INFINITY, 2.0),
+            new_entry("g", 2.0, 0.0),
+        ];
+        let x = Coordinate { lat: 0.0, lng: 0.0 };
+        entries.sort_by_distance_to(&x);
+        let ids: Vec<_> = entries.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, vec!["e", "g", "b", "a", "c", "d", "f"]);
+    }
+
     pub fn create_entries_with_ratings(n: usize) -> (Vec<Entry>, Vec<Rating>) {
         let entries: Vec<Entry> = (0..n).map(|_| Entry::build().finish()).collect();
 
@@ -310,6 +506,7 @@ pub mod tests {
                 value: 2,
                 context: RatingContext::Diversity,
                 source: None,
+                created_by: None,
             })
             .collect()
     }
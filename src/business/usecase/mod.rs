@@ -2,15 +2,18 @@ use super::error::{Error, ParameterError, RepoError};
 use std::result;
 use chrono::*;
 use entities::*;
-use super::db::Db;
-use super::filter;
-use super::validate::{self, Validate};
+use super::db::{Db, SearchParams};
+use super::validate::{self, SelfRatingPolicy, Validate};
 use uuid::Uuid;
 use std::collections::HashMap;
 use pwhash::bcrypt;
+use super::duplicates::{self, DuplicateConfig, DuplicateType};
 use super::geo;
-use super::sort::SortByAverageRating;
-use super::filter::InBBox;
+use super::geocode::{Address, Geocoder};
+use super::sanitize;
+use super::search;
+use super::sort::{SortByAverageRating, SortByDistanceTo, SortOrder};
+use super::filter::{Combination, InBBox};
 
 #[cfg(test)]
 pub mod tests;
@@ -63,23 +66,51 @@ impl Id for BboxSubscription {
     }
 }
 
+impl Id for PasswordReset {
+    fn id(&self) -> String {
+        self.token.clone()
+    }
+}
+
 #[cfg_attr(rustfmt, rustfmt_skip)]
 #[derive(Deserialize, Debug, Clone)]
 pub struct NewEntry {
-    pub title       : String,
-    pub description : String,
-    pub lat         : f64,
-    pub lng         : f64,
-    pub street      : Option<String>,
-    pub zip         : Option<String>,
-    pub city        : Option<String>,
-    pub country     : Option<String>,
-    pub email       : Option<String>,
-    pub telephone   : Option<String>,
-    pub homepage    : Option<String>,
-    pub categories  : Vec<String>,
-    pub tags        : Vec<String>,
-    pub license     : String,
+    pub title         : String,
+    pub description   : String,
+    /// Without coordinates the entry is located by its address
+    pub lat           : Option<f64>,
+    pub lng           : Option<f64>,
+    pub street        : Option<String>,
+    pub zip           : Option<String>,
+    pub city          : Option<String>,
+    pub country       : Option<String>,
+    pub email         : Option<String>,
+    pub telephone     : Option<String>,
+    pub homepage      : Option<String>,
+    pub opening_hours : Option<String>,
+    #[serde(default)]
+    pub image_urls    : Vec<String>,
+    pub categories    : Vec<String>,
+    pub tags          : Vec<String>,
+    pub license       : String,
+    /// Imports can choose the id themselves,
+    /// by default a random one is created
+    pub id            : Option<String>,
+    /// Update the entry if one with this `id` exists already
+    #[serde(default)]
+    pub upsert        : bool,
+    /// The next version of the entry that is updated by an upsert
+    #[serde(default)]
+    pub version       : u64,
+}
+
+/// The result of `upsert_entry`
+#[derive(Debug, Clone)]
+pub enum Upserted {
+    /// The id of the new entry
+    Created(String),
+    /// The update and the coordinate of the entry before it
+    Updated(UpdateEntry, Coordinate),
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -99,22 +130,48 @@ pub struct Login {
 #[cfg_attr(rustfmt, rustfmt_skip)]
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct UpdateEntry {
-    pub id          : String,
-    pub osm_node    : Option<u64>,
-    pub version     : u64,
-    pub title       : String,
-    pub description : String,
-    pub lat         : f64,
-    pub lng         : f64,
-    pub street      : Option<String>,
-    pub zip         : Option<String>,
-    pub city        : Option<String>,
-    pub country     : Option<String>,
-    pub email       : Option<String>,
-    pub telephone   : Option<String>,
-    pub homepage    : Option<String>,
-    pub categories  : Vec<String>,
-    pub tags        : Vec<String>,
+    pub id            : String,
+    pub osm_node      : Option<u64>,
+    pub version       : u64,
+    pub title         : String,
+    pub description   : String,
+    pub lat           : f64,
+    pub lng           : f64,
+    pub street        : Option<String>,
+    pub zip           : Option<String>,
+    pub city          : Option<String>,
+    pub country       : Option<String>,
+    pub email         : Option<String>,
+    pub telephone     : Option<String>,
+    pub homepage      : Option<String>,
+    pub opening_hours : Option<String>,
+    #[serde(default)]
+    pub image_urls    : Vec<String>,
+    pub categories    : Vec<String>,
+    pub tags          : Vec<String>,
+}
+
+/// Only the given fields are changed,
+/// fields can't be removed (use `UpdateEntry` for that).
+#[cfg_attr(rustfmt, rustfmt_skip)]
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct PatchEntry {
+    pub version       : u64,
+    pub title         : Option<String>,
+    pub description   : Option<String>,
+    pub lat           : Option<f64>,
+    pub lng           : Option<f64>,
+    pub street        : Option<String>,
+    pub zip           : Option<String>,
+    pub city          : Option<String>,
+    pub country       : Option<String>,
+    pub email         : Option<String>,
+    pub telephone     : Option<String>,
+    pub homepage      : Option<String>,
+    pub opening_hours : Option<String>,
+    pub image_urls    : Option<Vec<String>>,
+    pub categories    : Option<Vec<String>>,
+    pub tags          : Option<Vec<String>>,
 }
 
 #[cfg_attr(rustfmt, rustfmt_skip)]
@@ -126,23 +183,50 @@ pub struct RateEntry {
     pub context : RatingContext,
     pub comment : String,
     pub source  : Option<String>,
+    /// The logged in user, a `user` in the request body is ignored
+    #[serde(skip_deserializing)]
     pub user    : Option<String>,
 }
 
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct EntryTitle {
+    pub id: String,
+    pub title: String,
+}
+
+/// Two entries that are probably the same
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Duplicate {
+    pub entry1: EntryTitle,
+    pub entry2: EntryTitle,
+    pub kind: DuplicateType,
+}
+
 #[cfg_attr(rustfmt, rustfmt_skip)]
 #[derive(Debug, Clone)]
 pub struct SearchRequest<'a> {
-    pub bbox          : Bbox,
-    pub categories    : Option<Vec<String>>,
-    pub text          : String,
-    pub tags          : Vec<String>,
-    pub entry_ratings : &'a HashMap<String, f64>,
+    pub bbox                  : Bbox,
+    pub categories            : Option<Vec<String>>,
+    pub categories_mode       : Combination,
+    pub text                  : String,
+    pub tags                  : Vec<String>,
+    pub exclude_tags          : Vec<String>,
+    pub city                  : Option<String>,
+    pub country               : Option<String>,
+    pub created_after         : Option<u64>,
+    pub created_before        : Option<u64>,
+    pub entry_ratings         : &'a HashMap<String, f64>,
+    pub sort                  : SortOrder,
+    pub include_archived      : bool,
+    pub max_invisible_results : usize,
+    pub rating_half_life_days : f64,
 }
 
+/// The ratings in the order of their ids; unknown ids are ignored.
 pub fn get_ratings<D: Db>(db: &D, ids: &[String]) -> Result<Vec<Rating>> {
-    Ok(db.all_ratings()?
-        .iter()
-        .filter(|x| ids.iter().any(|id| *id == x.id))
+    let ratings = db.get_ratings(ids)?;
+    Ok(ids.iter()
+        .filter_map(|id| ratings.iter().find(|r| r.id == *id))
         .cloned()
         .collect())
 }
@@ -151,18 +235,51 @@ pub fn get_ratings_by_entry_ids<D: Db>(
     db: &D,
     ids: &[String],
 ) -> Result<HashMap<String, Vec<Rating>>> {
-    let ratings = db.all_ratings()?;
-    Ok(ids.iter()
-        .map(|e_id| {
-            (
-                e_id.clone(),
-                ratings
-                    .iter()
-                    .filter(|r| r.entry_id == **e_id)
-                    .cloned()
-                    .collect(),
-            )
-        })
+    let mut ratings: HashMap<String, Vec<Rating>> =
+        ids.iter().map(|e_id| (e_id.clone(), vec![])).collect();
+    for r in db.get_ratings_by_entry_ids(ids)? {
+        if let Some(rs) = ratings.get_mut(&r.entry_id) {
+            rs.push(r);
+        }
+    }
+    Ok(ratings)
+}
+
+/// The number of comments on the ratings of each entry
+pub fn count_comments_by_entry_ids<D: Db>(
+    db: &D,
+    ratings: &HashMap<String, Vec<Rating>>,
+) -> Result<HashMap<String, usize>> {
+    let entry_ids: HashMap<&str, &str> = ratings
+        .iter()
+        .flat_map(|(e_id, rs)| rs.iter().map(move |r| (r.id.as_str(), e_id.as_str())))
+        .collect();
+    let rating_ids: Vec<String> = entry_ids.keys().map(|r_id| (*r_id).to_string()).collect();
+    let mut counts: HashMap<String, usize> =
+        ratings.keys().map(|e_id| (e_id.clone(), 0)).collect();
+    for c in db.get_comments_by_rating_ids(&rating_ids)? {
+        if let Some(e_id) = entry_ids.get(c.rating_id.as_str()) {
+            *counts.entry((*e_id).to_string()).or_insert(0) += 1;
+        }
+    }
+    Ok(counts)
+}
+
+/// Averages the ratings of an entry per context.
+/// Contexts without any rating are omitted.
+pub fn average_ratings_by_context<D: Db>(
+    db: &D,
+    entry_id: &str,
+) -> Result<HashMap<RatingContext, f64>> {
+    db.get_entry(entry_id)?;
+    let mut sums: HashMap<RatingContext, (i64, usize)> = HashMap::new();
+    for r in db.get_ratings_by_entry_ids(&[entry_id.to_string()])? {
+        let s = sums.entry(r.context).or_insert((0, 0));
+        s.0 += i64::from(r.value);
+        s.1 += 1;
+    }
+    Ok(sums.into_iter()
+        .map(|(ctx, (sum, n))| (ctx, sum as f64 / n as f64))
         .collect())
 }
 
@@ -170,7 +287,7 @@ pub fn get_comments_by_rating_ids<D: Db>(
     db: &D,
     ids: &[String],
 ) -> Result<HashMap<String, Vec<Comment>>> {
-    let comments = db.all_comments()?;
+    let comments = db.get_comments_by_rating_ids(ids)?;
     Ok(ids.iter()
         .map(|r_id| {
             (
@@ -191,15 +308,60 @@ pub fn get_comments_by_rating_ids<D: Db>(
         .collect())
 }
 
+/// The ids that can be requested at once by default
+pub const DEFAULT_MAX_ENTRY_IDS: usize = 100;
+
+/// A single id that doesn't exist is `NotFound`,
+/// of multiple ids only the existing entries are returned.
 pub fn get_entries<D: Db>(db: &D, ids: &[String]) -> Result<Vec<Entry>> {
-    let entries = db.all_entries()?
+    let entries = db.get_entries(ids)?;
+    if ids.len() == 1 && entries.is_empty() {
+        return Err(Error::Repo(RepoError::NotFound));
+    }
+    Ok(entries)
+}
+
+/// Removes the ids of categories that don't exist (anymore),
+/// so that clients don't get dead references.
+pub fn prune_categories(entries: &mut [Entry], categories: &[Category]) {
+    for e in entries {
+        let (known, unknown): (Vec<_>, Vec<_>) = e.categories
+            .drain(..)
+            .partition(|id| categories.iter().any(|c| c.id == *id));
+        if !unknown.is_empty() {
+            warn!("Entry {} refers to unknown categories: {:?}", e.id, unknown);
+        }
+        e.categories = known;
+    }
+}
+
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+pub const MAX_PAGE_SIZE: usize = 1_000;
+
+/// Returns a page of all entries that are not archived,
+/// the oldest first. The page size is capped at `MAX_PAGE_SIZE`.
+pub fn get_entries_page<D: Db>(db: &D, offset: usize, limit: Option<usize>) -> Result<Vec<Entry>> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+    let mut entries: Vec<_> = db.all_entries()?
         .into_iter()
-        .filter(|e| ids.iter().any(|id| *id == e.id))
+        .filter(|e| e.archived.is_none())
         .collect();
-    Ok(entries)
+    entries.sort_by(|a, b| a.created.cmp(&b.created).then_with(|| a.id.cmp(&b.id)));
+    Ok(entries.into_iter().skip(offset).take(limit).collect())
 }
 
 pub fn create_new_user<D: Db>(db: &mut D, u: NewUser) -> Result<()> {
+    create_user(db, u, false)?;
+    Ok(())
+}
+
+/// Creates a user without the need to confirm the email address
+/// and returns the id of the new user.
+pub fn create_confirmed_user<D: Db>(db: &mut D, u: NewUser) -> Result<String> {
+    create_user(db, u, true)
+}
+
+fn create_user<D: Db>(db: &mut D, u: NewUser, email_confirmed: bool) -> Result<String> {
     validate::username(&u.username)?;
     validate::password(&u.password)?;
     validate::email(&u.email)?;
@@ -207,13 +369,22 @@ pub fn create_new_user<D: Db>(db: &mut D, u: NewUser) -> Result<()> {
         return Err(Error::Parameter(ParameterError::UserExists));
     }
     let pw = bcrypt::hash(&u.password)?;
+    let id = Uuid::new_v4().simple().to_string();
     db.create_user(&User {
-        id: Uuid::new_v4().simple().to_string(),
+        id: id.clone(),
         username: u.username,
         password: pw,
         email: u.email,
-        email_confirmed: false,
+        email_confirmed,
     })?;
+    Ok(id)
+}
+
+pub fn confirm_email<D: Db>(db: &mut D, u_id: &str) -> Result<()> {
+    let u = db.confirm_email_address(u_id)?;
+    if u.id != u_id {
+        return Err(Error::Repo(RepoError::NotFound));
+    }
     Ok(())
 }
 
@@ -257,30 +428,162 @@ pub fn login<D: Db>(db: &mut D, login: &Login) -> Result<String> {
     }
 }
 
-pub fn create_new_entry<D: Db>(db: &mut D, e: NewEntry) -> Result<String> {
-    let mut tags: Vec<_> = e.tags.into_iter().map(|t| t.replace("#", "")).collect();
-    tags.dedup();
+pub fn change_password<D: Db>(
+    db: &mut D,
+    username: &str,
+    old_password: &str,
+    new_password: &str,
+) -> Result<()> {
+    let mut u = db.get_user(username)?;
+    if !bcrypt::verify(old_password, &u.password) {
+        return Err(Error::Parameter(ParameterError::Credentials));
+    }
+    validate::password(new_password)?;
+    u.password = bcrypt::hash(new_password)?;
+    db.update_user(&u)?;
+    Ok(())
+}
+
+// Password reset tokens expire after 24 hours
+const PASSWORD_RESET_TOKEN_TTL: i64 = 24 * 60 * 60;
+
+/// Creates a new password reset token for the user with the given
+/// email address and returns it together with the username.
+pub fn request_password_reset<D: Db>(db: &mut D, email: &str) -> Result<(String, String)> {
+    let u = db.all_users()?
+        .into_iter()
+        .find(|u| u.email == email)
+        .ok_or(RepoError::NotFound)?;
+    let token = Uuid::new_v4().simple().to_string();
+    db.create_password_reset(&PasswordReset {
+        token: token.clone(),
+        username: u.username.clone(),
+        created: Utc::now().timestamp() as u64,
+    })?;
+    Ok((u.username, token))
+}
+
+pub fn reset_password<D: Db>(db: &mut D, token: &str, new_password: &str) -> Result<()> {
+    let reset = match db.get_password_reset(token) {
+        Ok(reset) => reset,
+        Err(RepoError::NotFound) => {
+            return Err(Error::Parameter(ParameterError::PasswordResetToken));
+        }
+        Err(err) => return Err(Error::Repo(err)),
+    };
+    if Utc::now().timestamp() - reset.created as i64 > PASSWORD_RESET_TOKEN_TTL {
+        db.delete_password_reset(token)?;
+        return Err(Error::Parameter(ParameterError::PasswordResetToken));
+    }
+    validate::password(new_password)?;
+    let mut u = db.get_user(&reset.username)?;
+    u.password = bcrypt::hash(new_password)?;
+    db.update_user(&u)?;
+    db.delete_password_reset(token)?;
+    Ok(())
+}
+
+// Missing address fields are looked up by the coordinates.
+// A failing lookup never prevents an entry from being created.
+fn complete_address(geocoder: &Geocoder, e: &mut NewEntry, lat: f64, lng: f64) {
+    if e.street.is_some() && e.zip.is_some() && e.city.is_some() && e.country.is_some() {
+        return;
+    }
+    if validate::coordinate(lat, lng).is_err() {
+        return;
+    }
+    match geocoder.reverse(lat, lng) {
+        Some(a) => {
+            e.street = e.street.take().or(a.street);
+            e.zip = e.zip.take().or(a.zip);
+            e.city = e.city.take().or(a.city);
+            e.country = e.country.take().or(a.country);
+        }
+        None => warn!("Could not find an address for {}/{}", lat, lng),
+    }
+}
+
+// Entries without coordinates are located by their address.
+fn locate(geocoder: Option<&Geocoder>, e: &NewEntry) -> Result<(f64, f64)> {
+    if let (Some(lat), Some(lng)) = (e.lat, e.lng) {
+        return Ok((lat, lng));
+    }
+    let address = Address {
+        street: e.street.clone(),
+        zip: e.zip.clone(),
+        city: e.city.clone(),
+        country: e.country.clone(),
+    };
+    if address == Address::default() {
+        return Err(ParameterError::Coordinate.into());
+    }
+    geocoder
+        .and_then(|g| g.forward(&address))
+        .map(|c| (c.lat, c.lng))
+        .ok_or_else(|| ParameterError::UnknownAddress.into())
+}
+
+// Different spellings of the same tag end up as one tag.
+fn normalize_tags(tags: &[String]) -> Result<Vec<String>> {
+    let mut res = vec![];
+    for t in tags {
+        let t = validate::tag(t)?;
+        if !res.contains(&t) {
+            res.push(t);
+        }
+    }
+    Ok(res)
+}
+
+pub fn create_new_entry<D: Db>(
+    db: &mut D,
+    geocoder: Option<&Geocoder>,
+    mut e: NewEntry,
+    created_by: Option<&str>,
+) -> Result<String> {
+    validate::license(&e.license)?;
+    let (lat, lng) = locate(geocoder, &e)?;
+    if let Some(g) = geocoder {
+        complete_address(g, &mut e, lat, lng);
+    }
+    let tags = normalize_tags(&e.tags)?;
+    let id = match e.id.take() {
+        Some(id) => {
+            validate::entry_id(&id)?;
+            match db.get_entry(&id) {
+                Ok(_) => return Err(ParameterError::EntryExists.into()),
+                Err(RepoError::NotFound) => id,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        None => Uuid::new_v4().simple().to_string(),
+    };
 
     #[cfg_attr(rustfmt, rustfmt_skip)]
     let new_entry = Entry{
-        id          :  Uuid::new_v4().simple().to_string(),
-        osm_node    :  None,
-        created     :  Utc::now().timestamp() as u64,
-        version     :  0,
-        title       :  e.title,
-        description :  e.description,
-        lat         :  e.lat,
-        lng         :  e.lng,
-        street      :  e.street,
-        zip         :  e.zip,
-        city        :  e.city,
-        country     :  e.country,
-        email       :  e.email,
-        telephone   :  e.telephone,
-        homepage    :  e.homepage,
-        categories  :  e.categories,
+        id,
+        osm_node      :  None,
+        created       :  Utc::now().timestamp() as u64,
+        updated       :  None,
+        archived      :  None,
+        version       :  0,
+        title         :  sanitize::text(&e.title),
+        description   :  sanitize::text(&e.description),
+        lat,
+        lng,
+        street        :  e.street,
+        zip           :  e.zip,
+        city          :  e.city,
+        country       :  e.country,
+        email         :  e.email,
+        telephone     :  e.telephone,
+        homepage      :  e.homepage,
+        opening_hours :  e.opening_hours,
+        image_urls    :  e.image_urls,
+        categories    :  e.categories,
         tags,
-        license     :  Some(e.license)
+        license       :  Some(e.license),
+        created_by    :  created_by.map(|u| u.into())
     };
     new_entry.validate()?;
     for t in &new_entry.tags {
@@ -290,34 +593,118 @@ pub fn create_new_entry<D: Db>(db: &mut D, e: NewEntry) -> Result<String> {
     Ok(new_entry.id)
 }
 
+/// Updates the entry with the `id` of `e` to its `version`
+/// or creates it if it doesn't exist yet.
+pub fn upsert_entry<D: Db>(
+    db: &mut D,
+    geocoder: Option<&Geocoder>,
+    mut e: NewEntry,
+    created_by: Option<&str>,
+) -> Result<Upserted> {
+    let existing = match e.id {
+        Some(ref id) => match db.get_entry(id) {
+            Ok(old) => Some(old),
+            Err(RepoError::NotFound) => None,
+            Err(err) => return Err(err.into()),
+        },
+        None => None,
+    };
+    let old = match existing {
+        Some(old) => old,
+        None => return create_new_entry(db, geocoder, e, created_by).map(Upserted::Created),
+    };
+    validate::license(&e.license)?;
+    let (lat, lng) = locate(geocoder, &e)?;
+    if let Some(g) = geocoder {
+        complete_address(g, &mut e, lat, lng);
+    }
+    let coordinate = Coordinate {
+        lat: old.lat,
+        lng: old.lng,
+    };
+    let update = into_update(e, old.id, lat, lng);
+    update_entry(db, update.clone())?;
+    Ok(Upserted::Updated(update, coordinate))
+}
+
+// The license of existing entries stays the same
+fn into_update(e: NewEntry, id: String, lat: f64, lng: f64) -> UpdateEntry {
+    UpdateEntry {
+        id,
+        osm_node: None,
+        version: e.version,
+        title: e.title,
+        description: e.description,
+        lat,
+        lng,
+        street: e.street,
+        zip: e.zip,
+        city: e.city,
+        country: e.country,
+        email: e.email,
+        telephone: e.telephone,
+        homepage: e.homepage,
+        opening_hours: e.opening_hours,
+        image_urls: e.image_urls,
+        categories: e.categories,
+        tags: e.tags,
+    }
+}
+
+/// Creates each entry on its own, so an invalid entry
+/// doesn't prevent the others from being created.
+/// Entries with `upsert` set update an existing entry instead.
+pub fn create_new_entries<D: Db>(
+    db: &mut D,
+    geocoder: Option<&Geocoder>,
+    entries: Vec<NewEntry>,
+    created_by: Option<&str>,
+) -> Vec<Result<Upserted>> {
+    entries
+        .into_iter()
+        .map(|e| {
+            if e.upsert {
+                upsert_entry(db, geocoder, e, created_by)
+            } else {
+                create_new_entry(db, geocoder, e, created_by).map(Upserted::Created)
+            }
+        })
+        .collect()
+}
+
 pub fn update_entry<D: Db>(db: &mut D, e: UpdateEntry) -> Result<()> {
     let old: Entry = db.get_entry(&e.id)?;
     if (old.version + 1) != e.version {
-        return Err(Error::Repo(RepoError::InvalidVersion));
+        return Err(Error::Repo(RepoError::InvalidVersion(old.version)));
     }
-    let mut tags = e.tags;
-    tags.dedup();
+    let tags = normalize_tags(&e.tags)?;
     #[cfg_attr(rustfmt, rustfmt_skip)]
     let new_entry = Entry{
-        id          :  e.id,
-        osm_node    :  None,
-        created     :  Utc::now().timestamp() as u64,
-        version     :  e.version,
-        title       :  e.title,
-        description :  e.description,
-        lat         :  e.lat,
-        lng         :  e.lng,
-        street      :  e.street,
-        zip         :  e.zip,
-        city        :  e.city,
-        country     :  e.country,
-        email       :  e.email,
-        telephone   :  e.telephone,
-        homepage    :  e.homepage,
-        categories  :  e.categories,
+        id            :  e.id,
+        osm_node      :  None,
+        created       :  old.created,
+        updated       :  Some(Utc::now().timestamp() as u64),
+        archived      :  old.archived,
+        version       :  e.version,
+        title         :  sanitize::text(&e.title),
+        description   :  sanitize::text(&e.description),
+        lat           :  e.lat,
+        lng           :  e.lng,
+        street        :  e.street,
+        zip           :  e.zip,
+        city          :  e.city,
+        country       :  e.country,
+        email         :  e.email,
+        telephone     :  e.telephone,
+        homepage      :  e.homepage,
+        opening_hours :  e.opening_hours,
+        image_urls    :  e.image_urls,
+        categories    :  e.categories,
         tags,
-        license     :  old.license
+        license       :  old.license,
+        created_by    :  old.created_by
     };
+    new_entry.validate()?;
     for t in &new_entry.tags {
         db.create_tag_if_it_does_not_exist(&Tag { id: t.clone() })?;
     }
@@ -325,38 +712,292 @@ pub fn update_entry<D: Db>(db: &mut D, e: UpdateEntry) -> Result<()> {
     Ok(())
 }
 
-pub fn rate_entry<D: Db>(db: &mut D, r: RateEntry) -> Result<()> {
+pub fn get_entry_history<D: Db>(db: &D, id: &str) -> Result<Vec<Entry>> {
+    let versions = db.get_entry_history(id)?;
+    if versions.is_empty() {
+        return Err(Error::Repo(RepoError::NotFound));
+    }
+    Ok(versions)
+}
+
+pub fn get_entries_by_user<D: Db>(db: &D, username: &str) -> Result<Vec<Entry>> {
+    let entries = db.all_entries()?
+        .into_iter()
+        .filter(|e| e.created_by.as_ref().map(|u| u == username).unwrap_or(false))
+        .collect();
+    Ok(entries)
+}
+
+/// Finds similar entries that are not archived
+pub fn find_duplicates<D: Db>(db: &D, cfg: &DuplicateConfig) -> Result<Vec<Duplicate>> {
+    let entries: Vec<_> = db.all_entries()?
+        .into_iter()
+        .filter(|e| e.archived.is_none())
+        .collect();
+    let titles: HashMap<_, _> = entries
+        .iter()
+        .map(|e| (e.id.clone(), e.title.clone()))
+        .collect();
+    let entry_title = |id: String| EntryTitle {
+        title: titles.get(&id).cloned().unwrap_or_default(),
+        id,
+    };
+    Ok(duplicates::find_duplicates(&entries, cfg)
+        .into_iter()
+        .map(|(id1, id2, kind)| Duplicate {
+            entry1: entry_title(id1),
+            entry2: entry_title(id2),
+            kind,
+        })
+        .collect())
+}
+
+/// Counts the entries of each tag, most used tags first.
+pub fn get_tag_usage_counts<D: Db>(db: &D) -> Result<Vec<(String, usize)>> {
+    let mut counts: HashMap<String, usize> = db.all_tags()?
+        .into_iter()
+        .map(|t| (t.id, 0))
+        .collect();
+    for e in db.all_entries()? {
+        for t in e.tags {
+            *counts.entry(t).or_insert(0) += 1;
+        }
+    }
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    Ok(counts)
+}
+
+/// Creates a category and returns its id.
+/// The names of categories are unique, regardless of their case.
+pub fn create_category<D: Db>(db: &mut D, name: &str) -> Result<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(ParameterError::EmptyCategoryName.into());
+    }
+    let lower_case_name = name.to_lowercase();
+    if db.all_categories()?
+        .iter()
+        .any(|c| c.name.to_lowercase() == lower_case_name)
+    {
+        return Err(ParameterError::CategoryExists.into());
+    }
+    let category = Category {
+        id: Uuid::new_v4().simple().to_string(),
+        created: Utc::now().timestamp() as u64,
+        version: 0,
+        name: name.into(),
+    };
+    db.create_category(&category)?;
+    Ok(category.id)
+}
+
+/// Replaces the tag `from` by `into` on all entries
+/// and removes `from` afterwards.
+pub fn merge_tags<D: Db>(db: &mut D, from: &str, into: &str) -> Result<()> {
+    if from == into {
+        return Ok(());
+    }
+    db.create_tag_if_it_does_not_exist(&Tag { id: into.into() })?;
+    let entries: Vec<Entry> = db.all_entries()?
+        .into_iter()
+        .filter(|e| e.tags.iter().any(|t| t == from))
+        .collect();
+    for mut e in entries {
+        let mut tags = vec![];
+        for t in e.tags {
+            let t = if t == from { into.to_string() } else { t };
+            if !tags.contains(&t) {
+                tags.push(t);
+            }
+        }
+        e.tags = tags;
+        e.version += 1;
+        e.updated = Some(Utc::now().timestamp() as u64);
+        db.update_entry(&e)?;
+    }
+    db.delete_tag(from)?;
+    Ok(())
+}
+
+/// Hides an entry from search results without deleting it.
+pub fn archive_entry<D: Db>(db: &mut D, id: &str) -> Result<()> {
+    set_entry_archived(db, id, Some(Utc::now().timestamp() as u64))
+}
+
+pub fn unarchive_entry<D: Db>(db: &mut D, id: &str) -> Result<()> {
+    set_entry_archived(db, id, None)
+}
+
+fn set_entry_archived<D: Db>(db: &mut D, id: &str, archived: Option<u64>) -> Result<()> {
+    let mut e = db.get_entry(id)?;
+    if e.archived.is_some() == archived.is_some() {
+        return Ok(());
+    }
+    e.archived = archived;
+    e.version += 1;
+    e.updated = Some(Utc::now().timestamp() as u64);
+    db.update_entry(&e)?;
+    Ok(())
+}
+
+/// Moves the tags and ratings of the entry `drop_id`
+/// to the entry `keep_id` and deletes `drop_id` afterwards.
+/// Merging an entry that was deleted already does nothing.
+pub fn merge_entries<D: Db>(db: &mut D, keep_id: &str, drop_id: &str) -> Result<()> {
+    if keep_id == drop_id {
+        return Ok(());
+    }
+    let drop = match db.get_entry(drop_id) {
+        Ok(e) => e,
+        Err(RepoError::NotFound) => return Ok(()),
+        Err(err) => return Err(err.into()),
+    };
+    let mut keep = db.get_entry(keep_id)?;
+    let new_tags: Vec<_> = drop.tags
+        .into_iter()
+        .filter(|t| !keep.tags.contains(t))
+        .collect();
+    db.transaction(|db| {
+        if !new_tags.is_empty() {
+            keep.tags.extend(new_tags);
+            keep.version += 1;
+            keep.updated = Some(Utc::now().timestamp() as u64);
+            db.update_entry(&keep)?;
+        }
+        // The comments belong to the ratings, so they move along.
+        for mut r in db.all_ratings()?.into_iter().filter(|r| r.entry_id == drop_id) {
+            r.entry_id = keep_id.into();
+            db.update_rating(&r)?;
+        }
+        db.delete_entry(drop_id)?;
+        Ok(())
+    })
+}
+
+/// Deletes an entry together with its ratings and their comments.
+pub fn delete_entry<D: Db>(db: &mut D, id: &str) -> Result<()> {
+    db.transaction(|db| {
+        let rating_ids: Vec<_> = db.get_ratings_by_entry_ids(&[id.into()])?
+            .into_iter()
+            .map(|r| r.id)
+            .collect();
+        for c in db.get_comments_by_rating_ids(&rating_ids)? {
+            db.delete_comment(&c.id)?;
+        }
+        for r in &rating_ids {
+            db.delete_rating(r)?;
+        }
+        db.delete_entry(id)?;
+        Ok(())
+    })
+}
+
+/// Replaces the text of a comment but keeps its creation time.
+pub fn update_comment<D: Db>(db: &mut D, id: &str, text: &str) -> Result<()> {
+    if text.is_empty() {
+        return Err(Error::Parameter(ParameterError::EmptyComment));
+    }
+    let mut comment = db.get_comment(id)?;
+    comment.text = text.into();
+    db.update_comment(&comment)?;
+    Ok(())
+}
+
+/// Deletes a rating together with its comments
+/// and returns the id of the rated entry.
+pub fn delete_rating<D: Db>(db: &mut D, id: &str) -> Result<String> {
+    let rating = db.get_rating(id)?;
+    for c in db.get_comments_by_rating_ids(&[id.to_string()])? {
+        db.delete_comment(&c.id)?;
+    }
+    db.delete_rating(id)?;
+    Ok(rating.entry_id)
+}
+
+/// Returns the id of the new rating
+pub fn rate_entry<D: Db>(db: &mut D, r: RateEntry) -> Result<String> {
     let e = db.get_entry(&r.entry)?;
     if r.comment.len() < 1 {
         return Err(Error::Parameter(ParameterError::EmptyComment));
     }
-    if r.value > 2 || r.value < -1 {
-        return Err(Error::Parameter(ParameterError::RatingValue));
+    validate::rating_value(r.value)?;
+    if validate::SELF_RATING_POLICY == SelfRatingPolicy::Reject
+        && validate::is_self_rating(&e, r.user.as_ref())
+    {
+        return Err(Error::Parameter(ParameterError::SelfRating));
+    }
+    if let Some(ref user) = r.user {
+        let rated_before = db
+            .get_ratings_by_entry_ids(&[e.id.clone()])?
+            .iter()
+            .any(|x| x.context == r.context && x.created_by.as_ref() == Some(user));
+        if rated_before {
+            return Err(Error::Parameter(ParameterError::AlreadyRated));
+        }
     }
     let now = Utc::now().timestamp() as u64;
     let rating_id = Uuid::new_v4().simple().to_string();
     let comment_id = Uuid::new_v4().simple().to_string();
+    db.transaction(|db| {
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        db.create_rating(&Rating{
+            id         : rating_id.clone(),
+            entry_id   : e.id,
+            created    : now,
+            title      : r.title,
+            value      : r.value,
+            context    : r.context,
+            source     : r.source,
+            created_by : r.user,
+        })?;
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        db.create_comment(&Comment {
+            id: comment_id.clone(),
+            created: now,
+            text: r.comment,
+            rating_id: rating_id.clone(),
+        })?;
+        Ok(rating_id)
+    })
+}
+
+/// Updates the given fields of an entry and keeps all others.
+/// Returns the complete update.
+pub fn patch_entry<D: Db>(db: &mut D, id: &str, p: PatchEntry) -> Result<UpdateEntry> {
+    let old = db.get_entry(id)?;
     #[cfg_attr(rustfmt, rustfmt_skip)]
-    db.create_rating(&Rating{
-        id       : rating_id.clone(),
-        entry_id : e.id,
-        created  : now,
-        title    : r.title,
-        value    : r.value,
-        context  : r.context,
-        source   : r.source
-    })?;
-    #[cfg_attr(rustfmt, rustfmt_skip)]
-    db.create_comment(&Comment {
-        id: comment_id.clone(),
-        created: now,
-        text: r.comment,
-        rating_id,
-    })?;
-    Ok(())
+    let e = UpdateEntry {
+        id            :  old.id,
+        osm_node      :  old.osm_node,
+        version       :  p.version,
+        title         :  p.title.unwrap_or(old.title),
+        description   :  p.description.unwrap_or(old.description),
+        lat           :  p.lat.unwrap_or(old.lat),
+        lng           :  p.lng.unwrap_or(old.lng),
+        street        :  p.street.or(old.street),
+        zip           :  p.zip.or(old.zip),
+        city          :  p.city.or(old.city),
+        country       :  p.country.or(old.country),
+        email         :  p.email.or(old.email),
+        telephone     :  p.telephone.or(old.telephone),
+        homepage      :  p.homepage.or(old.homepage),
+        opening_hours :  p.opening_hours.or(old.opening_hours),
+        image_urls    :  p.image_urls.unwrap_or(old.image_urls),
+        categories    :  p.categories.unwrap_or(old.categories),
+        tags          :  p.tags.unwrap_or(old.tags),
+    };
+    update_entry(db, e.clone())?;
+    Ok(e)
+}
+
+/// Rates each entry on its own, so an invalid rating
+/// doesn't prevent the others from being stored.
+pub fn rate_entries<D: Db>(db: &mut D, ratings: Vec<RateEntry>) -> Vec<Result<String>> {
+    ratings.into_iter().map(|r| rate_entry(db, r)).collect()
 }
 
-pub fn subscribe_to_bbox(coordinates: &[Coordinate], username: &str, db: &mut Db) -> Result<()> {
+pub fn subscribe_to_bbox<D: Db>(coordinates: &[Coordinate], username: &str, db: &mut D) -> Result<()> {
     if coordinates.len() != 2 {
         return Err(Error::Parameter(ParameterError::Bbox));
     }
@@ -369,15 +1010,17 @@ pub fn subscribe_to_bbox(coordinates: &[Coordinate], username: &str, db: &mut Db
     // TODO: support multiple subscriptions in KVM (frontend)
     // In the meanwile we just replace existing subscriptions
     // with a new one.
-    unsubscribe_all_bboxes_by_username(db, username)?;
+    db.transaction(|db| {
+        unsubscribe_all_bboxes_by_username(db, username)?;
 
-    let id = Uuid::new_v4().simple().to_string();
-    db.create_bbox_subscription(&BboxSubscription {
-        id,
-        bbox,
-        username: username.into(),
-    })?;
-    Ok(())
+        let id = Uuid::new_v4().simple().to_string();
+        db.create_bbox_subscription(&BboxSubscription {
+            id,
+            bbox,
+            username: username.into(),
+        })?;
+        Ok(())
+    })
 }
 
 pub fn get_bbox_subscriptions(username: &str, db: &Db) -> Result<Vec<BboxSubscription>> {
@@ -436,44 +1079,97 @@ pub fn email_addresses_by_coordinate(db: &mut Db, lat: &f64, lng: &f64) -> Resul
     Ok(addresses)
 }
 
-const MAX_INVISIBLE_RESULTS: usize = 5;
+/// The subscribers of the old and the new location of a moved entry,
+/// everyone only once.
+pub fn email_addresses_by_old_and_new_coordinate(
+    db: &mut Db,
+    old: &Coordinate,
+    new: &Coordinate,
+) -> Result<Vec<String>> {
+    let mut subs = bbox_subscriptions_by_coordinate(db, old)?;
+    for s in bbox_subscriptions_by_coordinate(db, new)? {
+        if !subs.iter().any(|x| x.id == s.id) {
+            subs.push(s);
+        }
+    }
+    let mut addresses = email_addresses_from_subscriptions(db, &subs)?;
+    addresses.sort();
+    addresses.dedup();
+    Ok(addresses)
+}
+
+/// Search results outside of the bbox that are returned by default
+pub const DEFAULT_MAX_INVISIBLE_RESULTS: usize = 5;
 
-const BBOX_LAT_EXT: f64 = 0.02;
-const BBOX_LNG_EXT: f64 = 0.04;
+// Search bboxes get extended by these distances (in meters) on each side.
+// At the equator this is about 0.02° of latitude and 0.04° of longitude.
+pub const BBOX_LAT_EXT_DISTANCE: f64 = 2_224.0;
+pub const BBOX_LNG_EXT_DISTANCE: f64 = 4_448.0;
+
+fn bbox_extension(bbox: &Bbox) -> (f64, f64) {
+    let lat = geo::center(bbox).lat;
+    (
+        geo::lat_degrees(BBOX_LAT_EXT_DISTANCE),
+        geo::lng_degrees(BBOX_LNG_EXT_DISTANCE, lat),
+    )
+}
 
-fn extend_bbox(bbox: &Bbox) -> Bbox {
+/// The bbox that searches without text or tags look in
+pub fn extend_bbox(bbox: &Bbox) -> Bbox {
+    let (lat_ext, lng_ext) = bbox_extension(bbox);
     let mut extended_bbox = bbox.to_owned();
-    extended_bbox.south_west.lat -= BBOX_LAT_EXT;
-    extended_bbox.south_west.lng -= BBOX_LNG_EXT;
-    extended_bbox.north_east.lat += BBOX_LAT_EXT;
-    extended_bbox.north_east.lng += BBOX_LNG_EXT;
+    extended_bbox.south_west.lat = (bbox.south_west.lat - lat_ext).max(-90.0);
+    extended_bbox.north_east.lat = (bbox.north_east.lat + lat_ext).min(90.0);
+    if geo::lng_span(bbox) + 2.0 * lng_ext >= 360.0 {
+        extended_bbox.south_west.lng = -180.0;
+        extended_bbox.north_east.lng = 180.0;
+    } else {
+        // wraps around the antimeridian if necessary
+        extended_bbox.south_west.lng = geo::normalize_lng(bbox.south_west.lng - lng_ext);
+        extended_bbox.north_east.lng = geo::normalize_lng(bbox.north_east.lng + lng_ext);
+    }
     extended_bbox
 }
 
-pub fn search<D: Db>(db: &D, req: &SearchRequest) -> Result<(Vec<Entry>, Vec<Entry>)> {
-    let mut entries = if req.text.is_empty() && req.tags.is_empty() {
-        let extended_bbox = extend_bbox(&req.bbox);
-        db.get_entries_by_bbox(&extended_bbox)?
+/// The visible and the (limited) invisible results
+/// and the number of all results before the limit.
+pub fn search<D: Db>(db: &D, req: &SearchRequest) -> Result<(Vec<Entry>, Vec<Entry>, usize)> {
+    // Searches for text or tags also return (invisible) results outside of the bbox.
+    let bbox = if req.text.is_empty() && req.tags.is_empty() {
+        Some(extend_bbox(&req.bbox))
     } else {
-        db.all_entries()?
+        None
+    };
+    let params = SearchParams {
+        bbox,
+        categories: req.categories.clone(),
+        categories_mode: req.categories_mode,
+        text: req.text.clone(),
+        tags: req.tags.clone(),
+        exclude_tags: req.exclude_tags.clone(),
+        city: req.city.clone(),
+        country: req.country.clone(),
+        created_after: req.created_after,
+        created_before: req.created_before,
+        include_archived: req.include_archived,
     };
+    let mut entries = db.search_entries(&params)?;
 
-    if let Some(ref cat_ids) = req.categories {
-        entries = entries
-            .into_iter()
-            .filter(&*filter::entries_by_category_ids(cat_ids))
-            .collect();
+    match req.sort {
+        SortOrder::Rating => entries.sort_by_avg_rating(req.entry_ratings),
+        SortOrder::RatingRecent => {
+            let ids: Vec<_> = entries.iter().map(|e| e.id.clone()).collect();
+            let ratings = db.get_ratings_by_entry_ids(&ids)?;
+            let now = Utc::now().timestamp() as u64;
+            let weighted_ratings =
+                entries.calc_weighted_avg_ratings(&ratings, now, req.rating_half_life_days);
+            entries.sort_by_avg_rating(&weighted_ratings);
+        }
+        SortOrder::Distance => entries.sort_by_distance_to(&geo::center(&req.bbox)),
+        SortOrder::Relevance => search::sort_by_relevance(&mut entries, &req.text),
     }
 
-    let mut entries: Vec<_> = entries
-        .into_iter()
-        .filter(&*filter::entries_by_tags_or_search_text(
-            &req.text,
-            &req.tags,
-        ))
-        .collect();
-
-    entries.sort_by_avg_rating(req.entry_ratings);
+    let total_count = entries.len();
 
     let visible_results: Vec<_> = entries
         .iter()
@@ -484,8 +1180,8 @@ pub fn search<D: Db>(db: &D, req: &SearchRequest) -> Result<(Vec<Entry>, Vec<Ent
     let invisible_results = entries
         .into_iter()
         .filter(|x| !x.in_bbox(&req.bbox))
-        .take(MAX_INVISIBLE_RESULTS)
+        .take(req.max_invisible_results)
         .collect();
 
-    Ok((visible_results, invisible_results))
+    Ok((visible_results, invisible_results, total_count))
 }
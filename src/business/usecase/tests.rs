@@ -2,11 +2,16 @@ use super::*;
 use business::builder::EntryBuilder;
 use entities;
 use business;
+use business::filter;
+use business::sort::{Rated, DEFAULT_RATING_HALF_LIFE_DAYS};
+use business::validate::SELF_RATING_POLICY;
+use business::geocode::{Address, Geocoder};
 use uuid::Uuid;
 use test::Bencher;
 
 type RepoResult<T> = result::Result<T, RepoError>;
 
+#[derive(Clone)]
 pub struct MockDb {
     pub entries: Vec<Entry>,
     pub categories: Vec<Category>,
@@ -15,6 +20,7 @@ pub struct MockDb {
     pub ratings: Vec<Rating>,
     pub comments: Vec<Comment>,
     pub bbox_subscriptions: Vec<BboxSubscription>,
+    pub password_resets: Vec<PasswordReset>,
 }
 
 impl MockDb {
@@ -27,6 +33,7 @@ impl MockDb {
             ratings: vec![],
             comments: vec![],
             bbox_subscriptions: vec![],
+            password_resets: vec![],
         }
     }
 }
@@ -85,6 +92,10 @@ impl Db for MockDb {
         Ok(())
     }
 
+    fn create_category(&mut self, c: &Category) -> RepoResult<()> {
+        create(&mut self.categories, c)
+    }
+
     fn create_user(&mut self, u: &User) -> RepoResult<()> {
         create(&mut self.users, u)
     }
@@ -101,10 +112,19 @@ impl Db for MockDb {
         create(&mut self.bbox_subscriptions, s)
     }
 
+    fn create_password_reset(&mut self, r: &PasswordReset) -> RepoResult<()> {
+        create(&mut self.password_resets, r)
+    }
+
     fn get_entry(&self, id: &str) -> RepoResult<Entry> {
         get(&self.entries, id)
     }
 
+    fn get_entry_history(&self, id: &str) -> RepoResult<Vec<Entry>> {
+        // the mock only keeps the current versions
+        Ok(self.entries.iter().filter(|e| e.id == id).cloned().collect())
+    }
+
     fn get_user(&self, username: &str) -> RepoResult<User> {
         let users: &Vec<User> = &self.users
             .iter()
@@ -118,10 +138,27 @@ impl Db for MockDb {
         }
     }
 
+    fn get_entries(&self, ids: &[String]) -> RepoResult<Vec<Entry>> {
+        Ok(self.entries
+            .iter()
+            .filter(|e| ids.contains(&e.id))
+            .cloned()
+            .collect())
+    }
+
     fn all_entries(&self) -> RepoResult<Vec<Entry>> {
         Ok(self.entries.clone())
     }
 
+    fn search_entries(&self, params: &SearchParams) -> RepoResult<Vec<Entry>> {
+        let matches = filter::entries_by_search_params(params, &self.categories);
+        Ok(self.entries
+            .iter()
+            .filter(|e| matches(*e))
+            .cloned()
+            .collect())
+    }
+
     fn get_entries_by_bbox(&self, bbox: &Bbox) -> RepoResult<Vec<Entry>> {
         Ok(self.entries
             .iter()
@@ -146,10 +183,54 @@ impl Db for MockDb {
         Ok(self.comments.clone())
     }
 
+    fn get_password_reset(&self, token: &str) -> RepoResult<PasswordReset> {
+        get(&self.password_resets, token)
+    }
+
+    fn get_rating(&self, id: &str) -> RepoResult<Rating> {
+        get(&self.ratings, id)
+    }
+
+    fn get_ratings(&self, ids: &[String]) -> RepoResult<Vec<Rating>> {
+        Ok(self.ratings
+            .iter()
+            .filter(|r| ids.contains(&r.id))
+            .cloned()
+            .collect())
+    }
+
+    fn get_ratings_by_entry_ids(&self, ids: &[String]) -> RepoResult<Vec<Rating>> {
+        Ok(self.ratings
+            .iter()
+            .filter(|r| ids.contains(&r.entry_id))
+            .cloned()
+            .collect())
+    }
+
+    fn get_comment(&self, id: &str) -> RepoResult<Comment> {
+        get(&self.comments, id)
+    }
+
+    fn get_comments_by_rating_ids(&self, ids: &[String]) -> RepoResult<Vec<Comment>> {
+        Ok(self.comments
+            .iter()
+            .filter(|c| ids.contains(&c.rating_id))
+            .cloned()
+            .collect())
+    }
+
     fn all_users(&self) -> RepoResult<Vec<User>> {
         Ok(self.users.clone())
     }
 
+    fn count_entries(&self) -> RepoResult<usize> {
+        Ok(self.entries.iter().filter(|e| e.archived.is_none()).count())
+    }
+
+    fn count_tags(&self) -> RepoResult<usize> {
+        Ok(self.tags.len())
+    }
+
     fn all_bbox_subscriptions(&self) -> RepoResult<Vec<BboxSubscription>> {
         Ok(self.bbox_subscriptions.clone())
     }
@@ -158,20 +239,25 @@ impl Db for MockDb {
         update(&mut self.entries, e)
     }
 
-    fn confirm_email_address(&mut self, u_id: &str) -> RepoResult<User> {
-        let a: String = self.all_users()?[0].clone().id;
-        let b: String = u_id.to_string();
-        debug!("u.id: {:?}", a);
-        debug!("u_id: {:?}", b);
+    fn update_user(&mut self, u: &User) -> RepoResult<()> {
+        update(&mut self.users, u)
+    }
+
+    fn update_comment(&mut self, c: &Comment) -> RepoResult<()> {
+        update(&mut self.comments, c)
+    }
+
+    fn update_rating(&mut self, r: &Rating) -> RepoResult<()> {
+        update(&mut self.ratings, r)
+    }
 
+    fn confirm_email_address(&mut self, u_id: &str) -> RepoResult<User> {
         let users: Vec<User> = self.all_users()?
             .into_iter()
             .filter(|u| u.id == u_id.to_string())
             .collect();
-        debug!("filtered users: {:?}", users);
         if users.len() > 0 {
             let mut u = users[0].clone();
-            println!("user: {:?}", u);
             u.email_confirmed = true;
             update(&mut self.users, &u)?;
             Ok(u)
@@ -180,6 +266,14 @@ impl Db for MockDb {
         }
     }
 
+    fn delete_entry(&mut self, id: &str) -> RepoResult<()> {
+        if !self.entries.iter().any(|e| e.id == id) {
+            return Err(RepoError::NotFound);
+        }
+        self.entries.retain(|e| e.id != id);
+        Ok(())
+    }
+
     fn delete_bbox_subscription(&mut self, s_id: &str) -> RepoResult<()> {
         self.bbox_subscriptions = self.bbox_subscriptions
             .iter()
@@ -189,6 +283,24 @@ impl Db for MockDb {
         Ok(())
     }
 
+    fn delete_tag(&mut self, id: &str) -> RepoResult<()> {
+        self.tags.retain(|t| t.id != id);
+        Ok(())
+    }
+
+    fn delete_rating(&mut self, id: &str) -> RepoResult<()> {
+        if !self.ratings.iter().any(|r| r.id == id) {
+            return Err(RepoError::NotFound);
+        }
+        self.ratings.retain(|r| r.id != id);
+        Ok(())
+    }
+
+    fn delete_comment(&mut self, id: &str) -> RepoResult<()> {
+        self.comments.retain(|c| c.id != id);
+        Ok(())
+    }
+
     fn delete_user(&mut self, u_id: &str) -> RepoResult<()> {
         self.users = self.users
             .clone()
@@ -197,6 +309,11 @@ impl Db for MockDb {
             .collect();
         Ok(())
     }
+
+    fn delete_password_reset(&mut self, token: &str) -> RepoResult<()> {
+        self.password_resets.retain(|r| r.token != token);
+        Ok(())
+    }
     fn import_multiple_entries(&mut self, entries: &[Entry]) -> RepoResult<()> {
         for e in entries.iter() {
             self.create_entry(e)?;
@@ -206,30 +323,48 @@ impl Db for MockDb {
         }
         Ok(())
     }
+
+    fn transaction<T, E, F>(&mut self, f: F) -> result::Result<T, E>
+    where
+        E: From<RepoError>,
+        F: FnOnce(&mut Self) -> result::Result<T, E>,
+    {
+        let snapshot = self.clone();
+        let res = f(self);
+        if res.is_err() {
+            *self = snapshot;
+        }
+        res
+    }
 }
 
 #[test]
 fn create_new_valid_entry() {
     #[cfg_attr(rustfmt, rustfmt_skip)]
     let x = NewEntry {
-        title       : "foo".into(),
-        description : "bar".into(),
-        lat         : 0.0,
-        lng         : 0.0,
-        street      : None,
-        zip         : None,
-        city        : None,
-        country     : None,
-        email       : None,
-        telephone   : None,
-        homepage    : None,
-        categories  : vec![],
-        tags        : vec![],
-        license     : "CC0-1.0".into()
+        title         : "foo".into(),
+        description   : "bar".into(),
+        lat           : Some(0.0),
+        lng           : Some(0.0),
+        street        : None,
+        zip           : None,
+        city          : None,
+        country       : None,
+        email         : None,
+        telephone     : None,
+        homepage      : None,
+        opening_hours : None,
+        image_urls    : vec![],
+        categories    : vec![],
+        tags          : vec![],
+        license       : "CC0-1.0".into(),
+        id            : None,
+        upsert        : false,
+        version       : 0
     };
     let mut mock_db = MockDb::new();
     let now = Utc::now();
-    let id = create_new_entry(&mut mock_db, x).unwrap();
+    let id = create_new_entry(&mut mock_db, None, x, None).unwrap();
     assert!(Uuid::parse_str(&id).is_ok());
     assert_eq!(mock_db.entries.len(), 1);
     let x = &mock_db.entries[0];
@@ -241,57 +376,507 @@ fn create_new_valid_entry() {
     assert_eq!(x.id, id);
 }
 
+fn new_entry_with_id(id: &str, title: &str, version: u64) -> NewEntry {
+    NewEntry {
+        title: title.into(),
+        description: "bar".into(),
+        lat: Some(0.0),
+        lng: Some(0.0),
+        street: None,
+        zip: None,
+        city: None,
+        country: None,
+        email: None,
+        telephone: None,
+        homepage: None,
+        opening_hours: None,
+        image_urls: vec![],
+        categories: vec![],
+        tags: vec![],
+        license: "CC0-1.0".into(),
+        id: Some(id.into()),
+        upsert: false,
+        version,
+    }
+}
+
+#[test]
+fn create_new_entry_with_given_id() {
+    let mut mock_db = MockDb::new();
+    let e = new_entry_with_id("foo-1", "foo", 0);
+    assert_eq!(create_new_entry(&mut mock_db, None, e, None).unwrap(), "foo-1");
+    assert_eq!(mock_db.entries[0].id, "foo-1");
+
+    let e = new_entry_with_id("foo-1", "bar", 0);
+    match create_new_entry(&mut mock_db, None, e, None) {
+        Err(Error::Parameter(ParameterError::EntryExists)) => {}
+        x => panic!("unexpected result: {:?}", x),
+    }
+    assert_eq!(mock_db.entries.len(), 1);
+    assert_eq!(mock_db.entries[0].title, "foo");
+
+    let e = new_entry_with_id("a,b", "foo", 0);
+    match create_new_entry(&mut mock_db, None, e, None) {
+        Err(Error::Parameter(ParameterError::EntryId)) => {}
+        x => panic!("unexpected result: {:?}", x),
+    }
+    assert_eq!(mock_db.entries.len(), 1);
+}
+
+#[test]
+fn upsert_entry_with_given_id() {
+    let mut mock_db = MockDb::new();
+    let e = new_entry_with_id("foo-1", "foo", 0);
+    match upsert_entry(&mut mock_db, None, e, None).unwrap() {
+        Upserted::Created(id) => assert_eq!(id, "foo-1"),
+        x => panic!("unexpected result: {:?}", x),
+    }
+
+    let e = new_entry_with_id("foo-1", "bar", 1);
+    match upsert_entry(&mut mock_db, None, e, None).unwrap() {
+        Upserted::Updated(update, old) => {
+            assert_eq!(update.id, "foo-1");
+            assert_eq!(old.lat, 0.0);
+        }
+        x => panic!("unexpected result: {:?}", x),
+    }
+    assert_eq!(mock_db.entries.len(), 1);
+    let x = &mock_db.entries[0];
+    assert_eq!(x.title, "bar");
+    assert_eq!(x.version, 1);
+    assert_eq!(x.license, Some("CC0-1.0".into()));
+
+    // The client has to know the current version
+    let e = new_entry_with_id("foo-1", "baz", 1);
+    match upsert_entry(&mut mock_db, None, e, None) {
+        Err(Error::Repo(RepoError::InvalidVersion(1))) => {}
+        x => panic!("unexpected result: {:?}", x),
+    }
+    assert_eq!(mock_db.entries[0].title, "bar");
+}
+
+#[test]
+fn sanitize_html_of_new_entry() {
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    let x = NewEntry {
+        title         : "<b>foo</b>".into(),
+        description   : "bar<script>alert(1)</script>\nbaz & more".into(),
+        lat           : Some(0.0),
+        lng           : Some(0.0),
+        street        : None,
+        zip           : None,
+        city          : None,
+        country       : None,
+        email         : None,
+        telephone     : None,
+        homepage      : None,
+        opening_hours : None,
+        image_urls    : vec![],
+        categories    : vec![],
+        tags          : vec![],
+        license       : "CC0-1.0".into(),
+        id            : None,
+        upsert        : false,
+        version       : 0
+    };
+    let mut mock_db = MockDb::new();
+    create_new_entry(&mut mock_db, None, x, None).unwrap();
+    let x = &mock_db.entries[0];
+    assert_eq!(x.title, "foo");
+    assert_eq!(x.description, "bar\nbaz & more");
+}
+
+struct FakeGeocoder;
+
+impl Geocoder for FakeGeocoder {
+    fn reverse(&self, lat: f64, lng: f64) -> Option<Address> {
+        if lat == 48.0 && lng == 9.0 {
+            Some(Address {
+                street: Some("Marktplatz 1".into()),
+                zip: Some("70173".into()),
+                city: Some("Stuttgart".into()),
+                country: Some("Germany".into()),
+            })
+        } else {
+            None
+        }
+    }
+    fn forward(&self, address: &Address) -> Option<Coordinate> {
+        if address.city == Some("Stuttgart".into()) {
+            Some(Coordinate { lat: 48.0, lng: 9.0 })
+        } else {
+            None
+        }
+    }
+}
+
+#[test]
+fn create_new_entry_with_geocoded_address() {
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    let x = NewEntry {
+        title         : "foo".into(),
+        description   : "bar".into(),
+        lat           : Some(48.0),
+        lng           : Some(9.0),
+        street        : None,
+        zip           : None,
+        city          : Some("Bad Cannstatt".into()),
+        country       : None,
+        email         : None,
+        telephone     : None,
+        homepage      : None,
+        opening_hours : None,
+        image_urls    : vec![],
+        categories    : vec![],
+        tags          : vec![],
+        license       : "CC0-1.0".into(),
+        id            : None,
+        upsert        : false,
+        version       : 0
+    };
+    let mut mock_db = MockDb::new();
+    create_new_entry(&mut mock_db, Some(&FakeGeocoder), x.clone(), None).unwrap();
+    let e = &mock_db.entries[0];
+    assert_eq!(e.street.as_ref().unwrap(), "Marktplatz 1");
+    assert_eq!(e.zip.as_ref().unwrap(), "70173");
+    assert_eq!(e.city.as_ref().unwrap(), "Bad Cannstatt");
+    assert_eq!(e.country.as_ref().unwrap(), "Germany");
+
+    // entries without an address can still be created
+    let mut y = x;
+    y.lat = Some(0.0);
+    y.city = None;
+    create_new_entry(&mut mock_db, Some(&FakeGeocoder), y, None).unwrap();
+    assert_eq!(mock_db.entries.len(), 2);
+    assert!(mock_db.entries[1].city.is_none());
+}
+
+#[test]
+fn create_new_entry_located_by_its_address() {
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    let x = NewEntry {
+        title         : "foo".into(),
+        description   : "bar".into(),
+        lat           : None,
+        lng           : None,
+        street        : Some("Marktplatz 1".into()),
+        zip           : Some("70173".into()),
+        city          : Some("Stuttgart".into()),
+        country       : Some("Germany".into()),
+        email         : None,
+        telephone     : None,
+        homepage      : None,
+        opening_hours : None,
+        image_urls    : vec![],
+        categories    : vec![],
+        tags          : vec![],
+        license       : "CC0-1.0".into(),
+        id            : None,
+        upsert        : false,
+        version       : 0
+    };
+    let mut mock_db = MockDb::new();
+    create_new_entry(&mut mock_db, Some(&FakeGeocoder), x.clone(), None).unwrap();
+    assert_eq!(mock_db.entries[0].lat, 48.0);
+    assert_eq!(mock_db.entries[0].lng, 9.0);
+
+    // explicit coordinates are kept
+    let mut y = x.clone();
+    y.lat = Some(1.0);
+    y.lng = Some(2.0);
+    create_new_entry(&mut mock_db, Some(&FakeGeocoder), y, None).unwrap();
+    assert_eq!(mock_db.entries[1].lat, 1.0);
+    assert_eq!(mock_db.entries[1].lng, 2.0);
+
+    let mut unknown = x.clone();
+    unknown.city = Some("Atlantis".into());
+    match create_new_entry(&mut mock_db, Some(&FakeGeocoder), unknown, None).err().unwrap() {
+        Error::Parameter(ParameterError::UnknownAddress) => {}
+        _ => panic!("invalid error type"),
+    }
+    match create_new_entry(&mut mock_db, None, x, None).err().unwrap() {
+        Error::Parameter(ParameterError::UnknownAddress) => {}
+        _ => panic!("invalid error type"),
+    }
+    assert_eq!(mock_db.entries.len(), 2);
+}
+
 #[test]
 fn create_entry_with_invalid_email() {
     #[cfg_attr(rustfmt, rustfmt_skip)]
     let x = NewEntry {
-        title       : "foo".into(),
-        description : "bar".into(),
-        lat         : 0.0,
-        lng         : 0.0,
-        street      : None,
-        zip         : None,
-        city        : None,
-        country     : None,
-        email       : Some("fooo-not-ok".into()),
-        telephone   : None,
-        homepage    : None,
-        categories  : vec![],
-        tags        : vec![],
-        license     : "CC0-1.0".into()
+        title         : "foo".into(),
+        description   : "bar".into(),
+        lat           : Some(0.0),
+        lng           : Some(0.0),
+        street        : None,
+        zip           : None,
+        city          : None,
+        country       : None,
+        email         : Some("fooo-not-ok".into()),
+        telephone     : None,
+        homepage      : None,
+        opening_hours : None,
+        image_urls    : vec![],
+        categories    : vec![],
+        tags          : vec![],
+        license       : "CC0-1.0".into(),
+        id            : None,
+        upsert        : false,
+        version       : 0
+    };
+    let mut mock_db: MockDb = MockDb::new();
+    assert!(create_new_entry(&mut mock_db, None, x, None).is_err());
+}
+
+#[test]
+fn create_multiple_entries_with_one_invalid() {
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    let valid = NewEntry {
+        title         : "foo".into(),
+        description   : "bar".into(),
+        lat           : Some(0.0),
+        lng           : Some(0.0),
+        street        : None,
+        zip           : None,
+        city          : None,
+        country       : None,
+        email         : None,
+        telephone     : None,
+        homepage      : None,
+        opening_hours : None,
+        image_urls    : vec![],
+        categories    : vec![],
+        tags          : vec![],
+        license       : "CC0-1.0".into(),
+        id            : None,
+        upsert        : false,
+        version       : 0
+    };
+    let mut invalid = valid.clone();
+    invalid.email = Some("fooo-not-ok".into());
+    let mut mock_db = MockDb::new();
+    let results = create_new_entries(
+        &mut mock_db,
+        None,
+        vec![valid.clone(), invalid, valid],
+        Some("alice"),
+    );
+    assert_eq!(results.len(), 3);
+    assert!(results[0].is_ok());
+    match results[1] {
+        Err(Error::Parameter(ParameterError::Email)) => {}
+        _ => panic!("invalid error type"),
+    }
+    assert!(results[2].is_ok());
+    assert_eq!(mock_db.entries.len(), 2);
+    assert_eq!(&mock_db.entries[0].id, results[0].as_ref().unwrap());
+    assert_eq!(&mock_db.entries[1].id, results[2].as_ref().unwrap());
+}
+
+#[test]
+fn create_entry_with_invalid_telephone() {
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    let x = NewEntry {
+        title         : "foo".into(),
+        description   : "bar".into(),
+        lat           : Some(0.0),
+        lng           : Some(0.0),
+        street        : None,
+        zip           : None,
+        city          : None,
+        country       : None,
+        email         : None,
+        telephone     : Some("call me maybe".into()),
+        homepage      : None,
+        opening_hours : None,
+        image_urls    : vec![],
+        categories    : vec![],
+        tags          : vec![],
+        license       : "CC0-1.0".into(),
+        id            : None,
+        upsert        : false,
+        version       : 0
     };
     let mut mock_db: MockDb = MockDb::new();
-    assert!(create_new_entry(&mut mock_db, x).is_err());
+    match create_new_entry(&mut mock_db, None, x, None).err().unwrap() {
+        Error::Parameter(ParameterError::Telephone) => {}
+        _ => panic!("invalid error type"),
+    }
+    assert!(mock_db.entries.is_empty());
+}
+
+#[test]
+fn create_entry_with_blank_title() {
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    let x = NewEntry {
+        title         : "   ".into(),
+        description   : "bar".into(),
+        lat           : Some(0.0),
+        lng           : Some(0.0),
+        street        : None,
+        zip           : None,
+        city          : None,
+        country       : None,
+        email         : None,
+        telephone     : None,
+        homepage      : None,
+        opening_hours : None,
+        image_urls    : vec![],
+        categories    : vec![],
+        tags          : vec![],
+        license       : "CC0-1.0".into(),
+        id            : None,
+        upsert        : false,
+        version       : 0
+    };
+    let mut mock_db: MockDb = MockDb::new();
+    match create_new_entry(&mut mock_db, None, x, None).err().unwrap() {
+        Error::Parameter(ParameterError::EmptyTitle) => {}
+        _ => panic!("invalid error type"),
+    }
+}
+
+#[test]
+fn create_entry_with_invalid_coordinates() {
+    use std::f64::{INFINITY, NAN};
+    for &(lat, lng) in &[(500.0, 0.0), (0.0, -9999.0), (NAN, 0.0), (0.0, INFINITY)] {
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let x = NewEntry {
+            title         : "foo".into(),
+            description   : "bar".into(),
+            lat           : Some(lat),
+            lng           : Some(lng),
+            street        : None,
+            zip           : None,
+            city          : None,
+            country       : None,
+            email         : None,
+            telephone     : None,
+            homepage      : None,
+            opening_hours : None,
+            image_urls    : vec![],
+            categories    : vec![],
+            tags          : vec![],
+            license       : "CC0-1.0".into(),
+            id            : None,
+            upsert        : false,
+            version       : 0
+        };
+        let mut mock_db: MockDb = MockDb::new();
+        match create_new_entry(&mut mock_db, None, x, None).err().unwrap() {
+            Error::Parameter(ParameterError::Coordinate) => {}
+            _ => panic!("invalid error type"),
+        }
+    }
+}
+
+#[test]
+fn update_entry_with_invalid_homepage() {
+    let id = Uuid::new_v4().simple().to_string();
+    let old = Entry::build().id(&id).version(1).finish();
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    let new = UpdateEntry {
+        id            : id.clone(),
+        osm_node      : None,
+        version       : 2,
+        title         : "foo".into(),
+        description   : "bar".into(),
+        lat           : 0.0,
+        lng           : 0.0,
+        street        : None,
+        zip           : None,
+        city          : None,
+        country       : None,
+        email         : None,
+        telephone     : None,
+        homepage      : Some("javascript:alert(1)".into()),
+        opening_hours : None,
+        image_urls    : vec![],
+        categories    : vec![],
+        tags          : vec![],
+    };
+    let mut mock_db = MockDb::new();
+    mock_db.entries = vec![old];
+    match update_entry(&mut mock_db, new).err().unwrap() {
+        Error::Parameter(ParameterError::Url) => {}
+        _ => panic!("invalid error type"),
+    }
+    assert_eq!(mock_db.entries[0].version, 1);
+}
+
+#[test]
+fn patch_entry_homepage() {
+    let mut old = Entry::build()
+        .id("foo")
+        .version(1)
+        .title("foo")
+        .description("bar")
+        .tags(vec!["bio"])
+        .finish();
+    old.street = Some("street".into());
+    let mut mock_db = MockDb::new();
+    mock_db.entries = vec![old.clone()];
+    let patch = PatchEntry {
+        version: 2,
+        homepage: Some("https://foo.org".into()),
+        ..Default::default()
+    };
+    let e = patch_entry(&mut mock_db, "foo", patch).unwrap();
+    assert_eq!(e.homepage, Some("https://foo.org".into()));
+    let x = &mock_db.entries[0];
+    assert_eq!(x.version, 2);
+    assert_eq!(x.homepage, Some("https://foo.org".into()));
+    assert_eq!(x.title, old.title);
+    assert_eq!(x.description, old.description);
+    assert_eq!(x.street, old.street);
+    assert_eq!(x.tags, old.tags);
+    assert_eq!(x.lat, old.lat);
+
+    // the version check still applies
+    let patch = PatchEntry {
+        version: 2,
+        title: Some("bar".into()),
+        ..Default::default()
+    };
+    match patch_entry(&mut mock_db, "foo", patch) {
+        Err(Error::Repo(RepoError::InvalidVersion(2))) => {}
+        x => panic!("unexpected result: {:?}", x),
+    }
+    assert_eq!(mock_db.entries[0].title, "foo");
 }
 
 #[test]
 fn update_valid_entry() {
     let id = Uuid::new_v4().simple().to_string();
-    let old = Entry::build()
+    let mut old = Entry::build()
         .id(&id)
         .version(1)
         .title("foo")
         .description("bar")
         .finish();
+    old.created = 1_500_000_000;
 
     #[cfg_attr(rustfmt, rustfmt_skip)]
     let new = UpdateEntry {
-        id          : id.clone(),
-        osm_node    :  None,
-        version     : 2,
-        title       : "foo".into(),
-        description : "bar".into(),
-        lat         : 0.0,
-        lng         : 0.0,
-        street      : Some("street".into()),
-        zip         : None,
-        city        : None,
-        country     : None,
-        email       : None,
-        telephone   : None,
-        homepage    : None,
-        categories  : vec![],
-        tags        : vec![],
+        id            : id.clone(),
+        osm_node      :  None,
+        version       : 2,
+        title         : "foo".into(),
+        description   : "bar".into(),
+        lat           : 0.0,
+        lng           : 0.0,
+        street        : Some("street".into()),
+        zip           : None,
+        city          : None,
+        country       : None,
+        email         : None,
+        telephone     : None,
+        homepage      : None,
+        opening_hours : None,
+        image_urls    : vec![],
+        categories    : vec![],
+        tags          : vec![],
     };
     let mut mock_db = MockDb::new();
     mock_db.entries = vec![old];
@@ -302,7 +887,8 @@ fn update_valid_entry() {
     assert_eq!(x.street, Some("street".into()));
     assert_eq!(x.description, "bar");
     assert_eq!(x.version, 2);
-    assert!(x.created as i64 >= now.timestamp());
+    assert_eq!(x.created, 1_500_000_000);
+    assert!(x.updated.unwrap() as i64 >= now.timestamp());
     assert!(Uuid::parse_str(&x.id).is_ok());
 }
 
@@ -311,43 +897,50 @@ fn update_entry_with_invalid_version() {
     let id = Uuid::new_v4().simple().to_string();
     #[cfg_attr(rustfmt, rustfmt_skip)]
     let old = Entry {
-        id          : id.clone(),
-        osm_node    :  None,
-        version     : 3,
-        created     : 0,
-        title       : "foo".into(),
-        description : "bar".into(),
-        lat         : 0.0,
-        lng         : 0.0,
-        street      : None,
-        zip         : None,
-        city        : None,
-        country     : None,
-        email       : None,
-        telephone   : None,
-        homepage    : None,
-        categories  : vec![],
-        tags        : vec![],
-        license     : None
+        id            : id.clone(),
+        osm_node      :  None,
+        version       : 3,
+        created       : 0,
+        updated       : None,
+        archived      : None,
+        title         : "foo".into(),
+        description   : "bar".into(),
+        lat           : 0.0,
+        lng           : 0.0,
+        street        : None,
+        zip           : None,
+        city          : None,
+        country       : None,
+        email         : None,
+        telephone     : None,
+        homepage      : None,
+        opening_hours : None,
+        image_urls    : vec![],
+        categories    : vec![],
+        tags          : vec![],
+        license       : None,
+        created_by    : None
     };
     #[cfg_attr(rustfmt, rustfmt_skip)]
     let new = UpdateEntry {
-        id          : id.clone(),
-        osm_node    :  None,
-        version     : 3,
-        title       : "foo".into(),
-        description : "bar".into(),
-        lat         : 0.0,
-        lng         : 0.0,
-        street      : Some("street".into()),
-        zip         : None,
-        city        : None,
-        country     : None,
-        email       : None,
-        telephone   : None,
-        homepage    : None,
-        categories  : vec![],
-        tags        : vec![],
+        id            : id.clone(),
+        osm_node      :  None,
+        version       : 3,
+        title         : "foo".into(),
+        description   : "bar".into(),
+        lat           : 0.0,
+        lng           : 0.0,
+        street        : Some("street".into()),
+        zip           : None,
+        city          : None,
+        country       : None,
+        email         : None,
+        telephone     : None,
+        homepage      : None,
+        opening_hours : None,
+        image_urls    : vec![],
+        categories    : vec![],
+        tags          : vec![],
     };
     let mut mock_db = MockDb::new();
     mock_db.entries = vec![old];
@@ -355,7 +948,7 @@ fn update_entry_with_invalid_version() {
     assert!(result.is_err());
     match result.err().unwrap() {
         Error::Repo(err) => match err {
-            RepoError::InvalidVersion => {}
+            RepoError::InvalidVersion(v) => assert_eq!(v, 3),
             _ => {
                 panic!("invalid error type");
             }
@@ -372,22 +965,24 @@ fn update_non_existing_entry() {
     let id = Uuid::new_v4().simple().to_string();
     #[cfg_attr(rustfmt, rustfmt_skip)]
     let new = UpdateEntry {
-        id          : id.clone(),
-        osm_node    :  None,
-        version     : 4,
-        title       : "foo".into(),
-        description : "bar".into(),
-        lat         : 0.0,
-        lng         : 0.0,
-        street      : Some("street".into()),
-        zip         : None,
-        city        : None,
-        country     : None,
-        email       : None,
-        telephone   : None,
-        homepage    : None,
-        categories  : vec![],
-        tags        : vec![],
+        id            : id.clone(),
+        osm_node      :  None,
+        version       : 4,
+        title         : "foo".into(),
+        description   : "bar".into(),
+        lat           : 0.0,
+        lng           : 0.0,
+        street        : Some("street".into()),
+        zip           : None,
+        city          : None,
+        country       : None,
+        email         : None,
+        telephone     : None,
+        homepage      : None,
+        opening_hours : None,
+        image_urls    : vec![],
+        categories    : vec![],
+        tags          : vec![],
     };
     let mut mock_db = MockDb::new();
     mock_db.entries = vec![];
@@ -407,31 +1002,197 @@ fn update_non_existing_entry() {
     assert_eq!(mock_db.entries.len(), 0);
 }
 
+#[test]
+fn delete_existing_entry() {
+    let mut mock_db = MockDb::new();
+    mock_db.entries = vec![
+        Entry::build().id("a").finish(),
+        Entry::build().id("b").finish(),
+    ];
+    assert!(delete_entry(&mut mock_db, "a").is_ok());
+    assert_eq!(mock_db.entries.len(), 1);
+    assert_eq!(mock_db.entries[0].id, "b");
+}
+
+#[test]
+fn delete_entry_with_its_ratings_and_comments() {
+    let mut db = MockDb::new();
+    db.entries = vec![
+        Entry::build().id("a").finish(),
+        Entry::build().id("b").finish(),
+    ];
+    let rating = |id: &str, entry_id: &str| Rating {
+        id: id.into(),
+        entry_id: entry_id.into(),
+        created: 0,
+        title: "title".into(),
+        value: 1,
+        context: RatingContext::Fairness,
+        source: None,
+        created_by: None,
+    };
+    let comment = |id: &str, rating_id: &str| Comment {
+        id: id.into(),
+        created: 0,
+        text: "text".into(),
+        rating_id: rating_id.into(),
+    };
+    db.ratings = vec![rating("1", "a"), rating("2", "b")];
+    db.comments = vec![comment("x", "1"), comment("y", "2")];
+    assert!(delete_entry(&mut db, "a").is_ok());
+    assert_eq!(db.entries.len(), 1);
+    assert_eq!(db.ratings.len(), 1);
+    assert_eq!(db.ratings[0].id, "2");
+    assert_eq!(db.comments.len(), 1);
+    assert_eq!(db.comments[0].id, "y");
+}
+
+#[test]
+fn delete_non_existing_entry() {
+    let mut mock_db = MockDb::new();
+    mock_db.entries = vec![Entry::build().id("a").finish()];
+    match delete_entry(&mut mock_db, "b").err().unwrap() {
+        Error::Repo(RepoError::NotFound) => {}
+        _ => panic!("invalid error type"),
+    }
+    assert_eq!(mock_db.entries.len(), 1);
+}
+
 #[test]
 fn add_new_valid_entry_with_tags() {
     #[cfg_attr(rustfmt, rustfmt_skip)]
     let x = NewEntry {
-        title       : "foo".into(),
-        description : "bar".into(),
-        lat         : 0.0,
-        lng         : 0.0,
-        street      : None,
-        zip         : None,
-        city        : None,
-        country     : None,
-        email       : None,
-        telephone   : None,
-        homepage    : None,
-        categories  : vec![],
-        tags        : vec!["foo".into(),"bar".into()],
-        license     : "CC0-1.0".into()
+        title         : "foo".into(),
+        description   : "bar".into(),
+        lat           : Some(0.0),
+        lng           : Some(0.0),
+        street        : None,
+        zip           : None,
+        city          : None,
+        country       : None,
+        email         : None,
+        telephone     : None,
+        homepage      : None,
+        opening_hours : None,
+        image_urls    : vec![],
+        categories    : vec![],
+        tags          : vec!["foo".into(),"bar".into()],
+        license       : "CC0-1.0".into(),
+        id            : None,
+        upsert        : false,
+        version       : 0
     };
     let mut mock_db = MockDb::new();
-    create_new_entry(&mut mock_db, x).unwrap();
+    create_new_entry(&mut mock_db, None, x, None).unwrap();
     assert_eq!(mock_db.tags.len(), 2);
     assert_eq!(mock_db.entries.len(), 1);
 }
 
+#[test]
+fn create_new_entry_with_normalized_tags() {
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    let x = NewEntry {
+        title         : "foo".into(),
+        description   : "bar".into(),
+        lat           : Some(0.0),
+        lng           : Some(0.0),
+        street        : None,
+        zip           : None,
+        city          : None,
+        country       : None,
+        email         : None,
+        telephone     : None,
+        homepage      : None,
+        opening_hours : None,
+        image_urls    : vec![],
+        categories    : vec![],
+        tags          : vec!["  Co Working ".into(), "co-working".into(), "#Bio".into()],
+        license       : "CC0-1.0".into(),
+        id            : None,
+        upsert        : false,
+        version       : 0
+    };
+    let mut mock_db = MockDb::new();
+    let id = create_new_entry(&mut mock_db, None, x.clone(), None).unwrap();
+    assert_eq!(mock_db.get_entry(&id).unwrap().tags, vec!["co-working", "bio"]);
+    assert_eq!(mock_db.tags.len(), 2);
+
+    let mut y = x;
+    y.tags = vec!["bio".into(), " ! ".into()];
+    match create_new_entry(&mut mock_db, None, y, None).err().unwrap() {
+        Error::Parameter(ParameterError::Tag) => {}
+        _ => panic!("invalid error type"),
+    }
+    assert_eq!(mock_db.entries.len(), 1);
+}
+
+#[test]
+fn prune_unknown_categories() {
+    let category = |id: &str| Category {
+        id: id.into(),
+        created: 0,
+        version: 0,
+        name: id.into(),
+    };
+    let mut entries = vec![
+        Entry::build().id("a").categories(vec!["x", "deleted", "y"]).finish(),
+        Entry::build().id("b").categories(vec!["deleted"]).finish(),
+    ];
+    prune_categories(&mut entries, &[category("x"), category("y")]);
+    assert_eq!(entries[0].categories, vec!["x", "y"]);
+    assert!(entries[1].categories.is_empty());
+}
+
+#[test]
+fn get_missing_entries() {
+    let mut db = MockDb::new();
+    db.entries = vec![Entry::build().id("a").finish()];
+    match get_entries(&db, &["b".to_string()]) {
+        Err(Error::Repo(RepoError::NotFound)) => {}
+        x => panic!("unexpected result: {:?}", x),
+    }
+    let entries = get_entries(&db, &["a".to_string(), "b".to_string()]).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].id, "a");
+    assert!(get_entries(&db, &["b".to_string(), "c".to_string()]).unwrap().is_empty());
+}
+
+#[test]
+fn get_entries_created_by_user() {
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    let x = NewEntry {
+        title         : "foo".into(),
+        description   : "bar".into(),
+        lat           : Some(0.0),
+        lng           : Some(0.0),
+        street        : None,
+        zip           : None,
+        city          : None,
+        country       : None,
+        email         : None,
+        telephone     : None,
+        homepage      : None,
+        opening_hours : None,
+        image_urls    : vec![],
+        categories    : vec![],
+        tags          : vec![],
+        license       : "CC0-1.0".into(),
+        id            : None,
+        upsert        : false,
+        version       : 0
+    };
+    let mut mock_db = MockDb::new();
+    let id = create_new_entry(&mut mock_db, None, x.clone(), Some("alice")).unwrap();
+    create_new_entry(&mut mock_db, None, x.clone(), Some("bob")).unwrap();
+    create_new_entry(&mut mock_db, None, x, None).unwrap();
+    assert_eq!(mock_db.entries.len(), 3);
+    let entries = get_entries_by_user(&mock_db, "alice").unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].id, id);
+    assert_eq!(entries[0].created_by, Some("alice".into()));
+    assert!(get_entries_by_user(&mock_db, "carol").unwrap().is_empty());
+}
+
 #[test]
 fn update_valid_entry_with_tags() {
     let id = Uuid::new_v4().simple().to_string();
@@ -442,22 +1203,24 @@ fn update_valid_entry_with_tags() {
         .finish();
     #[cfg_attr(rustfmt, rustfmt_skip)]
     let new = UpdateEntry {
-        id          : id.clone(),
-        osm_node    :  None,
-        version     : 2,
-        title       : "foo".into(),
-        description : "bar".into(),
-        lat         : 0.0,
-        lng         : 0.0,
-        street      : Some("street".into()),
-        zip         : None,
-        city        : None,
-        country     : None,
-        email       : None,
-        telephone   : None,
-        homepage    : None,
-        categories  : vec![],
-        tags        : vec!["vegan".into()],
+        id            : id.clone(),
+        osm_node      :  None,
+        version       : 2,
+        title         : "foo".into(),
+        description   : "bar".into(),
+        lat           : 0.0,
+        lng           : 0.0,
+        street        : Some("street".into()),
+        zip           : None,
+        city          : None,
+        country       : None,
+        email         : None,
+        telephone     : None,
+        homepage      : None,
+        opening_hours : None,
+        image_urls    : vec![],
+        categories    : vec![],
+        tags          : vec!["vegan".into()],
     };
     let mut mock_db = MockDb::new();
     mock_db.entries = vec![old];
@@ -468,6 +1231,185 @@ fn update_valid_entry_with_tags() {
     assert_eq!(mock_db.tags.len(), 3);
 }
 
+#[test]
+fn create_categories_with_unique_names() {
+    let mut mock_db = MockDb::new();
+    let id = create_category(&mut mock_db, " Initiative ").unwrap();
+    assert_eq!(mock_db.categories.len(), 1);
+    assert_eq!(mock_db.categories[0].id, id);
+    assert_eq!(mock_db.categories[0].name, "Initiative");
+    assert_eq!(mock_db.categories[0].version, 0);
+    match create_category(&mut mock_db, "initiative").err().unwrap() {
+        Error::Parameter(ParameterError::CategoryExists) => {}
+        _ => panic!("invalid error type"),
+    }
+    match create_category(&mut mock_db, "  ").err().unwrap() {
+        Error::Parameter(ParameterError::EmptyCategoryName) => {}
+        _ => panic!("invalid error type"),
+    }
+    assert!(create_category(&mut mock_db, "Company").is_ok());
+    assert_eq!(mock_db.categories.len(), 2);
+}
+
+#[test]
+fn merge_tags() {
+    let mut mock_db = MockDb::new();
+    mock_db.entries = vec![
+        Entry::build().id("a").tags(vec!["co-working", "bio"]).finish(),
+        Entry::build().id("b").tags(vec!["co-working", "coworking"]).finish(),
+        Entry::build().id("c").tags(vec!["fair"]).finish(),
+    ];
+    mock_db.tags = vec![
+        Tag { id: "co-working".into() },
+        Tag { id: "bio".into() },
+        Tag { id: "fair".into() },
+    ];
+    assert!(merge_tags(&mut mock_db, "co-working", "coworking").is_ok());
+    let a = mock_db.get_entry("a").unwrap();
+    assert_eq!(a.tags, vec!["coworking", "bio"]);
+    assert_eq!(a.version, 1);
+    assert_eq!(mock_db.get_entry("b").unwrap().tags, vec!["coworking"]);
+    assert_eq!(mock_db.get_entry("c").unwrap().version, 0);
+    let mut tags: Vec<_> = mock_db.tags.iter().map(|t| t.id.clone()).collect();
+    tags.sort();
+    assert_eq!(tags, vec!["bio", "coworking", "fair"]);
+}
+
+#[test]
+fn count_tag_usage() {
+    let mut mock_db = MockDb::new();
+    mock_db.entries = vec![
+        Entry::build().id("a").tags(vec!["bio", "fair"]).finish(),
+        Entry::build().id("b").tags(vec!["bio"]).finish(),
+        Entry::build().id("c").tags(vec!["fair", "bio"]).finish(),
+        Entry::build().id("d").tags(vec!["fair"]).finish(),
+    ];
+    mock_db.tags = vec![
+        Tag { id: "bio".into() },
+        Tag { id: "fair".into() },
+        Tag { id: "unused".into() },
+    ];
+    let counts = get_tag_usage_counts(&mock_db).unwrap();
+    assert_eq!(
+        counts,
+        vec![
+            ("bio".to_string(), 3),
+            ("fair".to_string(), 3),
+            ("unused".to_string(), 0),
+        ]
+    );
+}
+
+#[test]
+fn count_comments_of_the_ratings_of_entries() {
+    let mut db = MockDb::new();
+    let rating = |id: &str, entry_id: &str| Rating {
+        id: id.into(),
+        entry_id: entry_id.into(),
+        created: 0,
+        title: "title".into(),
+        value: 1,
+        context: RatingContext::Fairness,
+        source: None,
+        created_by: None,
+    };
+    let comment = |id: &str, rating_id: &str| Comment {
+        id: id.into(),
+        created: 0,
+        text: "text".into(),
+        rating_id: rating_id.into(),
+    };
+    db.ratings = vec![rating("1", "a"), rating("2", "a"), rating("3", "b")];
+    db.comments = vec![
+        comment("x", "1"),
+        comment("y", "2"),
+        comment("z", "2"),
+        comment("w", "3"),
+    ];
+    let ids = vec!["a".to_string(), "c".to_string()];
+    let ratings = get_ratings_by_entry_ids(&db, &ids).unwrap();
+    let counts = count_comments_by_entry_ids(&db, &ratings).unwrap();
+    assert_eq!(counts.len(), 2);
+    assert_eq!(counts["a"], 3);
+    assert_eq!(counts["c"], 0);
+}
+
+#[test]
+fn average_ratings_of_mixed_contexts() {
+    let mut db = MockDb::new();
+    db.entries = vec![
+        Entry::build().id("a").finish(),
+        Entry::build().id("b").finish(),
+    ];
+    let rating = |id: &str, entry_id: &str, value, context| Rating {
+        id: id.into(),
+        entry_id: entry_id.into(),
+        created: 0,
+        title: "title".into(),
+        value,
+        context,
+        source: None,
+        created_by: None,
+    };
+    db.ratings = vec![
+        rating("1", "a", 2, RatingContext::Fairness),
+        rating("2", "a", 1, RatingContext::Fairness),
+        rating("3", "a", -1, RatingContext::Humanity),
+        rating("4", "a", 0, RatingContext::Diversity),
+        rating("5", "a", 2, RatingContext::Diversity),
+        rating("6", "b", -1, RatingContext::Renewable),
+    ];
+    let avg = average_ratings_by_context(&db, "a").unwrap();
+    assert_eq!(avg.len(), 3);
+    assert_eq!(avg[&RatingContext::Fairness], 1.5);
+    assert_eq!(avg[&RatingContext::Humanity], -1.0);
+    assert_eq!(avg[&RatingContext::Diversity], 1.0);
+    assert!(!avg.contains_key(&RatingContext::Renewable));
+    assert!(average_ratings_by_context(&db, "does_not_exist").is_err());
+}
+
+#[test]
+fn merge_duplicate_entries() {
+    let mut db = MockDb::new();
+    db.entries = vec![
+        Entry::build().id("keep").tags(vec!["bio", "fair"]).finish(),
+        Entry::build().id("drop").tags(vec!["fair", "vegan"]).finish(),
+    ];
+    rate_entry(
+        &mut db,
+        RateEntry {
+            entry: "drop".into(),
+            title: "title".into(),
+            comment: "comment".into(),
+            context: RatingContext::Fairness,
+            user: None,
+            value: 2,
+            source: None,
+        },
+    ).unwrap();
+    assert!(merge_entries(&mut db, "keep", "drop").is_ok());
+    assert_eq!(db.entries.len(), 1);
+    let keep = db.get_entry("keep").unwrap();
+    assert_eq!(keep.tags, vec!["bio", "fair", "vegan"]);
+    assert_eq!(keep.version, 1);
+    assert_eq!(db.ratings[0].entry_id, "keep");
+    assert_eq!(db.comments[0].rating_id, db.ratings[0].id);
+    // merging again doesn't change anything
+    assert!(merge_entries(&mut db, "keep", "drop").is_ok());
+    assert_eq!(db.get_entry("keep").unwrap().version, 1);
+    assert!(merge_entries(&mut db, "keep", "keep").is_ok());
+}
+
+#[test]
+fn merge_tag_into_itself() {
+    let mut mock_db = MockDb::new();
+    mock_db.entries = vec![Entry::build().id("a").tags(vec!["bio"]).finish()];
+    mock_db.tags = vec![Tag { id: "bio".into() }];
+    assert!(merge_tags(&mut mock_db, "bio", "bio").is_ok());
+    assert_eq!(mock_db.get_entry("a").unwrap().version, 0);
+    assert_eq!(mock_db.tags.len(), 1);
+}
+
 #[test]
 fn create_two_users() {
     let mut db = MockDb::new();
@@ -490,6 +1432,36 @@ fn create_two_users() {
     assert_eq!(baz_username, "baz");
 }
 
+#[test]
+fn create_confirmed_user() {
+    let mut db = MockDb::new();
+    let u = NewUser {
+        username: "admin".into(),
+        password: "secret".into(),
+        email: "admin@bar.de".into(),
+    };
+    let id = super::create_confirmed_user(&mut db, u).unwrap();
+    assert_eq!(db.users.len(), 1);
+    assert_eq!(db.users[0].id, id);
+    assert!(db.users[0].email_confirmed);
+    assert!(db.users[0].password != "secret");
+    let u = NewUser {
+        username: "admin".into(),
+        password: "secret".into(),
+        email: "other@bar.de".into(),
+    };
+    match super::create_confirmed_user(&mut db, u).err().unwrap() {
+        Error::Parameter(ParameterError::UserExists) => {}
+        _ => panic!("invalid error type"),
+    }
+    let u = NewUser {
+        username: "Not Valid".into(),
+        password: "secret".into(),
+        email: "foo@bar.de".into(),
+    };
+    assert!(super::create_confirmed_user(&mut db, u).is_err());
+}
+
 #[test]
 fn create_user_with_invalid_name() {
     let mut db = MockDb::new();
@@ -601,6 +1573,29 @@ fn email_unconfirmed_on_default() {
     assert_eq!(db.users[0].email_confirmed, false);
 }
 
+#[test]
+fn confirm_email_of_existing_user() {
+    let mut db = MockDb::new();
+    let u = NewUser {
+        username: "user".into(),
+        password: "pass".into(),
+        email: "foo@bar.io".into(),
+    };
+    assert!(create_new_user(&mut db, u).is_ok());
+    let u_id = db.users[0].id.clone();
+    assert!(confirm_email(&mut db, &u_id).is_ok());
+    assert_eq!(db.users[0].email_confirmed, true);
+}
+
+#[test]
+fn confirm_email_of_unknown_user() {
+    let mut db = MockDb::new();
+    match confirm_email(&mut db, "does-not-exist").err().unwrap() {
+        Error::Repo(RepoError::NotFound) => {}
+        _ => panic!("invalid error type"),
+    }
+}
+
 #[test]
 fn encrypt_user_password() {
     let mut db = MockDb::new();
@@ -655,25 +1650,204 @@ fn rate_with_empty_comment() {
 }
 
 #[test]
-fn rate_with_invalid_value_comment() {
+fn rate_with_invalid_value_comment() {
+    let mut db = MockDb::new();
+    let e = Entry::build().id("foo").finish();
+    db.entries = vec![e];
+    assert!(
+        rate_entry(
+            &mut db,
+            RateEntry {
+                entry: "foo".into(),
+                comment: "comment".into(),
+                title: "title".into(),
+                context: RatingContext::Fairness,
+                user: None,
+                value: 3,
+                source: Some("source".into()),
+            },
+        ).is_err()
+    );
+    assert!(
+        rate_entry(
+            &mut db,
+            RateEntry {
+                entry: "foo".into(),
+                title: "title".into(),
+                comment: "comment".into(),
+                context: RatingContext::Fairness,
+                user: None,
+                value: -2,
+                source: Some("source".into()),
+            },
+        ).is_err()
+    );
+}
+
+#[test]
+fn delete_rating_with_comment() {
+    let mut db = MockDb::new();
+    db.entries = vec![Entry::build().id("foo").finish()];
+    for _ in 0..2 {
+        rate_entry(
+            &mut db,
+            RateEntry {
+                entry: "foo".into(),
+                title: "title".into(),
+                comment: "comment".into(),
+                context: RatingContext::Fairness,
+                user: None,
+                value: 1,
+                source: None,
+            },
+        ).unwrap();
+    }
+    let id = db.ratings[0].id.clone();
+    assert_eq!(delete_rating(&mut db, &id).unwrap(), "foo");
+    assert_eq!(db.ratings.len(), 1);
+    assert_eq!(db.comments.len(), 1);
+    assert!(db.comments[0].rating_id != id);
+    match delete_rating(&mut db, &id).err().unwrap() {
+        Error::Repo(RepoError::NotFound) => {}
+        _ => panic!("invalid error type"),
+    }
+}
+
+#[test]
+fn rate_entry_twice_as_the_same_user() {
+    let mut db = MockDb::new();
+    db.entries = vec![Entry::build().id("foo").finish()];
+    let rating = |user: Option<&str>, context| RateEntry {
+        entry: "foo".into(),
+        title: "title".into(),
+        comment: "comment".into(),
+        context,
+        user: user.map(|u| u.to_string()),
+        value: 1,
+        source: None,
+    };
+    assert!(rate_entry(&mut db, rating(Some("alice"), RatingContext::Fairness)).is_ok());
+    assert_eq!(db.ratings[0].created_by, Some("alice".into()));
+    match rate_entry(&mut db, rating(Some("alice"), RatingContext::Fairness))
+        .err()
+        .unwrap()
+    {
+        Error::Parameter(ParameterError::AlreadyRated) => {}
+        _ => panic!("invalid error type"),
+    }
+    assert!(rate_entry(&mut db, rating(Some("alice"), RatingContext::Humanity)).is_ok());
+    assert!(rate_entry(&mut db, rating(Some("bob"), RatingContext::Fairness)).is_ok());
+    assert!(rate_entry(&mut db, rating(None, RatingContext::Fairness)).is_ok());
+    assert!(rate_entry(&mut db, rating(None, RatingContext::Fairness)).is_ok());
+    assert_eq!(db.ratings.len(), 5);
+}
+
+#[test]
+fn rate_own_entry() {
+    let mut db = MockDb::new();
+    let mut e = Entry::build().id("foo").finish();
+    e.created_by = Some("alice".into());
+    db.entries = vec![e];
+    let rating = |user: &str| RateEntry {
+        entry: "foo".into(),
+        title: "title".into(),
+        comment: "comment".into(),
+        context: RatingContext::Fairness,
+        user: Some(user.into()),
+        value: 2,
+        source: None,
+    };
+    let res = rate_entry(&mut db, rating("alice"));
+    match SELF_RATING_POLICY {
+        SelfRatingPolicy::Reject => {
+            match res.err().unwrap() {
+                Error::Parameter(ParameterError::SelfRating) => {}
+                _ => panic!("invalid error type"),
+            }
+            assert!(db.ratings.is_empty());
+        }
+        SelfRatingPolicy::Exclude => {
+            assert!(res.is_ok());
+            assert_eq!(db.ratings.len(), 1);
+        }
+    }
+    assert!(rate_entry(&mut db, rating("bob")).is_ok());
+    let ratings = db.all_ratings().unwrap();
+    assert_eq!(db.entries[0].avg_rating(&ratings), 2.0 / 6.0);
+}
+
+#[test]
+fn rate_entries_in_a_batch() {
+    let mut db = MockDb::new();
+    db.entries = vec![Entry::build().id("foo").finish()];
+    let rating = |value| RateEntry {
+        entry: "foo".into(),
+        title: "title".into(),
+        comment: "comment".into(),
+        context: RatingContext::Fairness,
+        user: None,
+        value,
+        source: None,
+    };
+    let results = rate_entries(&mut db, vec![rating(1), rating(3), rating(-1)]);
+    assert_eq!(results.len(), 3);
+    match results[1] {
+        Err(Error::Parameter(ParameterError::RatingValue)) => {}
+        _ => panic!("invalid error type"),
+    }
+    assert_eq!(db.ratings.len(), 2);
+    assert_eq!(db.comments.len(), 2);
+    assert_eq!(&db.ratings[0].id, results[0].as_ref().unwrap());
+    assert_eq!(&db.ratings[1].id, results[2].as_ref().unwrap());
+}
+
+#[test]
+fn failing_transaction_is_rolled_back() {
+    let mut db = MockDb::new();
+    db.entries = vec![Entry::build().id("foo").finish()];
+    rate_entry(
+        &mut db,
+        RateEntry {
+            entry: "foo".into(),
+            title: "title".into(),
+            comment: "comment".into(),
+            context: RatingContext::Fairness,
+            user: None,
+            value: 1,
+            source: None,
+        },
+    ).unwrap();
+    let mut rating = db.ratings[0].clone();
+    rating.id = "new".into();
+    let res: Result<()> = db.transaction(|db| {
+        db.create_rating(&rating)?;
+        let comment_id = db.comments[0].id.clone();
+        db.delete_comment(&comment_id)?;
+        // the comment for the new rating can't be found
+        db.get_comment("new")?;
+        Ok(())
+    });
+    match res.err().unwrap() {
+        Error::Repo(RepoError::NotFound) => {}
+        _ => panic!("invalid error type"),
+    }
+    assert_eq!(db.ratings.len(), 1);
+    assert_eq!(db.comments.len(), 1);
+    assert!(db.ratings[0].id != "new");
+
+    let res: Result<()> = db.transaction(|db| {
+        db.create_rating(&rating)?;
+        Ok(())
+    });
+    assert!(res.is_ok());
+    assert_eq!(db.ratings.len(), 2);
+}
+
+#[test]
+fn get_ratings_by_ids() {
     let mut db = MockDb::new();
-    let e = Entry::build().id("foo").finish();
-    db.entries = vec![e];
-    assert!(
-        rate_entry(
-            &mut db,
-            RateEntry {
-                entry: "foo".into(),
-                comment: "comment".into(),
-                title: "title".into(),
-                context: RatingContext::Fairness,
-                user: None,
-                value: 3,
-                source: Some("source".into()),
-            },
-        ).is_err()
-    );
-    assert!(
+    db.entries = vec![Entry::build().id("foo").finish()];
+    for &value in &[1, 2] {
         rate_entry(
             &mut db,
             RateEntry {
@@ -682,11 +1856,75 @@ fn rate_with_invalid_value_comment() {
                 comment: "comment".into(),
                 context: RatingContext::Fairness,
                 user: None,
-                value: -2,
-                source: Some("source".into()),
+                value,
+                source: None,
             },
-        ).is_err()
-    );
+        ).unwrap();
+    }
+    let id = db.ratings[1].id.clone();
+    let ratings = get_ratings(&db, &[id.clone(), "unknown".into()]).unwrap();
+    assert_eq!(ratings.len(), 1);
+    assert_eq!(ratings[0].id, id);
+    assert_eq!(ratings[0].value, 2);
+    let ids = vec![id, db.ratings[0].id.clone()];
+    let ratings = get_ratings(&db, &ids).unwrap();
+    let values: Vec<_> = ratings.iter().map(|r| r.value).collect();
+    assert_eq!(values, vec![2, 1]);
+    match db.get_rating("unknown").err().unwrap() {
+        RepoError::NotFound => {}
+        _ => panic!("invalid error type"),
+    }
+    match db.get_comment("unknown").err().unwrap() {
+        RepoError::NotFound => {}
+        _ => panic!("invalid error type"),
+    }
+}
+
+#[test]
+fn update_comment_text() {
+    let mut db = MockDb::new();
+    db.comments = vec![Comment {
+        id: "c".into(),
+        created: 42,
+        text: "typo".into(),
+        rating_id: "r".into(),
+    }];
+    assert!(update_comment(&mut db, "c", "fixed").is_ok());
+    assert_eq!(db.comments[0].text, "fixed");
+    assert_eq!(db.comments[0].created, 42);
+    assert_eq!(db.comments[0].rating_id, "r");
+    match update_comment(&mut db, "c", "").err().unwrap() {
+        Error::Parameter(ParameterError::EmptyComment) => {}
+        _ => panic!("invalid error type"),
+    }
+    assert_eq!(db.comments[0].text, "fixed");
+    match update_comment(&mut db, "x", "text").err().unwrap() {
+        Error::Repo(RepoError::NotFound) => {}
+        _ => panic!("invalid error type"),
+    }
+}
+
+#[test]
+fn rate_with_boundary_values() {
+    let mut db = MockDb::new();
+    db.entries = vec![Entry::build().id("foo").finish()];
+    for &value in &[-1, 0, 2] {
+        assert!(
+            rate_entry(
+                &mut db,
+                RateEntry {
+                    entry: "foo".into(),
+                    comment: "comment".into(),
+                    title: "title".into(),
+                    context: RatingContext::Fairness,
+                    user: None,
+                    value,
+                    source: None,
+                },
+            ).is_ok()
+        );
+    }
+    assert_eq!(db.ratings.len(), 3);
 }
 
 #[test]
@@ -897,6 +2135,21 @@ fn get_bbox_subscriptions() {
     assert_eq!(bbox_subscriptions.unwrap()[0].id, "2");
 }
 
+#[test]
+fn reject_swapped_bbox_subscription() {
+    let mut db = MockDb::new();
+    let north_east = Coordinate {
+        lat: 10.0,
+        lng: 10.0,
+    };
+    let south_west = Coordinate { lat: 0.0, lng: 0.0 };
+    match subscribe_to_bbox(&[north_east, south_west], "a", &mut db) {
+        Err(Error::Parameter(ParameterError::Bbox)) => {}
+        _ => panic!("invalid error type"),
+    }
+    assert!(db.all_bbox_subscriptions().unwrap().is_empty());
+}
+
 #[test]
 fn email_addresses_by_coordinate() {
     let mut db = MockDb::new();
@@ -934,6 +2187,53 @@ fn email_addresses_by_coordinate() {
     assert_eq!(no_email_addresses.len(), 0);
 }
 
+#[test]
+fn email_addresses_of_old_and_new_location() {
+    let mut db = MockDb::new();
+    for &(id, username, email) in &[
+        ("1", "a", "a@abc.de"),
+        ("2", "b", "b@abc.de"),
+        ("3", "c", "c@abc.de"),
+        ("4", "d", "d@abc.de"),
+    ] {
+        db.create_user(&User {
+            id: id.into(),
+            username: username.into(),
+            password: "123".into(),
+            email: email.into(),
+            email_confirmed: true,
+        }).unwrap();
+    }
+    let old = Coordinate { lat: 5.0, lng: 5.0 };
+    let new = Coordinate {
+        lat: 25.0,
+        lng: 25.0,
+    };
+    // c watches both locations, d none of them
+    for &(username, sw, ne) in &[
+        ("a", 0.0, 10.0),
+        ("b", 20.0, 30.0),
+        ("c", 0.0, 30.0),
+        ("d", 40.0, 50.0),
+    ] {
+        business::usecase::subscribe_to_bbox(
+            &[Coordinate { lat: sw, lng: sw }, Coordinate { lat: ne, lng: ne }],
+            username,
+            &mut db,
+        ).unwrap();
+    }
+
+    let email_addresses =
+        business::usecase::email_addresses_by_old_and_new_coordinate(&mut db, &old, &new)
+            .unwrap();
+    assert_eq!(email_addresses, vec!["a@abc.de", "b@abc.de", "c@abc.de"]);
+
+    let email_addresses =
+        business::usecase::email_addresses_by_old_and_new_coordinate(&mut db, &old, &old)
+            .unwrap();
+    assert_eq!(email_addresses, vec!["a@abc.de", "c@abc.de"]);
+}
+
 #[test]
 fn delete_user() {
     let mut db = MockDb::new();
@@ -961,6 +2261,496 @@ fn delete_user() {
     assert_eq!(db.users.len(), 1);
 }
 
+#[test]
+fn change_password() {
+    let mut db = MockDb::new();
+    db.create_user(&User {
+        id: "1".into(),
+        username: "a".into(),
+        password: bcrypt::hash("old").unwrap(),
+        email: "abc@abc.de".into(),
+        email_confirmed: true,
+    }).unwrap();
+    match change_password(&mut db, "a", "wrong", "new").err().unwrap() {
+        Error::Parameter(ParameterError::Credentials) => {}
+        _ => panic!("invalid error"),
+    }
+    match change_password(&mut db, "a", "old", "").err().unwrap() {
+        Error::Parameter(ParameterError::Password) => {}
+        _ => panic!("invalid error"),
+    }
+    assert!(bcrypt::verify("old", &db.users[0].password));
+    assert!(change_password(&mut db, "a", "old", "new").is_ok());
+    assert!(bcrypt::verify("new", &db.users[0].password));
+}
+
+#[test]
+fn reset_password() {
+    let mut db = MockDb::new();
+    db.create_user(&User {
+        id: "1".into(),
+        username: "a".into(),
+        password: bcrypt::hash("old").unwrap(),
+        email: "abc@abc.de".into(),
+        email_confirmed: true,
+    }).unwrap();
+    let (username, token) = request_password_reset(&mut db, "abc@abc.de").unwrap();
+    assert_eq!(username, "a");
+    assert_eq!(db.password_resets.len(), 1);
+    assert!(reset_password(&mut db, &token, "new").is_ok());
+    assert!(bcrypt::verify("new", &db.users[0].password));
+    assert!(db.password_resets.is_empty());
+    match reset_password(&mut db, &token, "other").err().unwrap() {
+        Error::Parameter(ParameterError::PasswordResetToken) => {}
+        _ => panic!("invalid error"),
+    }
+}
+
+#[test]
+fn request_password_reset_for_unknown_email() {
+    let mut db = MockDb::new();
+    match request_password_reset(&mut db, "foo@bar.tld").err().unwrap() {
+        Error::Repo(RepoError::NotFound) => {}
+        _ => panic!("invalid error"),
+    }
+    assert!(db.password_resets.is_empty());
+}
+
+#[test]
+fn reset_password_with_expired_token() {
+    let mut db = MockDb::new();
+    db.create_user(&User {
+        id: "1".into(),
+        username: "a".into(),
+        password: bcrypt::hash("old").unwrap(),
+        email: "abc@abc.de".into(),
+        email_confirmed: true,
+    }).unwrap();
+    db.create_password_reset(&PasswordReset {
+        token: "xyz".into(),
+        username: "a".into(),
+        created: (Utc::now().timestamp() - 25 * 60 * 60) as u64,
+    }).unwrap();
+    match reset_password(&mut db, "xyz", "new").err().unwrap() {
+        Error::Parameter(ParameterError::PasswordResetToken) => {}
+        _ => panic!("invalid error"),
+    }
+    assert!(bcrypt::verify("old", &db.users[0].password));
+    assert!(db.password_resets.is_empty());
+}
+
+#[test]
+fn search_sorted_by_distance() {
+    let mut db = MockDb::new();
+    db.entries = vec![
+        Entry::build().id("a").lat(4.0).lng(4.0).finish(),
+        Entry::build().id("b").lat(1.0).lng(1.0).finish(),
+        Entry::build().id("c").lat(-2.0).lng(2.0).finish(),
+    ];
+    let mut entry_ratings = HashMap::new();
+    entry_ratings.insert("a".to_string(), 2.0);
+    entry_ratings.insert("c".to_string(), 1.0);
+    let mut req = SearchRequest {
+        bbox: Bbox {
+            south_west: Coordinate {
+                lat: -5.0,
+                lng: -5.0,
+            },
+            north_east: Coordinate { lat: 5.0, lng: 5.0 },
+        },
+        categories: None,
+        categories_mode: Combination::Any,
+        text: "".into(),
+        tags: vec![],
+        exclude_tags: vec![],
+        city: None,
+        country: None,
+        created_after: None,
+        created_before: None,
+        entry_ratings: &entry_ratings,
+        sort: SortOrder::Distance,
+        include_archived: false,
+        max_invisible_results: DEFAULT_MAX_INVISIBLE_RESULTS,
+        rating_half_life_days: DEFAULT_RATING_HALF_LIFE_DAYS,
+    };
+    let (visible, _, _) = search(&db, &req).unwrap();
+    let ids: Vec<_> = visible.iter().map(|e| e.id.as_str()).collect();
+    assert_eq!(ids, vec!["b", "c", "a"]);
+
+    req.sort = SortOrder::Rating;
+    let (visible, _, _) = search(&db, &req).unwrap();
+    let ids: Vec<_> = visible.iter().map(|e| e.id.as_str()).collect();
+    assert_eq!(ids, vec!["a", "c", "b"]);
+}
+
+#[test]
+fn search_sorted_by_recent_ratings() {
+    let mut db = MockDb::new();
+    db.entries = vec![
+        Entry::build().id("old").finish(),
+        Entry::build().id("new").finish(),
+    ];
+    let now = Utc::now().timestamp() as u64;
+    let rating = |id: &str, entry_id: &str, created| Rating {
+        id: id.into(),
+        entry_id: entry_id.into(),
+        created,
+        title: "title".into(),
+        value: 2,
+        context: RatingContext::Fairness,
+        source: None,
+        created_by: None,
+    };
+    db.ratings = vec![
+        rating("1", "old", now - 3 * 365 * 86_400),
+        rating("2", "new", now - 86_400),
+    ];
+    let entry_ratings = HashMap::new();
+    let req = SearchRequest {
+        bbox: Bbox {
+            south_west: Coordinate {
+                lat: -5.0,
+                lng: -5.0,
+            },
+            north_east: Coordinate { lat: 5.0, lng: 5.0 },
+        },
+        categories: None,
+        categories_mode: Combination::Any,
+        text: "".into(),
+        tags: vec![],
+        exclude_tags: vec![],
+        city: None,
+        country: None,
+        created_after: None,
+        created_before: None,
+        entry_ratings: &entry_ratings,
+        sort: SortOrder::RatingRecent,
+        include_archived: false,
+        max_invisible_results: DEFAULT_MAX_INVISIBLE_RESULTS,
+        rating_half_life_days: DEFAULT_RATING_HALF_LIFE_DAYS,
+    };
+    let (visible, _, _) = search(&db, &req).unwrap();
+    let ids: Vec<_> = visible.iter().map(|e| e.id.as_str()).collect();
+    assert_eq!(ids, vec!["new", "old"]);
+}
+
+#[test]
+fn find_duplicates_with_titles() {
+    let mut db = MockDb::new();
+    db.entries = vec![
+        Entry::build().id("a").title("Weltladen").finish(),
+        Entry::build().id("b").title("Weltladen!").finish(),
+        Entry::build().id("c").title("Bioladen").lat(10.0).finish(),
+    ];
+    let duplicates = find_duplicates(&db, &DuplicateConfig::default()).unwrap();
+    assert_eq!(
+        duplicates,
+        vec![Duplicate {
+            entry1: EntryTitle {
+                id: "a".into(),
+                title: "Weltladen".into(),
+            },
+            entry2: EntryTitle {
+                id: "b".into(),
+                title: "Weltladen!".into(),
+            },
+            kind: DuplicateType::SimilarChars,
+        }]
+    );
+    db.entries[1].archived = Some(0);
+    assert!(find_duplicates(&db, &DuplicateConfig::default()).unwrap().is_empty());
+}
+
+#[test]
+fn get_pages_of_entries() {
+    let mut db = MockDb::new();
+    db.entries = (0..5)
+        .map(|i| {
+            Entry::build()
+                .id(&format!("{}", i))
+                .created(10 - i as u64)
+                .finish()
+        })
+        .collect();
+    db.entries[2].archived = Some(1);
+    let ids = |entries: Vec<Entry>| entries.into_iter().map(|e| e.id).collect::<Vec<_>>();
+    assert_eq!(ids(get_entries_page(&db, 0, None).unwrap()), vec!["4", "3", "1", "0"]);
+    assert_eq!(ids(get_entries_page(&db, 1, Some(2)).unwrap()), vec!["3", "1"]);
+    assert!(get_entries_page(&db, 4, None).unwrap().is_empty());
+    db.entries = (0..MAX_PAGE_SIZE + 1)
+        .map(|i| Entry::build().id(&format!("{}", i)).finish())
+        .collect();
+    assert_eq!(get_entries_page(&db, 0, None).unwrap().len(), DEFAULT_PAGE_SIZE);
+    assert_eq!(
+        get_entries_page(&db, 0, Some(MAX_PAGE_SIZE + 1)).unwrap().len(),
+        MAX_PAGE_SIZE
+    );
+}
+
+#[test]
+fn search_sorted_by_relevance() {
+    let mut db = MockDb::new();
+    db.entries = vec![
+        Entry::build()
+            .id("a")
+            .title("Laden")
+            .description("bio food")
+            .finish(),
+        Entry::build()
+            .id("b")
+            .title("Bioladen")
+            .description("food")
+            .finish(),
+        Entry::build()
+            .id("c")
+            .title("Weltladen")
+            .description("fair food")
+            .finish(),
+    ];
+    let entry_ratings = HashMap::new();
+    let req = SearchRequest {
+        bbox: Bbox {
+            south_west: Coordinate {
+                lat: -5.0,
+                lng: -5.0,
+            },
+            north_east: Coordinate { lat: 5.0, lng: 5.0 },
+        },
+        categories: None,
+        categories_mode: Combination::Any,
+        text: "bio".into(),
+        tags: vec![],
+        exclude_tags: vec![],
+        city: None,
+        country: None,
+        created_after: None,
+        created_before: None,
+        entry_ratings: &entry_ratings,
+        sort: SortOrder::Relevance,
+        include_archived: false,
+        max_invisible_results: DEFAULT_MAX_INVISIBLE_RESULTS,
+        rating_half_life_days: DEFAULT_RATING_HALF_LIFE_DAYS,
+    };
+    let (visible, _, _) = search(&db, &req).unwrap();
+    let ids: Vec<_> = visible.iter().map(|e| e.id.as_str()).collect();
+    assert_eq!(ids, vec!["b", "a"]);
+}
+
+#[test]
+fn archive_and_unarchive_entry() {
+    let mut db = MockDb::new();
+    db.entries = vec![Entry::build().id("a").version(2).finish()];
+    assert!(archive_entry(&mut db, "a").is_ok());
+    assert!(db.entries[0].archived.is_some());
+    assert_eq!(db.entries[0].version, 3);
+    // archiving twice doesn't create a new version
+    assert!(archive_entry(&mut db, "a").is_ok());
+    assert_eq!(db.entries[0].version, 3);
+    assert!(unarchive_entry(&mut db, "a").is_ok());
+    assert!(db.entries[0].archived.is_none());
+    assert_eq!(db.entries[0].version, 4);
+    assert!(archive_entry(&mut db, "b").is_err());
+}
+
+#[test]
+fn search_without_archived_entries() {
+    let mut db = MockDb::new();
+    let mut archived = Entry::build().id("a").lat(1.0).lng(1.0).finish();
+    archived.archived = Some(0);
+    db.entries = vec![
+        archived,
+        Entry::build().id("b").lat(2.0).lng(2.0).finish(),
+    ];
+    let entry_ratings = HashMap::new();
+    let mut req = SearchRequest {
+        bbox: Bbox {
+            south_west: Coordinate {
+                lat: -5.0,
+                lng: -5.0,
+            },
+            north_east: Coordinate { lat: 5.0, lng: 5.0 },
+        },
+        categories: None,
+        categories_mode: Combination::Any,
+        text: "".into(),
+        tags: vec![],
+        exclude_tags: vec![],
+        city: None,
+        country: None,
+        created_after: None,
+        created_before: None,
+        entry_ratings: &entry_ratings,
+        sort: SortOrder::Distance,
+        include_archived: false,
+        max_invisible_results: DEFAULT_MAX_INVISIBLE_RESULTS,
+        rating_half_life_days: DEFAULT_RATING_HALF_LIFE_DAYS,
+    };
+    let (visible, _, _) = search(&db, &req).unwrap();
+    let ids: Vec<_> = visible.iter().map(|e| e.id.as_str()).collect();
+    assert_eq!(ids, vec!["b"]);
+
+    req.include_archived = true;
+    let (visible, _, _) = search(&db, &req).unwrap();
+    assert_eq!(visible.len(), 2);
+}
+
+#[test]
+fn extend_bbox_across_antimeridian() {
+    let bbox = Bbox {
+        south_west: Coordinate {
+            lat: -10.0,
+            lng: 170.0,
+        },
+        north_east: Coordinate {
+            lat: 10.0,
+            lng: 179.99,
+        },
+    };
+    let extended = extend_bbox(&bbox);
+    assert!((extended.south_west.lng - 169.96).abs() < 1e-4);
+    assert!(extended.north_east.lng < -179.0);
+    assert!(geo::crosses_antimeridian(&extended));
+
+    let world = Bbox {
+        south_west: Coordinate {
+            lat: -90.0,
+            lng: -180.0,
+        },
+        north_east: Coordinate {
+            lat: 90.0,
+            lng: 180.0,
+        },
+    };
+    let extended = extend_bbox(&world);
+    assert_eq!(extended, world);
+}
+
+fn bbox_around(lat: f64) -> Bbox {
+    Bbox {
+        south_west: Coordinate {
+            lat: lat - 0.1,
+            lng: 9.9,
+        },
+        north_east: Coordinate {
+            lat: lat + 0.1,
+            lng: 10.1,
+        },
+    }
+}
+
+#[test]
+fn bbox_extension_at_the_equator() {
+    let (lat_ext, lng_ext) = bbox_extension(&bbox_around(0.0));
+    assert!((lat_ext - 0.02).abs() < 1e-4);
+    assert!((lng_ext - 0.04).abs() < 1e-4);
+}
+
+#[test]
+fn bbox_extension_grows_with_latitude() {
+    let (lat_ext_0, lng_ext_0) = bbox_extension(&bbox_around(0.0));
+    let (lat_ext_48, lng_ext_48) = bbox_extension(&bbox_around(48.0));
+    let (lat_ext_70, lng_ext_70) = bbox_extension(&bbox_around(70.0));
+    assert_eq!(lat_ext_0, lat_ext_48);
+    assert_eq!(lat_ext_48, lat_ext_70);
+    assert!(lng_ext_48 > lng_ext_0);
+    assert!(lng_ext_70 > lng_ext_48);
+    // at 60° a degree of longitude is half as long as at the equator
+    let (_, lng_ext_60) = bbox_extension(&bbox_around(60.0));
+    assert!((lng_ext_60 - 2.0 * lng_ext_0).abs() < 1e-4);
+}
+
+#[test]
+fn search_across_antimeridian() {
+    let mut db = MockDb::new();
+    db.entries = vec![
+        Entry::build().id("a").lat(0.0).lng(179.0).finish(),
+        Entry::build().id("b").lat(0.0).lng(-179.0).finish(),
+        Entry::build().id("c").lat(0.0).lng(0.0).finish(),
+    ];
+    let entry_ratings = HashMap::new();
+    let req = SearchRequest {
+        bbox: Bbox {
+            south_west: Coordinate {
+                lat: -10.0,
+                lng: 170.0,
+            },
+            north_east: Coordinate {
+                lat: 10.0,
+                lng: -170.0,
+            },
+        },
+        categories: None,
+        categories_mode: Combination::Any,
+        text: "".into(),
+        tags: vec![],
+        exclude_tags: vec![],
+        city: None,
+        country: None,
+        created_after: None,
+        created_before: None,
+        entry_ratings: &entry_ratings,
+        sort: SortOrder::Distance,
+        include_archived: false,
+        max_invisible_results: DEFAULT_MAX_INVISIBLE_RESULTS,
+        rating_half_life_days: DEFAULT_RATING_HALF_LIFE_DAYS,
+    };
+    let (visible, invisible, _) = search(&db, &req).unwrap();
+    let mut ids: Vec<_> = visible.iter().map(|e| e.id.as_str()).collect();
+    ids.sort();
+    assert_eq!(ids, vec!["a", "b"]);
+    assert!(invisible.is_empty());
+}
+
+#[test]
+fn cap_invisible_search_results() {
+    let mut db = MockDb::new();
+    db.entries = (0..10)
+        .map(|i| {
+            Entry::build()
+                .id(&i.to_string())
+                .lat(50.0)
+                .lng(f64::from(i))
+                .tags(vec!["bio"])
+                .finish()
+        })
+        .collect();
+    let entry_ratings = HashMap::new();
+    let mut req = SearchRequest {
+        bbox: Bbox {
+            south_west: Coordinate {
+                lat: -10.0,
+                lng: -10.0,
+            },
+            north_east: Coordinate {
+                lat: 10.0,
+                lng: 10.0,
+            },
+        },
+        categories: None,
+        categories_mode: Combination::Any,
+        text: "".into(),
+        tags: vec!["bio".into()],
+        exclude_tags: vec![],
+        city: None,
+        country: None,
+        created_after: None,
+        created_before: None,
+        entry_ratings: &entry_ratings,
+        sort: SortOrder::Distance,
+        include_archived: false,
+        max_invisible_results: DEFAULT_MAX_INVISIBLE_RESULTS,
+        rating_half_life_days: DEFAULT_RATING_HALF_LIFE_DAYS,
+    };
+    let (visible, invisible, total_count) = search(&db, &req).unwrap();
+    assert!(visible.is_empty());
+    assert_eq!(invisible.len(), 5);
+    assert_eq!(total_count, 10);
+    req.max_invisible_results = 2;
+    let (_, invisible, total_count) = search(&db, &req).unwrap();
+    assert_eq!(invisible.len(), 2);
+    assert_eq!(total_count, 10);
+}
+
 #[bench]
 fn bench_search_in_1_000_rated_entries(b: &mut Bencher) {
     let mut db = MockDb::new();
@@ -980,9 +2770,19 @@ fn bench_search_in_1_000_rated_entries(b: &mut Bencher) {
             },
         },
         categories: None,
+        categories_mode: Combination::Any,
         text: "".into(),
         tags: vec![],
+        exclude_tags: vec![],
+        city: None,
+        country: None,
+        created_after: None,
+        created_before: None,
         entry_ratings: &entry_ratings,
+        sort: SortOrder::Rating,
+        include_archived: false,
+        max_invisible_results: DEFAULT_MAX_INVISIBLE_RESULTS,
+        rating_half_life_days: DEFAULT_RATING_HALF_LIFE_DAYS,
     };
 
     b.iter(|| super::search(&mut db, &req).unwrap());
@@ -1008,9 +2808,19 @@ fn bench_search_in_10_000_rated_entries(b: &mut Bencher) {
             },
         },
         categories: None,
+        categories_mode: Combination::Any,
         text: "".into(),
         tags: vec![],
+        exclude_tags: vec![],
+        city: None,
+        country: None,
+        created_after: None,
+        created_before: None,
         entry_ratings: &entry_ratings,
+        sort: SortOrder::Rating,
+        include_archived: false,
+        max_invisible_results: DEFAULT_MAX_INVISIBLE_RESULTS,
+        rating_half_life_days: DEFAULT_RATING_HALF_LIFE_DAYS,
     };
 
     b.iter(|| super::search(&mut db, &req).unwrap());
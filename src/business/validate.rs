@@ -1,11 +1,39 @@
 use business::error::ParameterError;
+use business::filter::normalize;
 use fast_chemail::is_valid_email;
 use url::Url;
 use entities::*;
 use regex::Regex;
 
+/// The maximum length of a value of an OSM tag
+pub const OPENING_HOURS_MAX_LEN: usize = 255;
+
+/// The maximum number of images of an entry
+pub const MAX_IMAGE_URLS: usize = 10;
+
+/// Ratings range from -1 (bad) to 2 (excellent)
+pub const RATING_VALUE_MIN: i8 = -1;
+pub const RATING_VALUE_MAX: i8 = 2;
+
+/// What happens to ratings of an entry by its creator
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SelfRatingPolicy {
+    /// Refuse them with `ParameterError::SelfRating`
+    Reject,
+    /// Store them but leave them out of the average ratings
+    Exclude,
+}
+
+/// Self-ratings never count for the average ratings,
+/// but with `Exclude` they are at least stored.
+pub const SELF_RATING_POLICY: SelfRatingPolicy = SelfRatingPolicy::Reject;
+
 lazy_static! {
     static ref USERNAME_REGEX: Regex = Regex::new(r"^[a-z0-9]{1,30}$").unwrap();
+    static ref ENTRY_ID_REGEX: Regex = Regex::new(r"^[a-zA-Z0-9_-]{1,64}$").unwrap();
+    static ref ZIP_DE_REGEX: Regex = Regex::new(r"^[0-9]{5}$").unwrap();
+    static ref ZIP_AT_CH_REGEX: Regex = Regex::new(r"^[1-9][0-9]{3}$").unwrap();
+    static ref ZIP_US_REGEX: Regex = Regex::new(r"^[0-9]{5}(-[0-9]{4})?$").unwrap();
 }
 
 pub trait Validate {
@@ -20,16 +48,83 @@ pub fn email(email: &str) -> Result<(), ParameterError> {
 }
 
 fn homepage(url: &str) -> Result<(), ParameterError> {
-    Url::parse(url).map_err(|_| ParameterError::Url).map(|_| ())
+    let url = Url::parse(url).map_err(|_| ParameterError::Url)?;
+    match url.scheme() {
+        "http" | "https" => Ok(()),
+        _ => Err(ParameterError::Url),
+    }
+}
+
+fn telephone(tel: &str) -> Result<(), ParameterError> {
+    let allowed = |c: char| c.is_digit(10) || " +-()".contains(c);
+    if !tel.chars().all(allowed) || !tel.chars().any(|c| c.is_digit(10)) {
+        return Err(ParameterError::Telephone);
+    }
+    Ok(())
+}
+
+// The syntax of the OSM `opening_hours` tag is too rich to be checked here.
+fn opening_hours(s: &str) -> Result<(), ParameterError> {
+    if s.trim().is_empty() || s.chars().count() > OPENING_HOURS_MAX_LEN {
+        return Err(ParameterError::OpeningHours);
+    }
+    Ok(())
+}
+
+/// Normalizes a tag: lower case without diacritics,
+/// whitespace and hyphens become a single `-`
+/// and all characters except letters, digits and `_` are dropped.
+/// So "  Co Working " becomes "co-working".
+pub fn tag(t: &str) -> Result<String, ParameterError> {
+    let mut res = String::with_capacity(t.len());
+    for c in normalize(t).chars() {
+        if c.is_whitespace() || c == '-' {
+            if !res.is_empty() && !res.ends_with('-') {
+                res.push('-');
+            }
+        } else if c.is_alphanumeric() || c == '_' {
+            res.push(c);
+        }
+    }
+    let res = res.trim_right_matches('-');
+    if res.is_empty() {
+        return Err(ParameterError::Tag);
+    }
+    Ok(res.into())
+}
+
+/// Checks the format of a zip code in Germany, Austria, Switzerland
+/// or the US. Other countries are not checked.
+/// The country may be its ISO code or its English or German name.
+pub fn zip(zip: &str, country: &str) -> Result<(), ParameterError> {
+    let pattern: &Regex = match normalize(country.trim()).as_str() {
+        "de" | "deu" | "germany" | "deutschland" => &*ZIP_DE_REGEX,
+        "at" | "aut" | "austria" | "osterreich" => &*ZIP_AT_CH_REGEX,
+        "ch" | "che" | "switzerland" | "schweiz" => &*ZIP_AT_CH_REGEX,
+        "us" | "usa" | "united states" | "vereinigte staaten" => &*ZIP_US_REGEX,
+        _ => return Ok(()),
+    };
+    if !pattern.is_match(zip.trim()) {
+        return Err(ParameterError::Zip);
+    }
+    Ok(())
 }
 
-fn license(s: &str) -> Result<(), ParameterError> {
+pub fn license(s: &str) -> Result<(), ParameterError> {
     match s {
-        "CC0-1.0" | "ODbL-1.0" => Ok(()),
+        "CC0-1.0" | "ODbL-1.0" | "CC-BY-SA-4.0" => Ok(()),
         _ => Err(ParameterError::License),
     }
 }
 
+pub fn coordinate(lat: f64, lng: f64) -> Result<(), ParameterError> {
+    // comparisons with NaN are always false
+    if !(lat >= -90.0 && lat <= 90.0 && lng >= -180.0 && lng <= 180.0) {
+        return Err(ParameterError::Coordinate);
+    }
+    Ok(())
+}
+
 pub fn bbox(bbox: &Bbox) -> Result<(), ParameterError> {
     let lats = vec![bbox.north_east.lat, bbox.south_west.lat];
     let lngs = vec![bbox.north_east.lng, bbox.south_west.lng];
@@ -46,6 +141,11 @@ pub fn bbox(bbox: &Bbox) -> Result<(), ParameterError> {
     if bbox.north_east.lat == bbox.south_west.lat && bbox.north_east.lng == bbox.south_west.lng {
         return Err(ParameterError::Bbox);
     }
+    // A south west longitude east of the north east one
+    // is fine because the bbox crosses the antimeridian then.
+    if bbox.south_west.lat > bbox.north_east.lat {
+        return Err(ParameterError::Bbox);
+    }
     Ok(())
 }
 
@@ -56,6 +156,14 @@ pub fn username(name: &str) -> Result<(), ParameterError> {
     Ok(())
 }
 
+/// Ids of imported entries have to be usable in URLs (e.g. `/entries/<id1>,<id2>`)
+pub fn entry_id(id: &str) -> Result<(), ParameterError> {
+    if !ENTRY_ID_REGEX.is_match(id) {
+        return Err(ParameterError::EntryId);
+    }
+    Ok(())
+}
+
 pub fn password(pw: &str) -> Result<(), ParameterError> {
     //TODO: use regex
     if pw == "" || pw.contains(' ') {
@@ -64,21 +172,73 @@ pub fn password(pw: &str) -> Result<(), ParameterError> {
     Ok(())
 }
 
+pub fn title(s: &str) -> Result<(), ParameterError> {
+    if s.trim().is_empty() {
+        return Err(ParameterError::EmptyTitle);
+    }
+    Ok(())
+}
+
+pub fn description(s: &str) -> Result<(), ParameterError> {
+    if s.trim().is_empty() {
+        return Err(ParameterError::EmptyDescription);
+    }
+    Ok(())
+}
+
+pub fn rating_value(v: i8) -> Result<(), ParameterError> {
+    if v < RATING_VALUE_MIN || v > RATING_VALUE_MAX {
+        return Err(ParameterError::RatingValue);
+    }
+    Ok(())
+}
+
+/// Whether the user created the entry
+pub fn is_self_rating(e: &Entry, user: Option<&String>) -> bool {
+    user.is_some() && e.created_by.as_ref() == user
+}
+
+// Empty optional fields are treated like missing ones
+fn non_empty(field: &Option<String>) -> Option<&str> {
+    match *field {
+        Some(ref s) if !s.trim().is_empty() => Some(s.trim()),
+        _ => None,
+    }
+}
+
 impl Validate for Entry {
     fn validate(&self) -> Result<(), ParameterError> {
-        self.license
-            .clone()
-            .ok_or(ParameterError::License)
-            .and_then(|ref l| license(l))?;
+        title(&self.title)?;
+        description(&self.description)?;
+        coordinate(self.lat, self.lng)?;
 
-        if let Some(ref e) = self.email {
+        if let Some(e) = non_empty(&self.email) {
             email(e)?;
         }
 
-        if let Some(ref h) = self.homepage {
+        if let Some(h) = non_empty(&self.homepage) {
             homepage(h)?;
         }
 
+        if let Some(t) = non_empty(&self.telephone) {
+            telephone(t)?;
+        }
+
+        if let (Some(z), Some(c)) = (non_empty(&self.zip), non_empty(&self.country)) {
+            zip(z, c)?;
+        }
+
+        if let Some(ref h) = self.opening_hours {
+            opening_hours(h)?;
+        }
+
+        if self.image_urls.len() > MAX_IMAGE_URLS {
+            return Err(ParameterError::TooManyImageUrls);
+        }
+        for url in &self.image_urls {
+            homepage(url).map_err(|_| ParameterError::ImageUrl)?;
+        }
+
         Ok(())
     }
 }
@@ -88,6 +248,9 @@ fn license_test() {
     assert!(license("CC0-1.0").is_ok());
     assert!(license("CC0").is_err());
     assert!(license("ODbL-1.0").is_ok());
+    assert!(license("CC-BY-SA-4.0").is_ok());
+    assert!(license("proprietary").is_err());
+    assert!(license("").is_err());
 }
 
 #[test]
@@ -100,7 +263,163 @@ fn email_test() {
 #[test]
 fn homepage_test() {
     assert!(homepage("https://openfairdb.org").is_ok());
+    assert!(homepage("http://openfairdb.org/foo").is_ok());
     assert!(homepage("openfairdb.org/foo").is_err());
+    assert!(homepage("ftp://openfairdb.org").is_err());
+    assert!(homepage("mailto:foo@bar.tld").is_err());
+}
+
+#[test]
+fn telephone_test() {
+    assert!(telephone("+43 316-422677").is_ok());
+    assert!(telephone("(0711) 123 456").is_ok());
+    assert!(telephone("0123456789").is_ok());
+    assert!(telephone("call me").is_err());
+    assert!(telephone("0711/123456").is_err());
+    assert!(telephone("+-()").is_err());
+}
+
+#[test]
+fn entry_contact_details_test() {
+    use business::builder::EntryBuilder;
+    let mut e = Entry::build().title("foo").description("bar").finish();
+    assert!(e.validate().is_ok());
+    e.email = Some("".into());
+    e.homepage = Some(" ".into());
+    e.telephone = Some("".into());
+    assert!(e.validate().is_ok());
+    e.email = Some("not-an-email".into());
+    assert!(e.validate().is_err());
+    e.email = Some("foo@bar.tld".into());
+    e.homepage = Some("not a url".into());
+    assert!(e.validate().is_err());
+    e.homepage = Some("https://foo.tld".into());
+    e.telephone = Some("abc".into());
+    assert!(e.validate().is_err());
+    e.telephone = Some("+49 711 123".into());
+    assert!(e.validate().is_ok());
+    e.zip = Some("ABCDE".into());
+    assert!(e.validate().is_ok());
+    e.country = Some("DE".into());
+    match e.validate() {
+        Err(ParameterError::Zip) => {}
+        _ => panic!("invalid error type"),
+    }
+    e.zip = Some("10115".into());
+    assert!(e.validate().is_ok());
+}
+
+#[test]
+fn opening_hours_test() {
+    assert!(opening_hours("Mo-Fr 09:00-18:00").is_ok());
+    assert!(opening_hours("24/7").is_ok());
+    assert!(opening_hours("").is_err());
+    assert!(opening_hours("  ").is_err());
+    assert!(opening_hours(&"Mo ".repeat(100)).is_err());
+}
+
+#[test]
+fn entry_image_urls_test() {
+    use business::builder::EntryBuilder;
+    let mut e = Entry::build().title("foo").description("bar").finish();
+    assert!(e.validate().is_ok());
+    e.image_urls = vec!["https://foo.tld/a.jpg".into(), "http://foo.tld/b.png".into()];
+    assert!(e.validate().is_ok());
+    e.image_urls.push("foo.tld/c.jpg".into());
+    match e.validate() {
+        Err(ParameterError::ImageUrl) => {}
+        _ => panic!("invalid error type"),
+    }
+    e.image_urls = vec!["https://foo.tld/a.jpg".into(); MAX_IMAGE_URLS];
+    assert!(e.validate().is_ok());
+    e.image_urls.push("https://foo.tld/a.jpg".into());
+    match e.validate() {
+        Err(ParameterError::TooManyImageUrls) => {}
+        _ => panic!("invalid error type"),
+    }
+}
+
+#[test]
+fn tag_test() {
+    assert_eq!(tag("  Co Working ").unwrap(), "co-working");
+    assert_eq!(tag("co-working").unwrap(), "co-working");
+    assert_eq!(tag("Co -- Working").unwrap(), "co-working");
+    assert_eq!(tag("Café ").unwrap(), "cafe");
+    assert_eq!(tag("CAFE").unwrap(), "cafe");
+    assert_eq!(tag("#bio!").unwrap(), "bio");
+    assert_eq!(tag("fair_trade").unwrap(), "fair_trade");
+    assert!(tag("").is_err());
+    assert!(tag("  ").is_err());
+    assert!(tag("#!?").is_err());
+    assert!(tag(" - ").is_err());
+}
+
+#[test]
+fn zip_test() {
+    assert!(zip("10115", "DE").is_ok());
+    assert!(zip("ABCDE", "DE").is_err());
+    assert!(zip("1011", "Deutschland").is_err());
+    assert!(zip("1010", "Österreich").is_ok());
+    assert!(zip("0100", "AT").is_err());
+    assert!(zip("8001", "Switzerland").is_ok());
+    assert!(zip("90210", "US").is_ok());
+    assert!(zip("90210-1234", "usa").is_ok());
+    assert!(zip("9021", "US").is_err());
+    assert!(zip("SW1A 1AA", "UK").is_ok());
+    assert!(zip("whatever", "").is_ok());
+}
+
+#[test]
+fn rating_value_test() {
+    assert!(rating_value(-1).is_ok());
+    assert!(rating_value(0).is_ok());
+    assert!(rating_value(2).is_ok());
+    assert!(rating_value(-2).is_err());
+    assert!(rating_value(3).is_err());
+}
+
+#[test]
+fn title_test() {
+    assert!(title("").is_err());
+    assert!(title("   ").is_err());
+    assert!(title("\t\n").is_err());
+    assert!(title("Weltladen").is_ok());
+    assert!(title(" Weltladen ").is_ok());
+}
+
+#[test]
+fn entry_id_test() {
+    assert!(entry_id("").is_err());
+    assert!(entry_id("a,b").is_err());
+    assert!(entry_id("a b").is_err());
+    assert!(entry_id("../a").is_err());
+    assert!(entry_id(&"a".repeat(65)).is_err());
+    assert!(entry_id("1e3a7b2c4d5e6f708192a3b4c5d6e7f8").is_ok());
+    assert!(entry_id("import-2018_42").is_ok());
+}
+
+#[test]
+fn description_test() {
+    assert!(description("").is_err());
+    assert!(description("   ").is_err());
+    assert!(description("fair trade shop").is_ok());
+}
+
+#[test]
+fn coordinate_test() {
+    use std::f64::{INFINITY, NAN, NEG_INFINITY};
+    assert!(coordinate(0.0, 0.0).is_ok());
+    assert!(coordinate(90.0, 180.0).is_ok());
+    assert!(coordinate(-90.0, -180.0).is_ok());
+    assert!(coordinate(48.7755, 9.1827).is_ok());
+    assert!(coordinate(500.0, 0.0).is_err());
+    assert!(coordinate(0.0, -9999.0).is_err());
+    assert!(coordinate(-90.1, 0.0).is_err());
+    assert!(coordinate(0.0, 180.1).is_err());
+    assert!(coordinate(NAN, 0.0).is_err());
+    assert!(coordinate(0.0, NAN).is_err());
+    assert!(coordinate(INFINITY, 0.0).is_err());
+    assert!(coordinate(0.0, NEG_INFINITY).is_err());
 }
 
 #[test]
@@ -129,7 +448,23 @@ fn bbox_test() {
         north_east: c1.clone(),
         south_west: c3.clone(),
     };
+    let swapped_bbox = Bbox {
+        north_east: c2.clone(),
+        south_west: c1.clone(),
+    };
+    let antimeridian_bbox = Bbox {
+        north_east: Coordinate {
+            lat: 10.0,
+            lng: -170.0,
+        },
+        south_west: Coordinate {
+            lat: -10.0,
+            lng: 170.0,
+        },
+    };
     assert!(bbox(&valid_bbox).is_ok());
     assert!(bbox(&empty_bbox).is_err());
     assert!(bbox(&too_large_bbox).is_err());
+    assert!(bbox(&swapped_bbox).is_err());
+    assert!(bbox(&antimeridian_bbox).is_ok());
 }
@@ -1,24 +1,31 @@
 #[cfg_attr(rustfmt, rustfmt_skip)]
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct Entry {
-    pub id          : String,
-    pub osm_node    : Option<u64>,
-    pub created     : u64,
-    pub version     : u64,
-    pub title       : String,
-    pub description : String,
-    pub lat         : f64,
-    pub lng         : f64,
-    pub street      : Option<String>,
-    pub zip         : Option<String>,
-    pub city        : Option<String>,
-    pub country     : Option<String>,
-    pub email       : Option<String>,
-    pub telephone   : Option<String>,
-    pub homepage    : Option<String>,
-    pub categories  : Vec<String>,
-    pub tags        : Vec<String>,
-    pub license     : Option<String>,
+    pub id            : String,
+    pub osm_node      : Option<u64>,
+    pub created       : u64,
+    pub updated       : Option<u64>,
+    pub archived      : Option<u64>,
+    pub version       : u64,
+    pub title         : String,
+    pub description   : String,
+    pub lat           : f64,
+    pub lng           : f64,
+    pub street        : Option<String>,
+    pub zip           : Option<String>,
+    pub city          : Option<String>,
+    pub country       : Option<String>,
+    pub email         : Option<String>,
+    pub telephone     : Option<String>,
+    pub homepage      : Option<String>,
+    /// In the syntax of the OSM `opening_hours` tag
+    pub opening_hours : Option<String>,
+    #[serde(default)]
+    pub image_urls    : Vec<String>,
+    pub categories    : Vec<String>,
+    pub tags          : Vec<String>,
+    pub license       : Option<String>,
+    pub created_by    : Option<String>,
 }
 
 #[cfg_attr(rustfmt, rustfmt_skip)]
@@ -61,6 +68,14 @@ pub struct User {
     pub email_confirmed : bool,
 }
 
+#[cfg_attr(rustfmt, rustfmt_skip)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct PasswordReset {
+    pub token    : String,
+    pub username : String,
+    pub created  : u64,
+}
+
 #[cfg_attr(rustfmt, rustfmt_skip)]
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct Comment {
@@ -70,7 +85,7 @@ pub struct Comment {
     pub rating_id : String,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
 pub enum RatingContext {
     #[serde(rename = "diversity")]
     Diversity,
@@ -89,13 +104,14 @@ pub enum RatingContext {
 #[cfg_attr(rustfmt, rustfmt_skip)]
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct Rating {
-    pub id       : String,
-    pub entry_id : String,
-    pub created  : u64,
-    pub title    : String,
-    pub value    : i8,
-    pub context  : RatingContext,
-    pub source   : Option<String>,
+    pub id         : String,
+    pub entry_id   : String,
+    pub created    : u64,
+    pub title      : String,
+    pub value      : i8,
+    pub context    : RatingContext,
+    pub source     : Option<String>,
+    pub created_by : Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -1,10 +1,54 @@
-use clap::{App, Arg, SubCommand};
-use super::web;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use super::web::{self, Mailer};
 use super::osm;
+use super::web::sqlite::create_connection_pool;
+use business::usecase::{self, NewUser};
 use dotenv::dotenv;
+use rocket::logger::LoggingLevel;
 use std::{env, process};
+use std::io::{self, Write};
+use std::net::ToSocketAddrs;
 
 const DEFAULT_DB_URL: &str = "openfair.db";
+const DEFAULT_HOST: &str = "127.0.0.1";
+const DEFAULT_LOG_LEVEL: &str = "normal";
+
+fn logging_level(level: &str) -> Option<LoggingLevel> {
+    match level {
+        // Rocket 0.3 can't turn logging off completely,
+        // so critical is as quiet as it gets.
+        "off" | "critical" => Some(LoggingLevel::Critical),
+        "normal" => Some(LoggingLevel::Normal),
+        "debug" => Some(LoggingLevel::Debug),
+        _ => None,
+    }
+}
+
+// Fails before the server runs instead of with every mail
+fn mailer() -> Mailer {
+    match Mailer::from_env() {
+        Ok(mailer) => mailer,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+// Takes the value of the argument or asks for it on stdin
+fn value_or_prompt(matches: &ArgMatches, name: &str) -> String {
+    if let Some(v) = matches.value_of(name) {
+        return v.into();
+    }
+    print!("{}: ", name);
+    io::stdout().flush().ok();
+    let mut v = String::new();
+    if io::stdin().read_line(&mut v).is_err() {
+        println!("Could not read {}", name);
+        process::exit(1)
+    }
+    v.trim().into()
+}
 
 pub fn run() {
     dotenv().ok();
@@ -19,6 +63,18 @@ pub fn run() {
                 .default_value("6767")
                 .help("Set the port to listen"),
         )
+        .arg(
+            Arg::with_name("host")
+                .long("host")
+                .value_name("HOST")
+                .help("Set the address to listen on (default: 127.0.0.1)"),
+        )
+        .arg(
+            Arg::with_name("log-level")
+                .long("log-level")
+                .value_name("LEVEL")
+                .help("Set the log level: off, critical, normal or debug (default: normal)"),
+        )
         .arg(
             Arg::with_name("db-url")
                 .long("db-url")
@@ -28,14 +84,36 @@ pub fn run() {
         .arg(
             Arg::with_name("enable-cors")
                 .long("enable-cors")
-                .help("Allow requests from any origin"),
+                .help("Allow cross-origin requests"),
+        )
+        .arg(
+            Arg::with_name("cors-origin")
+                .long("cors-origin")
+                .value_name("ORIGIN")
+                .default_value("*")
+                .help("Set the allowed origin if CORS is enabled"),
+        )
+        .subcommand(
+            SubCommand::with_name("create-user")
+                .about("create a user with a confirmed email address")
+                .arg(
+                    Arg::with_name("username")
+                        .long("username")
+                        .value_name("USERNAME"),
+                )
+                .arg(Arg::with_name("email").long("email").value_name("EMAIL"))
+                .arg(
+                    Arg::with_name("password")
+                        .long("password")
+                        .value_name("PASSWORD"),
+                ),
         )
         .subcommand(
             SubCommand::with_name("osm")
                 .about("OpenStreetMap functionalities")
                 .subcommand(
                     SubCommand::with_name("import")
-                        .about("import entries from OSM (JSON file)")
+                        .about("import or update entries from OSM (JSON file)")
                         .arg(
                             Arg::with_name("osm-file")
                                 .value_name("OSM_FILE")
@@ -54,6 +132,21 @@ pub fn run() {
     };
 
     match matches.subcommand() {
+        ("create-user", Some(user_matches)) => {
+            let u = NewUser {
+                username: value_or_prompt(user_matches, "username"),
+                email: value_or_prompt(user_matches, "email"),
+                password: value_or_prompt(user_matches, "password"),
+            };
+            let pool = create_connection_pool(&db_url).unwrap();
+            match usecase::create_confirmed_user(&mut *pool.get().unwrap(), u) {
+                Ok(id) => println!("{}", id),
+                Err(err) => {
+                    println!("Could not create user: {}", err);
+                    process::exit(1)
+                }
+            }
+        }
         ("osm", Some(osm_matches)) => match osm_matches.subcommand() {
             ("import", Some(import_matches)) => {
                 let osm_file = match import_matches.value_of("osm-file") {
@@ -79,7 +172,40 @@ pub fn run() {
                 }
             };
 
-            web::run(&db_url, port, matches.is_present("enable-cors"));
+            let host = match matches.value_of("host") {
+                Some(host) => host.into(),
+                None => match env::var("HOST") {
+                    Ok(host) => host,
+                    Err(_) => DEFAULT_HOST.to_string(),
+                },
+            };
+            if (host.as_str(), port).to_socket_addrs().is_err() {
+                println!("Invalid host address '{}'", host);
+                process::exit(1)
+            }
+
+            let log_level = match matches.value_of("log-level") {
+                Some(level) => level.into(),
+                None => match env::var("OFDB_LOG_LEVEL") {
+                    Ok(level) => level,
+                    Err(_) => DEFAULT_LOG_LEVEL.to_string(),
+                },
+            };
+            let log_level = match logging_level(&log_level) {
+                Some(level) => level,
+                None => {
+                    println!("{}", matches.usage());
+                    process::exit(1)
+                }
+            };
+
+            let cors_origin = if matches.is_present("enable-cors") {
+                matches.value_of("cors-origin")
+            } else {
+                None
+            };
+
+            web::run(&db_url, &host, port, log_level, cors_origin, mailer());
         }
     }
 }
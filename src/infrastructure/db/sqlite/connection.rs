@@ -3,14 +3,55 @@ use business::error::RepoError;
 use diesel;
 use diesel::prelude::*;
 use diesel::sqlite::SqliteConnection;
+use diesel::connection::TransactionManager;
 use std::result;
-use business::db::Db;
+use std::panic::{self, AssertUnwindSafe};
+use business::db::{Db, SearchParams};
+use business::filter;
+use diesel::expression::BoxableExpression;
+use diesel::sql_types::Bool;
+use diesel::sqlite::Sqlite;
 use super::models;
 use super::schema;
+use super::util::image_urls_from_text;
 use diesel::result::{DatabaseErrorKind, Error as DieselError};
 
 type Result<T> = result::Result<T, RepoError>;
 
+type EntryFilter = Box<BoxableExpression<schema::entries::table, Sqlite, SqlType = Bool>>;
+
+type Relations = (
+    Vec<models::EntryCategoryRelation>,
+    Vec<models::EntryTagRelation>,
+);
+
+// SQLite allows at most 999 variables per statement
+const MAX_IDS_PER_QUERY: usize = 500;
+
+/// Loads only the category and tag relations of the given entries.
+/// Relations of other versions are left out by `into_entry`.
+fn load_relations(con: &SqliteConnection, entries: &[models::Entry]) -> Result<Relations> {
+    use self::schema::entry_category_relations::dsl as e_c_dsl;
+    use self::schema::entry_tag_relations::dsl as e_t_dsl;
+
+    let ids: Vec<String> = entries.iter().map(|e| e.id.clone()).collect();
+    let mut cat_rels = vec![];
+    let mut tag_rels = vec![];
+    for chunk in ids.chunks(MAX_IDS_PER_QUERY) {
+        cat_rels.extend(
+            e_c_dsl::entry_category_relations
+                .filter(e_c_dsl::entry_id.eq_any(chunk))
+                .load::<models::EntryCategoryRelation>(con)?,
+        );
+        tag_rels.extend(
+            e_t_dsl::entry_tag_relations
+                .filter(e_t_dsl::entry_id.eq_any(chunk))
+                .load::<models::EntryTagRelation>(con)?,
+        );
+    }
+    Ok((cat_rels, tag_rels))
+}
+
 fn unset_current_on_all_entries(
     con: &&mut SqliteConnection,
     id: &str,
@@ -24,6 +65,81 @@ fn unset_current_on_all_entries(
         .execute(*con)
 }
 
+fn into_entry(
+    e: models::Entry,
+    cat_rels: &[models::EntryCategoryRelation],
+    tag_rels: &[models::EntryTagRelation],
+) -> Entry {
+    let categories = cat_rels
+        .iter()
+        .filter(|r| r.entry_id == e.id)
+        .filter(|r| r.entry_version == e.version)
+        .map(|r| &r.category_id)
+        .cloned()
+        .collect();
+    let tags = tag_rels
+        .iter()
+        .filter(|r| r.entry_id == e.id)
+        .filter(|r| r.entry_version == e.version)
+        .map(|r| &r.tag_id)
+        .cloned()
+        .collect();
+    Entry {
+        id: e.id,
+        osm_node: e.osm_node.map(|x| x as u64),
+        created: e.created as u64,
+        updated: e.updated.map(|x| x as u64),
+        archived: e.archived.map(|x| x as u64),
+        version: e.version as u64,
+        title: e.title,
+        description: e.description,
+        lat: e.lat as f64,
+        lng: e.lng as f64,
+        street: e.street,
+        zip: e.zip,
+        city: e.city,
+        country: e.country,
+        email: e.email,
+        telephone: e.telephone,
+        homepage: e.homepage,
+        opening_hours: e.opening_hours,
+        image_urls: image_urls_from_text(e.image_urls),
+        categories,
+        tags,
+        license: e.license,
+        created_by: e.created_by,
+    }
+}
+
+fn tags_filter(tags: &[String]) -> Option<EntryFilter> {
+    use self::schema::entries::dsl as e_dsl;
+    use self::schema::entry_tag_relations::dsl as e_t_dsl;
+    let mut filter: Option<EntryFilter> = None;
+    for t in tags {
+        let cond = e_dsl::id.eq_any(
+            e_t_dsl::entry_tag_relations
+                .select(e_t_dsl::entry_id)
+                .filter(e_t_dsl::tag_id.eq(t.to_lowercase())),
+        );
+        filter = Some(match filter {
+            Some(f) => Box::new(f.and(cond)),
+            None => Box::new(cond),
+        });
+    }
+    filter
+}
+
+// Mirrors `filter::entries_by_tags_or_search_text`.
+// `None` means that the entries can't be narrowed down.
+// The search text is matched without case and diacritics,
+// which SQLite's LIKE can't do, so only the tags narrow down the query.
+fn text_or_tags_filter(text: &str, tags: &[String]) -> Option<EntryFilter> {
+    if tags.is_empty() || !text.is_empty() || tags[0] == "" {
+        return None;
+    }
+    tags_filter(tags)
+}
+
 impl Db for SqliteConnection {
     fn create_entry(&mut self, e: &Entry) -> Result<()> {
         let new_entry = models::Entry::from(e.clone());
@@ -45,7 +161,7 @@ impl Db for SqliteConnection {
                 tag_id,
             })
             .collect();
-        self.transaction::<_, diesel::result::Error, _>(|| {
+        Connection::transaction::<_, diesel::result::Error, _>(self, || {
             unset_current_on_all_entries(&self, &e.id)?;
             diesel::insert_into(schema::entries::table)
                 .values(&new_entry)
@@ -108,6 +224,12 @@ impl Db for SqliteConnection {
         }
         Ok(())
     }
+    fn create_category(&mut self, c: &Category) -> Result<()> {
+        diesel::insert_into(schema::categories::table)
+            .values(&models::Category::from(c.clone()))
+            .execute(self)?;
+        Ok(())
+    }
     fn create_user(&mut self, u: &User) -> Result<()> {
         diesel::insert_into(schema::users::table)
             .values(&models::User::from(u.clone()))
@@ -132,6 +254,12 @@ impl Db for SqliteConnection {
             .execute(self)?;
         Ok(())
     }
+    fn create_password_reset(&mut self, r: &PasswordReset) -> Result<()> {
+        diesel::insert_into(schema::password_resets::table)
+            .values(&models::PasswordReset::from(r.clone()))
+            .execute(self)?;
+        Ok(())
+    }
     fn all_users(&self) -> Result<Vec<User>> {
         use self::schema::users::dsl;
         Ok(dsl::users
@@ -157,16 +285,57 @@ impl Db for SqliteConnection {
         let u: models::User = dsl::users.filter(dsl::id.eq(user_id)).first(self)?;
         Ok(u.into())
     }
+    fn delete_entry(&mut self, id: &str) -> Result<()> {
+        use self::schema::entries::dsl as e_dsl;
+        use self::schema::entry_category_relations::dsl as e_c_dsl;
+        use self::schema::entry_tag_relations::dsl as e_t_dsl;
+
+        Connection::transaction::<_, diesel::result::Error, _>(self, || {
+            diesel::delete(e_c_dsl::entry_category_relations.filter(e_c_dsl::entry_id.eq(id)))
+                .execute(self)?;
+            diesel::delete(e_t_dsl::entry_tag_relations.filter(e_t_dsl::entry_id.eq(id)))
+                .execute(self)?;
+            let count = diesel::delete(e_dsl::entries.filter(e_dsl::id.eq(id))).execute(self)?;
+            if count == 0 {
+                return Err(DieselError::NotFound);
+            }
+            Ok(())
+        })?;
+        Ok(())
+    }
     fn delete_bbox_subscription(&mut self, id: &str) -> Result<()> {
         use self::schema::bbox_subscriptions::dsl;
         diesel::delete(dsl::bbox_subscriptions.find(id)).execute(self)?;
         Ok(())
     }
+    fn delete_tag(&mut self, id: &str) -> Result<()> {
+        use self::schema::tags::dsl;
+        diesel::delete(dsl::tags.find(id)).execute(self)?;
+        Ok(())
+    }
+    fn delete_rating(&mut self, id: &str) -> Result<()> {
+        use self::schema::ratings::dsl;
+        let count = diesel::delete(dsl::ratings.find(id)).execute(self)?;
+        if count == 0 {
+            return Err(RepoError::NotFound);
+        }
+        Ok(())
+    }
+    fn delete_comment(&mut self, id: &str) -> Result<()> {
+        use self::schema::comments::dsl;
+        diesel::delete(dsl::comments.find(id)).execute(self)?;
+        Ok(())
+    }
     fn delete_user(&mut self, user: &str) -> Result<()> {
         use self::schema::users::dsl::*;
         diesel::delete(users.find(user)).execute(self)?;
         Ok(())
     }
+    fn delete_password_reset(&mut self, token: &str) -> Result<()> {
+        use self::schema::password_resets::dsl;
+        diesel::delete(dsl::password_resets.find(token)).execute(self)?;
+        Ok(())
+    }
 
     fn get_entry(&self, e_id: &str) -> Result<Entry> {
         use self::schema::entries::dsl as e_dsl;
@@ -190,6 +359,11 @@ impl Db for SqliteConnection {
             telephone,
             homepage,
             license,
+            created_by,
+            updated,
+            archived,
+            opening_hours,
+            image_urls,
             ..
         } = e_dsl::entries
             .filter(e_dsl::id.eq(e_id))
@@ -198,6 +372,7 @@ impl Db for SqliteConnection {
 
         let categories = e_c_dsl::entry_category_relations
             .filter(e_c_dsl::entry_id.eq(&id))
+            .filter(e_c_dsl::entry_version.eq(version))
             .load::<models::EntryCategoryRelation>(self)?
             .into_iter()
             .map(|r| r.category_id)
@@ -205,6 +380,7 @@ impl Db for SqliteConnection {
 
         let tags = e_t_dsl::entry_tag_relations
             .filter(e_t_dsl::entry_id.eq(&id))
+            .filter(e_t_dsl::entry_version.eq(version))
             .load::<models::EntryTagRelation>(self)?
             .into_iter()
             .map(|r| r.tag_id)
@@ -214,6 +390,8 @@ impl Db for SqliteConnection {
             id,
             osm_node: osm_node.map(|x| x as u64),
             created: created as u64,
+            updated: updated.map(|x| x as u64),
+            archived: archived.map(|x| x as u64),
             version: version as u64,
             title,
             description,
@@ -226,66 +404,135 @@ impl Db for SqliteConnection {
             email,
             telephone,
             homepage,
+            opening_hours,
+            image_urls: image_urls_from_text(image_urls),
             categories,
             tags,
             license,
+            created_by,
         })
     }
 
-    fn get_entries_by_bbox(&self, bbox: &Bbox) -> Result<Vec<Entry>> {
+    fn get_entry_history(&self, e_id: &str) -> Result<Vec<Entry>> {
         use self::schema::entries::dsl as e_dsl;
         use self::schema::entry_category_relations::dsl as e_c_dsl;
         use self::schema::entry_tag_relations::dsl as e_t_dsl;
 
         let entries: Vec<models::Entry> = e_dsl::entries
+            .filter(e_dsl::id.eq(e_id))
+            .order(e_dsl::version.asc())
+            .load(self)?;
+
+        let cat_rels = e_c_dsl::entry_category_relations
+            .filter(e_c_dsl::entry_id.eq(e_id))
+            .load::<models::EntryCategoryRelation>(self)?;
+
+        let tag_rels = e_t_dsl::entry_tag_relations
+            .filter(e_t_dsl::entry_id.eq(e_id))
+            .load::<models::EntryTagRelation>(self)?;
+
+        Ok(entries
+            .into_iter()
+            .map(|e| into_entry(e, &cat_rels, &tag_rels))
+            .collect())
+    }
+
+    fn search_entries(&self, params: &SearchParams) -> Result<Vec<Entry>> {
+        use self::schema::entries::dsl as e_dsl;
+        use self::schema::entry_category_relations::dsl as e_c_dsl;
+
+        // The query narrows down the candidates,
+        // the exact filtering happens below.
+        let mut query = e_dsl::entries
+            .filter(e_dsl::current.eq(true))
+            .into_boxed();
+
+        if let Some(ref bbox) = params.bbox {
+            query = query.filter(e_dsl::lat.between(bbox.south_west.lat, bbox.north_east.lat));
+            query = if bbox.south_west.lng <= bbox.north_east.lng {
+                query.filter(e_dsl::lng.between(bbox.south_west.lng, bbox.north_east.lng))
+            } else {
+                // The bbox spans the 180° meridian
+                query.filter(
+                    e_dsl::lng
+                        .ge(bbox.south_west.lng)
+                        .or(e_dsl::lng.le(bbox.north_east.lng)),
+                )
+            };
+        }
+
+        if !params.include_archived {
+            query = query.filter(e_dsl::archived.is_null());
+        }
+
+        if let Some(after) = params.created_after {
+            query = query.filter(e_dsl::created.ge(after as i64));
+        }
+        if let Some(before) = params.created_before {
+            query = query.filter(e_dsl::created.le(before as i64));
+        }
+
+        if let Some(ref cat_ids) = params.categories {
+            query = query.filter(
+                e_dsl::id.eq_any(
+                    e_c_dsl::entry_category_relations
+                        .select(e_c_dsl::entry_id)
+                        .filter(e_c_dsl::category_id.eq_any(cat_ids.clone())),
+                ),
+            );
+        }
+
+        if let Some(by_text_or_tags) = text_or_tags_filter(&params.text, &params.tags) {
+            query = query.filter(by_text_or_tags);
+        }
+        // Excluded tags and the location are only handled by the exact filter.
+
+        let entries: Vec<models::Entry> = query.load(self)?;
+
+        let (cat_rels, tag_rels) = load_relations(self, &entries)?;
+
+        // Category names are only needed to match the search text.
+        let categories = if params.text.is_empty() {
+            vec![]
+        } else {
+            self.all_categories()?
+        };
+
+        let matches = filter::entries_by_search_params(params, &categories);
+
+        Ok(entries
+            .into_iter()
+            .map(|e| into_entry(e, &cat_rels, &tag_rels))
+            .filter(|e| matches(e))
+            .collect())
+    }
+
+    fn get_entries_by_bbox(&self, bbox: &Bbox) -> Result<Vec<Entry>> {
+        use self::schema::entries::dsl as e_dsl;
+
+        let query = e_dsl::entries
             .filter(e_dsl::current.eq(true))
             .filter(e_dsl::lat.between(bbox.south_west.lat, bbox.north_east.lat))
-            .filter(e_dsl::lng.between(bbox.south_west.lng, bbox.north_east.lng))
-            .load(self)?;
+            .into_boxed();
 
-        let cat_rels =
-            e_c_dsl::entry_category_relations.load::<models::EntryCategoryRelation>(self)?;
+        let query = if bbox.south_west.lng <= bbox.north_east.lng {
+            query.filter(e_dsl::lng.between(bbox.south_west.lng, bbox.north_east.lng))
+        } else {
+            // The bbox spans the 180° meridian
+            query.filter(
+                e_dsl::lng
+                    .ge(bbox.south_west.lng)
+                    .or(e_dsl::lng.le(bbox.north_east.lng)),
+            )
+        };
 
-        let tag_rels = e_t_dsl::entry_tag_relations.load::<models::EntryTagRelation>(self)?;
+        let entries: Vec<models::Entry> = query.load(self)?;
+
+        let (cat_rels, tag_rels) = load_relations(self, &entries)?;
 
         Ok(entries
             .into_iter()
-            .map(|e| {
-                let cats = cat_rels
-                    .iter()
-                    .filter(|r| r.entry_id == e.id)
-                    .filter(|r| r.entry_version == e.version)
-                    .map(|r| &r.category_id)
-                    .cloned()
-                    .collect();
-                let tags = tag_rels
-                    .iter()
-                    .filter(|r| r.entry_id == e.id)
-                    .filter(|r| r.entry_version == e.version)
-                    .map(|r| &r.tag_id)
-                    .cloned()
-                    .collect();
-                Entry {
-                    id: e.id,
-                    osm_node: e.osm_node.map(|x| x as u64),
-                    created: e.created as u64,
-                    version: e.version as u64,
-                    title: e.title,
-                    description: e.description,
-                    lat: e.lat as f64,
-                    lng: e.lng as f64,
-                    street: e.street,
-                    zip: e.zip,
-                    city: e.city,
-                    country: e.country,
-                    email: e.email,
-                    telephone: e.telephone,
-                    homepage: e.homepage,
-                    categories: cats,
-                    tags: tags,
-                    license: e.license,
-                }
-            })
+            .map(|e| into_entry(e, &cat_rels, &tag_rels))
             .collect())
     }
 
@@ -295,6 +542,99 @@ impl Db for SqliteConnection {
         Ok(User::from(u))
     }
 
+    fn get_password_reset(&self, token: &str) -> Result<PasswordReset> {
+        use self::schema::password_resets::dsl::password_resets;
+        let r: models::PasswordReset = password_resets.find(token).first(self)?;
+        Ok(PasswordReset::from(r))
+    }
+
+    fn get_rating(&self, id: &str) -> Result<Rating> {
+        use self::schema::ratings::dsl::ratings;
+        let r: models::Rating = ratings.find(id).first(self)?;
+        Ok(Rating::from(r))
+    }
+
+    fn get_ratings(&self, ids: &[String]) -> Result<Vec<Rating>> {
+        use self::schema::ratings::dsl::*;
+        Ok(ratings
+            .filter(id.eq_any(ids))
+            .load::<models::Rating>(self)?
+            .into_iter()
+            .map(Rating::from)
+            .collect())
+    }
+
+    fn get_ratings_by_entry_ids(&self, ids: &[String]) -> Result<Vec<Rating>> {
+        use self::schema::ratings::dsl::*;
+        let mut found = vec![];
+        for chunk in ids.chunks(MAX_IDS_PER_QUERY) {
+            found.extend(
+                ratings
+                    .filter(entry_id.eq_any(chunk))
+                    .load::<models::Rating>(self)?
+                    .into_iter()
+                    .map(Rating::from),
+            );
+        }
+        Ok(found)
+    }
+
+    fn get_comment(&self, id: &str) -> Result<Comment> {
+        use self::schema::comments::dsl::comments;
+        let c: models::Comment = comments.find(id).first(self)?;
+        Ok(Comment::from(c))
+    }
+
+    fn get_comments_by_rating_ids(&self, ids: &[String]) -> Result<Vec<Comment>> {
+        use self::schema::comments::dsl::*;
+        Ok(comments
+            .filter(rating_id.eq_any(ids))
+            .load::<models::Comment>(self)?
+            .into_iter()
+            .map(Comment::from)
+            .collect())
+    }
+
+    fn count_entries(&self) -> Result<usize> {
+        use self::schema::entries::dsl;
+        let count: i64 = dsl::entries
+            .filter(dsl::current.eq(true))
+            .filter(dsl::archived.is_null())
+            .count()
+            .get_result(self)?;
+        Ok(count as usize)
+    }
+
+    fn count_tags(&self) -> Result<usize> {
+        use self::schema::tags::dsl;
+        let count: i64 = dsl::tags.count().get_result(self)?;
+        Ok(count as usize)
+    }
+
+    fn get_entries(&self, ids: &[String]) -> Result<Vec<Entry>> {
+        use self::schema::entries::dsl as e_dsl;
+        use self::schema::entry_category_relations::dsl as e_c_dsl;
+        use self::schema::entry_tag_relations::dsl as e_t_dsl;
+
+        let entries: Vec<models::Entry> = e_dsl::entries
+            .filter(e_dsl::id.eq_any(ids))
+            .filter(e_dsl::current.eq(true))
+            .load(self)?;
+
+        let cat_rels = e_c_dsl::entry_category_relations
+            .filter(e_c_dsl::entry_id.eq_any(ids))
+            .load::<models::EntryCategoryRelation>(self)?;
+
+        let tag_rels = e_t_dsl::entry_tag_relations
+            .filter(e_t_dsl::entry_id.eq_any(ids))
+            .load::<models::EntryTagRelation>(self)?;
+
+        Ok(entries
+            .into_iter()
+            .map(|e| into_entry(e, &cat_rels, &tag_rels))
+            .collect())
+    }
+
     fn all_entries(&self) -> Result<Vec<Entry>> {
         use self::schema::entries::dsl as e_dsl;
         use self::schema::entry_category_relations::dsl as e_c_dsl;
@@ -310,42 +650,7 @@ impl Db for SqliteConnection {
 
         Ok(entries
             .into_iter()
-            .map(|e| {
-                let cats = cat_rels
-                    .iter()
-                    .filter(|r| r.entry_id == e.id)
-                    .filter(|r| r.entry_version == e.version)
-                    .map(|r| &r.category_id)
-                    .cloned()
-                    .collect();
-                let tags = tag_rels
-                    .iter()
-                    .filter(|r| r.entry_id == e.id)
-                    .filter(|r| r.entry_version == e.version)
-                    .map(|r| &r.tag_id)
-                    .cloned()
-                    .collect();
-                Entry {
-                    id: e.id,
-                    osm_node: e.osm_node.map(|x| x as u64),
-                    created: e.created as u64,
-                    version: e.version as u64,
-                    title: e.title,
-                    description: e.description,
-                    lat: e.lat as f64,
-                    lng: e.lng as f64,
-                    street: e.street,
-                    zip: e.zip,
-                    city: e.city,
-                    country: e.country,
-                    email: e.email,
-                    telephone: e.telephone,
-                    homepage: e.homepage,
-                    categories: cats,
-                    tags: tags,
-                    license: e.license,
-                }
-            })
+            .map(|e| into_entry(e, &cat_rels, &tag_rels))
             .collect())
     }
     fn all_categories(&self) -> Result<Vec<Category>> {
@@ -405,7 +710,7 @@ impl Db for SqliteConnection {
             })
             .collect();
 
-        self.transaction::<_, diesel::result::Error, _>(|| {
+        Connection::transaction::<_, diesel::result::Error, _>(self, || {
             unset_current_on_all_entries(&self, &e.id)?;
             diesel::insert_into(schema::entries::table)
                 .values(&e)
@@ -423,6 +728,51 @@ impl Db for SqliteConnection {
         Ok(())
     }
 
+    fn update_user(&mut self, u: &User) -> Result<()> {
+        use self::schema::users::dsl;
+        let count = diesel::update(dsl::users.find(&u.username))
+            .set((
+                dsl::password.eq(&u.password),
+                dsl::email.eq(&u.email),
+                dsl::email_confirmed.eq(u.email_confirmed),
+            ))
+            .execute(self)?;
+        if count == 0 {
+            return Err(RepoError::NotFound);
+        }
+        Ok(())
+    }
+
+    fn update_comment(&mut self, c: &Comment) -> Result<()> {
+        use self::schema::comments::dsl;
+        let count = diesel::update(dsl::comments.find(&c.id))
+            .set(dsl::text.eq(&c.text))
+            .execute(self)?;
+        if count == 0 {
+            return Err(RepoError::NotFound);
+        }
+        Ok(())
+    }
+
+    fn update_rating(&mut self, r: &Rating) -> Result<()> {
+        use self::schema::ratings::dsl;
+        let r = models::Rating::from(r.clone());
+        let count = diesel::update(dsl::ratings.find(&r.id))
+            .set((
+                dsl::title.eq(&r.title),
+                dsl::value.eq(r.value),
+                dsl::context.eq(&r.context),
+                dsl::source.eq(&r.source),
+                dsl::entry_id.eq(&r.entry_id),
+                dsl::created_by.eq(&r.created_by),
+            ))
+            .execute(self)?;
+        if count == 0 {
+            return Err(RepoError::NotFound);
+        }
+        Ok(())
+    }
+
     fn import_multiple_entries(&mut self, new_entries: &[Entry]) -> Result<()> {
         let imports: Vec<_> = new_entries
             .into_iter()
@@ -448,7 +798,7 @@ impl Db for SqliteConnection {
                 (new_entry, cat_rels, tag_rels)
             })
             .collect();
-        self.transaction::<_, diesel::result::Error, _>(|| {
+        Connection::transaction::<_, diesel::result::Error, _>(self, || {
             for (new_entry, cat_rels, tag_rels) in imports {
                 unset_current_on_all_entries(&self, &new_entry.id)?;
                 diesel::insert_into(schema::entries::table)
@@ -490,4 +840,48 @@ impl Db for SqliteConnection {
         })?;
         Ok(())
     }
+
+    fn transaction<T, E, F>(&mut self, f: F) -> result::Result<T, E>
+    where
+        E: From<RepoError>,
+        F: FnOnce(&mut Self) -> result::Result<T, E>,
+    {
+        // Connection::transaction only hands out a shared reference,
+        // so we drive the transaction manager by hand.
+        // Nested calls end up in savepoints.
+        {
+            let conn: &SqliteConnection = self;
+            conn.transaction_manager()
+                .begin_transaction(conn)
+                .map_err(RepoError::from)?;
+        }
+        let res = panic::catch_unwind(AssertUnwindSafe(|| f(self)));
+        let res = match res {
+            Ok(res) => res,
+            Err(cause) => {
+                // The pooled connection must not stay within the transaction
+                let conn: &SqliteConnection = self;
+                if let Err(err) = conn.transaction_manager().rollback_transaction(conn) {
+                    error!("Could not roll back a transaction: {}", err);
+                }
+                panic::resume_unwind(cause);
+            }
+        };
+        match res {
+            Ok(value) => {
+                let conn: &SqliteConnection = self;
+                conn.transaction_manager()
+                    .commit_transaction(conn)
+                    .map_err(RepoError::from)?;
+                Ok(value)
+            }
+            Err(err) => {
+                let conn: &SqliteConnection = self;
+                conn.transaction_manager()
+                    .rollback_transaction(conn)
+                    .map_err(RepoError::from)?;
+                Err(err)
+            }
+        }
+    }
 }
@@ -20,6 +20,11 @@ pub struct Entry {
     pub telephone: Option<String>,
     pub homepage: Option<String>,
     pub license: Option<String>,
+    pub created_by: Option<String>,
+    pub updated: Option<i64>,
+    pub archived: Option<i64>,
+    pub opening_hours: Option<String>,
+    pub image_urls: Option<String>,
 }
 
 #[derive(Queryable, Insertable)]
@@ -86,6 +91,7 @@ pub struct Rating {
     pub context: String,
     pub source: Option<String>,
     pub entry_id: String,
+    pub created_by: Option<String>,
 }
 
 #[derive(Queryable, Insertable, Associations)]
@@ -99,3 +105,12 @@ pub struct BboxSubscription {
     pub north_east_lng: f64,
     pub username: String,
 }
+
+#[derive(Queryable, Insertable, Associations)]
+#[table_name = "password_resets"]
+#[belongs_to(User, foreign_key = "username")]
+pub struct PasswordReset {
+    pub token: String,
+    pub username: String,
+    pub created: i64,
+}
@@ -46,6 +46,11 @@ table! {
         telephone -> Nullable<Text>,
         homepage -> Nullable<Text>,
         license -> Nullable<Text>,
+        created_by -> Nullable<Text>,
+        updated -> Nullable<BigInt>,
+        archived -> Nullable<BigInt>,
+        opening_hours -> Nullable<Text>,
+        image_urls -> Nullable<Text>,
     }
 }
 
@@ -74,6 +79,15 @@ table! {
         context -> Text,
         source -> Nullable<Text>,
         entry_id -> Text,
+        created_by -> Nullable<Text>,
+    }
+}
+
+table! {
+    password_resets (token) {
+        token -> Text,
+        username -> Text,
+        created -> BigInt,
     }
 }
 
@@ -97,6 +111,7 @@ joinable!(bbox_subscriptions -> users (username));
 joinable!(comments -> ratings (rating_id));
 joinable!(entry_category_relations -> categories (category_id));
 joinable!(entry_tag_relations -> tags (tag_id));
+joinable!(password_resets -> users (username));
 
 allow_tables_to_appear_in_same_query!(
     bbox_subscriptions,
@@ -105,6 +120,7 @@ allow_tables_to_appear_in_same_query!(
     entries,
     entry_category_relations,
     entry_tag_relations,
+    password_resets,
     ratings,
     tags,
     users,
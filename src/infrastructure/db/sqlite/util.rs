@@ -1,6 +1,21 @@
 use entities as e;
 use super::models::*;
 use std::str::FromStr;
+use serde_json;
+
+// The URLs of the images are stored as a JSON array
+pub fn image_urls_to_text(urls: Vec<String>) -> Option<String> {
+    if urls.is_empty() {
+        None
+    } else {
+        serde_json::to_string(&urls).ok()
+    }
+}
+
+pub fn image_urls_from_text(text: Option<String>) -> Vec<String> {
+    text.and_then(|t| serde_json::from_str(&t).ok())
+        .unwrap_or_else(|| vec![])
+}
 
 impl From<e::Entry> for Entry {
     fn from(e: e::Entry) -> Entry {
@@ -21,6 +36,11 @@ impl From<e::Entry> for Entry {
             telephone,
             homepage,
             license,
+            created_by,
+            updated,
+            archived,
+            opening_hours,
+            image_urls,
             ..
         } = e;
 
@@ -42,6 +62,11 @@ impl From<e::Entry> for Entry {
             telephone,
             homepage,
             license,
+            created_by,
+            updated: updated.map(|x| x as i64),
+            archived: archived.map(|x| x as i64),
+            opening_hours,
+            image_urls: image_urls_to_text(image_urls),
         }
     }
 }
@@ -174,6 +199,7 @@ impl From<Rating> for e::Rating {
             context,
             value,
             source,
+            created_by,
         } = r;
         e::Rating {
             id,
@@ -183,6 +209,7 @@ impl From<Rating> for e::Rating {
             value: value as i8,
             context: context.parse().unwrap(),
             source,
+            created_by,
         }
     }
 }
@@ -197,6 +224,7 @@ impl From<e::Rating> for Rating {
             value,
             source,
             entry_id,
+            created_by,
         } = r;
         Rating {
             id,
@@ -206,6 +234,7 @@ impl From<e::Rating> for Rating {
             context: context.into(),
             source,
             entry_id,
+            created_by,
         }
     }
 }
@@ -251,6 +280,36 @@ impl From<e::BboxSubscription> for BboxSubscription {
     }
 }
 
+impl From<PasswordReset> for e::PasswordReset {
+    fn from(r: PasswordReset) -> e::PasswordReset {
+        let PasswordReset {
+            token,
+            username,
+            created,
+        } = r;
+        e::PasswordReset {
+            token,
+            username,
+            created: created as u64,
+        }
+    }
+}
+
+impl From<e::PasswordReset> for PasswordReset {
+    fn from(r: e::PasswordReset) -> PasswordReset {
+        let e::PasswordReset {
+            token,
+            username,
+            created,
+        } = r;
+        PasswordReset {
+            token,
+            username,
+            created: created as i64,
+        }
+    }
+}
+
 impl From<e::RatingContext> for String {
     fn from(context: e::RatingContext) -> String {
         match context {
@@ -53,5 +53,8 @@ quick_error!{
         Toml(err: ::toml::de::Error){
             from()
         }
+        Jwt(err: ::jsonwebtoken::errors::Error){
+            from()
+        }
     }
 }
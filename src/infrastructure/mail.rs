@@ -1,13 +1,109 @@
+use std::env;
 use std::process::{Command, Stdio};
 use std::io::prelude::*;
 use std::io::{Error, ErrorKind, Result};
 use chrono::*;
 use quoted_printable::encode;
 use fast_chemail::is_valid_email;
+use uuid::Uuid;
 
-const FROM_ADDRESS: &str = "\"Karte von morgen\" <no-reply@kartevonmorgen.org>";
+const EMAIL_FROM_ENV: &str = "OFDB_EMAIL_FROM";
+const EMAIL_REPLY_TO_ENV: &str = "OFDB_EMAIL_REPLY_TO";
+const DEFAULT_FROM: &str = "\"Karte von morgen\" <no-reply@kartevonmorgen.org>";
 
-pub fn create(to: &[String], subject: &str, body: &str) -> Result<String> {
+/// The `From` and the optional `Reply-To` header of all mails
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sender {
+    from: String,
+    reply_to: Option<String>,
+}
+
+// The address of `"Name" <address>` or of a plain address
+fn address(mailbox: &str) -> &str {
+    let mailbox = mailbox.trim();
+    match (mailbox.rfind('<'), mailbox.ends_with('>')) {
+        (Some(start), true) => mailbox[start + 1..mailbox.len() - 1].trim(),
+        _ => mailbox,
+    }
+}
+
+fn check_mailbox(name: &str, mailbox: &str) -> Result<String> {
+    // Line breaks would add headers
+    if mailbox.contains('\r') || mailbox.contains('\n') || !is_valid_email(address(mailbox)) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid email address in {}: '{}'", name, mailbox),
+        ));
+    }
+    Ok(mailbox.trim().into())
+}
+
+impl Sender {
+    pub fn new(from: &str, reply_to: Option<&str>) -> Result<Sender> {
+        let from = check_mailbox(EMAIL_FROM_ENV, from)?;
+        let reply_to = match reply_to {
+            Some(r) => Some(check_mailbox(EMAIL_REPLY_TO_ENV, r)?),
+            None => None,
+        };
+        Ok(Sender { from, reply_to })
+    }
+
+    /// Reads `OFDB_EMAIL_FROM` (default: `no-reply@kartevonmorgen.org`)
+    /// and the optional `OFDB_EMAIL_REPLY_TO`,
+    /// e.g. `"Karte von morgen" <info@kartevonmorgen.org>`.
+    pub fn from_env() -> Result<Sender> {
+        let var = |name: &str| {
+            env::var(name)
+                .ok()
+                .and_then(|v| if v.trim().is_empty() { None } else { Some(v) })
+        };
+        let from = var(EMAIL_FROM_ENV).unwrap_or_else(|| DEFAULT_FROM.into());
+        let reply_to = var(EMAIL_REPLY_TO_ENV);
+        Sender::new(&from, reply_to.as_ref().map(|r| r.as_str()))
+    }
+}
+
+impl Default for Sender {
+    fn default() -> Sender {
+        Sender {
+            from: DEFAULT_FROM.into(),
+            reply_to: None,
+        }
+    }
+}
+
+/// Creates a `multipart/alternative` mail with a plain text and an HTML part
+pub fn create_multipart(
+    sender: &Sender,
+    to: &[String],
+    subject: &str,
+    text: &str,
+    html: &str,
+) -> Result<String> {
+    let boundary = format!("openfairdb-{}", Uuid::new_v4().simple());
+    let body = format!(
+        "--{boundary}\r\n\
+         Content-Type: text/plain; charset=utf-8\r\n\r\n\
+         {text}\r\n\
+         --{boundary}\r\n\
+         Content-Type: text/html; charset=utf-8\r\n\r\n\
+         {html}\r\n\
+         --{boundary}--\r\n",
+        boundary = boundary,
+        text = text,
+        html = html
+    );
+    let content_type = format!("multipart/alternative; boundary=\"{}\"", boundary);
+    create(sender, to, subject, &content_type, &body)
+}
+
+fn create(
+    sender: &Sender,
+    to: &[String],
+    subject: &str,
+    content_type: &str,
+    body: &str,
+) -> Result<String> {
     let to: Vec<_> = to.into_iter()
         .filter(|m| is_valid_email(m))
         .cloned()
@@ -27,18 +123,26 @@ pub fn create(to: &[String], subject: &str, body: &str) -> Result<String> {
         String::from_utf8_lossy(&encode(subject.as_bytes()))
     );
 
+    let reply_to = match sender.reply_to {
+        Some(ref r) => format!("Reply-To:{}\r\n", r),
+        None => String::new(),
+    };
+
     let email = format!(
         "Date:{date}\r\n\
          From:{from}\r\n\
+         {reply_to}\
          To:{to}\r\n\
          Subject:{subject}\r\n\
          MIME-Version: 1.0\r\n\
-         Content-Type: text/plain; charset=utf-8\r\n\r\n\
+         Content-Type: {content_type}\r\n\r\n\
          {body}",
         date = now.as_str(),
-        from = FROM_ADDRESS,
+        from = sender.from,
+        reply_to = reply_to,
         to = to.join(","),
         subject = subject,
+        content_type = content_type,
         body = body
     );
 
@@ -57,7 +161,13 @@ pub fn send(mail: &str) -> Result<()> {
         .as_mut()
         .ok_or_else(|| Error::new(ErrorKind::Other, "Could not get stdin"))?
         .write_all(mail.as_bytes())?;
-    child.wait_with_output()?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!("sendmail failed: {}", output.status),
+        ));
+    }
     Ok(())
 }
 
@@ -67,7 +177,13 @@ mod tests {
 
     #[test]
     fn create_simple_mail() {
-        let mail = create(&vec!["mail@test.org".into()], "My Subject", "Hello Mail").unwrap();
+        let mail = create(
+            &Sender::default(),
+            &vec!["mail@test.org".into()],
+            "My Subject",
+            "text/plain; charset=utf-8",
+            "Hello Mail",
+        ).unwrap();
         let expected = "From:\"Karte von morgen\" <no-reply@kartevonmorgen.org>\r\n\
                         To:mail@test.org\r\n\
                         Subject:=?UTF-8?Q?My Subject?=\r\n\
@@ -77,9 +193,65 @@ mod tests {
         assert!(mail.contains(expected));
     }
 
+    #[test]
+    fn create_multipart_mail() {
+        let mail = create_multipart(
+            &Sender::default(),
+            &vec!["mail@test.org".into()],
+            "My Subject",
+            "Hello Mail",
+            "<b>Hello</b> Mail",
+        ).unwrap();
+        assert!(mail.contains("Content-Type: multipart/alternative; boundary=\"openfairdb-"));
+        let boundary = mail.split("boundary=\"").nth(1).unwrap().split('"').next().unwrap();
+        let parts: Vec<_> = mail.split(&format!("--{}", boundary)).collect();
+        assert_eq!(parts.len(), 4);
+        assert_eq!(
+            parts[1],
+            "\r\nContent-Type: text/plain; charset=utf-8\r\n\r\nHello Mail\r\n"
+        );
+        assert_eq!(
+            parts[2],
+            "\r\nContent-Type: text/html; charset=utf-8\r\n\r\n<b>Hello</b> Mail\r\n"
+        );
+        assert_eq!(parts[3], "--\r\n");
+    }
+
     #[test]
     fn check_addresses() {
-        assert!(create(&vec![], "foo", "bar").is_err());
-        assert!(create(&vec!["not-valid".into()], "foo", "bar").is_err());
+        let sender = Sender::default();
+        assert!(create_multipart(&sender, &vec![], "foo", "bar", "bar").is_err());
+        assert!(create_multipart(&sender, &vec!["not-valid".into()], "foo", "bar", "bar").is_err());
+    }
+
+    #[test]
+    fn create_mail_with_reply_to() {
+        let sender = Sender::new(
+            "\"Foo\" <no-reply@foo.org>",
+            Some("Foo Team <team@foo.org>"),
+        ).unwrap();
+        let mail = create(
+            &sender,
+            &vec!["mail@test.org".into()],
+            "My Subject",
+            "text/plain; charset=utf-8",
+            "Hello Mail",
+        ).unwrap();
+        assert!(mail.contains(
+            "From:\"Foo\" <no-reply@foo.org>\r\n\
+             Reply-To:Foo Team <team@foo.org>\r\n\
+             To:mail@test.org\r\n"
+        ));
+    }
+
+    #[test]
+    fn check_sender_addresses() {
+        assert!(Sender::new(DEFAULT_FROM, None).is_ok());
+        assert!(Sender::new("no-reply@foo.org", Some("team@foo.org")).is_ok());
+        assert!(Sender::new("Foo <no-reply@foo.org>", None).is_ok());
+        assert!(Sender::new("Foo", None).is_err());
+        assert!(Sender::new("Foo <no-reply>", None).is_err());
+        assert!(Sender::new("no-reply@foo.org\r\nBcc: x@foo.org", None).is_err());
+        assert!(Sender::new("no-reply@foo.org", Some("team")).is_err());
     }
 }
@@ -1,32 +1,86 @@
 use rocket::response::{Responder, Response};
+use rocket::response::content::Content;
+use rocket::response::status::Created;
 use rocket;
 use rocket_contrib::Json;
 use rocket::request::{self, FromRequest, Request};
-use rocket::{Outcome, Route};
-use rocket::http::{Cookie, Cookies, Status};
+use rocket::{Outcome, Route, State};
+use rocket::http::{ContentType, Cookie, Cookies, Status};
 use adapters::json;
+use adapters::feed;
+use adapters::geojson;
+use adapters::gpx;
 use adapters::user_communication;
 use entities::*;
 use business::db::Db;
 use business::error::{Error, ParameterError, RepoError};
 use infrastructure::error::AppError;
 use serde_json::ser::to_string;
-use business::{geo, usecase};
-use business::duplicates::{self, DuplicateType};
+use business::{cluster, geo, usecase, validate};
+use business::geocode::Geocoder;
+use business::duplicates::DuplicateConfig;
+use business::filter::{self, Combination};
+use business::sort::SortOrder;
+use std::cmp;
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::time::Instant;
 use std::result;
-use super::util;
-use super::sqlite::DbConn;
+use super::auth::{self, Admins, JwtSecret};
+use super::cache::CategoryCache;
+use super::etag::{self, ETagged, IfNoneMatch};
+use super::idempotency::{IdempotencyKey, IdempotencyKeys, Reservation};
+use super::metrics::{Metrics, PoolUsage};
+use super::openapi;
+use super::ratelimit::{RateLimited, RateLimiter};
+use super::settings::Settings;
+use super::util::{self, Mailer};
+use super::sqlite::{ConnectionPool, DbConn};
 
 type Result<T> = result::Result<Json<T>, AppError>;
 
 const COOKIE_USER_KEY: &str = "user_id";
 
+/// The maximum number of items of a batch request
+/// (unless the rate limit allows fewer requests at once)
+const MAX_BATCH_SIZE: usize = 100;
+
+#[derive(FromForm, Clone)]
+struct DuplicateQuery {
+    max_distance: Option<f64>,
+    min_title_similarity: Option<f32>,
+    max_words_different: Option<u32>,
+}
+
+#[derive(FromForm, Clone)]
+struct BboxQuery {
+    bbox: String,
+}
+
+#[derive(FromForm, Clone)]
+struct PageQuery {
+    offset: Option<usize>,
+    limit: Option<usize>,
+}
+
 #[derive(FromForm, Clone)]
 struct SearchQuery {
     bbox: String,
     categories: Option<String>,
+    categories_mode: Option<String>,
     text: Option<String>,
     tags: Option<String>,
+    exclude_tags: Option<String>,
+    city: Option<String>,
+    country: Option<String>,
+    created_after: Option<String>,
+    created_before: Option<String>,
+    sort: Option<String>,
+    include_archived: Option<bool>,
+    include: Option<String>,
+    unit: Option<String>,
+    embed: Option<bool>,
+    grid: Option<f64>,
 }
 
 impl<'a, 'r> FromRequest<'a, 'r> for Login {
@@ -37,6 +91,12 @@ impl<'a, 'r> FromRequest<'a, 'r> for Login {
             .cookies()
             .get_private(COOKIE_USER_KEY)
             .and_then(|cookie| cookie.value().parse().ok())
+            .or_else(|| {
+                let header = request.headers().get_one("Authorization")?;
+                let token = auth::bearer_token(header)?;
+                let secret = request.guard::<State<JwtSecret>>().succeeded()?;
+                auth::verify_token(secret.get()?, token).ok()
+            })
             .map(Login);
         match user {
             Some(user) => Outcome::Success(user),
@@ -45,38 +105,116 @@ impl<'a, 'r> FromRequest<'a, 'r> for Login {
     }
 }
 
+#[derive(Debug)]
+struct Admin(String);
+
+impl<'a, 'r> FromRequest<'a, 'r> for Admin {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Admin, ()> {
+        let admins = match request.guard::<State<Admins>>() {
+            Outcome::Success(admins) => admins,
+            _ => return Outcome::Failure((Status::Forbidden, ())),
+        };
+        match Login::from_request(request) {
+            Outcome::Success(Login(username)) => {
+                if admins.contains(&username) {
+                    Outcome::Success(Admin(username))
+                } else {
+                    Outcome::Failure((Status::Forbidden, ()))
+                }
+            }
+            Outcome::Failure(f) => Outcome::Failure(f),
+            Outcome::Forward(f) => Outcome::Forward(f),
+        }
+    }
+}
+
 pub fn routes() -> Vec<Route> {
     routes![
         login,
         logout,
         delete_user,
         confirm_email_address,
+        change_password,
+        request_password_reset,
+        reset_password,
         subscribe_to_bbox,
         get_bbox_subscriptions,
         unsubscribe_all_bboxes,
+        get_subscriptions,
+        delete_subscriptions,
         get_entry,
+        get_all_entries,
+        get_all_entries_page,
+        get_entry_history,
         post_entry,
+        post_entries,
         post_user,
         post_rating,
+        post_ratings,
+        delete_rating,
+        put_comment,
         put_entry,
+        patch_entry,
+        delete_entry,
+        merge_entries,
+        archive_entry,
+        unarchive_entry,
         get_user,
+        get_entries_by_user,
+        post_category,
         get_categories,
         get_tags,
+        get_tag_counts,
+        merge_tags,
         get_ratings,
+        get_rating,
+        get_rating_config,
+        get_rating_summary,
         get_category,
         get_search,
+        get_search_clusters,
+        get_export_geojson,
+        get_export_gpx,
+        get_feed,
         get_duplicates,
+        get_duplicates_with_config,
         get_count_entries,
         get_count_tags,
         get_version,
+        get_server_info,
+        get_geo_center,
+        get_metrics,
+        get_openapi,
     ]
 }
 
-#[get("/search?<search>")]
-fn get_search(db: DbConn, search: SearchQuery) -> Result<json::SearchResponse> {
+const TOTAL_COUNT_HEADER: &str = "X-Total-Count";
+
+/// Adds the number of all search results (before the invisible ones
+/// are limited) as `X-Total-Count` header to the response.
+struct TotalCount<R>(R, usize);
+
+impl<'r, R: Responder<'r>> Responder<'r> for TotalCount<R> {
+    fn respond_to(self, req: &Request) -> result::Result<Response<'r>, Status> {
+        let mut res = self.0.respond_to(req)?;
+        res.set_raw_header(TOTAL_COUNT_HEADER, self.1.to_string());
+        Ok(res)
+    }
+}
+
+fn search_entries(
+    db: &DbConn,
+    settings: &Settings,
+    search: SearchQuery,
+) -> result::Result<(Vec<Entry>, Vec<Entry>, usize), AppError> {
     let bbox = geo::extract_bbox(&search.bbox)
         .map_err(Error::Parameter)
         .map_err(AppError::Business)?;
+    validate::bbox(&bbox)
+        .map_err(Error::Parameter)
+        .map_err(AppError::Business)?;
 
     let categories = match search.categories {
         Some(cat_str) => Some(util::extract_ids(&cat_str)),
@@ -95,45 +233,211 @@ fn get_search(db: DbConn, search: SearchQuery) -> Result<json::SearchResponse> {
         }
     }
 
+    let mut exclude_tags = vec![];
+
+    if let Some(ref txt) = search.text {
+        exclude_tags = util::extract_excluded_hash_tags(txt);
+    }
+
+    if let Some(tags_str) = search.exclude_tags {
+        for t in util::extract_ids(&tags_str) {
+            exclude_tags.push(t);
+        }
+    }
+
     let text = match search.text {
         Some(txt) => util::remove_hash_tags(&txt),
         None => "".into(),
     };
 
-    let avg_ratings = match super::ENTRY_RATINGS.lock() {
-        Ok(guard) => guard,
-        Err(poisoned) => poisoned.into_inner(),
+    let categories_mode = match search.categories_mode {
+        Some(ref m) => m.parse::<Combination>()
+            .map_err(Error::Parameter)
+            .map_err(AppError::Business)?,
+        None => Combination::default(),
+    };
+
+    let created_after = match search.created_after {
+        Some(ref t) => Some(filter::timestamp(t, false)
+            .map_err(Error::Parameter)
+            .map_err(AppError::Business)?),
+        None => None,
+    };
+
+    let created_before = match search.created_before {
+        Some(ref t) => Some(filter::timestamp(t, true)
+            .map_err(Error::Parameter)
+            .map_err(AppError::Business)?),
+        None => None,
+    };
+
+    let sort = match search.sort {
+        Some(ref s) => s.parse::<SortOrder>()
+            .map_err(Error::Parameter)
+            .map_err(AppError::Business)?,
+        None => SortOrder::default(),
+    };
+
+    // The average ratings are only needed if we sort by them.
+    // Ratings weighted by their age are calculated for each search.
+    let no_ratings = HashMap::new();
+    let avg_ratings;
+    let entry_ratings = match sort {
+        SortOrder::Rating => {
+            avg_ratings = match super::ENTRY_RATINGS.lock() {
+                Ok(guard) => guard,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            &*avg_ratings
+        }
+        SortOrder::RatingRecent | SortOrder::Distance | SortOrder::Relevance => &no_ratings,
     };
 
     let req = usecase::SearchRequest {
         bbox,
         categories,
+        categories_mode,
         text,
         tags,
-        entry_ratings: &*avg_ratings,
+        exclude_tags,
+        city: search.city,
+        country: search.country,
+        created_after,
+        created_before,
+        entry_ratings,
+        sort,
+        include_archived: search.include_archived.unwrap_or(false),
+        max_invisible_results: settings.max_invisible_results,
+        rating_half_life_days: settings.rating_half_life_days,
     };
 
-    let (visible, invisible) = usecase::search(&*db, &req)?;
+    Ok(usecase::search(&**db, &req)?)
+}
 
-    let visible = visible
-        .into_iter()
-        .map(|e| json::EntryIdWithCoordinates {
-            id: e.id,
-            lat: e.lat,
-            lng: e.lng,
-        })
-        .collect();
+#[get("/search?<search>")]
+fn get_search(
+    db: DbConn,
+    settings: State<Settings>,
+    search: SearchQuery,
+) -> result::Result<TotalCount<Json<json::SearchResponse>>, AppError> {
+    let include = search
+        .include
+        .as_ref()
+        .map(|s| util::extract_ids(s))
+        .unwrap_or_else(Vec::new);
+    let center = if include.iter().any(|x| x == "distance") {
+        geo::extract_bbox(&search.bbox).ok().map(|b| geo::center(&b))
+    } else {
+        None
+    };
+    let unit = match search.unit {
+        Some(ref u) => u.parse::<json::DistanceUnit>()
+            .map_err(Error::Parameter)
+            .map_err(AppError::Business)?,
+        None => json::DistanceUnit::default(),
+    };
+
+    let embed = search.embed.unwrap_or(false);
+
+    let (visible, invisible, total_count) = search_entries(&db, &settings, search)?;
+
+    let visible = if embed {
+        with_ratings(&db, visible)?
+            .into_iter()
+            .map(json::SearchResult::Entry)
+            .collect()
+    } else {
+        visible
+            .into_iter()
+            .map(|e| {
+                let distance = center.as_ref().map(|c| {
+                    geo::distance(
+                        c,
+                        &Coordinate {
+                            lat: e.lat,
+                            lng: e.lng,
+                        },
+                    )
+                });
+                json::SearchResult::Id(json::EntryIdWithCoordinates::from_entry(e, distance, unit))
+            })
+            .collect()
+    };
 
     let invisible = invisible
         .into_iter()
-        .map(|e| json::EntryIdWithCoordinates {
-            id: e.id,
-            lat: e.lat,
-            lng: e.lng,
-        })
+        .map(|e| json::EntryIdWithCoordinates::from_entry(e, None, unit))
         .collect();
 
-    Ok(Json(json::SearchResponse { visible, invisible }))
+    Ok(TotalCount(
+        Json(json::SearchResponse { visible, invisible }),
+        total_count,
+    ))
+}
+
+/// Rocket only supports a single query parameter,
+/// so the `grid` size (in degrees) is a field of `SearchQuery`.
+#[get("/search/clusters?<search>")]
+fn get_search_clusters(
+    db: DbConn,
+    settings: State<Settings>,
+    search: SearchQuery,
+) -> Result<Vec<json::Cluster>> {
+    let grid = search
+        .grid
+        .ok_or(ParameterError::GridSize)
+        .and_then(cluster::grid_size)
+        .map_err(Error::Parameter)
+        .map_err(AppError::Business)?;
+    let (visible, _, _) = search_entries(&db, &settings, search)?;
+    Ok(Json(
+        cluster::clusters(&visible, grid)
+            .into_iter()
+            .map(json::Cluster::from)
+            .collect(),
+    ))
+}
+
+#[get("/export/geojson?<search>")]
+fn get_export_geojson(
+    db: DbConn,
+    settings: State<Settings>,
+    search: SearchQuery,
+) -> result::Result<TotalCount<Json<geojson::FeatureCollection>>, AppError> {
+    let (visible, _, total_count) = search_entries(&db, &settings, search)?;
+    Ok(TotalCount(
+        Json(geojson::FeatureCollection::from(visible)),
+        total_count,
+    ))
+}
+
+#[get("/feed?<search>")]
+fn get_feed(
+    db: DbConn,
+    settings: State<Settings>,
+    search: SearchQuery,
+) -> result::Result<Content<String>, AppError> {
+    let (visible, _, _) = search_entries(&db, &settings, search)?;
+    Ok(Content(
+        ContentType::new("application", "rss+xml"),
+        feed::rss(visible, &util::base_url(), util::feed_max_items()),
+    ))
+}
+
+#[get("/export/gpx?<search>")]
+fn get_export_gpx(
+    db: DbConn,
+    settings: State<Settings>,
+    search: SearchQuery,
+) -> result::Result<TotalCount<Content<String>>, AppError> {
+    let (visible, _, total_count) = search_entries(&db, &settings, search)?;
+    Ok(TotalCount(
+        Content(
+            ContentType::new("application", "gpx+xml"),
+            gpx::from_entries(&visible),
+        ),
+        total_count,
+    ))
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -144,38 +448,127 @@ struct UserId {
     u_id: String,
 }
 
+#[derive(Deserialize, Debug, Clone)]
+struct MergeEntries {
+    keep: String,
+    drop: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct NewCategory {
+    name: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct MergeTags {
+    from: String,
+    into: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct ChangePassword {
+    old_password: String,
+    new_password: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct PasswordResetRequest {
+    email: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct CommentText {
+    text: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct NewPassword {
+    token: String,
+    new_password: String,
+}
+
 #[get("/entries/<ids>")]
-fn get_entry(db: DbConn, ids: String) -> Result<Vec<json::Entry>> {
+fn get_entry(
+    db: DbConn,
+    settings: State<Settings>,
+    ids: String,
+    if_none_match: IfNoneMatch,
+) -> result::Result<ETagged<Json<Vec<json::Entry>>>, AppError> {
     let ids = util::extract_ids(&ids);
+    if ids.len() > settings.max_entry_ids {
+        return Err(Error::Parameter(ParameterError::TooManyIds).into());
+    }
     let entries = usecase::get_entries(&*db, &ids)?;
-    let ratings = usecase::get_ratings_by_entry_ids(&*db, &ids)?;
-    Ok(Json(
-        entries
-            .into_iter()
-            .map(|e| {
-                let r = ratings.get(&e.id).cloned().unwrap_or_else(|| vec![]);
-                json::Entry::from_entry_with_ratings(e, r)
-            })
-            .collect::<Vec<json::Entry>>(),
-    ))
+    let entries = with_ratings(&db, entries)?;
+    // Versions change with every update (including the tags)
+    // and the ratings only show up as ids with a count of their comments,
+    // so added or deleted ratings and comments change the tag as well.
+    let state: Vec<_> = entries
+        .iter()
+        .map(|e| (&e.id, e.version, &e.tags, &e.ratings, e.comment_count))
+        .collect();
+    let etag = etag::weak_etag(&state);
+    Ok(ETagged::new(Json(entries), etag, &if_none_match))
+}
+
+#[get("/entries", rank = 2)]
+fn get_all_entries(db: DbConn) -> Result<Vec<json::Entry>> {
+    let entries = usecase::get_entries_page(&*db, 0, None)?;
+    Ok(Json(with_ratings(&db, entries)?))
+}
+
+#[get("/entries?<q>")]
+fn get_all_entries_page(db: DbConn, q: PageQuery) -> Result<Vec<json::Entry>> {
+    let entries = usecase::get_entries_page(&*db, q.offset.unwrap_or(0), q.limit)?;
+    Ok(Json(with_ratings(&db, entries)?))
 }
 
-#[get("/duplicates")]
-fn get_duplicates(db: DbConn) -> Result<Vec<(String, String, DuplicateType)>> {
-    let entries = db.all_entries()?;
-    let ids = duplicates::find_duplicates(&entries);
-    Ok(Json(ids))
+fn with_ratings(
+    db: &DbConn,
+    mut entries: Vec<Entry>,
+) -> result::Result<Vec<json::Entry>, AppError> {
+    usecase::prune_categories(&mut entries, &db.all_categories()?);
+    let ids: Vec<_> = entries.iter().map(|e| e.id.clone()).collect();
+    let ratings = usecase::get_ratings_by_entry_ids(&**db, &ids)?;
+    let comment_counts = usecase::count_comments_by_entry_ids(&**db, &ratings)?;
+    Ok(entries
+        .into_iter()
+        .map(|e| {
+            let r = ratings.get(&e.id).cloned().unwrap_or_else(|| vec![]);
+            let c = comment_counts.get(&e.id).cloned().unwrap_or(0);
+            json::Entry::from_entry_with_ratings(e, r, c)
+        })
+        .collect())
+}
+
+#[get("/duplicates", rank = 2)]
+fn get_duplicates(db: DbConn) -> Result<Vec<usecase::Duplicate>> {
+    Ok(Json(usecase::find_duplicates(&*db, &DuplicateConfig::default())?))
+}
+
+#[get("/duplicates?<q>")]
+fn get_duplicates_with_config(db: DbConn, q: DuplicateQuery) -> Result<Vec<usecase::Duplicate>> {
+    let mut cfg = DuplicateConfig::default();
+    if let Some(d) = q.max_distance {
+        cfg.max_distance_m = d;
+    }
+    if let Some(s) = q.min_title_similarity {
+        cfg.min_title_similarity = s;
+    }
+    if let Some(w) = q.max_words_different {
+        cfg.max_words_different = w;
+    }
+    Ok(Json(usecase::find_duplicates(&*db, &cfg)?))
 }
 
 #[get("/count/entries")]
 fn get_count_entries(db: DbConn) -> Result<usize> {
-    let entries = db.all_entries()?;
-    Ok(Json(entries.len()))
+    Ok(Json(db.count_entries()?))
 }
 
 #[get("/count/tags")]
 fn get_count_tags(db: DbConn) -> Result<usize> {
-    Ok(Json(db.all_tags()?.len()))
+    Ok(Json(db.count_tags()?))
 }
 
 #[get("/server/version")]
@@ -183,14 +576,60 @@ fn get_version() -> &'static str {
     env!("CARGO_PKG_VERSION")
 }
 
+#[get("/server/info")]
+fn get_server_info() -> Json<json::ServerInfo> {
+    Json(json::ServerInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        build_timestamp: option_env!("BUILD_TIMESTAMP").and_then(|t| t.parse().ok()),
+        git_commit_hash: option_env!("GIT_COMMIT_HASH"),
+        base_url: util::base_url(),
+    })
+}
+
+#[get("/openapi.json")]
+fn get_openapi() -> Content<&'static str> {
+    Content(ContentType::JSON, openapi::SPEC)
+}
+
+#[get("/geo/center?<q>")]
+fn get_geo_center(q: BboxQuery) -> Result<json::GeoCenter> {
+    let bbox = geo::extract_bbox(&q.bbox)
+        .map_err(Error::Parameter)
+        .map_err(AppError::Business)?;
+    Ok(Json(json::GeoCenter {
+        center: geo::center(&bbox),
+        extended_bbox: usecase::extend_bbox(&bbox),
+        south_west: bbox.south_west,
+        north_east: bbox.north_east,
+    }))
+}
+
+#[get("/metrics")]
+fn get_metrics(metrics: State<Metrics>, pool: State<ConnectionPool>) -> Content<String> {
+    let state = pool.state();
+    let usage = PoolUsage {
+        in_use: state.connections - state.idle_connections,
+        idle: state.idle_connections,
+    };
+    Content(ContentType::Plain, metrics.render(usage))
+}
+
 #[post("/users", format = "application/json", data = "<u>")]
-fn post_user(mut db: DbConn, u: Json<usecase::NewUser>) -> Result<()> {
+fn post_user(
+    mut db: DbConn,
+    _limit: RateLimited,
+    mailer: State<Mailer>,
+    u: Json<usecase::NewUser>,
+) -> Result<()> {
     let new_user = u.into_inner();
     usecase::create_new_user(&mut *db, new_user.clone())?;
     let user = db.get_user(&new_user.username)?;
-    let subject = "Karte von Morgen: bitte bestätige deine Email-Adresse";
-    let body = user_communication::email_confirmation_email(&user.id);
-    util::send_mails(&[user.email], subject, &body);
+    let email = user_communication::email_confirmation_email(
+        util::email_language(),
+        &util::base_url(),
+        &user.id,
+    );
+    mailer.send_mails(&[user.email], &email);
     Ok(Json(()))
 }
 
@@ -201,49 +640,140 @@ fn delete_user(mut db: DbConn, user: Login, u_id: String) -> Result<()> {
 }
 
 #[post("/ratings", format = "application/json", data = "<u>")]
-fn post_rating(mut db: DbConn, u: Json<usecase::RateEntry>) -> Result<()> {
-    let u = u.into_inner();
+fn post_rating(
+    mut db: DbConn,
+    _limit: RateLimited,
+    user: Option<Login>,
+    u: Json<usecase::RateEntry>,
+) -> Result<()> {
+    let mut u = u.into_inner();
+    u.user = user.map(|u| u.0);
     let e_id = u.entry.clone();
     usecase::rate_entry(&mut *db, u)?;
     super::calculate_rating_for_entry(&*db, &e_id)?;
     Ok(Json(()))
 }
 
-#[get("/ratings/<id>")]
+#[post("/ratings/batch", format = "application/json", data = "<ratings>")]
+fn post_ratings(
+    mut db: DbConn,
+    limit: RateLimited,
+    limiter: State<RateLimiter>,
+    user: Option<Login>,
+    ratings: Json<Vec<usecase::RateEntry>>,
+) -> Result<Vec<json::BatchResult>> {
+    let mut ratings = ratings.into_inner();
+    charge_batch(&limit, &limiter, ratings.len())?;
+    for r in &mut ratings {
+        r.user = user.as_ref().map(|u| u.0.clone());
+    }
+    let entry_ids: Vec<_> = ratings.iter().map(|r| r.entry.clone()).collect();
+    let results = usecase::rate_entries(&mut *db, ratings);
+    let mut rated_entry_ids = vec![];
+    let mut batch = vec![];
+    for (e_id, res) in entry_ids.into_iter().zip(results.into_iter()) {
+        match res {
+            Ok(id) => {
+                if !rated_entry_ids.contains(&e_id) {
+                    rated_entry_ids.push(e_id);
+                }
+                batch.push(json::BatchResult::Ok(id));
+            }
+            Err(err) => batch.push(json::BatchResult::Err(err.to_string())),
+        }
+    }
+    for e_id in &rated_entry_ids {
+        super::calculate_rating_for_entry(&*db, e_id)?;
+    }
+    Ok(Json(batch))
+}
+
+// Only the creator of a rating or an admin may change it
+fn check_rating_owner(
+    rating: &Rating,
+    user: &Login,
+    admin: &Option<Admin>,
+) -> result::Result<(), AppError> {
+    if rating.created_by.as_ref() != Some(&user.0) && admin.is_none() {
+        return Err(Error::Parameter(ParameterError::Forbidden).into());
+    }
+    Ok(())
+}
+
+#[delete("/ratings/<id>")]
+fn delete_rating(mut db: DbConn, user: Login, admin: Option<Admin>, id: String) -> Result<()> {
+    check_rating_owner(&db.get_rating(&id)?, &user, &admin)?;
+    let e_id = usecase::delete_rating(&mut *db, &id)?;
+    super::calculate_rating_for_entry(&*db, &e_id)?;
+    Ok(Json(()))
+}
+
+#[put("/comments/<id>", format = "application/json", data = "<c>")]
+fn put_comment(
+    mut db: DbConn,
+    user: Login,
+    admin: Option<Admin>,
+    id: String,
+    c: Json<CommentText>,
+) -> Result<()> {
+    let rating = db.get_rating(&db.get_comment(&id)?.rating_id)?;
+    check_rating_owner(&rating, &user, &admin)?;
+    usecase::update_comment(&mut *db, &id, &c.into_inner().text)?;
+    Ok(Json(()))
+}
+
+#[get("/ratings/config")]
+fn get_rating_config() -> Json<json::RatingConfig> {
+    Json(json::RatingConfig {
+        min: validate::RATING_VALUE_MIN,
+        max: validate::RATING_VALUE_MAX,
+    })
+}
+
+#[get("/entries/<id>/ratings/summary")]
+fn get_rating_summary(db: DbConn, id: String) -> Result<HashMap<RatingContext, f64>> {
+    Ok(Json(usecase::average_ratings_by_context(&*db, &id)?))
+}
+
+#[get("/ratings/<id>", rank = 2)]
 fn get_ratings(db: DbConn, id: String) -> Result<Vec<json::Rating>> {
     let ratings = usecase::get_ratings(&*db, &util::extract_ids(&id))?;
     let r_ids: Vec<String> = ratings.iter().map(|r| r.id.clone()).collect();
     let comments = usecase::get_comments_by_rating_ids(&*db, &r_ids)?;
     let result = ratings
         .into_iter()
-        .map(|x| json::Rating {
-            id: x.id.clone(),
-            created: x.created,
-            title: x.title,
-            value: x.value,
-            context: x.context,
-            source: x.source.unwrap_or_else(|| "".into()),
-            comments: comments
-                .get(&x.id)
-                .cloned()
-                .unwrap_or_else(|| vec![])
-                .into_iter()
-                .map(|c| json::Comment {
-                    id: c.id.clone(),
-                    created: c.created,
-                    text: c.text,
-                })
-                .collect(),
+        .map(|r| {
+            let c = comments.get(&r.id).cloned().unwrap_or_else(|| vec![]);
+            json::Rating::from_rating_with_comments(r, c)
         })
         .collect();
     Ok(Json(result))
 }
 
+#[get("/ratings/<id>/full")]
+fn get_rating(db: DbConn, id: String) -> Result<json::Rating> {
+    let rating = db.get_rating(&id)?;
+    let comments = usecase::get_comments_by_rating_ids(&*db, &[id])?
+        .remove(&rating.id)
+        .unwrap_or_else(|| vec![]);
+    Ok(Json(json::Rating::from_rating_with_comments(rating, comments)))
+}
+
 #[post("/login", format = "application/json", data = "<login>")]
-fn login(mut db: DbConn, mut cookies: Cookies, login: Json<usecase::Login>) -> Result<()> {
+fn login(
+    mut db: DbConn,
+    mut cookies: Cookies,
+    secret: State<JwtSecret>,
+    login: Json<usecase::Login>,
+) -> Result<Option<String>> {
     let username = usecase::login(&mut *db, &login.into_inner())?;
+    // Clients that can't handle cookies may use the returned token instead
+    let token = match secret.get() {
+        Some(secret) => Some(auth::create_token(secret, &username)?),
+        None => None,
+    };
     cookies.add_private(Cookie::new(COOKIE_USER_KEY, username));
-    Ok(Json(()))
+    Ok(Json(token))
 }
 
 #[post("/logout", format = "application/json")]
@@ -255,12 +785,46 @@ fn logout(mut cookies: Cookies) -> Result<()> {
 #[post("/confirm-email-address", format = "application/json", data = "<user>")]
 fn confirm_email_address(mut db: DbConn, user: Json<UserId>) -> Result<()> {
     let u_id = user.into_inner().u_id;
-    let u = db.confirm_email_address(&u_id)?;
-    if u.id == u_id {
-        Ok(Json(()))
-    } else {
-        Err(AppError::Business(Error::Repo(RepoError::NotFound)))
+    usecase::confirm_email(&mut *db, &u_id)?;
+    Ok(Json(()))
+}
+
+#[post("/users/change-password", format = "application/json", data = "<pw>")]
+fn change_password(mut db: DbConn, user: Login, pw: Json<ChangePassword>) -> Result<()> {
+    let pw = pw.into_inner();
+    usecase::change_password(&mut *db, &user.0, &pw.old_password, &pw.new_password)?;
+    Ok(Json(()))
+}
+
+#[post("/users/request-password-reset", format = "application/json", data = "<req>")]
+fn request_password_reset(
+    mut db: DbConn,
+    mailer: State<Mailer>,
+    req: Json<PasswordResetRequest>,
+) -> Result<()> {
+    let email = req.into_inner().email;
+    match usecase::request_password_reset(&mut *db, &email) {
+        Ok((username, token)) => {
+            let mail = user_communication::password_reset_email(
+                util::email_language(),
+                &util::base_url(),
+                &username,
+                &token,
+            );
+            mailer.send_mails(&[email], &mail);
+        }
+        // Don't reveal whether an email address is registered
+        Err(Error::Repo(RepoError::NotFound)) => {}
+        Err(err) => return Err(err.into()),
     }
+    Ok(Json(()))
+}
+
+#[post("/users/reset-password", format = "application/json", data = "<reset>")]
+fn reset_password(mut db: DbConn, reset: Json<NewPassword>) -> Result<()> {
+    let reset = reset.into_inner();
+    usecase::reset_password(&mut *db, &reset.token, &reset.new_password)?;
+    Ok(Json(()))
 }
 
 #[post("/subscribe-to-bbox", format = "application/json", data = "<coordinates>")]
@@ -275,70 +839,380 @@ fn subscribe_to_bbox(
     Ok(Json(()))
 }
 
+// Deprecated in favour of `DELETE /subscriptions`
 #[delete("/unsubscribe-all-bboxes")]
-fn unsubscribe_all_bboxes(mut db: DbConn, user: Login) -> Result<()> {
-    let Login(username) = user;
-    usecase::unsubscribe_all_bboxes_by_username(&mut *db, &username)?;
-    Ok(Json(()))
+fn unsubscribe_all_bboxes(db: DbConn, user: Login) -> Result<()> {
+    delete_subscriptions(db, user)
 }
 
+// Deprecated in favour of `GET /subscriptions`
 #[get("/bbox-subscriptions")]
 fn get_bbox_subscriptions(db: DbConn, user: Login) -> Result<Vec<json::BboxSubscription>> {
-    let Login(username) = user;
-    let user_subscriptions = usecase::get_bbox_subscriptions(&username, &*db)?
+    let user_subscriptions = get_subscriptions(db, user)?
+        .into_inner()
         .into_iter()
         .map(|s| json::BboxSubscription {
             id: s.id,
-            south_west_lat: s.bbox.south_west.lat,
-            south_west_lng: s.bbox.south_west.lng,
-            north_east_lat: s.bbox.north_east.lat,
-            north_east_lng: s.bbox.north_east.lng,
+            south_west_lat: s.south_west.lat,
+            south_west_lng: s.south_west.lng,
+            north_east_lat: s.north_east.lat,
+            north_east_lng: s.north_east.lng,
         })
         .collect();
     Ok(Json(user_subscriptions))
 }
 
+#[get("/subscriptions")]
+fn get_subscriptions(db: DbConn, user: Login) -> Result<Vec<json::Subscription>> {
+    let Login(username) = user;
+    let subscriptions = usecase::get_bbox_subscriptions(&username, &*db)?
+        .into_iter()
+        .map(|s| json::Subscription {
+            id: s.id,
+            south_west: s.bbox.south_west,
+            north_east: s.bbox.north_east,
+        })
+        .collect();
+    Ok(Json(subscriptions))
+}
+
+#[delete("/subscriptions")]
+fn delete_subscriptions(mut db: DbConn, user: Login) -> Result<()> {
+    let Login(username) = user;
+    usecase::unsubscribe_all_bboxes_by_username(&mut *db, &username)?;
+    Ok(Json(()))
+}
+
 #[get("/users/<username>", format = "application/json")]
 fn get_user(mut db: DbConn, user: Login, username: String) -> Result<json::User> {
     let (_, email) = usecase::get_user(&mut *db, &user.0, &username)?;
     Ok(Json(json::User { username, email }))
 }
 
+#[get("/entries/<id>/history")]
+fn get_entry_history(db: DbConn, id: String) -> Result<Vec<json::Entry>> {
+    let versions = usecase::get_entry_history(&*db, &id)?;
+    // Ratings belong to the entry and not to a single version
+    Ok(Json(
+        versions
+            .into_iter()
+            .map(|e| json::Entry::from_entry_with_ratings(e, vec![], 0))
+            .collect(),
+    ))
+}
+
+#[get("/users/<username>/entries")]
+fn get_entries_by_user(db: DbConn, user: Login, username: String) -> Result<Vec<json::Entry>> {
+    if user.0 != username {
+        return Err(Error::Parameter(ParameterError::Forbidden).into());
+    }
+    let entries = usecase::get_entries_by_user(&*db, &username)?;
+    Ok(Json(with_ratings(&db, entries)?))
+}
+
+/// A new entry is `201 Created`, an upserted one `200 OK`
+enum SavedEntry {
+    Created(Created<Json<json::Entry>>),
+    Updated(Option<Json<json::Entry>>),
+}
+
+impl<'r> Responder<'r> for SavedEntry {
+    fn respond_to(self, req: &rocket::Request) -> result::Result<Response<'r>, Status> {
+        match self {
+            SavedEntry::Created(res) => res.respond_to(req),
+            SavedEntry::Updated(res) => res.respond_to(req),
+        }
+    }
+}
+
+// Only admins may choose the id of an entry or update it with an upsert
+fn check_upsert(e: &usecase::NewEntry, admin: &Option<Admin>) -> result::Result<(), AppError> {
+    if (e.id.is_some() || e.upsert) && admin.is_none() {
+        return Err(Error::Parameter(ParameterError::Forbidden).into());
+    }
+    Ok(())
+}
+
+// The entry is stored already, so don't fail because of the notifications
+fn notify_upserted(
+    db: &mut DbConn,
+    mailer: &Mailer,
+    e: &usecase::NewEntry,
+    upserted: &usecase::Upserted,
+) {
+    match *upserted {
+        usecase::Upserted::Created(ref id) => {
+            if let Err(err) = util::notify_create_entry(&mut **db, mailer, e, id) {
+                warn!("Could not notify subscribers about new entry {}: {}", id, err);
+            }
+        }
+        usecase::Upserted::Updated(ref update, ref old) => {
+            if let Err(err) = util::notify_update_entry(&mut **db, mailer, update, old) {
+                warn!(
+                    "Could not notify subscribers about updated entry {}: {}",
+                    update.id, err
+                );
+            }
+        }
+    }
+}
+
+// A new entry has neither ratings nor comments
+fn created_entry(db: &DbConn, id: &str) -> result::Result<SavedEntry, AppError> {
+    let entry = json::Entry::from_entry_with_ratings(db.get_entry(id)?, vec![], 0);
+    Ok(SavedEntry::Created(Created(
+        format!("/entries/{}", id),
+        Some(Json(entry)),
+    )))
+}
+
 #[post("/entries", format = "application/json", data = "<e>")]
-fn post_entry(mut db: DbConn, e: Json<usecase::NewEntry>) -> Result<String> {
+fn post_entry(
+    mut db: DbConn,
+    _limit: RateLimited,
+    user: Option<Login>,
+    admin: Option<Admin>,
+    key: IdempotencyKey,
+    keys: State<IdempotencyKeys>,
+    mailer: State<Mailer>,
+    e: Json<usecase::NewEntry>,
+) -> result::Result<SavedEntry, AppError> {
     let e = e.into_inner();
-    let id = usecase::create_new_entry(&mut *db, e.clone())?;
-    let email_addresses = usecase::email_addresses_by_coordinate(&mut *db, &e.lat, &e.lng)?;
-    let all_categories = db.all_categories()?;
-    util::notify_create_entry(&email_addresses, &e, &id, all_categories);
-    Ok(Json(id))
+    check_upsert(&e, &admin)?;
+    let created_by = user.as_ref().map(|u| u.0.as_str());
+    let geocoder = util::geocoder();
+    let geocoder = geocoder.as_ref().map(|g| g as &Geocoder);
+    if e.upsert {
+        let upserted = usecase::upsert_entry(&mut *db, geocoder, e.clone(), created_by)?;
+        notify_upserted(&mut db, &mailer, &e, &upserted);
+        return match upserted {
+            usecase::Upserted::Created(id) => created_entry(&db, &id),
+            usecase::Upserted::Updated(update, _) => {
+                let mut entries = with_ratings(&db, vec![db.get_entry(&update.id)?])?;
+                Ok(SavedEntry::Updated(entries.pop().map(Json)))
+            }
+        };
+    }
+    let key = key.scoped(created_by);
+    if let Some(ref k) = key {
+        match keys.reserve(k.clone(), Instant::now()) {
+            Reservation::Reserved => {}
+            Reservation::InFlight => {
+                return Err(Error::Parameter(ParameterError::IdempotencyKeyInUse).into());
+            }
+            // A retry of a request that already created the entry
+            Reservation::Created(id) => {
+                let mut entries = with_ratings(&db, vec![db.get_entry(&id)?])?;
+                return Ok(SavedEntry::Created(Created(
+                    format!("/entries/{}", id),
+                    entries.pop().map(Json),
+                )));
+            }
+        }
+    }
+    let id = match usecase::create_new_entry(&mut *db, geocoder, e.clone(), created_by) {
+        Ok(id) => id,
+        Err(err) => {
+            // The client may retry with a valid entry
+            if let Some(ref k) = key {
+                keys.release(k);
+            }
+            return Err(err.into());
+        }
+    };
+    if let Some(k) = key {
+        keys.complete(k, id.clone(), Instant::now());
+    }
+    notify_upserted(&mut db, &mailer, &e, &usecase::Upserted::Created(id.clone()));
+    created_entry(&db, &id)
+}
+
+// Each item of a batch counts as a request; the guard charged the first one.
+// Batches that could never fit into the bucket of a client are too large.
+fn charge_batch(
+    limit: &RateLimited,
+    limiter: &RateLimiter,
+    len: usize,
+) -> result::Result<(), AppError> {
+    let max_len = limiter
+        .capacity()
+        .map(|c| cmp::min(c as usize, MAX_BATCH_SIZE))
+        .unwrap_or(MAX_BATCH_SIZE);
+    if len > max_len {
+        return Err(Error::Parameter(ParameterError::TooManyItems).into());
+    }
+    if !limit.charge(limiter, len.saturating_sub(1) as u32, Instant::now()) {
+        return Err(Error::Parameter(ParameterError::TooManyRequests).into());
+    }
+    Ok(())
+}
+
+#[post("/entries/batch", format = "application/json", data = "<entries>")]
+fn post_entries(
+    mut db: DbConn,
+    limit: RateLimited,
+    limiter: State<RateLimiter>,
+    user: Option<Login>,
+    admin: Option<Admin>,
+    mailer: State<Mailer>,
+    entries: Json<Vec<usecase::NewEntry>>,
+) -> Result<Vec<json::BatchResult>> {
+    let entries = entries.into_inner();
+    charge_batch(&limit, &limiter, entries.len())?;
+    for e in &entries {
+        check_upsert(e, &admin)?;
+    }
+    let created_by = user.as_ref().map(|u| u.0.as_str());
+    let geocoder = util::geocoder();
+    let geocoder = geocoder.as_ref().map(|g| g as &Geocoder);
+    let results = usecase::create_new_entries(&mut *db, geocoder, entries.clone(), created_by);
+    let mut batch = vec![];
+    for (e, res) in entries.iter().zip(results.into_iter()) {
+        match res {
+            Ok(upserted) => {
+                notify_upserted(&mut db, &mailer, e, &upserted);
+                let id = match upserted {
+                    usecase::Upserted::Created(id) => id,
+                    usecase::Upserted::Updated(update, _) => update.id,
+                };
+                batch.push(json::BatchResult::Ok(id));
+            }
+            Err(err) => batch.push(json::BatchResult::Err(err.to_string())),
+        }
+    }
+    Ok(Json(batch))
 }
 
 #[put("/entries/<id>", format = "application/json", data = "<e>")]
-fn put_entry(mut db: DbConn, id: String, e: Json<usecase::UpdateEntry>) -> Result<String> {
+fn put_entry(
+    mut db: DbConn,
+    mailer: State<Mailer>,
+    id: String,
+    e: Json<usecase::UpdateEntry>,
+) -> Result<String> {
     let e = e.into_inner();
+    let old = db.get_entry(&e.id)?;
+    let old = Coordinate {
+        lat: old.lat,
+        lng: old.lng,
+    };
     usecase::update_entry(&mut *db, e.clone())?;
-    let email_addresses = usecase::email_addresses_by_coordinate(&mut *db, &e.lat, &e.lng)?;
-    let all_categories = db.all_categories()?;
-    util::notify_update_entry(&email_addresses, &e, all_categories);
+    // The entry is stored already, so don't fail because of the notifications
+    if let Err(err) = util::notify_update_entry(&mut *db, &mailer, &e, &old) {
+        warn!("Could not notify subscribers about updated entry {}: {}", id, err);
+    }
+    Ok(Json(id))
+}
+
+#[patch("/entries/<id>", format = "application/json", data = "<e>")]
+fn patch_entry(
+    mut db: DbConn,
+    mailer: State<Mailer>,
+    id: String,
+    e: Json<usecase::PatchEntry>,
+) -> Result<String> {
+    let old = db.get_entry(&id)?;
+    let old = Coordinate {
+        lat: old.lat,
+        lng: old.lng,
+    };
+    let e = usecase::patch_entry(&mut *db, &id, e.into_inner())?;
+    // The entry is stored already, so don't fail because of the notifications
+    if let Err(err) = util::notify_update_entry(&mut *db, &mailer, &e, &old) {
+        warn!("Could not notify subscribers about updated entry {}: {}", id, err);
+    }
     Ok(Json(id))
 }
 
+#[post("/entries/<id>/archive")]
+fn archive_entry(mut db: DbConn, admin: Admin, id: String) -> Result<()> {
+    usecase::archive_entry(&mut *db, &id)?;
+    info!("{} archived entry {}", admin.0, id);
+    Ok(Json(()))
+}
+
+#[post("/entries/<id>/unarchive")]
+fn unarchive_entry(mut db: DbConn, admin: Admin, id: String) -> Result<()> {
+    usecase::unarchive_entry(&mut *db, &id)?;
+    info!("{} unarchived entry {}", admin.0, id);
+    Ok(Json(()))
+}
+
+#[post("/entries/merge", format = "application/json", data = "<m>")]
+fn merge_entries(mut db: DbConn, admin: Admin, m: Json<MergeEntries>) -> Result<()> {
+    let m = m.into_inner();
+    usecase::merge_entries(&mut *db, &m.keep, &m.drop)?;
+    info!("{} merged entry {} into {}", admin.0, m.drop, m.keep);
+    {
+        let mut avg_ratings = match super::ENTRY_RATINGS.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        avg_ratings.remove(&m.drop);
+    }
+    super::calculate_rating_for_entry(&*db, &m.keep)?;
+    Ok(Json(()))
+}
+
+#[delete("/entries/<id>")]
+fn delete_entry(mut db: DbConn, admin: Admin, id: String) -> Result<()> {
+    usecase::delete_entry(&mut *db, &id)?;
+    info!("{} deleted entry {}", admin.0, id);
+    let mut avg_ratings = match super::ENTRY_RATINGS.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    avg_ratings.remove(&id);
+    Ok(Json(()))
+}
+
 #[get("/tags")]
 fn get_tags(db: DbConn) -> Result<Vec<String>> {
     Ok(Json(db.all_tags()?.into_iter().map(|t| t.id).collect()))
 }
 
+#[get("/tags/counts")]
+fn get_tag_counts(db: DbConn) -> Result<Vec<json::TagCount>> {
+    let counts = usecase::get_tag_usage_counts(&*db)?;
+    Ok(Json(
+        counts
+            .into_iter()
+            .map(|(tag, count)| json::TagCount { tag, count })
+            .collect(),
+    ))
+}
+
+#[post("/tags/merge", format = "application/json", data = "<tags>")]
+fn merge_tags(mut db: DbConn, admin: Admin, tags: Json<MergeTags>) -> Result<()> {
+    let tags = tags.into_inner();
+    usecase::merge_tags(&mut *db, &tags.from, &tags.into)?;
+    info!("{} merged tag '{}' into '{}'", admin.0, tags.from, tags.into);
+    Ok(Json(()))
+}
+
+#[post("/categories", format = "application/json", data = "<c>")]
+fn post_category(
+    mut db: DbConn,
+    cache: State<CategoryCache>,
+    admin: Admin,
+    c: Json<NewCategory>,
+) -> Result<String> {
+    let id = usecase::create_category(&mut *db, &c.into_inner().name)?;
+    cache.invalidate();
+    info!("{} created category {}", admin.0, id);
+    Ok(Json(id))
+}
+
 #[get("/categories")]
-fn get_categories(db: DbConn) -> Result<Vec<Category>> {
-    let categories = db.all_categories()?;
+fn get_categories(db: DbConn, cache: State<CategoryCache>) -> Result<Vec<Category>> {
+    let categories = cache.categories(Instant::now(), || db.all_categories())?;
     Ok(Json(categories))
 }
 
 #[get("/categories/<id>")]
-fn get_category(db: DbConn, id: String) -> Result<String> {
+fn get_category(db: DbConn, cache: State<CategoryCache>, id: String) -> Result<String> {
     let ids = util::extract_ids(&id);
-    let categories = db.all_categories()?;
+    let categories = cache.categories(Instant::now(), || db.all_categories())?;
     let res = match ids.len() {
         0 => to_string(&categories),
 
@@ -358,31 +1232,86 @@ fn get_category(db: DbConn, id: String) -> Result<String> {
     Ok(Json(res))
 }
 
+fn json_response<'r>(status: Status, body: String) -> result::Result<Response<'r>, Status> {
+    Ok(Response::build()
+        .status(status)
+        .header(ContentType::JSON)
+        .sized_body(Cursor::new(body))
+        .finalize())
+}
+
+fn error_response<'r>(
+    status: Status,
+    error: &str,
+    message: String,
+) -> result::Result<Response<'r>, Status> {
+    let body = to_string(&json::Error {
+        error: error.into(),
+        message,
+    }).map_err(|_| Status::InternalServerError)?;
+    json_response(status, body)
+}
+
 impl<'r> Responder<'r> for AppError {
     fn respond_to(self, _: &rocket::Request) -> result::Result<Response<'r>, Status> {
         if let AppError::Business(ref err) = self {
             match *err {
                 Error::Parameter(ref err) => {
-                    return Err(match *err {
+                    if let Some(field) = err.field() {
+                        let body = to_string(&json::ValidationErrors {
+                            error: err.kind().into(),
+                            message: err.to_string(),
+                            errors: vec![json::FieldError {
+                                field: field.into(),
+                                message: err.to_string(),
+                            }],
+                        }).map_err(|_| Status::InternalServerError)?;
+                        return json_response(Status::UnprocessableEntity, body);
+                    }
+                    let status = match *err {
                         ParameterError::Credentials => Status::Unauthorized,
                         ParameterError::UserExists => <Status>::new(400, "UserExists"),
+                        ParameterError::CategoryExists => <Status>::new(400, "CategoryExists"),
+                        ParameterError::AlreadyRated => <Status>::new(400, "AlreadyRated"),
+                        ParameterError::SelfRating => <Status>::new(400, "SelfRating"),
                         ParameterError::EmailNotConfirmed => {
                             <Status>::new(403, "EmailNotConfirmed")
                         }
                         ParameterError::Forbidden => Status::Forbidden,
+                        ParameterError::IdempotencyKeyInUse => Status::Conflict,
+                        ParameterError::TooManyItems => <Status>::new(413, "Payload Too Large"),
+                        ParameterError::TooManyRequests => {
+                            <Status>::new(429, "Too Many Requests")
+                        }
                         _ => Status::BadRequest,
-                    })
+                    };
+                    return error_response(status, err.kind(), err.to_string());
                 }
-                Error::Repo(ref err) => {
-                    if let RepoError::NotFound = *err {
-                        return Err(Status::NotFound);
+                Error::Repo(ref err) => match *err {
+                    RepoError::NotFound => {
+                        return error_response(Status::NotFound, err.kind(), err.to_string());
                     }
-                }
+                    // Clients need the current version to retry their update
+                    RepoError::InvalidVersion(current_version) => {
+                        let body = to_string(&json::VersionConflict {
+                            error: err.kind().into(),
+                            message: err.to_string(),
+                            current_version,
+                        }).map_err(|_| Status::InternalServerError)?;
+                        return json_response(Status::Conflict, body);
+                    }
+                    _ => {}
+                },
                 _ => {}
             }
         }
         error!("Error: {}", self);
-        Err(Status::InternalServerError)
+        // The details are only logged
+        error_response(
+            Status::InternalServerError,
+            "internal",
+            "Internal server error".into(),
+        )
     }
 }
 
@@ -411,6 +1340,7 @@ mod tests {
     //        categories: None,
     //        text: None,
     //        tags: None,
+    //        sort: None,
     //    };
     //    b.iter(move || {
     //        let conn = pool.get().unwrap();
@@ -0,0 +1,153 @@
+//! Stateless authentication with JSON Web Tokens (HS256)
+//! as an alternative to the session cookie.
+
+use chrono::Utc;
+use jsonwebtoken::{self, Header, Validation};
+use jsonwebtoken::errors::Result;
+use std::env;
+
+const SECRET_ENV: &str = "JWT_SECRET";
+const ADMINS_ENV: &str = "ADMINS";
+const BEARER_PREFIX: &str = "Bearer ";
+
+// Tokens expire after 24 hours
+const TOKEN_LIFETIME: i64 = 24 * 60 * 60;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    sub: String,
+    exp: i64,
+}
+
+/// The secret to sign tokens with.
+/// Without a secret bearer authentication is disabled.
+#[derive(Debug, Default)]
+pub struct JwtSecret(Option<String>);
+
+impl JwtSecret {
+    pub fn new(secret: &str) -> JwtSecret {
+        if secret.is_empty() {
+            JwtSecret(None)
+        } else {
+            JwtSecret(Some(secret.into()))
+        }
+    }
+
+    /// Reads the secret once from `JWT_SECRET`
+    pub fn from_env() -> JwtSecret {
+        JwtSecret::new(&env::var(SECRET_ENV).unwrap_or_default())
+    }
+
+    pub fn get(&self) -> Option<&str> {
+        self.0.as_ref().map(|s| s.as_str())
+    }
+}
+
+pub fn create_token(secret: &str, username: &str) -> Result<String> {
+    encode_token(secret, username, Utc::now().timestamp() + TOKEN_LIFETIME)
+}
+
+fn encode_token(secret: &str, username: &str, exp: i64) -> Result<String> {
+    let claims = Claims {
+        sub: username.into(),
+        exp,
+    };
+    jsonwebtoken::encode(&Header::default(), &claims, secret.as_bytes())
+}
+
+/// Verifies signature and expiry of the token
+/// and returns the username it was issued for.
+pub fn verify_token(secret: &str, token: &str) -> Result<String> {
+    let data = jsonwebtoken::decode::<Claims>(token, secret.as_bytes(), &Validation::default())?;
+    Ok(data.claims.sub)
+}
+
+/// The usernames of the admins
+#[derive(Debug, Default)]
+pub struct Admins(Vec<String>);
+
+impl Admins {
+    /// Admins are configured as a comma separated list of usernames.
+    pub fn new(admins: &str) -> Admins {
+        Admins(
+            admins
+                .split(',')
+                .map(|a| a.trim())
+                .filter(|a| !a.is_empty())
+                .map(|a| a.to_string())
+                .collect(),
+        )
+    }
+
+    /// Reads the admins once from `ADMINS`
+    pub fn from_env() -> Admins {
+        Admins::new(&env::var(ADMINS_ENV).unwrap_or_default())
+    }
+
+    pub fn contains(&self, username: &str) -> bool {
+        self.0.iter().any(|a| a == username)
+    }
+}
+
+/// Extracts the token of an `Authorization: Bearer <token>` header.
+pub fn bearer_token(header: &str) -> Option<&str> {
+    if header.starts_with(BEARER_PREFIX) {
+        Some(header[BEARER_PREFIX.len()..].trim())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create_and_verify_token() {
+        let token = create_token("secret", "foo").unwrap();
+        assert_eq!(verify_token("secret", &token).unwrap(), "foo");
+    }
+
+    #[test]
+    fn reject_expired_token() {
+        let exp = Utc::now().timestamp() - 60;
+        let token = encode_token("secret", "foo", exp).unwrap();
+        assert!(verify_token("secret", &token).is_err());
+    }
+
+    #[test]
+    fn reject_tampered_token() {
+        let token = create_token("secret", "foo").unwrap();
+        assert!(verify_token("other secret", &token).is_err());
+
+        // Sign a token for "bar" and swap its payload into the token for "foo"
+        let other = create_token("secret", "bar").unwrap();
+        let parts: Vec<&str> = token.split('.').collect();
+        let other_parts: Vec<&str> = other.split('.').collect();
+        let tampered = format!("{}.{}.{}", parts[0], other_parts[1], parts[2]);
+        assert!(verify_token("secret", &tampered).is_err());
+    }
+
+    #[test]
+    fn an_empty_secret_disables_tokens() {
+        assert_eq!(JwtSecret::new("secret").get(), Some("secret"));
+        assert_eq!(JwtSecret::new("").get(), None);
+        assert_eq!(JwtSecret::default().get(), None);
+    }
+
+    #[test]
+    fn admin_list() {
+        assert!(Admins::new("foo").contains("foo"));
+        assert!(Admins::new("foo, bar").contains("bar"));
+        assert!(!Admins::new("foo,bar").contains("ba"));
+        assert!(!Admins::new("").contains(""));
+        assert!(!Admins::default().contains("foo"));
+    }
+
+    #[test]
+    fn extract_bearer_token() {
+        assert_eq!(bearer_token("Bearer abc.def.ghi"), Some("abc.def.ghi"));
+        assert_eq!(bearer_token("Basic Zm9vOmJhcg=="), None);
+        assert_eq!(bearer_token("abc.def.ghi"), None);
+    }
+}
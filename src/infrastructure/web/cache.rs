@@ -0,0 +1,134 @@
+use entities::Category;
+use std::env;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+const CATEGORY_CACHE_TTL_ENV: &str = "CATEGORY_CACHE_TTL";
+const DEFAULT_TTL_SECONDS: u64 = 60;
+
+/// Keeps the rarely changing categories in memory
+/// for a limited time, shared by all request handlers.
+pub struct CategoryCache {
+    ttl: Duration,
+    cached: RwLock<Option<(Instant, Vec<Category>)>>,
+}
+
+impl CategoryCache {
+    /// A time to live of 0 turns off the cache.
+    pub fn new(ttl: Duration) -> CategoryCache {
+        CategoryCache {
+            ttl,
+            cached: RwLock::new(None),
+        }
+    }
+
+    /// Reads the time to live in seconds from `CATEGORY_CACHE_TTL` (default: 60)
+    pub fn from_env() -> CategoryCache {
+        let seconds = env::var(CATEGORY_CACHE_TTL_ENV)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_TTL_SECONDS);
+        CategoryCache::new(Duration::from_secs(seconds))
+    }
+
+    /// The cached categories if they are younger than the time to live,
+    /// otherwise the freshly loaded ones.
+    pub fn categories<F, E>(&self, now: Instant, load: F) -> Result<Vec<Category>, E>
+    where
+        F: FnOnce() -> Result<Vec<Category>, E>,
+    {
+        {
+            let cached = match self.cached.read() {
+                Ok(cached) => cached,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            if let Some((loaded, ref categories)) = *cached {
+                if now >= loaded && now - loaded < self.ttl {
+                    return Ok(categories.clone());
+                }
+            }
+        }
+        let categories = load()?;
+        let mut cached = match self.cached.write() {
+            Ok(cached) => cached,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        *cached = Some((now, categories.clone()));
+        Ok(categories)
+    }
+
+    /// Forces the next lookup to load the categories,
+    /// e.g. after a category was created or changed.
+    pub fn invalidate(&self) {
+        let mut cached = match self.cached.write() {
+            Ok(cached) => cached,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        *cached = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn category(id: &str) -> Category {
+        Category {
+            id: id.into(),
+            created: 0,
+            version: 0,
+            name: id.into(),
+        }
+    }
+
+    #[test]
+    fn load_categories_only_once_within_the_ttl() {
+        let cache = CategoryCache::new(Duration::from_secs(60));
+        let loads = Cell::new(0);
+        let load = || -> Result<Vec<Category>, ()> {
+            loads.set(loads.get() + 1);
+            Ok(vec![category("a")])
+        };
+        let start = Instant::now();
+        assert_eq!(cache.categories(start, &load).unwrap().len(), 1);
+        let later = start + Duration::from_secs(59);
+        assert_eq!(cache.categories(later, &load).unwrap()[0].id, "a");
+        assert_eq!(loads.get(), 1);
+        let expired = start + Duration::from_secs(60);
+        assert!(cache.categories(expired, &load).is_ok());
+        assert_eq!(loads.get(), 2);
+    }
+
+    #[test]
+    fn reload_categories_after_invalidation() {
+        let cache = CategoryCache::new(Duration::from_secs(60));
+        let now = Instant::now();
+        let ids = |c: Vec<Category>| c.into_iter().map(|c| c.id).collect::<Vec<_>>();
+        let res: Result<_, ()> = cache.categories(now, || Ok(vec![category("a")]));
+        assert_eq!(ids(res.unwrap()), vec!["a"]);
+        let res: Result<_, ()> = cache.categories(now, || Ok(vec![category("b")]));
+        assert_eq!(ids(res.unwrap()), vec!["a"]);
+        cache.invalidate();
+        let res: Result<_, ()> = cache.categories(now, || Ok(vec![category("b")]));
+        assert_eq!(ids(res.unwrap()), vec!["b"]);
+    }
+
+    #[test]
+    fn do_not_cache_failures() {
+        let cache = CategoryCache::new(Duration::from_secs(60));
+        let now = Instant::now();
+        assert!(cache.categories(now, || Err(())).is_err());
+        let res: Result<_, ()> = cache.categories(now, || Ok(vec![category("a")]));
+        assert_eq!(res.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn zero_turns_off_the_cache() {
+        let cache = CategoryCache::new(Duration::from_secs(0));
+        let now = Instant::now();
+        let _: Result<_, ()> = cache.categories(now, || Ok(vec![category("a")]));
+        let res: Result<_, ()> = cache.categories(now, || Ok(vec![category("b")]));
+        assert_eq!(res.unwrap()[0].id, "b");
+    }
+}
@@ -0,0 +1,141 @@
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression as Level;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+use rocket::{Request, Response};
+use std::io::{self, Cursor, Write};
+
+/// Smaller bodies are not worth the overhead
+const MIN_SIZE: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Encoding {
+    Gzip,
+    Deflate,
+}
+
+impl Encoding {
+    fn name(&self) -> &'static str {
+        match *self {
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+
+    fn encode(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match *self {
+            Encoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Level::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Encoding::Deflate => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Level::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+// Picks gzip if the client accepts it, deflate otherwise.
+// Encodings with a quality of 0 are not acceptable.
+fn preferred_encoding(accept_encoding: &str) -> Option<Encoding> {
+    let accepted: Vec<_> = accept_encoding
+        .split(',')
+        .filter_map(|e| {
+            let mut parts = e.split(';');
+            let name = parts.next()?.trim().to_lowercase();
+            let q = parts
+                .filter_map(|p| {
+                    let p = p.trim();
+                    if p.starts_with("q=") {
+                        p[2..].parse::<f32>().ok()
+                    } else {
+                        None
+                    }
+                })
+                .next()
+                .unwrap_or(1.0);
+            if q > 0.0 {
+                Some(name)
+            } else {
+                None
+            }
+        })
+        .collect();
+    let accepts = |name: &str| accepted.iter().any(|a| a == name || a == "*");
+    if accepts("gzip") {
+        Some(Encoding::Gzip)
+    } else if accepts("deflate") {
+        Some(Encoding::Deflate)
+    } else {
+        None
+    }
+}
+
+/// Compresses larger responses if the client supports it
+pub struct Compression;
+
+impl Fairing for Compression {
+    fn info(&self) -> Info {
+        Info {
+            name: "Compression",
+            kind: Kind::Response,
+        }
+    }
+
+    fn on_response(&self, req: &Request, res: &mut Response) {
+        if res.headers().contains("Content-Encoding") {
+            return;
+        }
+        let encoding = match req.headers()
+            .get_one("Accept-Encoding")
+            .and_then(preferred_encoding)
+        {
+            Some(encoding) => encoding,
+            None => return,
+        };
+        let body = match res.body_bytes() {
+            Some(body) => body,
+            None => return,
+        };
+        if body.len() < MIN_SIZE {
+            res.set_sized_body(Cursor::new(body));
+            return;
+        }
+        match encoding.encode(&body) {
+            Ok(compressed) => {
+                res.set_sized_body(Cursor::new(compressed));
+                res.set_header(Header::new("Content-Encoding", encoding.name()));
+                res.set_header(Header::new("Vary", "Accept-Encoding"));
+            }
+            Err(err) => {
+                warn!("Could not compress response: {}", err);
+                res.set_sized_body(Cursor::new(body));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefer_gzip() {
+        assert_eq!(preferred_encoding("gzip, deflate"), Some(Encoding::Gzip));
+        assert_eq!(preferred_encoding("deflate, gzip;q=0.5"), Some(Encoding::Gzip));
+        assert_eq!(preferred_encoding("deflate"), Some(Encoding::Deflate));
+        assert_eq!(preferred_encoding("*"), Some(Encoding::Gzip));
+    }
+
+    #[test]
+    fn fall_back_to_identity() {
+        assert_eq!(preferred_encoding(""), None);
+        assert_eq!(preferred_encoding("identity"), None);
+        assert_eq!(preferred_encoding("br"), None);
+        assert_eq!(preferred_encoding("gzip;q=0"), None);
+        assert_eq!(preferred_encoding("gzip;q=0, deflate"), Some(Encoding::Deflate));
+    }
+}
@@ -0,0 +1,47 @@
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{Header, Method, Status};
+use rocket::{Request, Response};
+use std::io::Cursor;
+
+const ALLOW_METHODS: &str = "GET, POST, PUT, PATCH, DELETE, OPTIONS";
+const ALLOW_HEADERS: &str = "Content-Type, Authorization";
+// Browsers hide all other headers from scripts
+const EXPOSE_HEADERS: &str = "ETag, Location, X-Total-Count";
+
+pub struct Cors {
+    origin: String,
+}
+
+impl Cors {
+    pub fn new(origin: &str) -> Cors {
+        Cors {
+            origin: origin.into(),
+        }
+    }
+}
+
+impl Fairing for Cors {
+    fn info(&self) -> Info {
+        Info {
+            name: "CORS",
+            kind: Kind::Response,
+        }
+    }
+
+    fn on_response(&self, req: &Request, res: &mut Response) {
+        res.set_header(Header::new(
+            "Access-Control-Allow-Origin",
+            self.origin.clone(),
+        ));
+        res.set_header(Header::new("Access-Control-Allow-Methods", ALLOW_METHODS));
+        res.set_header(Header::new("Access-Control-Allow-Headers", ALLOW_HEADERS));
+        res.set_header(Header::new("Access-Control-Expose-Headers", EXPOSE_HEADERS));
+        // There are no explicit OPTIONS routes,
+        // so we answer preflight requests here.
+        if req.method() == Method::Options && res.status() == Status::NotFound {
+            res.set_status(Status::Ok);
+            res.remove_header("Content-Type");
+            res.set_sized_body(Cursor::new(""));
+        }
+    }
+}
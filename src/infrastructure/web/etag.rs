@@ -0,0 +1,91 @@
+use rocket::http::Status;
+use rocket::request::{self, FromRequest, Request};
+use rocket::response::{Responder, Response};
+use rocket::Outcome;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// The value of the `If-None-Match` request header
+pub struct IfNoneMatch(Option<String>);
+
+impl<'a, 'r> FromRequest<'a, 'r> for IfNoneMatch {
+    type Error = ();
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<IfNoneMatch, ()> {
+        let header = request.headers().get_one("If-None-Match").map(String::from);
+        Outcome::Success(IfNoneMatch(header))
+    }
+}
+
+/// Creates a weak ETag from anything that identifies the state of a resource
+pub fn weak_etag<T: Hash>(state: &T) -> String {
+    let mut hasher = DefaultHasher::new();
+    state.hash(&mut hasher);
+    format!("W/\"{:x}\"", hasher.finish())
+}
+
+// Weak comparison as in RFC 7232: the `W/` prefix doesn't matter
+fn matches(if_none_match: &str, etag: &str) -> bool {
+    let strip = |t: &str| t.trim().trim_left_matches("W/").to_string();
+    let etag = strip(etag);
+    if_none_match
+        .split(',')
+        .any(|t| t.trim() == "*" || strip(t) == etag)
+}
+
+/// Adds an `ETag` header to the response or answers
+/// with `304 Not Modified` if the client has this version already.
+pub struct ETagged<R> {
+    inner: R,
+    etag: String,
+    not_modified: bool,
+}
+
+impl<R> ETagged<R> {
+    pub fn new(inner: R, etag: String, if_none_match: &IfNoneMatch) -> ETagged<R> {
+        let not_modified = match if_none_match.0 {
+            Some(ref h) => matches(h, &etag),
+            None => false,
+        };
+        ETagged {
+            inner,
+            etag,
+            not_modified,
+        }
+    }
+}
+
+impl<'r, R: Responder<'r>> Responder<'r> for ETagged<R> {
+    fn respond_to(self, req: &Request) -> Result<Response<'r>, Status> {
+        if self.not_modified {
+            return Ok(Response::build()
+                .status(Status::NotModified)
+                .raw_header("ETag", self.etag)
+                .finalize());
+        }
+        let mut res = self.inner.respond_to(req)?;
+        res.set_raw_header("ETag", self.etag);
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_etags() {
+        assert!(matches("W/\"abc\"", "W/\"abc\""));
+        assert!(matches("\"abc\"", "W/\"abc\""));
+        assert!(matches("\"x\", W/\"abc\"", "W/\"abc\""));
+        assert!(matches("*", "W/\"abc\""));
+        assert!(!matches("W/\"abd\"", "W/\"abc\""));
+        assert!(!matches("", "W/\"abc\""));
+    }
+
+    #[test]
+    fn etags_depend_on_the_state() {
+        assert_eq!(weak_etag(&("a", 1)), weak_etag(&("a", 1)));
+        assert_ne!(weak_etag(&("a", 1)), weak_etag(&("a", 2)));
+        assert!(weak_etag(&"a").starts_with("W/\""));
+    }
+}
@@ -0,0 +1,230 @@
+use rocket::request::{self, FromRequest, Request};
+use rocket::Outcome;
+use std::collections::HashMap;
+use std::env;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const IDEMPOTENCY_KEY_TTL_ENV: &str = "IDEMPOTENCY_KEY_TTL";
+const DEFAULT_TTL_SECONDS: u64 = 24 * 60 * 60;
+
+const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+// Longer keys are ignored
+const MAX_KEY_LEN: usize = 255;
+
+// A reservation of a request that never finished is given up after this
+const IN_FLIGHT_TIMEOUT_SECONDS: u64 = 60;
+
+/// Remembers the ids of the entries that were created
+/// with an `Idempotency-Key`, so retries don't create duplicates.
+pub struct IdempotencyKeys {
+    ttl: Duration,
+    // Keys without an id are reserved by a request that is still running
+    keys: Mutex<HashMap<String, (Instant, Option<String>)>>,
+}
+
+/// The state of a key after `IdempotencyKeys::reserve`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reservation {
+    /// The key is new and now belongs to the request
+    Reserved,
+    /// Another request with the key is still running
+    InFlight,
+    /// The id of the entry that was created with the key
+    Created(String),
+}
+
+impl IdempotencyKeys {
+    /// A time to live of 0 turns off the idempotency keys.
+    pub fn new(ttl: Duration) -> IdempotencyKeys {
+        IdempotencyKeys {
+            ttl,
+            keys: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reads the time to live in seconds from `IDEMPOTENCY_KEY_TTL` (default: 24 hours)
+    pub fn from_env() -> IdempotencyKeys {
+        let seconds = env::var(IDEMPOTENCY_KEY_TTL_ENV)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_TTL_SECONDS);
+        IdempotencyKeys::new(Duration::from_secs(seconds))
+    }
+
+    fn is_valid(&self, created: Instant, id: &Option<String>, now: Instant) -> bool {
+        let ttl = match *id {
+            Some(_) => self.ttl,
+            None => self.ttl.min(Duration::from_secs(IN_FLIGHT_TIMEOUT_SECONDS)),
+        };
+        now >= created && now - created < ttl
+    }
+
+    /// Looks up the key and reserves it if it is new,
+    /// all under one lock so concurrent retries can't both create an entry.
+    /// Expired keys are forgotten.
+    pub fn reserve(&self, key: String, now: Instant) -> Reservation {
+        let mut keys = match self.keys.lock() {
+            Ok(keys) => keys,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        keys.retain(|_, &mut (created, ref id)| self.is_valid(created, id, now));
+        if let Some(&(_, ref id)) = keys.get(&key) {
+            return match *id {
+                Some(ref id) => Reservation::Created(id.clone()),
+                None => Reservation::InFlight,
+            };
+        }
+        if self.ttl > Duration::from_secs(0) {
+            keys.insert(key, (now, None));
+        }
+        Reservation::Reserved
+    }
+
+    /// Stores the id of the entry that was created with a reserved key
+    pub fn complete(&self, key: String, id: String, now: Instant) {
+        let mut keys = match self.keys.lock() {
+            Ok(keys) => keys,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if self.ttl > Duration::from_secs(0) {
+            keys.insert(key, (now, Some(id)));
+        }
+    }
+
+    /// Gives up a reserved key, e.g. because the entry was invalid
+    pub fn release(&self, key: &str) {
+        let mut keys = match self.keys.lock() {
+            Ok(keys) => keys,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let in_flight = match keys.get(key) {
+            Some(&(_, None)) => true,
+            _ => false,
+        };
+        if in_flight {
+            keys.remove(key);
+        }
+    }
+}
+
+/// The optional `Idempotency-Key` header of a request
+/// and the address of the client that sent it
+pub struct IdempotencyKey {
+    key: Option<String>,
+    ip: Option<IpAddr>,
+}
+
+impl IdempotencyKey {
+    /// The key within the scope of the client: the logged in user
+    /// or else its address, so clients can't use the keys of others.
+    pub fn scoped(&self, user: Option<&str>) -> Option<String> {
+        let key = self.key.as_ref()?;
+        Some(match (user, self.ip) {
+            (Some(user), _) => format!("user:{}:{}", user, key),
+            (None, Some(ip)) => format!("ip:{}:{}", ip, key),
+            (None, None) => format!("anonymous:{}", key),
+        })
+    }
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for IdempotencyKey {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<IdempotencyKey, ()> {
+        let key = request
+            .headers()
+            .get_one(IDEMPOTENCY_KEY_HEADER)
+            .map(|k| k.trim())
+            .and_then(|k| {
+                if k.is_empty() || k.len() > MAX_KEY_LEN {
+                    None
+                } else {
+                    Some(k.to_string())
+                }
+            });
+        let ip = request.remote().map(|addr| addr.ip());
+        Outcome::Success(IdempotencyKey { key, ip })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remember_ids_within_the_ttl() {
+        let keys = IdempotencyKeys::new(Duration::from_secs(60));
+        let start = Instant::now();
+        assert_eq!(keys.reserve("a".into(), start), Reservation::Reserved);
+        keys.complete("a".into(), "foo".into(), start);
+        let later = start + Duration::from_secs(59);
+        assert_eq!(
+            keys.reserve("a".into(), later),
+            Reservation::Created("foo".into())
+        );
+        assert_eq!(keys.reserve("b".into(), later), Reservation::Reserved);
+        let expired = start + Duration::from_secs(60);
+        assert_eq!(keys.reserve("a".into(), expired), Reservation::Reserved);
+    }
+
+    #[test]
+    fn forget_expired_keys() {
+        let keys = IdempotencyKeys::new(Duration::from_secs(60));
+        let start = Instant::now();
+        keys.reserve("a".into(), start);
+        keys.complete("a".into(), "foo".into(), start);
+        keys.reserve("b".into(), start + Duration::from_secs(61));
+        assert_eq!(keys.keys.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn zero_turns_off_the_keys() {
+        let keys = IdempotencyKeys::new(Duration::from_secs(0));
+        let now = Instant::now();
+        assert_eq!(keys.reserve("a".into(), now), Reservation::Reserved);
+        keys.complete("a".into(), "foo".into(), now);
+        assert_eq!(keys.reserve("a".into(), now), Reservation::Reserved);
+    }
+
+    #[test]
+    fn reserve_a_key_only_once() {
+        let keys = IdempotencyKeys::new(Duration::from_secs(3600));
+        let start = Instant::now();
+        assert_eq!(keys.reserve("a".into(), start), Reservation::Reserved);
+        assert_eq!(keys.reserve("a".into(), start), Reservation::InFlight);
+        keys.release("a");
+        assert_eq!(keys.reserve("a".into(), start), Reservation::Reserved);
+        // the request that reserved the key might have crashed
+        let later = start + Duration::from_secs(IN_FLIGHT_TIMEOUT_SECONDS);
+        assert_eq!(keys.reserve("a".into(), later), Reservation::Reserved);
+    }
+
+    #[test]
+    fn never_release_the_key_of_a_created_entry() {
+        let keys = IdempotencyKeys::new(Duration::from_secs(60));
+        let now = Instant::now();
+        keys.reserve("a".into(), now);
+        keys.complete("a".into(), "foo".into(), now);
+        keys.release("a");
+        assert_eq!(
+            keys.reserve("a".into(), now),
+            Reservation::Created("foo".into())
+        );
+    }
+
+    #[test]
+    fn scope_keys_by_client() {
+        let ip = "10.0.0.1".parse().ok();
+        let key = IdempotencyKey {
+            key: Some("abc".into()),
+            ip,
+        };
+        assert_eq!(key.scoped(Some("alice")), Some("user:alice:abc".into()));
+        assert_eq!(key.scoped(None), Some("ip:10.0.0.1:abc".into()));
+        let none = IdempotencyKey { key: None, ip };
+        assert_eq!(none.scoped(Some("alice")), None);
+    }
+}
@@ -0,0 +1,164 @@
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+use rocket::{Data, Request, Response};
+use serde_json;
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const LOG_FORMAT_ENV: &str = "REQUEST_LOG_FORMAT";
+
+// Rocket has no request-local state,
+// so the time of receipt travels with the request as a header.
+const RECEIVED_HEADER: &str = "X-Ofdb-Received-Micros";
+
+fn micros_since_epoch() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() * 1_000_000 + u64::from(d.subsec_nanos()) / 1_000)
+        .unwrap_or(0)
+}
+
+/// Remembers when the request was received
+/// (replacing any value that a client might have sent).
+fn mark_received(req: &mut Request) {
+    req.replace_header(Header::new(
+        RECEIVED_HEADER,
+        micros_since_epoch().to_string(),
+    ));
+}
+
+/// Marks the time of receipt once for the request logger
+/// and the metrics (attach it before both of them).
+pub struct ReceivedMarker;
+
+impl Fairing for ReceivedMarker {
+    fn info(&self) -> Info {
+        Info {
+            name: "Time of receipt",
+            kind: Kind::Request,
+        }
+    }
+
+    fn on_request(&self, req: &mut Request, _: &Data) {
+        mark_received(req);
+    }
+}
+
+/// The milliseconds since the `ReceivedMarker` saw the request
+pub fn elapsed_ms(req: &Request) -> Option<f64> {
+    req.headers()
+        .get_one(RECEIVED_HEADER)
+        .and_then(|t| t.parse::<u64>().ok())
+        .map(|t| micros_since_epoch().saturating_sub(t) as f64 / 1_000.0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RequestLog<'a> {
+    pub method: &'a str,
+    pub path: &'a str,
+    pub status: u16,
+    pub duration_ms: f64,
+    pub ip: Option<String>,
+}
+
+impl<'a> RequestLog<'a> {
+    pub fn format(&self, format: LogFormat) -> String {
+        match format {
+            LogFormat::Json => serde_json::to_string(self).unwrap_or_default(),
+            LogFormat::Text => format!(
+                "{} {} {} {:.3}ms {}",
+                self.method,
+                self.path,
+                self.status,
+                self.duration_ms,
+                self.ip.as_ref().map(|ip| ip.as_str()).unwrap_or("-")
+            ),
+        }
+    }
+}
+
+/// Logs each request as a single line
+pub struct RequestLogger {
+    format: LogFormat,
+}
+
+impl RequestLogger {
+    pub fn new(format: LogFormat) -> RequestLogger {
+        RequestLogger { format }
+    }
+
+    /// Reads the format from `REQUEST_LOG_FORMAT` (`text` or `json`; default: `text`)
+    pub fn from_env() -> RequestLogger {
+        let format = match env::var(LOG_FORMAT_ENV) {
+            Ok(ref f) if f.trim().eq_ignore_ascii_case("json") => LogFormat::Json,
+            _ => LogFormat::Text,
+        };
+        RequestLogger::new(format)
+    }
+}
+
+impl Fairing for RequestLogger {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request logger",
+            kind: Kind::Response,
+        }
+    }
+
+    fn on_response(&self, req: &Request, res: &mut Response) {
+        let duration_ms = elapsed_ms(req).unwrap_or(0.0);
+        let method = req.method();
+        let path = req.uri().path();
+        let log = RequestLog {
+            method: method.as_str(),
+            path,
+            status: res.status().code,
+            duration_ms,
+            ip: req.remote().map(|addr| addr.ip().to_string()),
+        };
+        info!("{}", log.format(self.format));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log() -> RequestLog<'static> {
+        RequestLog {
+            method: "GET",
+            path: "/entries/foo",
+            status: 200,
+            duration_ms: 1.5,
+            ip: Some("127.0.0.1".into()),
+        }
+    }
+
+    #[test]
+    fn format_as_text() {
+        assert_eq!(
+            log().format(LogFormat::Text),
+            "GET /entries/foo 200 1.500ms 127.0.0.1"
+        );
+        let mut without_ip = log();
+        without_ip.ip = None;
+        assert_eq!(
+            without_ip.format(LogFormat::Text),
+            "GET /entries/foo 200 1.500ms -"
+        );
+    }
+
+    #[test]
+    fn format_as_json() {
+        assert_eq!(
+            log().format(LogFormat::Json),
+            r#"{"method":"GET","path":"/entries/foo","status":200,"duration_ms":1.5,"ip":"127.0.0.1"}"#
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::{Outcome, Request, Response, State};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use super::logging;
+
+/// The upper bounds of the request duration buckets in seconds
+const DURATION_BUCKETS: [f64; 10] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+// Requests that didn't match any route
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+#[derive(Debug, Default)]
+struct Histogram {
+    // Not cumulative, one more than there are buckets for +Inf
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        if self.counts.is_empty() {
+            self.counts = vec![0; DURATION_BUCKETS.len() + 1];
+        }
+        let bucket = DURATION_BUCKETS
+            .iter()
+            .position(|&le| value <= le)
+            .unwrap_or(DURATION_BUCKETS.len());
+        self.counts[bucket] += 1;
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    // (method, route, status) -> count
+    requests: BTreeMap<(String, String, u16), u64>,
+    durations: Histogram,
+}
+
+/// The connections of the DB pool
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PoolUsage {
+    pub in_use: u32,
+    pub idle: u32,
+}
+
+/// Counts the requests for Prometheus
+#[derive(Debug, Default)]
+pub struct Metrics {
+    registry: Mutex<Registry>,
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics::default()
+    }
+
+    pub fn record(&self, method: &str, route: &str, status: u16, seconds: f64) {
+        let mut registry = match self.registry.lock() {
+            Ok(registry) => registry,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        *registry
+            .requests
+            .entry((method.into(), route.into(), status))
+            .or_insert(0) += 1;
+        registry.durations.observe(seconds);
+    }
+
+    /// All metrics in the Prometheus text format
+    pub fn render(&self, pool: PoolUsage) -> String {
+        let registry = match self.registry.lock() {
+            Ok(registry) => registry,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let mut out = String::new();
+
+        out.push_str("# HELP ofdb_http_requests_total The number of HTTP requests.\n");
+        out.push_str("# TYPE ofdb_http_requests_total counter\n");
+        for (&(ref method, ref route, status), count) in &registry.requests {
+            let _ = writeln!(
+                out,
+                "ofdb_http_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+                escape(method),
+                escape(route),
+                status,
+                count
+            );
+        }
+
+        let durations = &registry.durations;
+        out.push_str(
+            "# HELP ofdb_http_request_duration_seconds The duration of HTTP requests.\n",
+        );
+        out.push_str("# TYPE ofdb_http_request_duration_seconds histogram\n");
+        let mut cumulative = 0;
+        for (i, le) in DURATION_BUCKETS.iter().enumerate() {
+            cumulative += durations.counts.get(i).cloned().unwrap_or(0);
+            let _ = writeln!(
+                out,
+                "ofdb_http_request_duration_seconds_bucket{{le=\"{}\"}} {}",
+                le, cumulative
+            );
+        }
+        let _ = writeln!(
+            out,
+            "ofdb_http_request_duration_seconds_bucket{{le=\"+Inf\"}} {}",
+            durations.count
+        );
+        let _ = writeln!(
+            out,
+            "ofdb_http_request_duration_seconds_sum {}",
+            durations.sum
+        );
+        let _ = writeln!(
+            out,
+            "ofdb_http_request_duration_seconds_count {}",
+            durations.count
+        );
+
+        out.push_str("# HELP ofdb_db_connections_in_use The DB connections in use.\n");
+        out.push_str("# TYPE ofdb_db_connections_in_use gauge\n");
+        let _ = writeln!(out, "ofdb_db_connections_in_use {}", pool.in_use);
+        out.push_str("# HELP ofdb_db_connections_idle The idle DB connections.\n");
+        out.push_str("# TYPE ofdb_db_connections_idle gauge\n");
+        let _ = writeln!(out, "ofdb_db_connections_idle {}", pool.idle);
+        out
+    }
+}
+
+/// Records each request in the managed `Metrics`
+pub struct MetricsFairing;
+
+impl Fairing for MetricsFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Metrics",
+            kind: Kind::Response,
+        }
+    }
+
+    fn on_response(&self, req: &Request, res: &mut Response) {
+        let metrics = match req.guard::<State<Metrics>>() {
+            Outcome::Success(metrics) => metrics,
+            _ => return,
+        };
+        let route = req.route()
+            .map(|r| r.uri.path().to_string())
+            .unwrap_or_else(|| UNMATCHED_ROUTE.into());
+        let seconds = logging::elapsed_ms(req).unwrap_or(0.0) / 1_000.0;
+        metrics.record(req.method().as_str(), &route, res.status().code, seconds);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_requests_by_route_and_status() {
+        let metrics = Metrics::new();
+        metrics.record("GET", "/entries/<ids>", 200, 0.003);
+        metrics.record("GET", "/entries/<ids>", 200, 0.02);
+        metrics.record("GET", "/entries/<ids>", 404, 0.2);
+        let out = metrics.render(PoolUsage::default());
+        assert!(out.contains(
+            "ofdb_http_requests_total{method=\"GET\",route=\"/entries/<ids>\",status=\"200\"} 2\n"
+        ));
+        assert!(out.contains(
+            "ofdb_http_requests_total{method=\"GET\",route=\"/entries/<ids>\",status=\"404\"} 1\n"
+        ));
+    }
+
+    #[test]
+    fn count_durations_in_cumulative_buckets() {
+        let metrics = Metrics::new();
+        metrics.record("GET", "/", 200, 0.003);
+        metrics.record("GET", "/", 200, 0.02);
+        metrics.record("GET", "/", 200, 10.0);
+        let out = metrics.render(PoolUsage::default());
+        assert!(out.contains("ofdb_http_request_duration_seconds_bucket{le=\"0.005\"} 1\n"));
+        assert!(out.contains("ofdb_http_request_duration_seconds_bucket{le=\"0.025\"} 2\n"));
+        assert!(out.contains("ofdb_http_request_duration_seconds_bucket{le=\"5\"} 2\n"));
+        assert!(out.contains("ofdb_http_request_duration_seconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(out.contains("ofdb_http_request_duration_seconds_count 3\n"));
+    }
+
+    #[test]
+    fn render_pool_usage() {
+        let out = Metrics::new().render(PoolUsage { in_use: 2, idle: 3 });
+        assert!(out.contains("ofdb_db_connections_in_use 2\n"));
+        assert!(out.contains("ofdb_db_connections_idle 3\n"));
+        assert!(out.contains("ofdb_http_request_duration_seconds_bucket{le=\"+Inf\"} 0\n"));
+    }
+
+    #[test]
+    fn escape_label_values() {
+        assert_eq!(escape(r#"a"b\c"#), r#"a\"b\\c"#);
+    }
+}
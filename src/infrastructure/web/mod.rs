@@ -1,6 +1,7 @@
 use rocket::{self, Rocket};
 use rocket_contrib::Json;
 use rocket::config::{Config, Environment};
+use rocket::logger::LoggingLevel;
 use business::db::Db;
 use infrastructure::error::AppError;
 use business::sort::Rated;
@@ -17,6 +18,17 @@ lazy_static! {
 }
 
 mod api;
+mod auth;
+mod cache;
+mod compression;
+mod cors;
+mod etag;
+mod idempotency;
+mod logging;
+mod metrics;
+mod openapi;
+mod ratelimit;
+mod settings;
 mod util;
 pub mod sqlite;
 #[cfg(test)]
@@ -25,6 +37,17 @@ mod tests;
 mod mockdb;
 
 use self::sqlite::create_connection_pool;
+use self::auth::{Admins, JwtSecret};
+use self::cache::CategoryCache;
+use self::compression::Compression;
+use self::cors::Cors;
+use self::idempotency::IdempotencyKeys;
+use self::logging::{ReceivedMarker, RequestLogger};
+use self::metrics::{Metrics, MetricsFairing};
+use self::ratelimit::RateLimiter;
+use self::settings::Settings;
+
+pub use self::util::Mailer;
 
 type Result<T> = result::Result<Json<T>, AppError>;
 
@@ -52,32 +75,63 @@ fn calculate_rating_for_entry<D: Db>(db: &D, e_id: &str) -> Result<()> {
     Ok(Json(()))
 }
 
-fn rocket_instance<T: r2d2::ManageConnection>(cfg: Config, pool: Pool<T>) -> Rocket
+fn rocket_instance<T: r2d2::ManageConnection>(
+    cfg: Config,
+    pool: Pool<T>,
+    cors: Option<Cors>,
+    admins: Admins,
+    secret: JwtSecret,
+    mailer: Mailer,
+) -> Rocket
 where
     <T as r2d2::ManageConnection>::Connection: Db,
 {
     info!("Calculating the average rating of all entries...");
     calculate_all_ratings(&*pool.get().unwrap()).unwrap();
-    rocket::custom(cfg, true)
+    let r = rocket::custom(cfg, true)
         .manage(pool)
+        .manage(RateLimiter::from_env())
+        .manage(CategoryCache::from_env())
+        .manage(IdempotencyKeys::from_env())
+        .manage(Metrics::new())
+        .manage(Settings::from_env())
+        .manage(admins)
+        .manage(secret)
+        .manage(mailer)
         .mount("/", api::routes())
-}
-
-pub fn run(db_url: &str, port: u16, enable_cors: bool) {
-    if enable_cors {
-        panic!(
-            "enable-cors is currently not available until\
-             \nhttps://github.com/SergioBenitez/Rocket/pull/141\nis merged :("
-        );
+        .attach(Compression)
+        .attach(ReceivedMarker)
+        .attach(RequestLogger::from_env())
+        .attach(MetricsFairing);
+    match cors {
+        Some(cors) => r.attach(cors),
+        None => r,
     }
+}
 
+pub fn run(
+    db_url: &str,
+    host: &str,
+    port: u16,
+    log_level: LoggingLevel,
+    cors_origin: Option<&str>,
+    mailer: Mailer,
+) {
     let cfg = Config::build(Environment::Production)
-        .address("127.0.0.1")
+        .address(host)
         .port(port)
+        .log_level(log_level)
         .finalize()
         .unwrap();
 
     let pool = create_connection_pool(db_url).unwrap();
 
-    rocket_instance(cfg, pool).launch();
+    rocket_instance(
+        cfg,
+        pool,
+        cors_origin.map(Cors::new),
+        Admins::from_env(),
+        JwtSecret::from_env(),
+        mailer,
+    ).launch();
 }
@@ -0,0 +1,66 @@
+/// The hand-maintained OpenAPI 3.0 description of all routes.
+/// Please keep it up to date when adding or changing routes.
+pub const SPEC: &str = include_str!("openapi.json");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::api;
+    use serde_json::{self, Value};
+
+    // Rocket's `<id>` and OpenAPI's `{id}` both become `{}`
+    fn template(path: &str) -> String {
+        let mut res = String::with_capacity(path.len());
+        let mut in_param = false;
+        for c in path.chars() {
+            match c {
+                '<' | '{' => {
+                    in_param = true;
+                    res.push_str("{}");
+                }
+                '>' | '}' => in_param = false,
+                _ if !in_param => res.push(c),
+                _ => {}
+            }
+        }
+        res
+    }
+
+    #[test]
+    fn spec_is_valid_json() {
+        let spec: Value = serde_json::from_str(SPEC).unwrap();
+        assert!(spec["openapi"].as_str().unwrap().starts_with("3.0"));
+        assert!(spec["paths"].is_object());
+        assert!(spec["components"]["schemas"]["Entry"].is_object());
+    }
+
+    #[test]
+    fn spec_describes_every_route() {
+        let spec: Value = serde_json::from_str(SPEC).unwrap();
+        let paths: Vec<_> = spec["paths"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .map(|(path, item)| (template(path), item))
+            .collect();
+        for route in api::routes() {
+            let path = template(route.uri.path());
+            let method = route.method.as_str().to_lowercase();
+            assert!(
+                paths
+                    .iter()
+                    .any(|&(ref p, item)| *p == path && item.get(&method).is_some()),
+                "{} {} is missing",
+                route.method,
+                route.uri.path()
+            );
+        }
+    }
+
+    #[test]
+    fn replace_path_parameters() {
+        assert_eq!(template("/entries/<ids>/history"), "/entries/{}/history");
+        assert_eq!(template("/entries/{id}/history"), "/entries/{}/history");
+        assert_eq!(template("/entries"), "/entries");
+    }
+}
@@ -0,0 +1,251 @@
+use rocket::http::Status;
+use rocket::request::{self, FromRequest, Request};
+use rocket::{Outcome, State};
+use std::collections::HashMap;
+use std::env;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const RATE_LIMIT_ENV: &str = "WRITE_RATE_LIMIT";
+const DEFAULT_REQUESTS_PER_MINUTE: u32 = 30;
+
+// Full buckets are dropped if there are more clients than this
+const MAX_BUCKETS: usize = 10_000;
+
+fn seconds(d: Duration) -> f64 {
+    d.as_secs() as f64 + f64::from(d.subsec_nanos()) * 1e-9
+}
+
+/// A bucket of `capacity` tokens that refills
+/// at a constant rate of `capacity` tokens per minute.
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(capacity: u32, now: Instant) -> TokenBucket {
+        TokenBucket {
+            capacity: f64::from(capacity),
+            tokens: f64::from(capacity),
+            last_refill: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if now <= self.last_refill {
+            return;
+        }
+        let elapsed = seconds(now - self.last_refill);
+        self.tokens = (self.tokens + elapsed * self.capacity / 60.0).min(self.capacity);
+        self.last_refill = now;
+    }
+
+    fn is_full(&self) -> bool {
+        self.tokens >= self.capacity
+    }
+
+    /// Takes a token if there is one left
+    pub fn take(&mut self, now: Instant) -> bool {
+        self.take_n(1, now)
+    }
+
+    /// Takes `n` tokens if there are enough left, otherwise none
+    pub fn take_n(&mut self, n: u32, now: Instant) -> bool {
+        self.refill(now);
+        if self.tokens >= f64::from(n) {
+            self.tokens -= f64::from(n);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Limits the requests per minute of each client
+pub struct RateLimiter {
+    requests_per_minute: u32,
+    buckets: Mutex<HashMap<IpAddr, TokenBucket>>,
+}
+
+impl RateLimiter {
+    /// A limit of 0 requests per minute turns off the limiter.
+    pub fn new(requests_per_minute: u32) -> RateLimiter {
+        RateLimiter {
+            requests_per_minute,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reads the requests per minute from `WRITE_RATE_LIMIT` (default: 30)
+    pub fn from_env() -> RateLimiter {
+        let limit = env::var(RATE_LIMIT_ENV)
+            .ok()
+            .and_then(|l| l.parse().ok())
+            .unwrap_or(DEFAULT_REQUESTS_PER_MINUTE);
+        RateLimiter::new(limit)
+    }
+
+    /// The most requests a client can make at once
+    /// (`None` if the limiter is turned off)
+    pub fn capacity(&self) -> Option<u32> {
+        if self.requests_per_minute == 0 {
+            None
+        } else {
+            Some(self.requests_per_minute)
+        }
+    }
+
+    pub fn allows(&self, ip: IpAddr, now: Instant) -> bool {
+        self.allows_n(ip, 1, now)
+    }
+
+    /// Like `allows` but counts as `n` requests
+    pub fn allows_n(&self, ip: IpAddr, n: u32, now: Instant) -> bool {
+        if self.requests_per_minute == 0 {
+            return true;
+        }
+        let mut buckets = match self.buckets.lock() {
+            Ok(buckets) => buckets,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if buckets.len() > MAX_BUCKETS {
+            for b in buckets.values_mut() {
+                b.refill(now);
+            }
+            buckets.retain(|_, b| !b.is_full());
+        }
+        let limit = self.requests_per_minute;
+        buckets
+            .entry(ip)
+            .or_insert_with(|| TokenBucket::new(limit, now))
+            .take_n(n, now)
+    }
+}
+
+/// A request guard for write endpoints that fails
+/// with `429 Too Many Requests` if the client exceeded its limit.
+pub struct RateLimited {
+    ip: Option<IpAddr>,
+}
+
+impl RateLimited {
+    /// Counts `n` further requests of the client,
+    /// e.g. for the other items of a batch.
+    pub fn charge(&self, limiter: &RateLimiter, n: u32, now: Instant) -> bool {
+        match self.ip {
+            Some(ip) => limiter.allows_n(ip, n, now),
+            None => true,
+        }
+    }
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for RateLimited {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<RateLimited, ()> {
+        let limiter = match request.guard::<State<RateLimiter>>() {
+            Outcome::Success(limiter) => limiter,
+            _ => return Outcome::Success(RateLimited { ip: None }),
+        };
+        // Without an address there is nothing to limit
+        let ip = match request.remote() {
+            Some(addr) => addr.ip(),
+            None => return Outcome::Success(RateLimited { ip: None }),
+        };
+        if limiter.allows(ip, Instant::now()) {
+            Outcome::Success(RateLimited { ip: Some(ip) })
+        } else {
+            warn!("Too many requests from {}", ip);
+            Outcome::Failure((<Status>::new(429, "Too Many Requests"), ()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn take_tokens_until_the_bucket_is_empty() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(3, now);
+        assert!(bucket.take(now));
+        assert!(bucket.take(now));
+        assert!(bucket.take(now));
+        assert!(!bucket.take(now));
+    }
+
+    #[test]
+    fn refill_tokens_over_time() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(6, start);
+        for _ in 0..6 {
+            assert!(bucket.take(start));
+        }
+        assert!(!bucket.take(start));
+        // six tokens per minute: one every ten seconds
+        assert!(!bucket.take(start + Duration::from_secs(9)));
+        assert!(bucket.take(start + Duration::from_secs(10)));
+        assert!(!bucket.take(start + Duration::from_secs(10)));
+        assert!(bucket.take(start + Duration::from_secs(25)));
+        assert!(!bucket.take(start + Duration::from_secs(25)));
+    }
+
+    #[test]
+    fn never_refill_more_than_the_capacity() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(2, start);
+        let later = start + Duration::from_secs(3600);
+        assert!(bucket.take(later));
+        assert!(bucket.take(later));
+        assert!(!bucket.take(later));
+    }
+
+    #[test]
+    fn limit_each_client_on_its_own() {
+        let now = Instant::now();
+        let limiter = RateLimiter::new(1);
+        let a: IpAddr = "127.0.0.1".parse().unwrap();
+        let b: IpAddr = "::1".parse().unwrap();
+        assert!(limiter.allows(a, now));
+        assert!(!limiter.allows(a, now));
+        assert!(limiter.allows(b, now));
+    }
+
+    #[test]
+    fn take_several_tokens_at_once_or_none() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(5, now);
+        assert!(bucket.take_n(3, now));
+        assert!(!bucket.take_n(3, now));
+        assert!(bucket.take_n(2, now));
+        assert!(!bucket.take(now));
+    }
+
+    #[test]
+    fn allow_a_request_of_the_full_capacity_once_the_bucket_is_full_again() {
+        let start = Instant::now();
+        let limiter = RateLimiter::new(3);
+        let a: IpAddr = "127.0.0.1".parse().unwrap();
+        assert_eq!(limiter.capacity(), Some(3));
+        assert!(limiter.allows_n(a, 3, start));
+        assert!(!limiter.allows_n(a, 3, start));
+        assert!(!limiter.allows_n(a, 3, start + Duration::from_secs(30)));
+        assert!(limiter.allows_n(a, 3, start + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn zero_turns_off_the_limit() {
+        let now = Instant::now();
+        let limiter = RateLimiter::new(0);
+        let a: IpAddr = "127.0.0.1".parse().unwrap();
+        assert_eq!(limiter.capacity(), None);
+        for _ in 0..100 {
+            assert!(limiter.allows(a, now));
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use business::sort::DEFAULT_RATING_HALF_LIFE_DAYS;
+use business::usecase::{DEFAULT_MAX_ENTRY_IDS, DEFAULT_MAX_INVISIBLE_RESULTS};
+use std::env;
+use std::str::FromStr;
+
+const MAX_INVISIBLE_RESULTS_ENV: &str = "OFDB_MAX_INVISIBLE_RESULTS";
+const MAX_ENTRY_IDS_ENV: &str = "OFDB_MAX_ENTRY_IDS";
+const RATING_HALF_LIFE_DAYS_ENV: &str = "OFDB_RATING_HALF_LIFE_DAYS";
+
+/// Limits of the search and the entry lookup
+/// that are read once at startup.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    /// The maximum number of search results outside of the bbox
+    pub max_invisible_results: usize,
+    /// The maximum number of ids of `GET /entries/<ids>`
+    pub max_entry_ids: usize,
+    /// The age in days after which a rating only counts half
+    /// when sorting by `rating_recent`
+    pub rating_half_life_days: f64,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            max_invisible_results: DEFAULT_MAX_INVISIBLE_RESULTS,
+            max_entry_ids: DEFAULT_MAX_ENTRY_IDS,
+            rating_half_life_days: DEFAULT_RATING_HALF_LIFE_DAYS,
+        }
+    }
+}
+
+fn var_or<T: FromStr>(name: &str, default: T) -> T {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+impl Settings {
+    /// Reads `OFDB_MAX_INVISIBLE_RESULTS` (default: 5),
+    /// `OFDB_MAX_ENTRY_IDS` (default: 100)
+    /// and `OFDB_RATING_HALF_LIFE_DAYS` (default: 365)
+    pub fn from_env() -> Settings {
+        let d = Settings::default();
+        Settings {
+            max_invisible_results: var_or(MAX_INVISIBLE_RESULTS_ENV, d.max_invisible_results),
+            max_entry_ids: var_or(MAX_ENTRY_IDS_ENV, d.max_entry_ids),
+            rating_half_life_days: var_or(RATING_HALF_LIFE_DAYS_ENV, d.rating_half_life_days),
+        }
+    }
+}
@@ -1,8 +1,9 @@
 use rocket::logger::LoggingLevel;
 use rocket::config::{Config, Environment};
 use rocket::local::Client;
-use rocket::http::{ContentType, Cookie, Status};
-use business::db::Db;
+use rocket::http::{ContentType, Cookie, Header, Status};
+use business::db::{Db, SearchParams};
+use business::filter::Combination;
 use business::builder::*;
 use business::usecase;
 use serde_json;
@@ -13,10 +14,35 @@ use super::util::*;
 use pwhash::bcrypt;
 use test::Bencher;
 use super::sqlite;
+use super::auth::{Admins, JwtSecret};
+use super::cors::Cors;
 use uuid::Uuid;
 use std::fs;
+use std::net::SocketAddr;
+use std::io::Read;
+use flate2::read::GzDecoder;
 
 fn setup() -> (Client, sqlite::ConnectionPool) {
+    setup_with(None, Admins::default())
+}
+
+fn setup_with_cors(cors: Option<Cors>) -> (Client, sqlite::ConnectionPool) {
+    setup_with(cors, Admins::default())
+}
+
+fn setup_with_admins(admins: &str) -> (Client, sqlite::ConnectionPool) {
+    setup_with(None, Admins::new(admins))
+}
+
+fn setup_with(cors: Option<Cors>, admins: Admins) -> (Client, sqlite::ConnectionPool) {
+    setup_with_secret(cors, admins, JwtSecret::default())
+}
+
+fn setup_with_secret(
+    cors: Option<Cors>,
+    admins: Admins,
+    secret: JwtSecret,
+) -> (Client, sqlite::ConnectionPool) {
     let cfg = Config::build(Environment::Development)
         .log_level(LoggingLevel::Debug)
         .finalize()
@@ -24,7 +50,8 @@ fn setup() -> (Client, sqlite::ConnectionPool) {
     let uuid = Uuid::new_v4().simple().to_string();
     fs::create_dir_all("test-dbs").unwrap();
     let pool = sqlite::create_connection_pool(&format!("./test-dbs/{}", uuid)).unwrap();
-    let rocket = super::rocket_instance(cfg, pool.clone());
+    let rocket =
+        super::rocket_instance(cfg, pool.clone(), cors, admins, secret, Mailer::default());
     let client = Client::new(rocket).unwrap();
     (client, pool)
 }
@@ -45,7 +72,7 @@ fn create_entry() {
                     .header(ContentType::JSON)
                     .body(r#"{"title":"foo","description":"blablabla","lat":0.0,"lng":0.0,"categories":["x"],"license":"CC0-1.0","tags":[]}"#);
     let mut response = req.dispatch();
-    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.status(), Status::Created);
     assert!(
         response
             .headers()
@@ -58,9 +85,295 @@ fn create_entry() {
             _ => { /* let these through */ }
         }
     }
-    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
     let eid = db.get().unwrap().all_entries().unwrap()[0].id.clone();
-    assert_eq!(body_str, format!("\"{}\"", eid));
+    assert_eq!(
+        response.headers().get_one("Location"),
+        Some(format!("/entries/{}", eid).as_str())
+    );
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    let e: Entry = serde_json::from_str(&body_str).unwrap();
+    assert_eq!(e.id, eid);
+    assert_eq!(e.version, 0);
+    assert_eq!(e.title, "foo");
+    assert!(e.created > 0);
+}
+
+// Each request checks out a single connection (the `DbConn` guard)
+// and passes it down, so a pool of one connection has to suffice.
+#[test]
+fn handle_requests_with_a_single_pooled_connection() {
+    use diesel::r2d2::{ConnectionManager, Pool};
+    use diesel::sqlite::SqliteConnection;
+    use std::time::Duration;
+
+    let cfg = Config::build(Environment::Development)
+        .log_level(LoggingLevel::Debug)
+        .finalize()
+        .unwrap();
+    fs::create_dir_all("test-dbs").unwrap();
+    let db_url = format!("./test-dbs/{}", Uuid::new_v4().simple());
+    // runs the migrations
+    sqlite::create_connection_pool(&db_url).unwrap();
+    let pool = Pool::builder()
+        .max_size(1)
+        .connection_timeout(Duration::from_secs(1))
+        .build(ConnectionManager::<SqliteConnection>::new(db_url))
+        .unwrap();
+    let rocket = super::rocket_instance(
+        cfg,
+        pool,
+        None,
+        Admins::default(),
+        JwtSecret::default(),
+        Mailer::default(),
+    );
+    let client = Client::new(rocket).unwrap();
+
+    let mut response = client
+        .post("/entries")
+        .header(ContentType::JSON)
+        .body(r#"{"title":"foo","description":"blablabla","lat":0.0,"lng":0.0,"categories":[],"license":"CC0-1.0","tags":[]}"#)
+        .dispatch();
+    assert_eq!(response.status(), Status::Created);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    let id = serde_json::from_str::<Entry>(&body_str).unwrap().id;
+    let response = client
+        .post("/ratings")
+        .header(ContentType::JSON)
+        .body(format!(
+            r#"{{"value":1,"context":"fairness","entry":"{}","comment":"test","title":"title"}}"#,
+            id
+        ))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let response = client.get(format!("/entries/{}", id)).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let response = client.get("/search?bbox=-10,-10,10,10").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+}
+
+#[test]
+fn limit_the_rate_of_new_entries() {
+    let (client, db) = setup();
+    let json = r#"{"title":"foo","description":"blablabla","lat":0.0,"lng":0.0,"categories":[],"license":"CC0-1.0","tags":[]}"#;
+    let client_addr: SocketAddr = "10.0.0.1:4000".parse().unwrap();
+    let other_addr: SocketAddr = "10.0.0.2:4000".parse().unwrap();
+    // the default limit is 30 requests per minute
+    for _ in 0..30 {
+        let response = client
+            .post("/entries")
+            .header(ContentType::JSON)
+            .remote(client_addr)
+            .body(json)
+            .dispatch();
+        assert_eq!(response.status(), Status::Created);
+    }
+    let response = client
+        .post("/entries")
+        .header(ContentType::JSON)
+        .remote(client_addr)
+        .body(json)
+        .dispatch();
+    assert_eq!(response.status().code, 429);
+    let response = client
+        .post("/entries")
+        .header(ContentType::JSON)
+        .remote(other_addr)
+        .body(json)
+        .dispatch();
+    assert_eq!(response.status(), Status::Created);
+    assert_eq!(db.get().unwrap().all_entries().unwrap().len(), 31);
+}
+
+#[test]
+fn create_entry_only_once_per_idempotency_key() {
+    let (client, db) = setup();
+    let json = r#"{"title":"foo","description":"blablabla","lat":0.0,"lng":0.0,"categories":[],"license":"CC0-1.0","tags":[]}"#;
+    let post = |key: &'static str, addr: &str| {
+        let mut response = client
+            .post("/entries")
+            .header(ContentType::JSON)
+            .header(Header::new("Idempotency-Key", key))
+            .remote(addr.parse().unwrap())
+            .body(json)
+            .dispatch();
+        assert_eq!(response.status(), Status::Created);
+        let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+        serde_json::from_str::<Entry>(&body_str).unwrap().id
+    };
+    let id = post("abc", "10.0.1.1:4000");
+    assert_eq!(post("abc", "10.0.1.1:4000"), id);
+    assert_eq!(db.get().unwrap().all_entries().unwrap().len(), 1);
+    assert!(post("xyz", "10.0.1.1:4000") != id);
+    assert_eq!(db.get().unwrap().all_entries().unwrap().len(), 2);
+    // another client can't get the entry of the key
+    assert!(post("abc", "10.0.1.2:4000") != id);
+    assert_eq!(db.get().unwrap().all_entries().unwrap().len(), 3);
+}
+
+#[test]
+fn release_the_idempotency_key_of_an_invalid_entry() {
+    let (client, db) = setup();
+    let post = |title: &str| {
+        client
+            .post("/entries")
+            .header(ContentType::JSON)
+            .header(Header::new("Idempotency-Key", "abc"))
+            .body(format!(
+                r#"{{"title":"{}","description":"blablabla","lat":0.0,"lng":0.0,"categories":[],"license":"CC0-1.0","tags":[]}}"#,
+                title
+            ))
+            .dispatch()
+            .status()
+    };
+    assert_eq!(post(""), Status::UnprocessableEntity);
+    assert_eq!(post("foo"), Status::Created);
+    assert_eq!(db.get().unwrap().all_entries().unwrap().len(), 1);
+}
+
+#[test]
+fn create_entry_with_opening_hours() {
+    let (client, db) = setup();
+    let response = client.post("/entries")
+                    .header(ContentType::JSON)
+                    .body(r#"{"title":"foo","description":"blablabla","lat":0.0,"lng":0.0,"categories":[],"license":"CC0-1.0","tags":[],"opening_hours":"Mo-Fr 09:00-18:00"}"#)
+                    .dispatch();
+    assert_eq!(response.status(), Status::Created);
+    let id = db.get().unwrap().all_entries().unwrap()[0].id.clone();
+    let mut response = client.get(format!("/entries/{}", id)).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    let entries: Vec<Entry> = serde_json::from_str(&body_str).unwrap();
+    assert_eq!(entries[0].opening_hours, Some("Mo-Fr 09:00-18:00".into()));
+
+    let response = client.post("/entries")
+                    .header(ContentType::JSON)
+                    .body(r#"{"title":"foo","description":"blablabla","lat":0.0,"lng":0.0,"categories":[],"license":"CC0-1.0","tags":[],"opening_hours":" "}"#)
+                    .dispatch();
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+}
+
+#[test]
+fn create_entry_with_image_urls() {
+    let (client, db) = setup();
+    let response = client.post("/entries")
+                    .header(ContentType::JSON)
+                    .body(r#"{"title":"foo","description":"blablabla","lat":0.0,"lng":0.0,"categories":[],"license":"CC0-1.0","tags":[],"image_urls":["https://foo.tld/a.jpg","https://foo.tld/b.jpg"]}"#)
+                    .dispatch();
+    assert_eq!(response.status(), Status::Created);
+    let id = db.get().unwrap().all_entries().unwrap()[0].id.clone();
+    let mut response = client.get(format!("/entries/{}", id)).dispatch();
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    let entries: Vec<Entry> = serde_json::from_str(&body_str).unwrap();
+    assert_eq!(
+        entries[0].image_urls,
+        vec!["https://foo.tld/a.jpg", "https://foo.tld/b.jpg"]
+    );
+
+    // no images at all
+    let response = client.post("/entries")
+                    .header(ContentType::JSON)
+                    .body(r#"{"title":"bar","description":"blablabla","lat":0.0,"lng":0.0,"categories":[],"license":"CC0-1.0","tags":[]}"#)
+                    .dispatch();
+    assert_eq!(response.status(), Status::Created);
+    let entries = db.get().unwrap().all_entries().unwrap();
+    assert!(entries.iter().any(|e| e.title == "bar" && e.image_urls.is_empty()));
+
+    let response = client.post("/entries")
+                    .header(ContentType::JSON)
+                    .body(r#"{"title":"foo","description":"blablabla","lat":0.0,"lng":0.0,"categories":[],"license":"CC0-1.0","tags":[],"image_urls":["javascript:alert(1)"]}"#)
+                    .dispatch();
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+}
+
+#[test]
+fn create_entry_with_unsupported_license() {
+    let (client, db) = setup();
+    let req = client.post("/entries")
+                    .header(ContentType::JSON)
+                    .body(r#"{"title":"foo","description":"blablabla","lat":0.0,"lng":0.0,"categories":[],"license":"proprietary","tags":[]}"#);
+    let response = req.dispatch();
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    assert!(db.get().unwrap().all_entries().unwrap().is_empty());
+}
+
+#[test]
+fn cors_preflight() {
+    let (client, _) = setup_with_cors(Some(Cors::new("*")));
+    let response = client.options("/entries/x").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let headers = response.headers();
+    assert_eq!(headers.get_one("Access-Control-Allow-Origin"), Some("*"));
+    assert!(
+        headers
+            .get_one("Access-Control-Allow-Methods")
+            .unwrap()
+            .contains("PUT")
+    );
+    assert_eq!(
+        headers.get_one("Access-Control-Allow-Headers"),
+        Some("Content-Type, Authorization")
+    );
+    assert!(
+        headers
+            .get_one("Access-Control-Expose-Headers")
+            .unwrap()
+            .contains("X-Total-Count")
+    );
+}
+
+#[test]
+fn cors_without_credentials() {
+    let (client, _) = setup_with_cors(Some(Cors::new("https://example.com")));
+    let response = client.get("/server/version").dispatch();
+    let headers = response.headers();
+    assert_eq!(
+        headers.get_one("Access-Control-Allow-Origin"),
+        Some("https://example.com")
+    );
+    assert!(
+        headers
+            .get_one("Access-Control-Allow-Credentials")
+            .is_none()
+    );
+}
+
+#[test]
+fn count_entries_and_tags() {
+    let (client, db) = setup();
+    {
+        let mut conn = db.get().unwrap();
+        let mut e = Entry::build().id("a").finish();
+        conn.create_entry(&e).unwrap();
+        e.version = 1;
+        conn.update_entry(&e).unwrap();
+        conn.create_entry(&Entry::build().id("b").finish()).unwrap();
+        for t in &["bio", "fair", "vegan"] {
+            conn.create_tag_if_it_does_not_exist(&Tag { id: t.to_string() })
+                .unwrap();
+        }
+    }
+    let mut response = client.get("/count/entries").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    assert_eq!(body_str, "2");
+    let mut response = client.get("/count/tags").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    assert_eq!(body_str, "3");
+}
+
+#[test]
+fn no_cors_headers_by_default() {
+    let (client, _) = setup();
+    let response = client.get("/count/entries").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert!(
+        response
+            .headers()
+            .get_one("Access-Control-Allow-Origin")
+            .is_none()
+    );
 }
 
 #[test]
@@ -79,7 +392,7 @@ fn create_entry_with_tag_duplicates() {
                     .header(ContentType::JSON)
                     .body(r#"{"title":"foo","description":"blablabla","lat":0.0,"lng":0.0,"categories":["x"],"license":"CC0-1.0","tags":["foo","foo"]}"#);
     let mut response = req.dispatch();
-    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.status(), Status::Created);
     assert!(
         response
             .headers()
@@ -88,7 +401,8 @@ fn create_entry_with_tag_duplicates() {
     );
     let body_str = response.body().and_then(|b| b.into_string()).unwrap();
     let eid = db.get().unwrap().all_entries().unwrap()[0].id.clone();
-    assert_eq!(body_str, format!("\"{}\"", eid));
+    let e: Entry = serde_json::from_str(&body_str).unwrap();
+    assert_eq!(e.id, eid);
 }
 
 #[test]
@@ -109,7 +423,7 @@ fn create_entry_with_sharp_tag() {
         .header(ContentType::JSON)
         .body(json)
         .dispatch();
-    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.status(), Status::Created);
     let tags = db.get().unwrap().all_entries().unwrap()[0].tags.clone();
     assert_eq!(tags, vec!["foo", "bar"]);
 }
@@ -147,98 +461,571 @@ fn update_entry_with_tag_duplicates() {
 }
 
 #[test]
-fn get_one_entry() {
-    let e = Entry::build()
-        .id("get_one_entry_test")
-        .title("some")
-        .description("desc")
-        .finish();
+fn patch_entry_homepage() {
+    let (client, db) = setup();
+    let req = client.post("/entries")
+                    .header(ContentType::JSON)
+                    .body(r#"{"title":"foo","description":"blablabla","lat":1.0,"lng":2.0,"city":"Berlin","categories":[],"license":"CC0-1.0","tags":["bio"]}"#);
+    let _res = req.dispatch();
+    let old = db.get().unwrap().all_entries().unwrap()[0].clone();
+    let response = client
+        .patch(format!("/entries/{}", old.id))
+        .header(ContentType::JSON)
+        .body(r#"{"version":1,"homepage":"https://foo.org"}"#)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let e = db.get().unwrap().get_entry(&old.id).unwrap();
+    assert_eq!(e.version, 1);
+    assert_eq!(e.homepage, Some("https://foo.org".into()));
+    assert_eq!(e.title, old.title);
+    assert_eq!(e.description, old.description);
+    assert_eq!(e.lat, old.lat);
+    assert_eq!(e.lng, old.lng);
+    assert_eq!(e.city, old.city);
+    assert_eq!(e.tags, old.tags);
+    assert_eq!(e.license, old.license);
+}
 
+#[test]
+fn update_entry_with_stale_version() {
     let (client, db) = setup();
-    db.get().unwrap().create_entry(&e).unwrap();
-    usecase::rate_entry(
-        &mut *db.get().unwrap(),
-        usecase::RateEntry {
-            context: RatingContext::Humanity,
-            value: 2,
-            title: "title".into(),
-            user: None,
-            entry: "get_one_entry_test".into(),
-            comment: "bla".into(),
-            source: Some("blabla".into()),
-        },
-    ).unwrap();
-    let req = client.get("/entries/get_one_entry_test");
-    let mut response = req.dispatch();
+    let req = client.post("/entries")
+                    .header(ContentType::JSON)
+                    .body(r#"{"title":"foo","description":"blablabla","lat":0.0,"lng":0.0,"categories":[],"license":"CC0-1.0","tags":[]}"#);
+    let _res = req.dispatch();
+    let e = db.get().unwrap().all_entries().unwrap()[0].clone();
+    let json = |version: u64| {
+        format!(
+            r#"{{"version":{},"id":"{}","title":"bar","description":"blablabla","lat":0.0,"lng":0.0,"categories":[],"tags":[]}}"#,
+            version, e.id
+        )
+    };
+    let url = format!("/entries/{}", e.id);
+    let response = client
+        .put(url.clone())
+        .header(ContentType::JSON)
+        .body(json(1))
+        .dispatch();
     assert_eq!(response.status(), Status::Ok);
-    assert!(
-        response
-            .headers()
-            .iter()
-            .any(|h| h.name.as_str() == "Content-Type")
-    );
-    for h in response.headers().iter() {
-        match h.name.as_str() {
-            "Content-Type" => assert_eq!(h.value, "application/json"),
-            _ => { /* let these through */ }
-        }
-    }
+    // Somebody else has updated the entry in the meantime
+    let mut response = client
+        .put(url)
+        .header(ContentType::JSON)
+        .body(json(1))
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
     let body_str = response.body().and_then(|b| b.into_string()).unwrap();
-    assert_eq!(body_str.as_str().chars().nth(0).unwrap(), '[');
-    let entries: Vec<Entry> = serde_json::from_str(&body_str).unwrap();
-    let rid = db.get().unwrap().all_ratings().unwrap()[0].id.clone();
-    assert!(body_str.contains(&format!(r#""ratings":["{}"]"#, rid)));
-    assert!(entries[0] == e);
+    let conflict: json::VersionConflict = serde_json::from_str(&body_str).unwrap();
+    assert_eq!(conflict.error, "invalid_version");
+    assert_eq!(conflict.current_version, 1);
 }
 
 #[test]
-fn get_multiple_entries() {
-    let one = Entry::build()
-        .id("get_multiple_entry_test_one")
-        .title("some")
-        .description("desc")
-        .finish();
-    let two = Entry::build()
-        .id("get_multiple_entry_test_two")
-        .title("some")
-        .description("desc")
-        .finish();
-    let (client, db) = setup();
-    db.get().unwrap().create_entry(&one).unwrap();
-    db.get().unwrap().create_entry(&two).unwrap();
-    let req = client.get("/entries/get_multiple_entry_test_one,get_multiple_entry_test_two");
-    let mut response = req.dispatch();
+fn upsert_entries_as_admin() {
+    let (client, db) = setup_with_admins("upsertadmin");
+    let body = |title: &str, version: u64| {
+        format!(
+            r#"{{"id":"foo-1","upsert":true,"version":{},"title":"{}","description":"bar","lat":0.0,"lng":0.0,"categories":[],"license":"CC0-1.0","tags":[]}}"#,
+            version, title
+        )
+    };
+    let response = client
+        .post("/entries")
+        .header(ContentType::JSON)
+        .body(body("foo", 0))
+        .dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+    let response = client
+        .post("/entries/batch")
+        .header(ContentType::JSON)
+        .body(format!("[{}]", body("foo", 0)))
+        .dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+    assert!(db.get().unwrap().all_entries().unwrap().is_empty());
+
+    let cookie = login_as(&client, &db, "upsertadmin");
+    let response = client
+        .post("/entries")
+        .header(ContentType::JSON)
+        .cookie(cookie.clone())
+        .body(body("foo", 0))
+        .dispatch();
+    assert_eq!(response.status(), Status::Created);
+    let mut response = client
+        .post("/entries")
+        .header(ContentType::JSON)
+        .cookie(cookie.clone())
+        .body(body("bar", 1))
+        .dispatch();
     assert_eq!(response.status(), Status::Ok);
-    assert!(
-        response
-            .headers()
-            .iter()
-            .any(|h| h.name.as_str() == "Content-Type")
-    );
-    for h in response.headers().iter() {
-        match h.name.as_str() {
-            "Content-Type" => assert_eq!(h.value, "application/json"),
-            _ => { /* let these through */ }
-        }
-    }
     let body_str = response.body().and_then(|b| b.into_string()).unwrap();
-    assert_eq!(body_str.as_str().chars().nth(0).unwrap(), '[');
-    let entries: Vec<Entry> = serde_json::from_str(&body_str).unwrap();
-    assert_eq!(entries.len(), 2);
-    assert!(entries.iter().any(|x| *x == one));
-    assert!(entries.iter().any(|x| *x == two));
+    let e: Entry = serde_json::from_str(&body_str).unwrap();
+    assert_eq!(e.title, "bar");
+    assert_eq!(e.version, 1);
+    let response = client
+        .post("/entries")
+        .header(ContentType::JSON)
+        .cookie(cookie)
+        .body(body("baz", 1))
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    assert_eq!(db.get().unwrap().get_entry("foo-1").unwrap().title, "bar");
 }
 
 #[test]
-fn search_with_categories() {
-    let entries = vec![
-        Entry::build().id("a").categories(vec!["foo"]).finish(),
-        Entry::build().id("b").categories(vec!["foo"]).finish(),
-        Entry::build().id("c").categories(vec!["bar"]).finish(),
-    ];
+fn create_entries_in_a_batch() {
     let (client, db) = setup();
-    let mut conn = db.get().unwrap();
-    conn.create_category_if_it_does_not_exist(&Category {
+    let mut res = client.post("/entries/batch")
+                    .header(ContentType::JSON)
+                    .body(r#"[{"title":"foo","description":"bar","lat":0.0,"lng":0.0,"categories":[],"license":"CC0-1.0","tags":[]},{"title":"","description":"bar","lat":0.0,"lng":0.0,"categories":[],"license":"CC0-1.0","tags":[]}]"#)
+                    .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    let body_str = res.body().and_then(|b| b.into_string()).unwrap();
+    let results: Vec<json::BatchResult> = serde_json::from_str(&body_str).unwrap();
+    assert_eq!(results.len(), 2);
+    let entries = db.get().unwrap().all_entries().unwrap();
+    assert_eq!(entries.len(), 1);
+    match results[0] {
+        json::BatchResult::Ok(ref id) => assert_eq!(*id, entries[0].id),
+        _ => panic!("the first entry should have been created"),
+    }
+    match results[1] {
+        json::BatchResult::Err(_) => {}
+        _ => panic!("the second entry should have been rejected"),
+    }
+}
+
+#[test]
+fn limit_the_size_and_rate_of_entry_batches() {
+    let (client, db) = setup();
+    let json = r#"{"title":"foo","description":"blablabla","lat":0.0,"lng":0.0,"categories":[],"license":"CC0-1.0","tags":[]}"#;
+    let batch = |n: usize| format!("[{}]", vec![json; n].join(","));
+    let client_addr: SocketAddr = "10.0.0.3:4000".parse().unwrap();
+    let response = client
+        .post("/entries/batch")
+        .header(ContentType::JSON)
+        .remote(client_addr)
+        .body(batch(101))
+        .dispatch();
+    assert_eq!(response.status().code, 413);
+    // the default limit is 30 requests per minute
+    let response = client
+        .post("/entries/batch")
+        .header(ContentType::JSON)
+        .remote(client_addr)
+        .body(batch(31))
+        .dispatch();
+    assert_eq!(response.status().code, 413);
+    assert!(db.get().unwrap().all_entries().unwrap().is_empty());
+    // a full bucket takes a batch of the limit
+    let other_addr: SocketAddr = "10.0.0.4:4000".parse().unwrap();
+    let response = client
+        .post("/entries/batch")
+        .header(ContentType::JSON)
+        .remote(other_addr)
+        .body(batch(30))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(db.get().unwrap().all_entries().unwrap().len(), 30);
+    // but then the client has to wait for the bucket to refill
+    let response = client
+        .post("/entries/batch")
+        .header(ContentType::JSON)
+        .remote(other_addr)
+        .body(batch(2))
+        .dispatch();
+    assert_eq!(response.status().code, 429);
+    assert_eq!(db.get().unwrap().all_entries().unwrap().len(), 30);
+}
+
+#[test]
+fn rate_entries_in_a_batch() {
+    let (client, db) = setup();
+    db.get()
+        .unwrap()
+        .create_entry(&Entry::build().id("foo").finish())
+        .unwrap();
+    let mut res = client.post("/ratings/batch")
+                    .header(ContentType::JSON)
+                    .body(r#"[{"value":1,"context":"fairness","entry":"foo","comment":"a","title":"a"},{"value":3,"context":"fairness","entry":"foo","comment":"b","title":"b"},{"value":2,"context":"fairness","entry":"foo","comment":"c","title":"c"}]"#)
+                    .dispatch();
+    assert_eq!(res.status(), Status::Ok);
+    let body_str = res.body().and_then(|b| b.into_string()).unwrap();
+    let results: Vec<json::BatchResult> = serde_json::from_str(&body_str).unwrap();
+    assert_eq!(results.len(), 3);
+    match results[1] {
+        json::BatchResult::Err(_) => {}
+        _ => panic!("the second rating should have been rejected"),
+    }
+    let ratings = db.get().unwrap().all_ratings().unwrap();
+    assert_eq!(ratings.len(), 2);
+    for i in &[0, 2] {
+        match results[*i] {
+            json::BatchResult::Ok(ref id) => assert!(ratings.iter().any(|r| r.id == *id)),
+            _ => panic!("the rating should have been stored"),
+        }
+    }
+}
+
+#[test]
+fn limit_the_size_and_rate_of_rating_batches() {
+    let (client, db) = setup();
+    db.get()
+        .unwrap()
+        .create_entry(&Entry::build().id("foo").finish())
+        .unwrap();
+    let json = r#"{"value":1,"context":"fairness","entry":"foo","comment":"a","title":"a"}"#;
+    let batch = |n: usize| format!("[{}]", vec![json; n].join(","));
+    let client_addr: SocketAddr = "10.0.0.5:4000".parse().unwrap();
+    let response = client
+        .post("/ratings/batch")
+        .header(ContentType::JSON)
+        .remote(client_addr)
+        .body(batch(101))
+        .dispatch();
+    assert_eq!(response.status().code, 413);
+    let response = client
+        .post("/ratings/batch")
+        .header(ContentType::JSON)
+        .remote(client_addr)
+        .body(batch(31))
+        .dispatch();
+    assert_eq!(response.status().code, 413);
+    assert!(db.get().unwrap().all_ratings().unwrap().is_empty());
+    let other_addr: SocketAddr = "10.0.0.6:4000".parse().unwrap();
+    let response = client
+        .post("/ratings/batch")
+        .header(ContentType::JSON)
+        .remote(other_addr)
+        .body(batch(30))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(db.get().unwrap().all_ratings().unwrap().len(), 30);
+}
+
+#[test]
+fn get_duplicates_with_max_distance() {
+    let (client, db) = setup();
+    {
+        let mut conn = db.get().unwrap();
+        conn.create_entry(&Entry::build().id("a").title("Weltladen").lat(48.0).lng(9.0).finish())
+            .unwrap();
+        conn.create_entry(&Entry::build().id("b").title("Weltladen").lat(48.001).lng(9.0).finish())
+            .unwrap();
+    }
+    let mut response = client.get("/duplicates").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    assert_eq!(body_str, "[]");
+    let mut response = client.get("/duplicates?max_distance=200").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    assert_eq!(
+        body_str,
+        r#"[{"entry1":{"id":"a","title":"Weltladen"},"entry2":{"id":"b","title":"Weltladen"},"kind":"SimilarChars"}]"#
+    );
+}
+
+#[test]
+fn get_entry_history() {
+    let (client, db) = setup();
+    let req = client.post("/entries")
+                    .header(ContentType::JSON)
+                    .body(r#"{"title":"foo","description":"blablabla","lat":0.0,"lng":0.0,"categories":[],"license":"CC0-1.0","tags":[]}"#);
+    let _res = req.dispatch();
+    let e = db.get().unwrap().all_entries().unwrap()[0].clone();
+    let json = format!(
+        r#"{{"version":1,"id":"{}","title":"bar","description":"blablabla","lat":0.0,"lng":0.0,"categories":[],"tags":[]}}"#,
+        e.id
+    );
+    let response = client
+        .put(format!("/entries/{}", e.id))
+        .header(ContentType::JSON)
+        .body(json)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let mut response = client.get(format!("/entries/{}/history", e.id)).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    let versions: Vec<Entry> = serde_json::from_str(&body_str).unwrap();
+    assert_eq!(versions.len(), 2);
+    assert_eq!(versions[0].version, 0);
+    assert_eq!(versions[0].title, "foo");
+    assert_eq!(versions[0].updated, None);
+    assert_eq!(versions[1].version, 1);
+    assert_eq!(versions[1].title, "bar");
+    assert_eq!(versions[1].created, e.created);
+    assert!(versions[1].updated.is_some());
+    let response = client.get("/entries/unknown/history").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn delete_entry() {
+    let e = Entry::build()
+        .id("delete_entry_test")
+        .tags(vec!["foo"])
+        .finish();
+    let (client, db) = setup_with_admins("deleteadmin");
+    db.get()
+        .unwrap()
+        .create_tag_if_it_does_not_exist(&Tag { id: "foo".into() })
+        .unwrap();
+    db.get().unwrap().create_entry(&e).unwrap();
+    db.get()
+        .unwrap()
+        .create_rating(&Rating {
+            id: "r".into(),
+            entry_id: "delete_entry_test".into(),
+            created: 0,
+            title: "title".into(),
+            value: 1,
+            context: RatingContext::Fairness,
+            source: None,
+            created_by: None,
+        })
+        .unwrap();
+    let response = client.delete("/entries/delete_entry_test").dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+    let cookie = login_as(&client, &db, "deleteadmin");
+    let response = client
+        .delete("/entries/delete_entry_test")
+        .cookie(cookie.clone())
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert!(db.get().unwrap().all_entries().unwrap().is_empty());
+    assert!(db.get().unwrap().get_entry("delete_entry_test").is_err());
+    assert!(db.get().unwrap().all_ratings().unwrap().is_empty());
+    let response = client
+        .delete("/entries/delete_entry_test")
+        .cookie(cookie)
+        .dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn get_one_entry() {
+    let e = Entry::build()
+        .id("get_one_entry_test")
+        .title("some")
+        .description("desc")
+        .finish();
+
+    let (client, db) = setup();
+    db.get().unwrap().create_entry(&e).unwrap();
+    usecase::rate_entry(
+        &mut *db.get().unwrap(),
+        usecase::RateEntry {
+            context: RatingContext::Humanity,
+            value: 2,
+            title: "title".into(),
+            user: None,
+            entry: "get_one_entry_test".into(),
+            comment: "bla".into(),
+            source: Some("blabla".into()),
+        },
+    ).unwrap();
+    let req = client.get("/entries/get_one_entry_test");
+    let mut response = req.dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert!(
+        response
+            .headers()
+            .iter()
+            .any(|h| h.name.as_str() == "Content-Type")
+    );
+    for h in response.headers().iter() {
+        match h.name.as_str() {
+            "Content-Type" => assert_eq!(h.value, "application/json"),
+            _ => { /* let these through */ }
+        }
+    }
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    assert_eq!(body_str.as_str().chars().nth(0).unwrap(), '[');
+    let entries: Vec<Entry> = serde_json::from_str(&body_str).unwrap();
+    let rid = db.get().unwrap().all_ratings().unwrap()[0].id.clone();
+    assert!(body_str.contains(&format!(r#""ratings":["{}"]"#, rid)));
+    assert!(entries[0] == e);
+}
+
+#[test]
+fn get_entry_without_unknown_categories() {
+    let (client, db) = setup();
+    let mut conn = db.get().unwrap();
+    conn.create_category_if_it_does_not_exist(&Category {
+        id: "a".into(),
+        created: 0,
+        version: 0,
+        name: "a".into(),
+    }).unwrap();
+    let e = Entry::build()
+        .id("foo")
+        .categories(vec!["a", "deleted"])
+        .finish();
+    conn.create_entry(&e).unwrap();
+    let mut response = client.get("/entries/foo").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    let entries: serde_json::Value = serde_json::from_str(&body_str).unwrap();
+    let categories = entries[0]["categories"].as_array().unwrap();
+    assert_eq!(categories.len(), 1);
+    assert_eq!(categories[0], "a");
+}
+
+#[test]
+fn get_missing_entries() {
+    let (client, db) = setup();
+    db.get()
+        .unwrap()
+        .create_entry(&Entry::build().id("a").finish())
+        .unwrap();
+    let mut response = client.get("/entries/unknown").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+    assert_eq!(response.content_type(), Some(ContentType::JSON));
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    let err: json::Error = serde_json::from_str(&body_str).unwrap();
+    assert_eq!(err.error, "not_found");
+    assert_eq!(err.message, "The requested object could not be found");
+    let mut response = client.get("/entries/a,unknown").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    let entries: serde_json::Value = serde_json::from_str(&body_str).unwrap();
+    assert_eq!(entries.as_array().unwrap().len(), 1);
+    assert_eq!(entries[0]["id"], "a");
+}
+
+#[test]
+fn get_multiple_entries() {
+    let one = Entry::build()
+        .id("get_multiple_entry_test_one")
+        .title("some")
+        .description("desc")
+        .finish();
+    let two = Entry::build()
+        .id("get_multiple_entry_test_two")
+        .title("some")
+        .description("desc")
+        .finish();
+    let (client, db) = setup();
+    db.get().unwrap().create_entry(&one).unwrap();
+    db.get().unwrap().create_entry(&two).unwrap();
+    let req = client.get("/entries/get_multiple_entry_test_one,get_multiple_entry_test_two");
+    let mut response = req.dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert!(
+        response
+            .headers()
+            .iter()
+            .any(|h| h.name.as_str() == "Content-Type")
+    );
+    for h in response.headers().iter() {
+        match h.name.as_str() {
+            "Content-Type" => assert_eq!(h.value, "application/json"),
+            _ => { /* let these through */ }
+        }
+    }
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    assert_eq!(body_str.as_str().chars().nth(0).unwrap(), '[');
+    let entries: Vec<Entry> = serde_json::from_str(&body_str).unwrap();
+    assert_eq!(entries.len(), 2);
+    assert!(entries.iter().any(|x| *x == one));
+    assert!(entries.iter().any(|x| *x == two));
+}
+
+#[test]
+fn get_too_many_entries_at_once() {
+    let (client, _) = setup();
+    let ids = |n: usize| {
+        (0..n)
+            .map(|i| format!("id{}", i))
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    let response = client.get(format!("/entries/{}", ids(100))).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let response = client.get(format!("/entries/{}", ids(101))).dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+}
+
+#[test]
+fn get_unmodified_entry() {
+    let e = Entry::build().id("a").title("foo").description("bar").finish();
+    let (client, db) = setup();
+    db.get().unwrap().create_entry(&e).unwrap();
+    let mut response = client.get("/entries/a").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let etag = response.headers().get_one("ETag").unwrap().to_string();
+    assert!(etag.starts_with("W/"));
+    assert!(response.body().is_some());
+
+    let mut response = client
+        .get("/entries/a")
+        .header(Header::new("If-None-Match", etag.clone()))
+        .dispatch();
+    assert_eq!(response.status(), Status::NotModified);
+    assert_eq!(response.headers().get_one("ETag").unwrap(), etag);
+    assert!(response.body().is_none());
+
+    let mut e = e;
+    e.version = 1;
+    e.title = "changed".into();
+    db.get().unwrap().update_entry(&e).unwrap();
+    let response = client
+        .get("/entries/a")
+        .header(Header::new("If-None-Match", etag.clone()))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_ne!(response.headers().get_one("ETag").unwrap(), etag);
+}
+
+#[test]
+fn get_all_entries() {
+    let (client, db) = setup();
+    let mut conn = db.get().unwrap();
+    for i in 0..3 {
+        let e = Entry::build()
+            .id(&format!("entry-{}", i))
+            .created(i)
+            .finish();
+        conn.create_entry(&e).unwrap();
+    }
+    conn.create_rating(&Rating {
+        id: "r".into(),
+        entry_id: "entry-0".into(),
+        created: 0,
+        title: "title".into(),
+        value: 2,
+        context: RatingContext::Fairness,
+        source: None,
+        created_by: None,
+    }).unwrap();
+    let mut response = client.get("/entries").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    let entries: Vec<serde_json::Value> = serde_json::from_str(&body_str).unwrap();
+    let ids: Vec<_> = entries.iter().map(|e| e["id"].as_str().unwrap()).collect();
+    assert_eq!(ids, vec!["entry-0", "entry-1", "entry-2"]);
+    assert_eq!(entries[0]["ratings"].as_array().unwrap().len(), 1);
+    assert_eq!(entries[0]["ratings"][0], "r");
+
+    let mut response = client.get("/entries?offset=1&limit=1").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    let entries: Vec<serde_json::Value> = serde_json::from_str(&body_str).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["id"], "entry-1");
+}
+
+#[test]
+fn search_with_categories() {
+    let entries = vec![
+        Entry::build().id("a").categories(vec!["foo"]).finish(),
+        Entry::build().id("b").categories(vec!["foo"]).finish(),
+        Entry::build().id("c").categories(vec!["bar"]).finish(),
+    ];
+    let (client, db) = setup();
+    let mut conn = db.get().unwrap();
+    conn.create_category_if_it_does_not_exist(&Category {
         id: "foo".into(),
         created: 0,
         version: 0,
@@ -279,14 +1066,96 @@ fn search_with_categories() {
 }
 
 #[test]
-fn search_with_text() {
+fn search_clusters() {
     let entries = vec![
-        Entry::build()
-            .title("Foo")
-            .description("bla")
-            .id("a")
-            .finish(),
-        Entry::build()
+        Entry::build().id("a").lat(1.0).lng(1.0).finish(),
+        Entry::build().id("b").lat(3.0).lng(3.0).finish(),
+        Entry::build().id("c").lat(-5.0).lng(-5.0).finish(),
+        Entry::build().id("d").lat(50.0).lng(50.0).finish(),
+    ];
+    let (client, db) = setup();
+    let mut conn = db.get().unwrap();
+    for e in entries {
+        conn.create_entry(&e).unwrap();
+    }
+    let mut response = client
+        .get("/search/clusters?bbox=-10,-10,10,10&grid=5")
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    let clusters: serde_json::Value = serde_json::from_str(&body_str).unwrap();
+    assert_eq!(clusters.as_array().unwrap().len(), 2);
+    assert_eq!(clusters[0]["lat"], -5.0);
+    assert_eq!(clusters[0]["count"], 1);
+    assert_eq!(clusters[1]["lat"], 2.0);
+    assert_eq!(clusters[1]["lng"], 2.0);
+    assert_eq!(clusters[1]["count"], 2);
+
+    let response = client
+        .get("/search/clusters?bbox=-10,-10,10,10&grid=0")
+        .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+    let response = client.get("/search/clusters?bbox=-10,-10,10,10").dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+}
+
+#[test]
+fn search_with_all_categories() {
+    let entries = vec![
+        Entry::build().id("a").categories(vec!["foo"]).finish(),
+        Entry::build().id("b").categories(vec!["foo", "bar"]).finish(),
+    ];
+    let (client, db) = setup();
+    let mut conn = db.get().unwrap();
+    for id in &["foo", "bar"] {
+        conn.create_category_if_it_does_not_exist(&Category {
+            id: id.to_string(),
+            created: 0,
+            version: 0,
+            name: id.to_string(),
+        }).unwrap();
+    }
+    for e in entries {
+        conn.create_entry(&e).unwrap();
+    }
+    let ids = |url: &str| {
+        let mut response = client.get(url).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+        let res: serde_json::Value = serde_json::from_str(&body_str).unwrap();
+        let mut ids: Vec<_> = res["visible"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["id"].as_str().unwrap().to_string())
+            .collect();
+        ids.sort();
+        ids
+    };
+    assert_eq!(ids("/search?bbox=-10,-10,10,10&categories=foo,bar"), vec!["a", "b"]);
+    assert_eq!(
+        ids("/search?bbox=-10,-10,10,10&categories=foo,bar&categories_mode=any"),
+        vec!["a", "b"]
+    );
+    assert_eq!(
+        ids("/search?bbox=-10,-10,10,10&categories=foo,bar&categories_mode=all"),
+        vec!["b"]
+    );
+    let response = client
+        .get("/search?bbox=-10,-10,10,10&categories=foo&categories_mode=some")
+        .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+}
+
+#[test]
+fn search_with_text() {
+    let entries = vec![
+        Entry::build()
+            .title("Foo")
+            .description("bla")
+            .id("a")
+            .finish(),
+        Entry::build()
             .title("bar")
             .description("foo")
             .id("b")
@@ -311,6 +1180,397 @@ fn search_with_text() {
     assert!(!body_str.contains("\"c\""));
 }
 
+#[test]
+fn search_sorted_by_distance() {
+    let entries = vec![
+        Entry::build().id("a").lat(3.0).lng(3.0).finish(),
+        Entry::build().id("b").lat(0.5).lng(0.5).finish(),
+        Entry::build().id("c").lat(-1.0).lng(-1.0).finish(),
+    ];
+    let (client, db) = setup();
+    let mut conn = db.get().unwrap();
+    for e in entries {
+        conn.create_entry(&e).unwrap();
+    }
+    let mut response = client
+        .get("/search?bbox=-10,-10,10,10&sort=distance")
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    assert!(body_str.contains(
+        r#""visible":[{"id":"b","lat":0.5,"lng":0.5},{"id":"c","lat":-1.0,"lng":-1.0},{"id":"a","lat":3.0,"lng":3.0}]"#
+    ));
+
+    let response = client
+        .get("/search?bbox=-10,-10,10,10&sort=foo")
+        .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+}
+
+#[test]
+fn search_with_distances() {
+    let entries = vec![
+        Entry::build().id("a").lat(3.0).lng(3.0).finish(),
+        Entry::build().id("b").lat(0.0).lng(0.0).finish(),
+        Entry::build().id("c").lat(-1.0).lng(0.0).finish(),
+    ];
+    let (client, db) = setup();
+    let mut conn = db.get().unwrap();
+    for e in entries {
+        conn.create_entry(&e).unwrap();
+    }
+    let mut response = client
+        .get("/search?bbox=-10,-10,10,10&sort=distance")
+        .dispatch();
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    assert!(!body_str.contains("distance_m"));
+
+    let mut response = client
+        .get("/search?bbox=-10,-10,10,10&sort=distance&include=distance")
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    let res: serde_json::Value = serde_json::from_str(&body_str).unwrap();
+    let visible = res["visible"].as_array().unwrap();
+    let ids: Vec<_> = visible.iter().map(|x| x["id"].as_str().unwrap()).collect();
+    assert_eq!(ids, vec!["b", "c", "a"]);
+    let distances: Vec<_> = visible
+        .iter()
+        .map(|x| x["distance_m"].as_f64().unwrap())
+        .collect();
+    assert_eq!(distances[0], 0.0);
+    // one degree of latitude is about 111 km
+    assert!((distances[1] - 111_195.0).abs() < 100.0);
+    assert!(distances.windows(2).all(|d| d[0] <= d[1]));
+
+    let distance_of_c = |unit: &str, field: &str| {
+        let mut response = client
+            .get(format!(
+                "/search?bbox=-10,-10,10,10&sort=distance&include=distance&unit={}",
+                unit
+            ))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+        let res: serde_json::Value = serde_json::from_str(&body_str).unwrap();
+        let c = &res["visible"][1];
+        assert_eq!(c["id"], "c");
+        assert!(c["distance_m"].is_null() || field == "distance_m");
+        c[field].as_f64().unwrap()
+    };
+    assert!((distance_of_c("m", "distance_m") - 111_195.0).abs() < 100.0);
+    assert!((distance_of_c("km", "distance_km") - 111.195).abs() < 0.1);
+    assert!((distance_of_c("mi", "distance_mi") - 69.09).abs() < 0.1);
+
+    let response = client
+        .get("/search?bbox=-10,-10,10,10&include=distance&unit=ft")
+        .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+}
+
+#[test]
+fn compress_large_responses() {
+    let (client, db) = setup();
+    let mut conn = db.get().unwrap();
+    for i in 0..100 {
+        let e = Entry::build()
+            .id(&format!("entry-{}", i))
+            .title("foo")
+            .description("bar")
+            .finish();
+        conn.create_entry(&e).unwrap();
+    }
+    let mut response = client
+        .get("/search?bbox=-10,-10,10,10")
+        .header(Header::new("Accept-Encoding", "gzip, deflate"))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.headers().get_one("Content-Encoding"), Some("gzip"));
+    let body = response.body().and_then(|b| b.into_bytes()).unwrap();
+    let mut body_str = String::new();
+    GzDecoder::new(&body[..])
+        .read_to_string(&mut body_str)
+        .unwrap();
+    assert!(body_str.contains("entry-99"));
+
+    let response = client
+        .get("/search?bbox=-10,-10,10,10")
+        .dispatch();
+    assert!(response.headers().get_one("Content-Encoding").is_none());
+
+    let mut response = client
+        .get("/entries/unknown,other")
+        .header(Header::new("Accept-Encoding", "gzip"))
+        .dispatch();
+    assert!(response.headers().get_one("Content-Encoding").is_none());
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    assert_eq!(body_str, "[]");
+}
+
+#[test]
+fn search_with_embedded_entries() {
+    let (client, db) = setup();
+    let mut conn = db.get().unwrap();
+    conn.create_tag_if_it_does_not_exist(&Tag { id: "bio".into() })
+        .unwrap();
+    conn.create_entry(&Entry::build()
+        .id("a")
+        .title("Weltladen")
+        .lat(1.0)
+        .lng(1.0)
+        .tags(vec!["bio"])
+        .finish())
+        .unwrap();
+    conn.create_entry(&Entry::build().id("b").lat(20.0).lng(20.0).finish())
+        .unwrap();
+    usecase::rate_entry(
+        &mut *conn,
+        usecase::RateEntry {
+            context: RatingContext::Fairness,
+            value: 2,
+            user: None,
+            title: "title".into(),
+            entry: "a".into(),
+            comment: "bla".into(),
+            source: None,
+        },
+    ).unwrap();
+    let mut response = client
+        .get("/search?bbox=-10,-10,10,10&text=weltladen&embed=true")
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    let res: serde_json::Value = serde_json::from_str(&body_str).unwrap();
+    let visible = res["visible"].as_array().unwrap();
+    assert_eq!(visible.len(), 1);
+    assert_eq!(visible[0]["id"], "a");
+    assert_eq!(visible[0]["title"], "Weltladen");
+    assert_eq!(visible[0]["tags"][0], "bio");
+    assert_eq!(visible[0]["ratings"].as_array().unwrap().len(), 1);
+
+    let mut response = client
+        .get("/search?bbox=-10,-10,10,10&text=weltladen&embed=false")
+        .dispatch();
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    assert!(body_str.contains(r#""visible":[{"id":"a","lat":1.0,"lng":1.0}]"#));
+}
+
+#[test]
+fn export_geojson() {
+    let entries = vec![
+        Entry::build()
+            .id("a")
+            .title("foo")
+            .lat(1.0)
+            .lng(2.0)
+            .finish(),
+        Entry::build().id("b").lat(20.0).lng(20.0).finish(),
+    ];
+    let (client, db) = setup();
+    let mut conn = db.get().unwrap();
+    for e in entries {
+        conn.create_entry(&e).unwrap();
+    }
+    let mut response = client.get("/export/geojson?bbox=-10,-10,10,10").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    assert!(body_str.starts_with(r#"{"type":"FeatureCollection","features":[{"type":"Feature","id":"a","geometry":{"type":"Point","coordinates":[2.0,1.0]}"#));
+    assert!(!body_str.contains(r#""id":"b""#));
+}
+
+#[test]
+fn get_feed_of_new_entries() {
+    let mut entries = vec![
+        Entry::build().id("old").title("old").lat(1.0).lng(2.0).finish(),
+        Entry::build().id("new").title("new").lat(1.0).lng(2.0).finish(),
+        Entry::build().id("far").title("far").lat(20.0).lng(20.0).finish(),
+    ];
+    entries[0].created = 100;
+    entries[1].created = 200;
+    let (client, db) = setup();
+    let mut conn = db.get().unwrap();
+    for e in entries {
+        conn.create_entry(&e).unwrap();
+    }
+    let mut response = client.get("/feed?bbox=-10,-10,10,10").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(
+        response.headers().get_one("Content-Type"),
+        Some("application/rss+xml")
+    );
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    let new = body_str.find("<title>new</title>").unwrap();
+    let old = body_str.find("<title>old</title>").unwrap();
+    assert!(new < old);
+    assert!(!body_str.contains("far"));
+}
+
+#[test]
+fn export_gpx() {
+    let entries = vec![
+        Entry::build()
+            .id("a")
+            .title("foo & bar")
+            .lat(1.0)
+            .lng(2.0)
+            .finish(),
+        Entry::build().id("b").title("baz").lat(20.0).lng(20.0).finish(),
+    ];
+    let (client, db) = setup();
+    let mut conn = db.get().unwrap();
+    for e in entries {
+        conn.create_entry(&e).unwrap();
+    }
+    let mut response = client.get("/export/gpx?bbox=-10,-10,10,10").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(
+        response.headers().get_one("Content-Type"),
+        Some("application/gpx+xml")
+    );
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    assert!(body_str.contains(r#"<wpt lat="1" lon="2"><name>foo &amp; bar</name>"#));
+    assert!(!body_str.contains("baz"));
+}
+
+fn create_searchable_entries(n: usize) -> Vec<Entry> {
+    let titles = ["Weltladen", "Solawi", "Repair Café", "BIOLADEN", "Ökokiste"];
+    let tags = ["bio", "fair", "vegan", "regional"];
+    let categories = ["a", "b", "c"];
+    (0..n)
+        .map(|i| {
+            Entry::build()
+                .title(&format!("{} {}", titles[i % titles.len()], i))
+                .description("a searchable entry")
+                .lat((i % 179) as f64 - 89.0)
+                .lng((i % 359) as f64 - 179.0)
+                .tags(vec![
+                    tags[i % tags.len()],
+                    // never the same tag twice
+                    tags[(i + 1 + (i / 7) % (tags.len() - 1)) % tags.len()],
+                ])
+                .categories(vec![categories[i % categories.len()]])
+                .finish()
+        })
+        .collect()
+}
+
+fn setup_searchable_entries(n: usize) -> (Client, sqlite::ConnectionPool) {
+    let (client, db) = setup();
+    {
+        let mut conn = db.get().unwrap();
+        for id in &["a", "b", "c"] {
+            conn.create_category_if_it_does_not_exist(&Category {
+                id: id.to_string(),
+                created: 0,
+                version: 0,
+                name: id.to_string(),
+            }).unwrap();
+        }
+        conn.import_multiple_entries(&create_searchable_entries(n))
+            .unwrap();
+    }
+    (client, db)
+}
+
+fn search_params(
+    bbox: Option<&str>,
+    categories: Option<Vec<&str>>,
+    text: &str,
+    tags: Vec<&str>,
+) -> SearchParams {
+    SearchParams {
+        bbox: bbox.map(|b| ::business::geo::extract_bbox(b).unwrap()),
+        categories: categories.map(|c| c.into_iter().map(|x| x.to_string()).collect()),
+        categories_mode: Combination::Any,
+        text: text.into(),
+        tags: tags.into_iter().map(|x| x.to_string()).collect(),
+        exclude_tags: vec![],
+        city: None,
+        country: None,
+        created_after: None,
+        created_before: None,
+        include_archived: false,
+    }
+}
+
+#[test]
+fn search_entries_in_db_like_in_memory() {
+    let (_, db) = setup_searchable_entries(2_000);
+    let conn = db.get().unwrap();
+    let all_entries = conn.all_entries().unwrap();
+    let queries = vec![
+        search_params(Some("-10,-10,10,10"), None, "", vec![]),
+        search_params(Some("-10,170,10,-170"), None, "", vec![]),
+        search_params(Some("-50,-50,50,50"), Some(vec!["a", "c"]), "", vec![]),
+        search_params(None, None, "weltladen", vec![]),
+        search_params(None, None, "bioladen,solawi", vec![]),
+        search_params(None, None, "öko", vec![]),
+        search_params(None, None, "oko", vec![]),
+        search_params(None, None, "repair cafe", vec![]),
+        search_params(None, None, "vegan", vec![]),
+        search_params(None, None, "vegan", vec!["fair"]),
+        search_params(None, None, "", vec!["bio", "fair"]),
+        search_params(None, Some(vec!["b"]), "repair", vec!["vegan"]),
+        search_params(None, None, "", vec![""]),
+        SearchParams {
+            exclude_tags: vec!["vegan".into()],
+            ..search_params(None, None, "", vec!["bio"])
+        },
+        SearchParams {
+            exclude_tags: vec!["fair".into(), "regional".into()],
+            ..search_params(Some("-50,-50,50,50"), None, "", vec![])
+        },
+        SearchParams {
+            categories_mode: Combination::All,
+            ..search_params(None, Some(vec!["b"]), "", vec!["bio"])
+        },
+    ];
+    let categories = conn.all_categories().unwrap();
+    for params in queries {
+        let matches = ::business::filter::entries_by_search_params(&params, &categories);
+        let mut expected: Vec<_> = all_entries
+            .iter()
+            .filter(|e| matches(*e))
+            .map(|e| e.id.clone())
+            .collect();
+        let mut found: Vec<_> = conn.search_entries(&params)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.id)
+            .collect();
+        expected.sort();
+        found.sort();
+        assert!(!expected.is_empty(), "{:?}", params);
+        assert_eq!(found, expected, "{:?}", params);
+    }
+}
+
+#[ignore]
+#[bench]
+fn bench_search_5_000_entries_in_db(b: &mut Bencher) {
+    let (_, db) = setup_searchable_entries(5_000);
+    let conn = db.get().unwrap();
+    let params = search_params(None, Some(vec!["a"]), "weltladen", vec!["bio"]);
+    b.iter(|| conn.search_entries(&params).unwrap());
+}
+
+#[ignore]
+#[bench]
+fn bench_search_5_000_entries_in_memory(b: &mut Bencher) {
+    let (_, db) = setup_searchable_entries(5_000);
+    let conn = db.get().unwrap();
+    let params = search_params(None, Some(vec!["a"]), "weltladen", vec!["bio"]);
+    let categories = conn.all_categories().unwrap();
+    b.iter(|| {
+        let matches = ::business::filter::entries_by_search_params(&params, &categories);
+        conn.all_entries()
+            .unwrap()
+            .into_iter()
+            .filter(|e| matches(e))
+            .collect::<Vec<_>>()
+    });
+}
+
 #[ignore]
 #[bench]
 fn bench_search_in_10_000_rated_entries(b: &mut Bencher) {
@@ -326,6 +1586,64 @@ fn bench_search_in_10_000_rated_entries(b: &mut Bencher) {
     b.iter(|| client.get("/search?bbox=-10,-10,10,10").dispatch());
 }
 
+#[test]
+fn search_with_excluded_tags() {
+    let entries = vec![
+        Entry::build().id("a").tags(vec!["cafe"]).finish(),
+        Entry::build().id("b").tags(vec!["cafe", "chain"]).finish(),
+        Entry::build().id("c").tags(vec!["chain"]).finish(),
+    ];
+    let (client, db) = setup();
+    let mut conn = db.get().unwrap();
+    for t in &["cafe", "chain"] {
+        conn.create_tag_if_it_does_not_exist(&Tag { id: t.to_string() })
+            .unwrap();
+    }
+    for e in entries {
+        conn.create_entry(&e).unwrap();
+    }
+    let visible_ids = |url: &str| {
+        let mut response = client.get(url.to_string()).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+        let res: serde_json::Value = serde_json::from_str(&body_str).unwrap();
+        res["visible"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|x| x["id"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+    let mut ids = visible_ids("/search?bbox=-10,-10,10,10&text=%23cafe%20%23-chain");
+    ids.sort();
+    assert_eq!(ids, vec!["a"]);
+    let mut ids = visible_ids("/search?bbox=-10,-10,10,10&exclude_tags=chain");
+    ids.sort();
+    assert_eq!(ids, vec!["a"]);
+    let mut ids = visible_ids("/search?bbox=-10,-10,10,10&tags=chain&exclude_tags=cafe");
+    ids.sort();
+    assert_eq!(ids, vec!["c"]);
+}
+
+#[test]
+fn search_by_country() {
+    let mut at = Entry::build().id("at").title("Weltladen").finish();
+    at.country = Some("AT".into());
+    let mut de = Entry::build().id("de").title("Weltladen").finish();
+    de.country = Some("DE".into());
+    let (client, db) = setup();
+    let mut conn = db.get().unwrap();
+    conn.create_entry(&at).unwrap();
+    conn.create_entry(&de).unwrap();
+    let mut response = client
+        .get("/search?bbox=-10,-10,10,10&text=weltladen&country=DE")
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    assert!(body_str.contains(r#""id":"de""#));
+    assert!(!body_str.contains(r#""id":"at""#));
+}
+
 #[test]
 fn search_with_tags() {
     let entries = vec![
@@ -532,6 +1850,18 @@ fn remove_hash_tag_from_text() {
     assert_eq!(remove_hash_tags("some#tag"), "some");
     assert_eq!(remove_hash_tags("#tag"), "");
     assert_eq!(remove_hash_tags("some #text with #tags"), "some with");
+    assert_eq!(remove_hash_tags("some #tag but #-not-this"), "some but");
+}
+
+#[test]
+fn extract_excluded_hash_tags_from_text() {
+    assert_eq!(extract_excluded_hash_tags("#cafe").len(), 0);
+    assert_eq!(extract_excluded_hash_tags("#-").len(), 0);
+    assert_eq!(
+        extract_excluded_hash_tags("#cafe #-chain #-fast-food"),
+        vec!["chain".to_string(), "fast-food".to_string()]
+    );
+    assert_eq!(extract_hash_tags("#cafe #-chain"), vec!["cafe".to_string()]);
 }
 
 #[test]
@@ -589,15 +1919,218 @@ fn create_rating() {
 }
 
 #[test]
-fn get_one_rating() {
+fn create_rating_as_the_logged_in_user() {
+    let (client, db) = setup();
+    db.get()
+        .unwrap()
+        .create_entry(&Entry::build().id("foo").finish())
+        .unwrap();
+    let body = r#"{"value":1,"context":"fairness","entry":"foo","comment":"test","title":"t","user":"alice"}"#;
+    let response = client
+        .post("/ratings")
+        .header(ContentType::JSON)
+        .body(body)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(db.get().unwrap().all_ratings().unwrap()[0].created_by, None);
+    let bob = login_as(&client, &db, "bob");
+    let response = client
+        .post("/ratings")
+        .header(ContentType::JSON)
+        .cookie(bob)
+        .body(body)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let ratings = db.get().unwrap().all_ratings().unwrap();
+    assert_eq!(ratings.len(), 2);
+    assert!(ratings.iter().any(|r| r.created_by == Some("bob".into())));
+    assert!(ratings.iter().all(|r| r.created_by != Some("alice".into())));
+}
+
+#[test]
+fn get_rating_config() {
+    let (client, _) = setup();
+    let mut response = client.get("/ratings/config").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    assert_eq!(body_str, r#"{"min":-1,"max":2}"#);
+}
+
+#[test]
+fn get_rating_summary() {
     let e = Entry::build().id("foo").finish();
     let (client, db) = setup();
     db.get().unwrap().create_entry(&e).unwrap();
-    usecase::rate_entry(
-        &mut *db.get().unwrap(),
-        usecase::RateEntry {
-            context: RatingContext::Humanity,
-            value: 2,
+    for &value in &[2, 1] {
+        usecase::rate_entry(
+            &mut *db.get().unwrap(),
+            usecase::RateEntry {
+                context: RatingContext::Fairness,
+                value,
+                user: None,
+                title: "title".into(),
+                entry: "foo".into(),
+                comment: "bla".into(),
+                source: None,
+            },
+        ).unwrap();
+    }
+    let mut response = client.get("/entries/foo/ratings/summary").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    assert_eq!(body_str, r#"{"fairness":1.5}"#);
+    let response = client.get("/entries/bar/ratings/summary").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn delete_rating() {
+    let e = Entry::build().id("foo").finish();
+    let (client, db) = setup_with_admins("ratingadmin");
+    db.get().unwrap().create_entry(&e).unwrap();
+    let rate = |user: &str| {
+        usecase::rate_entry(
+            &mut *db.get().unwrap(),
+            usecase::RateEntry {
+                context: RatingContext::Humanity,
+                value: 2,
+                user: Some(user.into()),
+                title: "title".into(),
+                entry: "foo".into(),
+                comment: "bla".into(),
+                source: None,
+            },
+        ).unwrap()
+    };
+    let alices = rate("alice");
+    let bobs = rate("bob");
+
+    let response = client.delete(format!("/ratings/{}", alices)).dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+    let bob = login_as(&client, &db, "bob");
+    let response = client
+        .delete(format!("/ratings/{}", alices))
+        .cookie(bob)
+        .dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+
+    let alice = login_as(&client, &db, "alice");
+    let response = client
+        .delete(format!("/ratings/{}", alices))
+        .cookie(alice.clone())
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(db.get().unwrap().all_ratings().unwrap().len(), 1);
+    assert_eq!(db.get().unwrap().all_comments().unwrap().len(), 1);
+    let response = client
+        .delete(format!("/ratings/{}", alices))
+        .cookie(alice)
+        .dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+
+    let admin = login_as(&client, &db, "ratingadmin");
+    let response = client
+        .delete(format!("/ratings/{}", bobs))
+        .cookie(admin)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert!(db.get().unwrap().all_ratings().unwrap().is_empty());
+    assert!(db.get().unwrap().all_comments().unwrap().is_empty());
+}
+
+#[test]
+fn put_comment() {
+    let e = Entry::build().id("foo").finish();
+    let (client, db) = setup();
+    db.get().unwrap().create_entry(&e).unwrap();
+    usecase::rate_entry(
+        &mut *db.get().unwrap(),
+        usecase::RateEntry {
+            context: RatingContext::Humanity,
+            value: 2,
+            user: Some("alice".into()),
+            title: "title".into(),
+            entry: "foo".into(),
+            comment: "tpyo".into(),
+            source: None,
+        },
+    ).unwrap();
+    let old = db.get().unwrap().all_comments().unwrap()[0].clone();
+    let response = client
+        .put(format!("/comments/{}", old.id))
+        .header(ContentType::JSON)
+        .body(r#"{"text":"typo"}"#)
+        .dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+    let bob = login_as(&client, &db, "bob");
+    let response = client
+        .put(format!("/comments/{}", old.id))
+        .header(ContentType::JSON)
+        .cookie(bob)
+        .body(r#"{"text":"typo"}"#)
+        .dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+    let alice = login_as(&client, &db, "alice");
+    let response = client
+        .put(format!("/comments/{}", old.id))
+        .header(ContentType::JSON)
+        .cookie(alice.clone())
+        .body(r#"{"text":"typo"}"#)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let new = db.get().unwrap().all_comments().unwrap()[0].clone();
+    assert_eq!(new.text, "typo");
+    assert_eq!(new.created, old.created);
+    let response = client
+        .put(format!("/comments/{}", old.id))
+        .header(ContentType::JSON)
+        .cookie(alice.clone())
+        .body(r#"{"text":""}"#)
+        .dispatch();
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    let response = client
+        .put("/comments/unknown")
+        .header(ContentType::JSON)
+        .cookie(alice)
+        .body(r#"{"text":"foo"}"#)
+        .dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn create_entry_with_invalid_email() {
+    let (client, db) = setup();
+    let mut response = client.post("/entries")
+                    .header(ContentType::JSON)
+                    .body(r#"{"title":"foo","description":"blablabla","lat":0.0,"lng":0.0,"categories":[],"license":"CC0-1.0","tags":[],"email":"not-an-email"}"#)
+                    .dispatch();
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    let res: json::ValidationErrors = serde_json::from_str(&body_str).unwrap();
+    assert_eq!(res.error, "email");
+    assert_eq!(res.errors.len(), 1);
+    assert_eq!(res.errors[0].field, "email");
+    assert_eq!(res.errors[0].message, "Invalid email address");
+    assert!(db.get().unwrap().all_entries().unwrap().is_empty());
+
+    // malformed JSON
+    let response = client.post("/entries")
+                    .header(ContentType::JSON)
+                    .body(r#"{"title":"foo","#)
+                    .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+}
+
+#[test]
+fn get_one_rating() {
+    let e = Entry::build().id("foo").finish();
+    let (client, db) = setup();
+    db.get().unwrap().create_entry(&e).unwrap();
+    usecase::rate_entry(
+        &mut *db.get().unwrap(),
+        usecase::RateEntry {
+            context: RatingContext::Humanity,
+            value: 2,
             user: None,
             title: "title".into(),
             entry: "foo".into(),
@@ -628,6 +2161,39 @@ fn get_one_rating() {
     assert_eq!(ratings[0].comments.len(), 1);
 }
 
+#[test]
+fn get_a_single_rating_with_comments() {
+    let e = Entry::build().id("foo").finish();
+    let (client, db) = setup();
+    db.get().unwrap().create_entry(&e).unwrap();
+    usecase::rate_entry(
+        &mut *db.get().unwrap(),
+        usecase::RateEntry {
+            context: RatingContext::Fairness,
+            value: 1,
+            user: Some("alice".into()),
+            title: "title".into(),
+            entry: "foo".into(),
+            comment: "a comment".into(),
+            source: None,
+        },
+    ).unwrap();
+    let rid = db.get().unwrap().all_ratings().unwrap()[0].id.clone();
+    let mut response = client.get(format!("/ratings/{}/full", rid)).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    assert_eq!(body_str.chars().nth(0).unwrap(), '{');
+    let rating: json::Rating = serde_json::from_str(&body_str).unwrap();
+    assert_eq!(rating.id, rid);
+    assert_eq!(rating.value, 1);
+    assert_eq!(rating.user, Some("alice".into()));
+    assert_eq!(rating.comments.len(), 1);
+    assert_eq!(rating.comments[0].text, "a comment");
+
+    let response = client.get("/ratings/unknown/full").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
 #[test]
 fn ratings_with_and_without_source() {
     let e1 = Entry::build().id("foo").finish();
@@ -694,6 +2260,26 @@ fn user_id_cookie(response: &Response) -> Option<Cookie<'static>> {
     cookie.map(|c| c.into_owned())
 }
 
+/// Creates a confirmed user with the password "bar" and logs in.
+fn login_as(client: &Client, db: &sqlite::ConnectionPool, username: &str) -> Cookie<'static> {
+    db.get()
+        .unwrap()
+        .create_user(&User {
+            id: username.into(),
+            username: username.into(),
+            password: bcrypt::hash("bar").unwrap(),
+            email: format!("{}@bar", username),
+            email_confirmed: true,
+        })
+        .unwrap();
+    let response = client
+        .post("/login")
+        .header(ContentType::JSON)
+        .body(format!(r#"{{"username": "{}", "password": "bar"}}"#, username))
+        .dispatch();
+    user_id_cookie(&response).unwrap()
+}
+
 #[test]
 fn post_user() {
     let (client, _) = setup();
@@ -717,12 +2303,17 @@ fn login_with_invalid_credentials() {
         .post("/login")
         .header(ContentType::JSON)
         .body(r#"{"username": "foo", "password": "bar"}"#);
-    let response = req.dispatch();
+    let mut response = req.dispatch();
     assert!(!response
         .headers()
         .iter()
         .any(|h| h.name.as_str() == "Set-Cookie"));
     assert_eq!(response.status(), Status::Unauthorized);
+    assert_eq!(response.content_type(), Some(ContentType::JSON));
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    let err: json::Error = serde_json::from_str(&body_str).unwrap();
+    assert_eq!(err.error, "credentials");
+    assert_eq!(err.message, "Invalid credentials");
 }
 
 #[test]
@@ -744,169 +2335,714 @@ fn login_with_valid_credentials() {
     let response = client
         .post("/login")
         .header(ContentType::JSON)
-        .body(r#"{"username": "foo", "password": "bar"}"#)
+        .body(r#"{"username": "foo", "password": "bar"}"#)
+        .dispatch();
+    let cookie = user_id_cookie(&response).unwrap();
+    assert_eq!(response.status(), Status::Ok);
+    assert!(cookie.value().len() > 25);
+}
+
+#[test]
+fn login_with_a_bearer_token() {
+    let (client, db) = setup_with_secret(None, Admins::default(), JwtSecret::new("secret"));
+    db.get()
+        .unwrap()
+        .create_user(&User {
+            id: "123".into(),
+            username: "foo".into(),
+            password: bcrypt::hash("bar").unwrap(),
+            email: "foo@bar".into(),
+            email_confirmed: true,
+        })
+        .unwrap();
+    let mut response = client
+        .post("/login")
+        .header(ContentType::JSON)
+        .body(r#"{"username": "foo", "password": "bar"}"#)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    let token: Option<String> = serde_json::from_str(&body_str).unwrap();
+    let token = token.expect("a token");
+    let response = client
+        .get("/subscriptions")
+        .header(Header::new("Authorization", format!("Bearer {}", token)))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let response = client
+        .get("/subscriptions")
+        .header(Header::new("Authorization", "Bearer invalid"))
+        .dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+}
+
+#[test]
+fn login_logout_succeeds() {
+    let (client, db) = setup();
+    let users = vec![
+        User {
+            id: "123".into(),
+            username: "foo".into(),
+            password: bcrypt::hash("bar").unwrap(),
+            email: "foo@bar".into(),
+            email_confirmed: true,
+        },
+    ];
+    let mut conn = db.get().unwrap();
+    for u in users {
+        conn.create_user(&u).unwrap();
+    }
+
+    // Login
+    let response = client
+        .post("/login")
+        .header(ContentType::JSON)
+        .body(r#"{"username": "foo", "password": "bar"}"#)
+        .dispatch();
+    let cookie = user_id_cookie(&response).expect("login cookie");
+
+    // Logout
+    let response = client
+        .post("/logout")
+        .header(ContentType::JSON)
+        .cookie(cookie)
+        .dispatch();
+    let cookie = user_id_cookie(&response).expect("logout cookie");
+    assert_eq!(response.status(), Status::Ok);
+    assert!(cookie.value().is_empty());
+}
+
+#[test]
+fn get_user() {
+    let (client, db) = setup();
+    let users = vec![
+        User {
+            id: "123".into(),
+            username: "a".into(),
+            password: bcrypt::hash("a").unwrap(),
+            email: "a@bar".into(),
+            email_confirmed: true,
+        },
+        User {
+            id: "123".into(),
+            username: "b".into(),
+            password: bcrypt::hash("b").unwrap(),
+            email: "b@bar".into(),
+            email_confirmed: true,
+        },
+    ];
+    let mut conn = db.get().unwrap();
+    for u in users {
+        conn.create_user(&u).unwrap();
+    }
+    let response = client
+        .post("/login")
+        .header(ContentType::JSON)
+        .body(r#"{"username": "a", "password": "a"}"#)
+        .dispatch();
+
+    let cookie = user_id_cookie(&response).unwrap();
+
+    let response = client
+        .get("/users/b")
+        .header(ContentType::JSON)
+        .cookie(cookie.clone())
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Forbidden);
+
+    let mut response = client
+        .get("/users/a")
+        .header(ContentType::JSON)
+        .cookie(cookie)
+        .dispatch();
+
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(body_str, r#"{"username":"a","email":"a@bar"}"#);
+    assert!(
+        response
+            .headers()
+            .iter()
+            .any(|h| h.name.as_str() == "Content-Type")
+    );
+    for h in response.headers().iter() {
+        match h.name.as_str() {
+            "Content-Type" => assert_eq!(h.value, "application/json"),
+            _ => { /* let these through */ }
+        }
+    }
+}
+
+#[test]
+fn confirm_email_address() {
+    let (client, db) = setup();
+    let users = vec![
+        User {
+            id: "123".into(),
+            username: "foo".into(),
+            password: bcrypt::hash("bar").unwrap(),
+            email: "a@bar.de".into(),
+            email_confirmed: false,
+        },
+    ];
+    let mut conn = db.get().unwrap();
+    for u in users {
+        conn.create_user(&u).unwrap();
+    }
+
+    let response = client
+        .post("/login")
+        .header(ContentType::JSON)
+        .body(r#"{"username": "foo", "password": "bar"}"#)
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Forbidden);
+    assert_eq!(
+        db.get().unwrap().all_users().unwrap()[0].email_confirmed,
+        false
+    );
+
+    let response = client
+        .post("/confirm-email-address")
+        .header(ContentType::JSON)
+        .body(r#"{"u_id": "123"}"#)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(
+        db.get().unwrap().all_users().unwrap()[0].email_confirmed,
+        true
+    );
+
+    let response = client
+        .post("/login")
+        .header(ContentType::JSON)
+        .body(r#"{"username": "foo", "password": "bar"}"#)
+        .dispatch();
+    let cookie: Cookie = response
+        .headers()
+        .iter()
+        .filter(|h| h.name == "Set-Cookie")
+        .filter(|h| h.value.contains("user_id="))
+        .nth(0)
+        .unwrap()
+        .value
+        .parse()
+        .unwrap();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert!(cookie.value().len() > 25);
+}
+
+#[test]
+fn confirm_email_address_of_unknown_user() {
+    let (client, _) = setup();
+    let response = client
+        .post("/confirm-email-address")
+        .header(ContentType::JSON)
+        .body(r#"{"u_id": "does-not-exist"}"#)
+        .dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn get_tag_counts() {
+    let (client, db) = setup();
+    {
+        let mut conn = db.get().unwrap();
+        for t in vec!["bio", "fair", "unused"] {
+            conn.create_tag_if_it_does_not_exist(&Tag { id: t.into() })
+                .unwrap();
+        }
+        conn.create_entry(&Entry::build().id("a").tags(vec!["bio", "fair"]).finish())
+            .unwrap();
+        conn.create_entry(&Entry::build().id("b").tags(vec!["fair"]).finish())
+            .unwrap();
+    }
+    let mut response = client.get("/tags/counts").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    let counts: Vec<json::TagCount> = serde_json::from_str(&body_str).unwrap();
+    let counts: Vec<_> = counts.into_iter().map(|c| (c.tag, c.count)).collect();
+    assert_eq!(
+        counts,
+        vec![
+            ("fair".to_string(), 2),
+            ("bio".to_string(), 1),
+            ("unused".to_string(), 0),
+        ]
+    );
+}
+
+#[test]
+fn merge_tags_as_admin() {
+    let (client, db) = setup_with_admins("mergeadmin");
+    {
+        let mut conn = db.get().unwrap();
+        for (id, username) in vec![("1", "mergeadmin"), ("2", "mergeuser")] {
+            conn.create_user(&User {
+                id: id.into(),
+                username: username.into(),
+                password: bcrypt::hash("bar").unwrap(),
+                email: format!("{}@bar", username),
+                email_confirmed: true,
+            }).unwrap();
+        }
+        let e = Entry::build()
+            .id("e")
+            .tags(vec!["co-working", "bio"])
+            .finish();
+        conn.create_tag_if_it_does_not_exist(&Tag { id: "co-working".into() })
+            .unwrap();
+        conn.create_tag_if_it_does_not_exist(&Tag { id: "bio".into() })
+            .unwrap();
+        conn.create_entry(&e).unwrap();
+    }
+    let body = r#"{"from": "co-working", "into": "coworking"}"#;
+
+    let response = client
+        .post("/tags/merge")
+        .header(ContentType::JSON)
+        .body(body)
+        .dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+
+    let response = client
+        .post("/login")
+        .header(ContentType::JSON)
+        .body(r#"{"username": "mergeuser", "password": "bar"}"#)
+        .dispatch();
+    let cookie = user_id_cookie(&response).unwrap();
+    let response = client
+        .post("/tags/merge")
+        .header(ContentType::JSON)
+        .cookie(cookie)
+        .body(body)
+        .dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+
+    let response = client
+        .post("/login")
+        .header(ContentType::JSON)
+        .body(r#"{"username": "mergeadmin", "password": "bar"}"#)
+        .dispatch();
+    let cookie = user_id_cookie(&response).unwrap();
+    let response = client
+        .post("/tags/merge")
+        .header(ContentType::JSON)
+        .cookie(cookie)
+        .body(body)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let conn = db.get().unwrap();
+    let mut e = conn.get_entry("e").unwrap();
+    e.tags.sort();
+    assert_eq!(e.tags, vec!["bio", "coworking"]);
+    let tags: Vec<_> = conn.all_tags().unwrap().into_iter().map(|t| t.id).collect();
+    assert!(!tags.contains(&"co-working".to_string()));
+    assert!(tags.contains(&"coworking".to_string()));
+}
+
+#[test]
+fn create_category_as_admin() {
+    let (client, db) = setup_with_admins("categoryadmin");
+    {
+        let mut conn = db.get().unwrap();
+        for (id, username) in vec![("1", "categoryadmin"), ("2", "categoryuser")] {
+            conn.create_user(&User {
+                id: id.into(),
+                username: username.into(),
+                password: bcrypt::hash("bar").unwrap(),
+                email: format!("{}@bar", username),
+                email_confirmed: true,
+            }).unwrap();
+        }
+    }
+    let body = r#"{"name": "Initiative"}"#;
+
+    let response = client
+        .post("/login")
+        .header(ContentType::JSON)
+        .body(r#"{"username": "categoryuser", "password": "bar"}"#)
+        .dispatch();
+    let cookie = user_id_cookie(&response).unwrap();
+    let response = client
+        .post("/categories")
+        .header(ContentType::JSON)
+        .cookie(cookie)
+        .body(body)
+        .dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+
+    let response = client
+        .post("/login")
+        .header(ContentType::JSON)
+        .body(r#"{"username": "categoryadmin", "password": "bar"}"#)
+        .dispatch();
+    let cookie = user_id_cookie(&response).unwrap();
+    let mut response = client
+        .post("/categories")
+        .header(ContentType::JSON)
+        .cookie(cookie.clone())
+        .body(body)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    let id: String = serde_json::from_str(&body_str).unwrap();
+
+    let mut response = client.get("/categories").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    let categories: Vec<Category> = serde_json::from_str(&body_str).unwrap();
+    assert_eq!(categories.len(), 1);
+    assert_eq!(categories[0].id, id);
+    assert_eq!(categories[0].name, "Initiative");
+
+    let response = client
+        .post("/categories")
+        .header(ContentType::JSON)
+        .cookie(cookie)
+        .body(r#"{"name": "initiative"}"#)
+        .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+    assert_eq!(db.get().unwrap().all_categories().unwrap().len(), 1);
+}
+
+#[test]
+fn archive_entry_as_admin() {
+    let (client, db) = setup_with_admins("archiveadmin");
+    {
+        let mut conn = db.get().unwrap();
+        conn.create_user(&User {
+            id: "1".into(),
+            username: "archiveadmin".into(),
+            password: bcrypt::hash("bar").unwrap(),
+            email: "archiveadmin@bar".into(),
+            email_confirmed: true,
+        }).unwrap();
+        conn.create_entry(&Entry::build().id("a").title("foo").finish())
+            .unwrap();
+        conn.create_entry(&Entry::build().id("b").title("foo").finish())
+            .unwrap();
+    }
+
+    let response = client.post("/entries/a/archive").dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+
+    let response = client
+        .post("/login")
+        .header(ContentType::JSON)
+        .body(r#"{"username": "archiveadmin", "password": "bar"}"#)
+        .dispatch();
+    let cookie = user_id_cookie(&response).unwrap();
+    let response = client
+        .post("/entries/a/archive")
+        .cookie(cookie.clone())
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert!(db.get().unwrap().get_entry("a").unwrap().archived.is_some());
+
+    let mut response = client.get("/count/entries").dispatch();
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    assert_eq!(body_str, "1");
+
+    let mut response = client.get("/search?bbox=-10,-10,10,10&text=foo").dispatch();
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    assert!(!body_str.contains("\"a\""));
+    assert!(body_str.contains("\"b\""));
+
+    let mut response = client
+        .get("/search?bbox=-10,-10,10,10&text=foo&include_archived=true")
+        .dispatch();
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    assert!(body_str.contains("\"a\""));
+
+    let response = client
+        .post("/entries/a/unarchive")
+        .cookie(cookie)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert!(db.get().unwrap().get_entry("a").unwrap().archived.is_none());
+}
+
+#[test]
+fn rollback_failing_transaction() {
+    use business::error::RepoError;
+    let (_, db) = setup();
+    let mut conn = db.get().unwrap();
+    conn.create_entry(&Entry::build().id("foo").finish()).unwrap();
+    usecase::rate_entry(
+        &mut *conn,
+        usecase::RateEntry {
+            context: RatingContext::Fairness,
+            value: 1,
+            user: None,
+            title: "title".into(),
+            entry: "foo".into(),
+            comment: "bla".into(),
+            source: None,
+        },
+    ).unwrap();
+    let mut rating = conn.all_ratings().unwrap()[0].clone();
+    rating.id = "new".into();
+
+    let res: Result<(), RepoError> = Db::transaction(&mut *conn, |db| {
+        db.create_rating(&rating)?;
+        let comment_id = db.all_comments()?[0].id.clone();
+        db.delete_comment(&comment_id)?;
+        db.get_comment("new")?;
+        Ok(())
+    });
+    assert!(res.is_err());
+    assert_eq!(conn.all_ratings().unwrap().len(), 1);
+    assert_eq!(conn.all_comments().unwrap().len(), 1);
+
+    let res: Result<(), RepoError> = Db::transaction(&mut *conn, |db| {
+        Db::transaction(db, |db| db.create_rating(&rating))?;
+        db.get_rating("new")?;
+        Ok(())
+    });
+    assert!(res.is_ok());
+    assert_eq!(conn.all_ratings().unwrap().len(), 2);
+}
+
+#[test]
+fn rollback_panicking_transaction() {
+    use business::error::RepoError;
+    use std::panic::{self, AssertUnwindSafe};
+    let (_, db) = setup();
+    let mut conn = db.get().unwrap();
+    let res = panic::catch_unwind(AssertUnwindSafe(|| {
+        let _: Result<(), RepoError> = Db::transaction(&mut *conn, |db| {
+            db.create_entry(&Entry::build().id("foo").finish())?;
+            panic!("failed within the transaction");
+        });
+    }));
+    assert!(res.is_err());
+    assert!(conn.all_entries().unwrap().is_empty());
+    // the connection is usable for new transactions
+    let res: Result<(), RepoError> = Db::transaction(&mut *conn, |db| {
+        db.create_entry(&Entry::build().id("bar").finish())
+    });
+    assert!(res.is_ok());
+    assert_eq!(conn.all_entries().unwrap().len(), 1);
+}
+
+#[test]
+fn merge_entries_as_admin() {
+    let (client, db) = setup_with_admins("mergeadmin");
+    {
+        let mut conn = db.get().unwrap();
+        conn.create_user(&User {
+            id: "1".into(),
+            username: "mergeadmin".into(),
+            password: bcrypt::hash("bar").unwrap(),
+            email: "mergeadmin@bar".into(),
+            email_confirmed: true,
+        }).unwrap();
+        for t in &["bio", "fair"] {
+            conn.create_tag_if_it_does_not_exist(&Tag { id: t.to_string() })
+                .unwrap();
+        }
+        conn.create_entry(&Entry::build().id("a").tags(vec!["bio"]).finish())
+            .unwrap();
+        conn.create_entry(&Entry::build().id("b").tags(vec!["bio", "fair"]).finish())
+            .unwrap();
+    }
+    usecase::rate_entry(
+        &mut *db.get().unwrap(),
+        usecase::RateEntry {
+            context: RatingContext::Humanity,
+            value: 2,
+            user: None,
+            title: "title".into(),
+            entry: "b".into(),
+            comment: "bla".into(),
+            source: None,
+        },
+    ).unwrap();
+    let body = r#"{"keep": "a", "drop": "b"}"#;
+
+    let response = client
+        .post("/entries/merge")
+        .header(ContentType::JSON)
+        .body(body)
+        .dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+
+    let response = client
+        .post("/login")
+        .header(ContentType::JSON)
+        .body(r#"{"username": "mergeadmin", "password": "bar"}"#)
         .dispatch();
     let cookie = user_id_cookie(&response).unwrap();
-    assert_eq!(response.status(), Status::Ok);
-    assert!(cookie.value().len() > 25);
+    for _ in 0..2 {
+        let response = client
+            .post("/entries/merge")
+            .header(ContentType::JSON)
+            .cookie(cookie.clone())
+            .body(body)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    let conn = db.get().unwrap();
+    let mut e = conn.get_entry("a").unwrap();
+    e.tags.sort();
+    assert_eq!(e.tags, vec!["bio", "fair"]);
+    assert!(conn.get_entry("b").is_err());
+    assert_eq!(conn.all_ratings().unwrap()[0].entry_id, "a");
 }
 
 #[test]
-fn login_logout_succeeds() {
+fn change_password() {
     let (client, db) = setup();
-    let users = vec![
-        User {
+    db.get()
+        .unwrap()
+        .create_user(&User {
             id: "123".into(),
             username: "foo".into(),
             password: bcrypt::hash("bar").unwrap(),
             email: "foo@bar".into(),
             email_confirmed: true,
-        },
-    ];
-    let mut conn = db.get().unwrap();
-    for u in users {
-        conn.create_user(&u).unwrap();
-    }
+        })
+        .unwrap();
+    let response = client
+        .post("/users/change-password")
+        .header(ContentType::JSON)
+        .body(r#"{"old_password": "bar", "new_password": "baz"}"#)
+        .dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
 
-    // Login
     let response = client
         .post("/login")
         .header(ContentType::JSON)
         .body(r#"{"username": "foo", "password": "bar"}"#)
         .dispatch();
-    let cookie = user_id_cookie(&response).expect("login cookie");
+    let cookie = user_id_cookie(&response).unwrap();
 
-    // Logout
     let response = client
-        .post("/logout")
+        .post("/users/change-password")
+        .header(ContentType::JSON)
+        .cookie(cookie.clone())
+        .body(r#"{"old_password": "wrong", "new_password": "baz"}"#)
+        .dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+
+    let response = client
+        .post("/users/change-password")
         .header(ContentType::JSON)
         .cookie(cookie)
+        .body(r#"{"old_password": "bar", "new_password": "baz"}"#)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let response = client
+        .post("/login")
+        .header(ContentType::JSON)
+        .body(r#"{"username": "foo", "password": "baz"}"#)
         .dispatch();
-    let cookie = user_id_cookie(&response).expect("logout cookie");
     assert_eq!(response.status(), Status::Ok);
-    assert!(cookie.value().is_empty());
 }
 
 #[test]
-fn get_user() {
+fn get_entries_created_by_user() {
     let (client, db) = setup();
-    let users = vec![
-        User {
-            id: "123".into(),
-            username: "a".into(),
-            password: bcrypt::hash("a").unwrap(),
-            email: "a@bar".into(),
-            email_confirmed: true,
-        },
-        User {
+    db.get()
+        .unwrap()
+        .create_user(&User {
             id: "123".into(),
-            username: "b".into(),
-            password: bcrypt::hash("b").unwrap(),
-            email: "b@bar".into(),
+            username: "foo".into(),
+            password: bcrypt::hash("bar").unwrap(),
+            email: "foo@bar".into(),
             email_confirmed: true,
-        },
-    ];
-    let mut conn = db.get().unwrap();
-    for u in users {
-        conn.create_user(&u).unwrap();
-    }
+        })
+        .unwrap();
     let response = client
         .post("/login")
         .header(ContentType::JSON)
-        .body(r#"{"username": "a", "password": "a"}"#)
+        .body(r#"{"username": "foo", "password": "bar"}"#)
         .dispatch();
-
     let cookie = user_id_cookie(&response).unwrap();
 
+    let body = r#"{"title":"foo","description":"blablabla","lat":0.0,"lng":0.0,"categories":[],"license":"CC0-1.0","tags":[]}"#;
+    let mut response = client
+        .post("/entries")
+        .header(ContentType::JSON)
+        .cookie(cookie.clone())
+        .body(body)
+        .dispatch();
+    assert_eq!(response.status(), Status::Created);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    let id = serde_json::from_str::<Entry>(&body_str).unwrap().id;
+    // anonymous entries are still allowed
     let response = client
-        .get("/users/b")
+        .post("/entries")
         .header(ContentType::JSON)
+        .body(body)
+        .dispatch();
+    assert_eq!(response.status(), Status::Created);
+
+    let mut response = client
+        .get("/users/foo/entries")
         .cookie(cookie.clone())
         .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    let entries: Vec<Entry> = serde_json::from_str(&body_str).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].id, id);
 
+    let response = client.get("/users/baz/entries").cookie(cookie).dispatch();
     assert_eq!(response.status(), Status::Forbidden);
+}
 
-    let mut response = client
-        .get("/users/a")
+#[test]
+fn request_password_reset_for_unknown_email() {
+    let (client, _) = setup();
+    let response = client
+        .post("/users/request-password-reset")
         .header(ContentType::JSON)
-        .cookie(cookie)
+        .body(r#"{"email": "does-not-exist@bar.de"}"#)
         .dispatch();
-
-    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
     assert_eq!(response.status(), Status::Ok);
-    assert_eq!(body_str, r#"{"username":"a","email":"a@bar"}"#);
-    assert!(
-        response
-            .headers()
-            .iter()
-            .any(|h| h.name.as_str() == "Content-Type")
-    );
-    for h in response.headers().iter() {
-        match h.name.as_str() {
-            "Content-Type" => assert_eq!(h.value, "application/json"),
-            _ => { /* let these through */ }
-        }
-    }
 }
 
 #[test]
-fn confirm_email_address() {
+fn reset_password() {
     let (client, db) = setup();
-    let users = vec![
-        User {
-            id: "123".into(),
-            username: "foo".into(),
-            password: bcrypt::hash("bar").unwrap(),
-            email: "a@bar.de".into(),
-            email_confirmed: false,
-        },
-    ];
     let mut conn = db.get().unwrap();
-    for u in users {
-        conn.create_user(&u).unwrap();
-    }
-
+    conn.create_user(&User {
+        id: "123".into(),
+        username: "foo".into(),
+        password: bcrypt::hash("bar").unwrap(),
+        email: "foo@bar".into(),
+        email_confirmed: true,
+    }).unwrap();
+    conn.create_password_reset(&PasswordReset {
+        token: "abc".into(),
+        username: "foo".into(),
+        created: ::chrono::Utc::now().timestamp() as u64,
+    }).unwrap();
     let response = client
-        .post("/login")
+        .post("/users/reset-password")
         .header(ContentType::JSON)
-        .body(r#"{"username": "foo", "password": "bar"}"#)
+        .body(r#"{"token": "abc", "new_password": "baz"}"#)
         .dispatch();
-
-    assert_eq!(response.status(), Status::Forbidden);
-    assert_eq!(
-        db.get().unwrap().all_users().unwrap()[0].email_confirmed,
-        false
-    );
-
+    assert_eq!(response.status(), Status::Ok);
     let response = client
-        .post("/confirm-email-address")
+        .post("/login")
         .header(ContentType::JSON)
-        .body(r#"{"u_id": "123"}"#)
+        .body(r#"{"username": "foo", "password": "baz"}"#)
         .dispatch();
     assert_eq!(response.status(), Status::Ok);
-    assert_eq!(
-        db.get().unwrap().all_users().unwrap()[0].email_confirmed,
-        true
-    );
-
     let response = client
-        .post("/login")
+        .post("/users/reset-password")
         .header(ContentType::JSON)
-        .body(r#"{"username": "foo", "password": "bar"}"#)
+        .body(r#"{"token": "abc", "new_password": "qux"}"#)
         .dispatch();
-    let cookie: Cookie = response
-        .headers()
-        .iter()
-        .filter(|h| h.name == "Set-Cookie")
-        .filter(|h| h.value.contains("user_id="))
-        .nth(0)
-        .unwrap()
-        .value
-        .parse()
-        .unwrap();
-
-    assert_eq!(response.status(), Status::Ok);
-    assert!(cookie.value().len() > 25);
+    assert_eq!(response.status(), Status::BadRequest);
 }
 
 //TODO: make it pass
@@ -967,3 +3103,176 @@ fn subscribe_to_bbox() {
         .dispatch();
     assert_eq!(response.status(), Status::Ok);
 }
+
+#[test]
+fn get_and_delete_subscriptions() {
+    let (client, db) = setup();
+    db.get()
+        .unwrap()
+        .create_user(&User {
+            id: "123".into(),
+            username: "foo".into(),
+            password: bcrypt::hash("bar").unwrap(),
+            email: "foo@bar".into(),
+            email_confirmed: true,
+        })
+        .unwrap();
+    let response = client.get("/subscriptions").dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+
+    let response = client
+        .post("/login")
+        .header(ContentType::JSON)
+        .body(r#"{"username": "foo", "password": "bar"}"#)
+        .dispatch();
+    let cookie = user_id_cookie(&response).unwrap();
+
+    let mut response = client.get("/subscriptions").cookie(cookie.clone()).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    assert_eq!(body_str, "[]");
+
+    let response = client
+        .post("/subscribe-to-bbox")
+        .header(ContentType::JSON)
+        .cookie(cookie.clone())
+        .body(r#"[{"lat":-10.0,"lng":-10.0},{"lat":10.0,"lng":10.0}]"#)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let mut response = client.get("/subscriptions").cookie(cookie.clone()).dispatch();
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    let subs: Vec<json::Subscription> = serde_json::from_str(&body_str).unwrap();
+    assert_eq!(subs.len(), 1);
+    assert_eq!(subs[0].south_west.lat, -10.0);
+    assert_eq!(subs[0].north_east.lng, 10.0);
+
+    let response = client.delete("/subscriptions").cookie(cookie.clone()).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let mut response = client.get("/subscriptions").cookie(cookie).dispatch();
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    assert_eq!(body_str, "[]");
+}
+
+#[test]
+fn get_server_info() {
+    let (client, _) = setup();
+    let mut response = client.get("/server/info").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    let info: serde_json::Value = serde_json::from_str(&body_str).unwrap();
+    assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+    assert!(info["build_timestamp"].as_u64().unwrap() > 0);
+    assert!(info["base_url"].is_string());
+
+    let mut response = client.get("/server/version").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    assert_eq!(body_str, env!("CARGO_PKG_VERSION"));
+}
+
+#[test]
+fn search_with_swapped_bbox() {
+    let (client, _) = setup();
+    let response = client.get("/search?bbox=10,-10,-10,10").dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+    // crosses the antimeridian
+    let response = client.get("/search?bbox=-10,170,10,-170").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+}
+
+#[test]
+fn search_by_created_range() {
+    let (client, db) = setup();
+    let mut conn = db.get().unwrap();
+    // 2017-12-31T23:59:59Z and 2018-01-01T00:00:00Z
+    conn.create_entry(&Entry::build().id("a").created(1_514_764_799).finish()).unwrap();
+    conn.create_entry(&Entry::build().id("b").created(1_514_764_800).finish()).unwrap();
+    let mut response = client
+        .get("/search?bbox=-10,-10,10,10&created_after=2018-01-01")
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    assert!(!body_str.contains("\"a\""));
+    assert!(body_str.contains("\"b\""));
+    let mut response = client
+        .get("/search?bbox=-10,-10,10,10&created_before=2017-12-31")
+        .dispatch();
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    assert!(body_str.contains("\"a\""));
+    assert!(!body_str.contains("\"b\""));
+    let response = client
+        .get("/search?bbox=-10,-10,10,10&created_after=yesterday")
+        .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+}
+
+#[test]
+fn search_with_total_count() {
+    let (client, db) = setup();
+    let mut conn = db.get().unwrap();
+    conn.create_entry(&Entry::build().id("in").title("Foo").finish()).unwrap();
+    for i in 0..7 {
+        let e = Entry::build()
+            .id(&format!("out{}", i))
+            .title("Foo")
+            .lat(50.0)
+            .lng(f64::from(i))
+            .finish();
+        conn.create_entry(&e).unwrap();
+    }
+    let mut response = client.get("/search?bbox=-10,-10,10,10&text=Foo").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.headers().get_one("X-Total-Count"), Some("8"));
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    let res: serde_json::Value = serde_json::from_str(&body_str).unwrap();
+    assert_eq!(res["visible"].as_array().unwrap().len(), 1);
+    assert_eq!(res["invisible"].as_array().unwrap().len(), 5);
+
+    let response = client.get("/export/gpx?bbox=-10,-10,10,10&text=Foo").dispatch();
+    assert_eq!(response.headers().get_one("X-Total-Count"), Some("8"));
+}
+
+#[test]
+fn get_openapi_spec() {
+    let (client, _) = setup();
+    let mut response = client.get("/openapi.json").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.content_type(), Some(ContentType::JSON));
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    let spec: serde_json::Value = serde_json::from_str(&body_str).unwrap();
+    assert!(spec["paths"]["/search"]["get"].is_object());
+}
+
+#[test]
+fn get_geo_center() {
+    let (client, _) = setup();
+    let mut response = client.get("/geo/center?bbox=-10,-20,10,20").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    let res: serde_json::Value = serde_json::from_str(&body_str).unwrap();
+    assert_eq!(res["south_west"]["lat"], -10.0);
+    assert_eq!(res["north_east"]["lng"], 20.0);
+    assert_eq!(res["center"]["lat"], 0.0);
+    assert_eq!(res["center"]["lng"], 0.0);
+    assert!(res["extended_bbox"]["south_west"]["lat"].as_f64().unwrap() < -10.0);
+    assert!(res["extended_bbox"]["north_east"]["lng"].as_f64().unwrap() > 20.0);
+
+    let response = client.get("/geo/center?bbox=1,2,3").dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+}
+
+#[test]
+fn get_metrics() {
+    let (client, _) = setup();
+    let response = client.get("/entries/unknown/history").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+    let mut response = client.get("/metrics").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body_str = response.body().and_then(|b| b.into_string()).unwrap();
+    assert!(body_str.contains(
+        "ofdb_http_requests_total{method=\"GET\",route=\"/entries/<id>/history\",status=\"404\"} 1\n"
+    ));
+    assert!(body_str.contains("ofdb_http_request_duration_seconds_count 1\n"));
+    assert!(body_str.contains("ofdb_db_connections_in_use "));
+}
@@ -1,11 +1,53 @@
 use regex::Regex;
 use entities::*;
-use adapters::user_communication;
+use adapters::geocode::Nominatim;
+use adapters::user_communication::{self, Email, Language};
+use business::db::Db;
+use business::error::Error;
 use business::usecase;
+use std::io;
+#[cfg(feature = "email")]
 use super::mail;
 
 lazy_static! {
     static ref HASH_TAG_REGEX: Regex = Regex::new(r"#(?P<tag>\w+((-\w+)*)?)").unwrap();
+    static ref EXCLUDED_HASH_TAG_REGEX: Regex =
+        Regex::new(r"#-(?P<tag>\w+((-\w+)*)?)").unwrap();
+}
+
+const DEFAULT_BASE_URL: &str = "https://kartevonmorgen.org";
+const DEFAULT_FEED_MAX_ITEMS: usize = 20;
+
+/// The language of outgoing emails (`EMAIL_LANGUAGE`: `de` or `en`)
+pub fn email_language() -> Language {
+    ::std::env::var("EMAIL_LANGUAGE")
+        .ok()
+        .and_then(|l| l.parse().ok())
+        .unwrap_or_default()
+}
+
+/// The URL of the frontend that is linked in emails (`BASE_URL`)
+pub fn base_url() -> String {
+    ::std::env::var("BASE_URL")
+        .unwrap_or_else(|_| DEFAULT_BASE_URL.into())
+        .trim_right_matches('/')
+        .into()
+}
+
+/// The maximum number of entries in the feed (`FEED_MAX_ITEMS`)
+pub fn feed_max_items() -> usize {
+    ::std::env::var("FEED_MAX_ITEMS")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(DEFAULT_FEED_MAX_ITEMS)
+}
+
+/// A geocoder for missing addresses of new entries (`GEOCODING_URL`).
+/// Without this variable nothing gets looked up.
+pub fn geocoder() -> Option<Nominatim> {
+    ::std::env::var("GEOCODING_URL")
+        .ok()
+        .and_then(|url| if url.is_empty() { None } else { Some(Nominatim::new(&url)) })
 }
 
 pub fn extract_ids(s: &str) -> Vec<String> {
@@ -15,60 +57,103 @@ pub fn extract_ids(s: &str) -> Vec<String> {
         .collect::<Vec<String>>()
 }
 
-#[cfg(feature = "email")]
-pub fn send_mails(email_addresses: &[String], subject: &str, body: &str) {
-    debug!("sending emails to: {:?}", email_addresses);
-    for email_address in email_addresses.to_owned() {
-        let to = vec![email_address];
-        match mail::create(&to, subject, body) {
-            Ok(mail) => {
-                ::std::thread::spawn(move || {
-                    if let Err(err) = mail::send(&mail) {
-                        warn!("Could not send mail: {}", err);
-                    }
-                });
-            }
-            Err(e) => {
-                warn!("could not create notification mail: {}", e);
+/// Sends mails from the sender that was checked at startup
+/// (or nothing at all without the `email` feature)
+#[derive(Debug, Clone, Default)]
+pub struct Mailer {
+    #[cfg(feature = "email")]
+    sender: mail::Sender,
+}
+
+impl Mailer {
+    /// Reads the sender once from `OFDB_EMAIL_FROM` and `OFDB_EMAIL_REPLY_TO`
+    #[cfg(feature = "email")]
+    pub fn from_env() -> io::Result<Mailer> {
+        Ok(Mailer {
+            sender: mail::Sender::from_env()?,
+        })
+    }
+
+    #[cfg(not(feature = "email"))]
+    pub fn from_env() -> io::Result<Mailer> {
+        Ok(Mailer {})
+    }
+
+    #[cfg(feature = "email")]
+    pub fn send_mails(&self, email_addresses: &[String], email: &Email) {
+        debug!("sending emails to: {:?}", email_addresses);
+        for email_address in email_addresses.to_owned() {
+            let to = vec![email_address];
+            match mail::create_multipart(
+                &self.sender,
+                &to,
+                &email.subject,
+                &email.text,
+                &email.html,
+            ) {
+                Ok(mail) => {
+                    ::std::thread::spawn(move || {
+                        if let Err(err) = mail::send(&mail) {
+                            warn!("Could not send mail: {}", err);
+                        }
+                    });
+                }
+                Err(e) => {
+                    warn!("could not create notification mail: {}", e);
+                }
             }
         }
     }
-}
 
-#[cfg(not(feature = "email"))]
-pub fn send_mails(_: Vec<String>, _: &str, _: &str) {
-    // do nothing
+    #[cfg(not(feature = "email"))]
+    pub fn send_mails(&self, _: &[String], _: &Email) {
+        // do nothing
+    }
 }
 
-pub fn notify_create_entry(
-    email_addresses: &[String],
+pub fn notify_create_entry<D: Db>(
+    db: &mut D,
+    mailer: &Mailer,
     e: &usecase::NewEntry,
     id: &str,
-    all_categories: Vec<Category>,
-) {
-    let subject = String::from("Karte von Morgen - neuer Eintrag: ") + &e.title;
+) -> Result<(), Error> {
+    // The coordinates of new entries might have been looked up
+    let created = db.get_entry(id)?;
+    let email_addresses = usecase::email_addresses_by_coordinate(db, &created.lat, &created.lng)?;
+    let all_categories = db.all_categories()?;
     let categories: Vec<String> = all_categories
         .into_iter()
         .filter(|c| e.categories.clone().into_iter().any(|c_id| *c.id == c_id))
         .map(|c| c.name)
         .collect();
-    let body = user_communication::new_entry_email(e, id, &categories);
-    send_mails(email_addresses, &subject, &body);
+    let email =
+        user_communication::new_entry_email(email_language(), &base_url(), e, id, &categories);
+    mailer.send_mails(&email_addresses, &email);
+    Ok(())
 }
 
-pub fn notify_update_entry(
-    email_addresses: &[String],
+/// Notifies the subscribers of the old and the new location
+pub fn notify_update_entry<D: Db>(
+    db: &mut D,
+    mailer: &Mailer,
     e: &usecase::UpdateEntry,
-    all_categories: Vec<Category>,
-) {
-    let subject = String::from("Karte von Morgen - Eintrag verändert: ") + &e.title;
+    old: &Coordinate,
+) -> Result<(), Error> {
+    let new = Coordinate {
+        lat: e.lat,
+        lng: e.lng,
+    };
+    let email_addresses = usecase::email_addresses_by_old_and_new_coordinate(db, old, &new)?;
+    let all_categories = db.all_categories()?;
     let categories: Vec<String> = all_categories
         .into_iter()
         .filter(|c| e.categories.clone().into_iter().any(|c_id| *c.id == c_id))
         .map(|c| c.name)
         .collect();
-    let body = user_communication::changed_entry_email(e, &categories);
-    send_mails(email_addresses, &subject, &body);
+    let email =
+        user_communication::changed_entry_email(email_language(), &base_url(), e, &categories);
+    mailer.send_mails(&email_addresses, &email);
+    Ok(())
 }
 
 pub fn extract_hash_tags(text: &str) -> Vec<String> {
@@ -79,9 +164,19 @@ pub fn extract_hash_tags(text: &str) -> Vec<String> {
     res
 }
 
+/// Extracts the tags that are marked as excluded (`#-tag`)
+pub fn extract_excluded_hash_tags(text: &str) -> Vec<String> {
+    let mut res: Vec<String> = vec![];
+    for cap in EXCLUDED_HASH_TAG_REGEX.captures_iter(text) {
+        res.push(cap["tag"].into());
+    }
+    res
+}
+
 pub fn remove_hash_tags(text: &str) -> String {
+    let text = EXCLUDED_HASH_TAG_REGEX.replace_all(text, "");
     HASH_TAG_REGEX
-        .replace_all(text, "")
+        .replace_all(&text, "")
         .into_owned()
         .replace("  ", " ")
         .trim()
@@ -13,6 +13,8 @@ extern crate diesel_migrations;
 extern crate dotenv;
 extern crate env_logger;
 extern crate fast_chemail;
+extern crate flate2;
+extern crate jsonwebtoken;
 #[macro_use]
 extern crate lazy_static;
 #[macro_use]
@@ -22,6 +24,7 @@ extern crate pwhash;
 extern crate quick_error;
 extern crate quoted_printable;
 extern crate regex;
+extern crate reqwest;
 extern crate rocket;
 extern crate rocket_contrib;
 #[macro_use]